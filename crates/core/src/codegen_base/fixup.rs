@@ -0,0 +1,382 @@
+use crate::{
+    ir_base::{Instruction, Operand, Size},
+    r,
+};
+
+/// Rewrites instructions whose operand combination is not encodable on
+/// x86-64, using `r10` and `r11` as scratch registers.
+pub(super) fn fix_invalid_operands(instructions: Vec<Instruction>) -> Vec<Instruction> {
+    let r10 = Operand::Register(r!("r10"));
+    let r11 = Operand::Register(r!("r11"));
+    let mut fixed = Vec::with_capacity(instructions.len());
+
+    for inst in instructions {
+        match inst {
+            // At most one memory operand per instruction
+            Instruction::Mov { size, src, dst } if src.is_memory() && dst.is_memory() => {
+                fixed.push(Instruction::Mov {
+                    size,
+                    src,
+                    dst: r10.clone(),
+                });
+                fixed.push(Instruction::Mov {
+                    size,
+                    src: r10.clone(),
+                    dst,
+                });
+            }
+            Instruction::Add { src, dst } if src.is_memory() && dst.is_memory() => {
+                fix_binary(&mut fixed, src, dst, |src, dst| Instruction::Add {
+                    src,
+                    dst,
+                })
+            }
+            Instruction::Sub { src, dst } if src.is_memory() && dst.is_memory() => {
+                fix_binary(&mut fixed, src, dst, |src, dst| Instruction::Sub {
+                    src,
+                    dst,
+                })
+            }
+            Instruction::And { src, dst } if src.is_memory() && dst.is_memory() => {
+                fix_binary(&mut fixed, src, dst, |src, dst| Instruction::And {
+                    src,
+                    dst,
+                })
+            }
+            Instruction::Or { src, dst } if src.is_memory() && dst.is_memory() => {
+                fix_binary(&mut fixed, src, dst, |src, dst| Instruction::Or {
+                    src,
+                    dst,
+                })
+            }
+            Instruction::Xor { src, dst } if src.is_memory() && dst.is_memory() => {
+                fix_binary(&mut fixed, src, dst, |src, dst| Instruction::Xor {
+                    src,
+                    dst,
+                })
+            }
+            // `imul` cannot write to memory
+            Instruction::IMul { src, dst } if dst.is_memory() => {
+                fixed.push(Instruction::Mov {
+                    size: Size::Long,
+                    src: dst.clone(),
+                    dst: r11.clone(),
+                });
+                fixed.push(Instruction::IMul {
+                    src,
+                    dst: r11.clone(),
+                });
+                fixed.push(Instruction::Mov {
+                    size: Size::Long,
+                    src: r11.clone(),
+                    dst,
+                });
+            }
+            // `cmov` reads a register or memory, and writes a register
+            Instruction::Cmov { cond, src, dst }
+                if matches!(src, Operand::Immediate(_)) || dst.is_memory() =>
+            {
+                let src = match src {
+                    src @ Operand::Immediate(_) => {
+                        fixed.push(Instruction::Mov {
+                            size: Size::Long,
+                            src,
+                            dst: r10.clone(),
+                        });
+                        r10.clone()
+                    }
+                    src => src,
+                };
+                if dst.is_memory() {
+                    fixed.push(Instruction::Mov {
+                        size: Size::Long,
+                        src: dst.clone(),
+                        dst: r11.clone(),
+                    });
+                    fixed.push(Instruction::Cmov {
+                        cond,
+                        src,
+                        dst: r11.clone(),
+                    });
+                    fixed.push(Instruction::Mov {
+                        size: Size::Long,
+                        src: r11.clone(),
+                        dst,
+                    });
+                } else {
+                    fixed.push(Instruction::Cmov { cond, src, dst });
+                }
+            }
+            // `idiv` cannot take an immediate
+            Instruction::IDiv {
+                divisor: divisor @ Operand::Immediate(_),
+            } => {
+                fixed.push(Instruction::Mov {
+                    size: Size::Long,
+                    src: divisor,
+                    dst: r10.clone(),
+                });
+                fixed.push(Instruction::IDiv {
+                    divisor: r10.clone(),
+                });
+            }
+            // `cmp` cannot compare into an immediate, nor memory with memory
+            Instruction::Cmp { src, dst } if matches!(dst, Operand::Immediate(_)) => {
+                fixed.push(Instruction::Mov {
+                    size: Size::Long,
+                    src: dst,
+                    dst: r11.clone(),
+                });
+                fixed.push(Instruction::Cmp {
+                    src,
+                    dst: r11.clone(),
+                });
+            }
+            Instruction::Cmp { src, dst } if src.is_memory() && dst.is_memory() => {
+                fixed.push(Instruction::Mov {
+                    size: Size::Long,
+                    src,
+                    dst: r10.clone(),
+                });
+                fixed.push(Instruction::Cmp {
+                    src: r10.clone(),
+                    dst,
+                });
+            }
+            // `test` of a value with itself is a comparison with 0, which
+            // works on memory too
+            Instruction::Test { src, dst } if src.is_memory() && src == dst => {
+                fixed.push(Instruction::Cmp {
+                    src: Operand::Immediate(0),
+                    dst,
+                });
+            }
+            // The address of `lea` is made of registers, and the result
+            // goes to a register
+            Instruction::Lea {
+                base,
+                index,
+                scale,
+                offset,
+                dst,
+            } => {
+                let mut in_register = |operand: Option<Operand>, scratch: &Operand| match operand {
+                    Some(operand) if operand.is_memory() => {
+                        fixed.push(Instruction::Mov {
+                            size: Size::Long,
+                            src: operand,
+                            dst: scratch.clone(),
+                        });
+                        Some(scratch.clone())
+                    }
+                    operand => operand,
+                };
+                let base = in_register(base, &r10);
+                let index = in_register(index, &r11);
+                if dst.is_memory() {
+                    fixed.push(Instruction::Lea {
+                        base,
+                        index,
+                        scale,
+                        offset,
+                        dst: r11.clone(),
+                    });
+                    fixed.push(Instruction::Mov {
+                        size: Size::Long,
+                        src: r11.clone(),
+                        dst,
+                    });
+                } else {
+                    fixed.push(Instruction::Lea {
+                        base,
+                        index,
+                        scale,
+                        offset,
+                        dst,
+                    });
+                }
+            }
+            inst => fixed.push(inst),
+        }
+    }
+
+    fixed
+}
+
+/// Loads the memory source of a two-operand instruction into `r10` first.
+fn fix_binary<F>(fixed: &mut Vec<Instruction>, src: Operand, dst: Operand, rebuild: F)
+where
+    F: FnOnce(Operand, Operand) -> Instruction,
+{
+    let r10 = Operand::Register(r!("r10"));
+    fixed.push(Instruction::Mov {
+        size: Size::Long,
+        src,
+        dst: r10.clone(),
+    });
+    fixed.push(rebuild(r10, dst));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir_base::CondCode;
+
+    fn stack(offset: i64) -> Operand {
+        Operand::memory(r!("rbp"), offset)
+    }
+
+    #[test]
+    fn test_memory_to_memory_mov_goes_through_r10() {
+        let fixed = fix_invalid_operands(vec![Instruction::Mov {
+            size: Size::Long,
+            src: stack(-4),
+            dst: stack(-8),
+        }]);
+        assert_eq!(
+            fixed,
+            vec![
+                Instruction::Mov {
+                    size: Size::Long,
+                    src: stack(-4),
+                    dst: Operand::Register(r!("r10")),
+                },
+                Instruction::Mov {
+                    size: Size::Long,
+                    src: Operand::Register(r!("r10")),
+                    dst: stack(-8),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_imul_into_memory_goes_through_r11() {
+        let fixed = fix_invalid_operands(vec![Instruction::IMul {
+            src: Operand::Immediate(3),
+            dst: stack(-4),
+        }]);
+        assert_eq!(fixed.len(), 3);
+        assert_eq!(
+            fixed[1],
+            Instruction::IMul {
+                src: Operand::Immediate(3),
+                dst: Operand::Register(r!("r11")),
+            }
+        );
+    }
+
+    #[test]
+    fn test_cmov_of_immediate_into_memory_goes_through_scratch_registers() {
+        let fixed = fix_invalid_operands(vec![Instruction::Cmov {
+            cond: CondCode::L,
+            src: Operand::Immediate(5),
+            dst: stack(-4),
+        }]);
+        assert_eq!(
+            fixed,
+            vec![
+                Instruction::Mov {
+                    size: Size::Long,
+                    src: Operand::Immediate(5),
+                    dst: Operand::Register(r!("r10")),
+                },
+                Instruction::Mov {
+                    size: Size::Long,
+                    src: stack(-4),
+                    dst: Operand::Register(r!("r11")),
+                },
+                Instruction::Cmov {
+                    cond: CondCode::L,
+                    src: Operand::Register(r!("r10")),
+                    dst: Operand::Register(r!("r11")),
+                },
+                Instruction::Mov {
+                    size: Size::Long,
+                    src: Operand::Register(r!("r11")),
+                    dst: stack(-4),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lea_on_stack_slots_goes_through_scratch_registers() {
+        let fixed = fix_invalid_operands(vec![Instruction::Lea {
+            base: Some(stack(-4)),
+            index: Some(stack(-8)),
+            scale: 4,
+            offset: 8,
+            dst: stack(-12),
+        }]);
+        assert_eq!(
+            fixed,
+            vec![
+                Instruction::Mov {
+                    size: Size::Long,
+                    src: stack(-4),
+                    dst: Operand::Register(r!("r10")),
+                },
+                Instruction::Mov {
+                    size: Size::Long,
+                    src: stack(-8),
+                    dst: Operand::Register(r!("r11")),
+                },
+                Instruction::Lea {
+                    base: Some(Operand::Register(r!("r10"))),
+                    index: Some(Operand::Register(r!("r11"))),
+                    scale: 4,
+                    offset: 8,
+                    dst: Operand::Register(r!("r11")),
+                },
+                Instruction::Mov {
+                    size: Size::Long,
+                    src: Operand::Register(r!("r11")),
+                    dst: stack(-12),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_test_of_memory_becomes_cmp() {
+        let fixed = fix_invalid_operands(vec![Instruction::Test {
+            src: stack(-4),
+            dst: stack(-4),
+        }]);
+        assert_eq!(
+            fixed,
+            vec![Instruction::Cmp {
+                src: Operand::Immediate(0),
+                dst: stack(-4),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_idiv_immediate_and_cmp_immediate_are_fixed() {
+        let fixed = fix_invalid_operands(vec![
+            Instruction::IDiv {
+                divisor: Operand::Immediate(2),
+            },
+            Instruction::Cmp {
+                src: Operand::Immediate(0),
+                dst: Operand::Immediate(5),
+            },
+        ]);
+        assert_eq!(fixed.len(), 4);
+        assert_eq!(
+            fixed[1],
+            Instruction::IDiv {
+                divisor: Operand::Register(r!("r10")),
+            }
+        );
+        assert_eq!(
+            fixed[3],
+            Instruction::Cmp {
+                src: Operand::Immediate(0),
+                dst: Operand::Register(r!("r11")),
+            }
+        );
+    }
+}
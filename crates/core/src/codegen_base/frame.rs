@@ -0,0 +1,293 @@
+//! Stack frame layout.
+//!
+//! Function bodies are generated without a frame: their stack slots are
+//! addressed from a base register, and returns are bare `ret`s. Once the
+//! slots are known, [`Frame`] adds the prologue and the epilogues.
+//!
+//! With a frame pointer, the base is `rbp`, which the prologue pushes and
+//! points at the saved value. With `-fomit-frame-pointer`, slots are first
+//! addressed from `rsp` as it was on entry; each offset is then corrected
+//! for how far `rsp` has moved at that point, and `rbp` is free to hold
+//! values.
+//!
+//! A function that calls nothing may keep its slots in the 128-byte red
+//! zone below `rsp`, which the System V ABI guarantees signal handlers
+//! leave alone. It then does not move `rsp` at all.
+
+use crate::{
+    codegen_base::{CodegenOptions, regalloc},
+    ir_base::{Instruction, Operand, Size, reg::PhyRegister},
+    r,
+};
+
+/// Bytes below `rsp` a leaf function may use without allocating them
+const RED_ZONE_SIZE: i64 = 128;
+
+/// Frame of one function
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Frame {
+    frame_pointer: bool,
+    /// Bytes the prologue subtracts from `rsp`
+    allocation: i64,
+    /// Callee-saved registers the body uses, each with the offset of its
+    /// slot from the base register
+    saves: Vec<(PhyRegister, i64)>,
+}
+
+impl Frame {
+    /// Register the stack slots of the body are addressed from.
+    pub(super) fn base_register(options: &CodegenOptions) -> PhyRegister {
+        match options.omit_frame_pointer {
+            true => r!("rsp"),
+            false => r!("rbp"),
+        }
+    }
+
+    /// Offset of the canonical frame address, where the arguments passed on
+    /// the stack start, from the base register: past the return address,
+    /// and past the saved `rbp` with a frame pointer.
+    pub(super) fn cfa_offset(options: &CodegenOptions) -> i64 {
+        match options.omit_frame_pointer {
+            true => 8,
+            false => 16,
+        }
+    }
+
+    /// Lays out the frame of `body`, whose stack slots take `stack_size`
+    /// bytes, with a slot for each register of `saved`.
+    pub(super) fn new(
+        body: &[Instruction],
+        stack_size: i64,
+        saved: Vec<PhyRegister>,
+        options: &CodegenOptions,
+    ) -> Self {
+        // Callee-saved registers go below the stack slots
+        let save_area = (stack_size + 7) / 8 * 8;
+        let saves: Vec<(PhyRegister, i64)> = saved
+            .into_iter()
+            .enumerate()
+            .map(|(idx, reg)| (reg, -(save_area + 8 * (idx as i64 + 1))))
+            .collect();
+        let size = save_area + 8 * saves.len() as i64;
+
+        let is_leaf = !body
+            .iter()
+            .any(|inst| matches!(inst, Instruction::Call { .. }));
+        let frame_pointer = !options.omit_frame_pointer;
+        let allocation = if is_leaf && options.red_zone && size <= RED_ZONE_SIZE {
+            0
+        } else if frame_pointer {
+            // `rsp` is 16-byte aligned once `rbp` is pushed; keep it so
+            align16(size)
+        } else if is_leaf {
+            size
+        } else {
+            // The return address leaves `rsp` 8 bytes off the alignment
+            // calls need
+            align16(size + 8) - 8
+        };
+
+        Self {
+            frame_pointer,
+            allocation,
+            saves,
+        }
+    }
+
+    /// Sets up the frame before `body` and releases it before each return.
+    pub(super) fn wrap(&self, body: Vec<Instruction>) -> Vec<Instruction> {
+        let base = match self.frame_pointer {
+            true => r!("rbp"),
+            false => r!("rsp"),
+        };
+        let mut instructions = Vec::with_capacity(body.len() + 4);
+
+        if self.frame_pointer {
+            instructions.push(Instruction::Push(Operand::Register(r!("rbp"))));
+            instructions.push(Instruction::Mov {
+                size: Size::Quad,
+                src: Operand::Register(r!("rsp")),
+                dst: Operand::Register(r!("rbp")),
+            });
+        }
+        if self.allocation > 0 {
+            instructions.push(Instruction::AllocateStack(self.allocation));
+        }
+        instructions.extend(self.saves.iter().map(|&(reg, offset)| Instruction::Mov {
+            size: Size::Quad,
+            src: Operand::Register(reg),
+            dst: Operand::memory(base, offset),
+        }));
+
+        for inst in body {
+            if matches!(inst, Instruction::Ret | Instruction::TailCall { .. }) {
+                instructions.extend(self.saves.iter().map(|&(reg, offset)| Instruction::Mov {
+                    size: Size::Quad,
+                    src: Operand::memory(base, offset),
+                    dst: Operand::Register(reg),
+                }));
+                self.emit_teardown(&mut instructions);
+            }
+            instructions.push(inst);
+        }
+
+        if !self.frame_pointer {
+            self.track_rsp(&mut instructions);
+        }
+        instructions
+    }
+
+    /// Releases the frame, leaving `rsp` as it was on entry.
+    fn emit_teardown(&self, instructions: &mut Vec<Instruction>) {
+        if self.frame_pointer {
+            instructions.push(Instruction::Mov {
+                size: Size::Quad,
+                src: Operand::Register(r!("rbp")),
+                dst: Operand::Register(r!("rsp")),
+            });
+            instructions.push(Instruction::Pop(Operand::Register(r!("rbp"))));
+        } else if self.allocation > 0 {
+            instructions.push(Instruction::DeallocateStack(self.allocation));
+        }
+    }
+
+    /// Turns offsets from `rsp` on entry into offsets from `rsp` where
+    /// each instruction runs.
+    fn track_rsp(&self, instructions: &mut [Instruction]) {
+        let mut depth = 0;
+        for inst in instructions {
+            for operand in regalloc::operands_mut(inst) {
+                if let Operand::Memory {
+                    base: Some(r!("rsp")),
+                    offset,
+                    ..
+                } = operand
+                {
+                    *offset += depth;
+                }
+            }
+            match inst {
+                Instruction::Push(_) => depth += 8,
+                Instruction::Pop(_) => depth -= 8,
+                Instruction::AllocateStack(bytes) => depth += *bytes,
+                Instruction::DeallocateStack(bytes) => depth -= *bytes,
+                // Code after a return is reached by a jump from the body
+                Instruction::Ret | Instruction::TailCall { .. } => depth = self.allocation,
+                _ => {}
+            }
+        }
+    }
+}
+
+fn align16(bytes: i64) -> i64 {
+    (bytes + 15) / 16 * 16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(omit_frame_pointer: bool, red_zone: bool) -> CodegenOptions {
+        CodegenOptions {
+            omit_frame_pointer,
+            red_zone,
+            ..CodegenOptions::default()
+        }
+    }
+
+    fn call() -> Instruction {
+        Instruction::Call {
+            name: "f".into(),
+            arg_registers: 0,
+        }
+    }
+
+    fn load(base: PhyRegister, offset: i64) -> Instruction {
+        Instruction::Mov {
+            size: Size::Long,
+            src: Operand::memory(base, offset),
+            dst: Operand::Register(r!("rax")),
+        }
+    }
+
+    #[test]
+    fn test_leaf_function_keeps_its_slots_in_the_red_zone() {
+        let body = vec![load(r!("rbp"), -4), Instruction::Ret];
+        let frame = Frame::new(&body, 4, vec![], &options(false, true));
+        assert_eq!(frame.allocation, 0);
+
+        let instructions = frame.wrap(body);
+        assert!(
+            !instructions
+                .iter()
+                .any(|inst| matches!(inst, Instruction::AllocateStack(_)))
+        );
+    }
+
+    #[test]
+    fn test_red_zone_is_not_used_when_disabled_or_too_small() {
+        let body = vec![load(r!("rbp"), -4), Instruction::Ret];
+        let frame = Frame::new(&body, 4, vec![], &options(false, false));
+        assert_eq!(frame.allocation, 16);
+
+        let frame = Frame::new(&body, 132, vec![], &options(false, true));
+        assert_eq!(frame.allocation, 144);
+    }
+
+    #[test]
+    fn test_calls_need_an_allocated_and_aligned_frame() {
+        let body = vec![call(), Instruction::Ret];
+        let frame = Frame::new(&body, 4, vec![], &options(false, true));
+        assert_eq!(frame.allocation, 16);
+
+        // Without `rbp` pushed, the return address alone is on the stack
+        let frame = Frame::new(&body, 4, vec![], &options(true, true));
+        assert_eq!(frame.allocation, 8);
+        let frame = Frame::new(&body, 12, vec![r!("rbx")], &options(true, true));
+        assert_eq!(frame.allocation, 24);
+        let frame = Frame::new(&body, 0, vec![], &options(true, true));
+        assert_eq!(frame.allocation, 8);
+    }
+
+    #[test]
+    fn test_without_frame_pointer_slots_follow_rsp() {
+        let body = vec![
+            load(r!("rsp"), -4),
+            Instruction::Push(Operand::Immediate(1)),
+            load(r!("rsp"), -4),
+            call(),
+            Instruction::DeallocateStack(8),
+            load(r!("rsp"), 8),
+            Instruction::Ret,
+        ];
+        let frame = Frame::new(&body, 4, vec![r!("rbx")], &options(true, true));
+        assert_eq!(frame.allocation, 24);
+
+        let instructions = frame.wrap(body);
+        assert_eq!(
+            instructions,
+            vec![
+                Instruction::AllocateStack(24),
+                Instruction::Mov {
+                    size: Size::Quad,
+                    src: Operand::Register(r!("rbx")),
+                    dst: Operand::memory(r!("rsp"), 8),
+                },
+                load(r!("rsp"), 20),
+                Instruction::Push(Operand::Immediate(1)),
+                load(r!("rsp"), 28),
+                call(),
+                Instruction::DeallocateStack(8),
+                // The first stack argument, above the return address
+                load(r!("rsp"), 32),
+                Instruction::Mov {
+                    size: Size::Quad,
+                    src: Operand::memory(r!("rsp"), 8),
+                    dst: Operand::Register(r!("rbx")),
+                },
+                Instruction::DeallocateStack(24),
+                Instruction::Ret,
+            ]
+        );
+    }
+}
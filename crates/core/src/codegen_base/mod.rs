@@ -1,36 +1,127 @@
+mod fixup;
+mod frame;
+mod regalloc;
+mod schedule;
+mod select;
+mod stack;
+
+pub use regalloc::RegAllocator;
+pub use schedule::schedule;
+
+use std::collections::HashMap;
+
 use crate::{
-    grammar::{Expression, Program, Statement, UnaryOp},
-    ir_base::{self, IRFuncDef, IRProgram, Instruction, Operand},
+    grammar::{BinaryOp, UnaryOp},
+    ir_base::{self, CondCode, IRFuncDef, IRProgram, Instruction, Operand, Size, reg::PhyRegister},
+    opt_base::OptLevel,
     r,
+    tacky_base::{self as tacky, TackyFuncDef, TackyProgram},
 };
+use frame::Frame;
+use select::Selection;
+
+/// Registers used to pass the first six integer arguments (System V ABI)
+pub const ARG_REGISTERS: [PhyRegister; 6] = [
+    r!("rdi"),
+    r!("rsi"),
+    r!("rdx"),
+    r!("rcx"),
+    r!("r8"),
+    r!("r9"),
+];
+
+/// How code refers to functions, selected with `-fno-pic`, `-fpie` and
+/// `-fpic`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelocationModel {
+    /// Code linked at a fixed address
+    Static,
+    /// Position-independent executable: functions defined elsewhere are
+    /// called through the PLT. The default of modern Linux toolchains
+    Pie,
+    /// Position-independent code for shared libraries: global functions
+    /// may be overridden by another module, so only calls to local ones
+    /// are direct
+    Pic,
+}
+
+/// Code generation settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodegenOptions {
+    /// How values are assigned to registers. Without an allocator, every
+    /// value lives in the stack frame.
+    pub register_allocator: Option<RegAllocator>,
+    /// Address the stack frame from `rsp` and use `rbp` as an ordinary
+    /// register (`-fomit-frame-pointer`)
+    pub omit_frame_pointer: bool,
+    /// Let functions that call nothing keep their frame below `rsp`
+    /// without allocating it. Turned off with `-mno-red-zone`, for code
+    /// where interrupts may run on the same stack.
+    pub red_zone: bool,
+    pub relocation_model: RelocationModel,
+    /// Pick between two values with `cmov` instead of a branch
+    pub conditional_moves: bool,
+}
+
+impl CodegenOptions {
+    /// The settings `level` implies: no register allocation at `-O0`, graph
+    /// coloring at `-O2` and linear scan otherwise, and conditional moves
+    /// when optimizing.
+    pub fn new(level: OptLevel) -> Self {
+        let register_allocator = match level {
+            OptLevel::O0 => None,
+            OptLevel::O2 => Some(RegAllocator::Graph),
+            OptLevel::O1 | OptLevel::Os => Some(RegAllocator::Linear),
+        };
+        Self {
+            register_allocator,
+            omit_frame_pointer: false,
+            red_zone: true,
+            relocation_model: RelocationModel::Pie,
+            conditional_moves: level != OptLevel::O0,
+        }
+    }
+}
+
+impl Default for CodegenOptions {
+    fn default() -> Self {
+        Self::new(OptLevel::O0)
+    }
+}
 
+/// Translates TACKY into the assembly IR.
 pub struct CodeGenerator {
     current_function: Vec<Instruction>,
-    #[allow(dead_code)]
-    label_counter: usize,
+    options: CodegenOptions,
+    /// Whether each function defined in the program is global
+    functions: HashMap<String, bool>,
 }
 
 impl CodeGenerator {
     pub fn new() -> Self {
         Self {
             current_function: Vec::new(),
-            label_counter: 0,
+            options: CodegenOptions::default(),
+            functions: HashMap::new(),
         }
     }
 
-    #[allow(dead_code)]
-    fn generate_label(&mut self, prefix: &str) -> String {
-        let label = format!(".L{}_{}", prefix, self.label_counter);
-        self.label_counter += 1;
-        label
+    pub fn with_options(mut self, options: CodegenOptions) -> Self {
+        self.options = options;
+        self
     }
 
     fn emit(&mut self, inst: Instruction) {
         self.current_function.push(inst);
     }
 
-    pub fn generate<'a>(&mut self, program: &'a Program<'a>) -> IRProgram<'a> {
+    pub fn generate(&mut self, program: &TackyProgram) -> IRProgram<'static> {
         let mut ir_program = IRProgram::new();
+        self.functions = program
+            .functions
+            .iter()
+            .map(|func| (func.name.clone(), func.is_global))
+            .collect();
 
         for func in &program.functions {
             let ir_func = self.generate_function(func);
@@ -40,88 +131,754 @@ impl CodeGenerator {
         ir_program
     }
 
-    fn generate_function<'a>(
-        &mut self,
-        func: &'a crate::grammar::FuncDef<'a>,
-    ) -> ir_base::IRFuncDef<'a> {
+    fn generate_function(&mut self, func: &TackyFuncDef) -> ir_base::IRFuncDef<'static> {
         self.current_function.clear();
 
-        // function prologue
-        self.emit(Instruction::Push(Operand::Register(r!("rbp"))));
-        self.emit(Instruction::Mov {
-            src: Operand::Register(r!("rsp")),
-            dst: Operand::Register(r!("rbp")),
-        });
+        // move parameters out of their argument registers / stack slots
+        for (idx, param) in func.params.iter().enumerate() {
+            let src = match ARG_REGISTERS.get(idx) {
+                Some(reg) => Operand::Register(*reg),
+                None => Operand::memory(
+                    Frame::base_register(&self.options),
+                    Frame::cfa_offset(&self.options) + 8 * (idx - ARG_REGISTERS.len()) as i64,
+                ),
+            };
+            self.emit(Instruction::Mov {
+                size: Size::Long,
+                src,
+                dst: Operand::Pseudo(param.clone()),
+            });
+        }
 
         // generate body
-        self.generate_statement(Statement::Block(func.body.clone()));
+        for (inst, selection) in func
+            .body
+            .iter()
+            .zip(select::select(&func.body, self.options.conditional_moves))
+        {
+            match selection {
+                Selection::Single => self.generate_instruction(inst),
+                Selection::Folded => {}
+                Selection::Address {
+                    base,
+                    index,
+                    offset,
+                    dst,
+                } => {
+                    let (index, scale) = match index {
+                        Some((index, scale)) => (Some(Self::operand(&index)), scale),
+                        None => (None, 1),
+                    };
+                    self.emit(Instruction::Lea {
+                        base: base.as_ref().map(Self::operand),
+                        index,
+                        scale,
+                        offset,
+                        dst: Operand::Pseudo(dst),
+                    });
+                }
+                Selection::CompareAndBranch {
+                    cond,
+                    lhs,
+                    rhs,
+                    target,
+                } => {
+                    let cond = self.emit_comparison(cond, Self::operand(&lhs), Self::operand(&rhs));
+                    self.emit(Instruction::JmpCC { cond, target });
+                }
+                Selection::ConditionalMove {
+                    cond,
+                    lhs,
+                    rhs,
+                    then_value,
+                    else_value,
+                    dst,
+                } => {
+                    let cond = match rhs {
+                        tacky::Value::Constant(0) if matches!(cond, CondCode::E | CondCode::NE) => {
+                            self.emit_zero_test(Self::operand(&lhs));
+                            cond
+                        }
+                        rhs => self.emit_comparison(cond, Self::operand(&lhs), Self::operand(&rhs)),
+                    };
+                    // `mov` leaves the flags as they are
+                    let dst = Operand::Pseudo(dst);
+                    self.emit(Instruction::Mov {
+                        size: Size::Long,
+                        src: Self::operand(&else_value),
+                        dst: dst.clone(),
+                    });
+                    self.emit(Instruction::Cmov {
+                        cond,
+                        src: Self::operand(&then_value),
+                        dst,
+                    });
+                }
+            }
+        }
 
-        // function epilogue
-        self.emit(Instruction::Pop(Operand::Register(r!("rbp"))));
-        self.emit(Instruction::Ret);
+        let mut instructions = std::mem::take(&mut self.current_function);
+        let saved = match self.options.register_allocator {
+            Some(allocator) => regalloc::allocate_registers(
+                &mut instructions,
+                allocator,
+                &regalloc::allocatable(&self.options),
+            ),
+            None => Vec::new(),
+        };
+        let base = Frame::base_register(&self.options);
+        let (stack_size, stack_slots) = stack::replace_pseudo_registers(&mut instructions, base);
+        let frame = Frame::new(&instructions, stack_size, saved, &self.options);
+        let instructions = fixup::fix_invalid_operands(frame.wrap(instructions));
 
-        IRFuncDef::new(func.name.clone(), true, &self.current_function)
+        let mut ir_func = IRFuncDef::new(func.name.clone().into(), func.is_global, &instructions);
+        let cfa_offset = Frame::cfa_offset(&self.options);
+        ir_func.stack_slots = stack_slots
+            .into_iter()
+            .map(|(name, offset)| (name, offset - cfa_offset))
+            .collect();
+        ir_func
     }
 
-    fn generate_statement(&mut self, stmt: Statement<'_>) {
-        match stmt {
-            Statement::Return(ret) => {
-                self.generate_expression(&ret.expr);
-                self.emit(Instruction::Pop(Operand::Register(r!("rbp"))));
+    fn generate_instruction(&mut self, inst: &tacky::Instruction) {
+        match inst {
+            tacky::Instruction::Return(value) => {
+                self.emit(Instruction::Mov {
+                    size: Size::Long,
+                    src: Self::operand(value),
+                    dst: Operand::Register(r!("rax")),
+                });
                 self.emit(Instruction::Ret);
             }
-            Statement::Block(block) => {
-                for s in block.statements {
-                    self.generate_statement(s);
-                }
-            }
-            Statement::Null(_) => {}
-            Statement::Break(_) => todo!(),
-            Statement::Continue(_) => todo!(),
-            Statement::Declaration(_) => todo!(),
-            Statement::DoWhile(_) => todo!(),
-            Statement::Expr(_) => todo!(),
-            Statement::For(_) => todo!(),
-            Statement::If(_) => todo!(),
-            Statement::While(_) => todo!(),
-        }
-    }
-
-    fn generate_expression(&mut self, expr: &Expression<'_>) {
-        match expr {
-            Expression::Constant(val) => self.emit(Instruction::Mov {
-                src: Operand::Immediate(*val),
-                dst: Operand::Register(r!("rax")),
+            tacky::Instruction::Copy { src, dst } => self.emit(Instruction::Mov {
+                size: Size::Long,
+                src: Self::operand(src),
+                dst: Operand::Pseudo(dst.clone()),
             }),
-            Expression::Variable(_) => todo!(),
-            Expression::Grouped(_) => todo!(),
-            Expression::Binary {
-                op: _op,
-                lhs: _lhs,
-                rhs: _rhs,
-            } => todo!(),
-            Expression::Unary { op, expr } => {
-                self.generate_expression(expr);
+            tacky::Instruction::Unary { op, src, dst } => {
+                let src = Self::operand(src);
+                let dst = Operand::Pseudo(dst.clone());
                 match op {
                     UnaryOp::Negate => {
-                        self.emit(Instruction::Neg {
-                            dst: Operand::Register(r!("rax")),
+                        self.emit(Instruction::Mov {
+                            size: Size::Long,
+                            src,
+                            dst: dst.clone(),
                         });
+                        self.emit(Instruction::Neg { dst });
                     }
                     UnaryOp::Not => {
-                        todo!()
+                        self.emit_zero_test(src);
+                        self.emit_set_condition(CondCode::E, dst);
+                    }
+                    UnaryOp::Complement => {
+                        self.emit(Instruction::Mov {
+                            size: Size::Long,
+                            src,
+                            dst: dst.clone(),
+                        });
+                        self.emit(Instruction::Not { dst });
                     }
                 }
             }
-            Expression::Assignment {
-                op: _op,
-                lhs: _lhs,
-                rhs: _rhs,
-            } => todo!(),
-            Expression::FunctionCall {
-                callee: _callee,
-                args: _args,
-            } => todo!(),
+            tacky::Instruction::Binary { op, lhs, rhs, dst } => {
+                self.generate_binary(op, Self::operand(lhs), Self::operand(rhs), dst)
+            }
+            tacky::Instruction::Jump(target) => self.emit(Instruction::Jmp(target.clone())),
+            tacky::Instruction::JumpIfZero { cond, target } => {
+                self.emit_zero_test(Self::operand(cond));
+                self.emit(Instruction::JmpCC {
+                    cond: CondCode::E,
+                    target: target.clone(),
+                });
+            }
+            tacky::Instruction::JumpIfNotZero { cond, target } => {
+                self.emit_zero_test(Self::operand(cond));
+                self.emit(Instruction::JmpCC {
+                    cond: CondCode::NE,
+                    target: target.clone(),
+                });
+            }
+            tacky::Instruction::Label(label) => self.emit(Instruction::Label(label.clone())),
+            tacky::Instruction::Loc { line, column } => self.emit(Instruction::Loc {
+                line: *line,
+                column: *column,
+            }),
+            tacky::Instruction::FunCall { name, args, dst } => {
+                self.generate_call(name, args, dst.as_deref())
+            }
+            tacky::Instruction::TailCall { name, args } => {
+                // The optimizer only emits tail calls passing all arguments
+                // in registers, so our frame can go before the jump, which
+                // is where the epilogue is added
+                for (arg, reg) in args.iter().zip(ARG_REGISTERS) {
+                    self.emit(Instruction::Mov {
+                        size: Size::Long,
+                        src: Self::operand(arg),
+                        dst: Operand::Register(reg),
+                    });
+                }
+                self.emit(Instruction::TailCall {
+                    name: self.call_target(name),
+                    arg_registers: args.len(),
+                });
+            }
+        }
+    }
+
+    fn generate_binary(&mut self, op: &BinaryOp, lhs: Operand, rhs: Operand, dst: &str) {
+        let dst = Operand::Pseudo(dst.to_string());
+        let cond = match op {
+            BinaryOp::Add
+            | BinaryOp::Subtract
+            | BinaryOp::Multiply
+            | BinaryOp::BitwiseAnd
+            | BinaryOp::BitwiseOr
+            | BinaryOp::BitwiseXor => {
+                // An update in place, e.g. from `x++`, works on `dst` directly
+                if lhs != dst {
+                    self.emit(Instruction::Mov {
+                        size: Size::Long,
+                        src: lhs,
+                        dst: dst.clone(),
+                    });
+                }
+                self.emit(match op {
+                    BinaryOp::Add => Instruction::Add { src: rhs, dst },
+                    BinaryOp::Subtract => Instruction::Sub { src: rhs, dst },
+                    BinaryOp::BitwiseAnd => Instruction::And { src: rhs, dst },
+                    BinaryOp::BitwiseOr => Instruction::Or { src: rhs, dst },
+                    BinaryOp::BitwiseXor => Instruction::Xor { src: rhs, dst },
+                    _ => Instruction::IMul { src: rhs, dst },
+                });
+                return;
+            }
+            BinaryOp::Divide | BinaryOp::Remainder => {
+                self.emit(Instruction::Mov {
+                    size: Size::Long,
+                    src: lhs,
+                    dst: Operand::Register(r!("rax")),
+                });
+                self.emit(Instruction::Cdq);
+                self.emit(Instruction::IDiv { divisor: rhs });
+                // The quotient is left in `eax`, the remainder in `edx`
+                let result = match op {
+                    BinaryOp::Divide => r!("rax"),
+                    _ => r!("rdx"),
+                };
+                self.emit(Instruction::Mov {
+                    size: Size::Long,
+                    src: Operand::Register(result),
+                    dst,
+                });
+                return;
+            }
+            BinaryOp::LeftShift | BinaryOp::RightShift => {
+                if lhs != dst {
+                    self.emit(Instruction::Mov {
+                        size: Size::Long,
+                        src: lhs,
+                        dst: dst.clone(),
+                    });
+                }
+                // A variable count has to be in `cl`. The hardware only
+                // looks at the low 5 bits of the count, so constants are
+                // cut down the same way to keep them encodable.
+                let count = match rhs {
+                    Operand::Immediate(count) => Operand::Immediate(count & 31),
+                    rhs => {
+                        self.emit(Instruction::Mov {
+                            size: Size::Long,
+                            src: rhs,
+                            dst: Operand::Register(r!("rcx")),
+                        });
+                        Operand::Register(r!("rcx"))
+                    }
+                };
+                self.emit(match op {
+                    BinaryOp::LeftShift => Instruction::Sal { count, dst },
+                    _ => Instruction::Sar { count, dst },
+                });
+                return;
+            }
+            BinaryOp::LessThan => CondCode::L,
+            BinaryOp::GreaterThan => CondCode::G,
+            BinaryOp::Equal => CondCode::E,
+            BinaryOp::NotEqual => CondCode::NE,
+            BinaryOp::LessThanOrEqual => CondCode::LE,
+            BinaryOp::GreaterThanOrEqual => CondCode::GE,
+        };
+        let cond = self.emit_comparison(cond, lhs, rhs);
+        self.emit_set_condition(cond, dst);
+    }
+
+    /// Compares `lhs` with `rhs` and returns the condition code for `cond`
+    /// to test afterwards. An immediate `lhs` is moved to the right, where
+    /// `cmp` can take it, and the condition is swapped accordingly.
+    fn emit_comparison(&mut self, cond: CondCode, lhs: Operand, rhs: Operand) -> CondCode {
+        if matches!(lhs, Operand::Immediate(_)) && !matches!(rhs, Operand::Immediate(_)) {
+            self.emit(Instruction::Cmp { src: lhs, dst: rhs });
+            cond.swap()
+        } else {
+            self.emit(Instruction::Cmp { src: rhs, dst: lhs });
+            cond
+        }
+    }
+
+    /// Sets the flags from comparing `operand` with 0.
+    fn emit_zero_test(&mut self, operand: Operand) {
+        match operand {
+            Operand::Immediate(_) => self.emit(Instruction::Cmp {
+                src: Operand::Immediate(0),
+                dst: operand,
+            }),
+            operand => self.emit(Instruction::Test {
+                src: operand.clone(),
+                dst: operand,
+            }),
+        }
+    }
+
+    /// Stores 1 into `dst` if `cond` holds for the preceding comparison,
+    /// 0 otherwise.
+    fn emit_set_condition(&mut self, cond: CondCode, dst: Operand) {
+        self.emit(Instruction::Mov {
+            size: Size::Long,
+            src: Operand::Immediate(0),
+            dst: dst.clone(),
+        });
+        self.emit(Instruction::SetCC { cond, dst });
+    }
+
+    fn generate_call(&mut self, name: &str, args: &[tacky::Value], dst: Option<&str>) {
+        let split = args.len().min(ARG_REGISTERS.len());
+        let (register_args, stack_args) = args.split_at(split);
+
+        // Keep the stack 16-byte aligned at the call
+        let padding = if stack_args.len() % 2 == 1 { 8 } else { 0 };
+        if padding != 0 {
+            self.emit(Instruction::AllocateStack(padding));
+        }
+
+        for (arg, reg) in register_args.iter().zip(ARG_REGISTERS) {
+            self.emit(Instruction::Mov {
+                size: Size::Long,
+                src: Self::operand(arg),
+                dst: Operand::Register(reg),
+            });
+        }
+
+        for arg in stack_args.iter().rev() {
+            match Self::operand(arg) {
+                operand @ (Operand::Immediate(_) | Operand::Register(_)) => {
+                    self.emit(Instruction::Push(operand))
+                }
+                operand => {
+                    self.emit(Instruction::Mov {
+                        size: Size::Long,
+                        src: operand,
+                        dst: Operand::Register(r!("rax")),
+                    });
+                    self.emit(Instruction::Push(Operand::Register(r!("rax"))));
+                }
+            }
+        }
+
+        self.emit(Instruction::Call {
+            name: self.call_target(name),
+            arg_registers: register_args.len(),
+        });
+
+        let bytes_to_remove = 8 * stack_args.len() as i64 + padding;
+        if bytes_to_remove != 0 {
+            self.emit(Instruction::DeallocateStack(bytes_to_remove));
+        }
+
+        if let Some(dst) = dst {
+            self.emit(Instruction::Mov {
+                size: Size::Long,
+                src: Operand::Register(r!("rax")),
+                dst: Operand::Pseudo(dst.to_string()),
+            });
+        }
+    }
+
+    /// Symbol to call the function `name` through: its PLT entry when the
+    /// relocation model does not let us call it directly.
+    fn call_target(&self, name: &str) -> String {
+        let symbol = IRFuncDef::platfrom_mangle_name(name);
+        let direct = match self.options.relocation_model {
+            RelocationModel::Static => true,
+            RelocationModel::Pie => self.functions.contains_key(name),
+            RelocationModel::Pic => self.functions.get(name) == Some(&false),
+        };
+        // Mach-O has no PLT: the linker adds stubs for calls to other images
+        if direct || cfg!(target_os = "macos") {
+            symbol
+        } else {
+            format!("{}@PLT", symbol)
+        }
+    }
+
+    fn operand(value: &tacky::Value) -> Operand {
+        match value {
+            tacky::Value::Constant(val) => Operand::Immediate(*val),
+            tacky::Value::Var(name) => Operand::Pseudo(name.clone()),
+        }
+    }
+}
+
+impl Default for CodeGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tacky_base::Value;
+
+    fn generate(body: Vec<tacky::Instruction>) -> Vec<Instruction> {
+        generate_with(body, CodegenOptions::default())
+    }
+
+    fn generate_with(body: Vec<tacky::Instruction>, options: CodegenOptions) -> Vec<Instruction> {
+        let program = TackyProgram {
+            functions: vec![TackyFuncDef {
+                name: "main".into(),
+                is_global: true,
+                params: vec![],
+                body,
+            }],
+        };
+        CodeGenerator::new()
+            .with_options(options)
+            .generate(&program)
+            .functions
+            .remove(0)
+            .instructions
+    }
+
+    #[test]
+    fn test_generate_return_constant() {
+        let instructions = generate(vec![tacky::Instruction::Return(Value::Constant(2))]);
+        assert_eq!(
+            instructions,
+            vec![
+                Instruction::Push(Operand::Register(r!("rbp"))),
+                Instruction::Mov {
+                    size: Size::Quad,
+                    src: Operand::Register(r!("rsp")),
+                    dst: Operand::Register(r!("rbp")),
+                },
+                Instruction::Mov {
+                    size: Size::Long,
+                    src: Operand::Immediate(2),
+                    dst: Operand::Register(r!("rax")),
+                },
+                Instruction::Mov {
+                    size: Size::Quad,
+                    src: Operand::Register(r!("rbp")),
+                    dst: Operand::Register(r!("rsp")),
+                },
+                Instruction::Pop(Operand::Register(r!("rbp"))),
+                Instruction::Ret,
+            ]
+        );
+    }
+
+    fn copy_and_return() -> Vec<tacky::Instruction> {
+        vec![
+            tacky::Instruction::Copy {
+                src: Value::Constant(1),
+                dst: "x".into(),
+            },
+            tacky::Instruction::Return(Value::Var("x".into())),
+        ]
+    }
+
+    #[test]
+    fn test_generate_allocates_aligned_stack_for_variables() {
+        let options = CodegenOptions {
+            red_zone: false,
+            ..CodegenOptions::default()
+        };
+        let instructions = generate_with(copy_and_return(), options);
+        assert_eq!(instructions[2], Instruction::AllocateStack(16));
+        assert!(
+            instructions
+                .iter()
+                .all(|inst| !format!("{:?}", inst).contains("Pseudo"))
+        );
+    }
+
+    #[test]
+    fn test_leaf_function_keeps_variables_in_red_zone() {
+        let instructions = generate(copy_and_return());
+        assert!(
+            !instructions
+                .iter()
+                .any(|inst| matches!(inst, Instruction::AllocateStack(_)))
+        );
+        assert!(instructions.contains(&Instruction::Mov {
+            size: Size::Long,
+            src: Operand::Immediate(1),
+            dst: Operand::memory(r!("rbp"), -4),
+        }));
+    }
+
+    #[test]
+    fn test_omitted_frame_pointer_addresses_variables_from_rsp() {
+        let mut body = copy_and_return();
+        body.insert(
+            1,
+            tacky::Instruction::FunCall {
+                name: "f".into(),
+                args: vec![],
+                dst: None,
+            },
+        );
+        let options = CodegenOptions {
+            omit_frame_pointer: true,
+            ..CodegenOptions::default()
+        };
+        let instructions = generate_with(body, options);
+        // 8 bytes keep `rsp` aligned at the call, and hold `x`
+        assert_eq!(instructions[0], Instruction::AllocateStack(8));
+        assert_eq!(
+            instructions[1],
+            Instruction::Mov {
+                size: Size::Long,
+                src: Operand::Immediate(1),
+                dst: Operand::memory(r!("rsp"), 4),
+            }
+        );
+        assert_eq!(
+            instructions[instructions.len() - 2..],
+            [Instruction::DeallocateStack(8), Instruction::Ret]
+        );
+        assert!(
+            instructions
+                .iter()
+                .all(|inst| !format!("{:?}", inst).contains("RBP"))
+        );
+    }
+
+    fn binary(op: BinaryOp, lhs: Value, rhs: Value, dst: &str) -> tacky::Instruction {
+        tacky::Instruction::Binary {
+            op,
+            lhs,
+            rhs,
+            dst: dst.into(),
+        }
+    }
+
+    #[test]
+    fn test_remainder_is_taken_from_edx() {
+        let instructions = generate(vec![
+            binary(
+                BinaryOp::Remainder,
+                Value::Var("a".into()),
+                Value::Var("b".into()),
+                "r",
+            ),
+            tacky::Instruction::Return(Value::Var("r".into())),
+        ]);
+        let idiv = instructions
+            .iter()
+            .position(|inst| matches!(inst, Instruction::IDiv { .. }))
+            .unwrap();
+        assert!(matches!(
+            instructions[idiv + 1],
+            Instruction::Mov {
+                src: Operand::Register(r!("rdx")),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_update_in_place_adds_to_the_variable() {
+        let instructions = generate(vec![
+            tacky::Instruction::Copy {
+                src: Value::Constant(1),
+                dst: "x".into(),
+            },
+            binary(
+                BinaryOp::Subtract,
+                Value::Var("x".into()),
+                Value::Constant(1),
+                "x",
+            ),
+            tacky::Instruction::Return(Value::Var("x".into())),
+        ]);
+        let x = Operand::memory(r!("rbp"), -4);
+        let sub = instructions
+            .iter()
+            .position(|inst| matches!(inst, Instruction::Sub { .. }))
+            .unwrap();
+        assert_eq!(
+            instructions[sub],
+            Instruction::Sub {
+                src: Operand::Immediate(1),
+                dst: x.clone(),
+            }
+        );
+        assert_eq!(
+            instructions[sub - 1],
+            Instruction::Mov {
+                size: Size::Long,
+                src: Operand::Immediate(1),
+                dst: x,
+            }
+        );
+    }
+
+    #[test]
+    fn test_shift_count_is_an_immediate_or_cl() {
+        let instructions = generate(vec![
+            binary(
+                BinaryOp::LeftShift,
+                Value::Var("a".into()),
+                Value::Var("b".into()),
+                "l",
+            ),
+            binary(
+                BinaryOp::RightShift,
+                Value::Var("l".into()),
+                Value::Constant(33),
+                "r",
+            ),
+            tacky::Instruction::Return(Value::Var("r".into())),
+        ]);
+        let sal = instructions
+            .iter()
+            .position(|inst| matches!(inst, Instruction::Sal { .. }))
+            .unwrap();
+        assert!(matches!(
+            &instructions[sal - 1..=sal],
+            [
+                Instruction::Mov {
+                    dst: Operand::Register(r!("rcx")),
+                    ..
+                },
+                Instruction::Sal {
+                    count: Operand::Register(r!("rcx")),
+                    ..
+                },
+            ]
+        ));
+        // Only the low 5 bits of the count matter
+        assert!(instructions.iter().any(|inst| matches!(
+            inst,
+            Instruction::Sar {
+                count: Operand::Immediate(1),
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn test_generate_call_with_stack_arguments() {
+        let args = (1..=7).map(Value::Constant).collect();
+        let instructions = generate(vec![
+            tacky::Instruction::FunCall {
+                name: "f".into(),
+                args,
+                dst: None,
+            },
+            tacky::Instruction::Return(Value::Constant(0)),
+        ]);
+        // one stack argument: padded to 16 bytes and popped afterwards
+        assert!(instructions.contains(&Instruction::AllocateStack(8)));
+        assert!(instructions.contains(&Instruction::Push(Operand::Immediate(7))));
+        assert!(instructions.contains(&Instruction::DeallocateStack(16)));
+    }
+
+    #[test]
+    #[cfg(not(target_os = "macos"))]
+    fn test_calls_go_through_plt_where_the_relocation_model_requires() {
+        let call = |name: &str| tacky::Instruction::FunCall {
+            name: name.into(),
+            args: vec![],
+            dst: None,
+        };
+        let body = vec![
+            call("main"),
+            call("putchar"),
+            tacky::Instruction::Return(Value::Constant(0)),
+        ];
+        let targets = |relocation_model| -> Vec<String> {
+            let options = CodegenOptions {
+                relocation_model,
+                ..CodegenOptions::default()
+            };
+            generate_with(body.clone(), options)
+                .into_iter()
+                .filter_map(|inst| match inst {
+                    Instruction::Call { name, .. } => Some(name),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(targets(RelocationModel::Static), ["main", "putchar"]);
+        assert_eq!(targets(RelocationModel::Pie), ["main", "putchar@PLT"]);
+        // A global function may be replaced by another module's
+        assert_eq!(targets(RelocationModel::Pic), ["main@PLT", "putchar@PLT"]);
+    }
+
+    #[test]
+    fn test_value_live_across_call_is_kept_in_callee_saved_register() {
+        let program = TackyProgram {
+            functions: vec![TackyFuncDef {
+                name: "main".into(),
+                is_global: true,
+                params: vec![],
+                body: vec![
+                    tacky::Instruction::Copy {
+                        src: Value::Constant(1),
+                        dst: "x".into(),
+                    },
+                    tacky::Instruction::FunCall {
+                        name: "f".into(),
+                        args: vec![],
+                        dst: None,
+                    },
+                    tacky::Instruction::Return(Value::Var("x".into())),
+                ],
+            }],
+        };
+        for allocator in [RegAllocator::Linear, RegAllocator::Graph] {
+            let instructions = CodeGenerator::new()
+                .with_options(CodegenOptions {
+                    register_allocator: Some(allocator),
+                    ..CodegenOptions::default()
+                })
+                .generate(&program)
+                .functions
+                .remove(0)
+                .instructions;
+
+            let save = Instruction::Mov {
+                size: Size::Quad,
+                src: Operand::Register(r!("rbx")),
+                dst: Operand::memory(r!("rbp"), -8),
+            };
+            let restore = Instruction::Mov {
+                size: Size::Quad,
+                src: Operand::memory(r!("rbp"), -8),
+                dst: Operand::Register(r!("rbx")),
+            };
+            assert_eq!(instructions[2], Instruction::AllocateStack(16));
+            assert_eq!(instructions[3], save);
+            assert!(instructions.contains(&Instruction::Mov {
+                size: Size::Long,
+                src: Operand::Immediate(1),
+                dst: Operand::Register(r!("rbx")),
+            }));
+            let teardown = instructions.len() - 3;
+            assert_eq!(instructions[teardown - 1], restore);
         }
     }
 }
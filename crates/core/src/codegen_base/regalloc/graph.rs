@@ -0,0 +1,462 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    codegen_base::regalloc::{
+        Loc,
+        liveness::{live_out, uses_and_defs},
+    },
+    ir_base::{Instruction, Operand, reg::PhyRegister},
+};
+
+/// Graph-coloring register allocation (Chaitin-Briggs, with George and
+/// Appel's conservative coalescing).
+///
+/// The interference graph has a node per pseudo and per register of
+/// `registers`, the `K` colors. The registers are precolored, which takes
+/// care of the argument registers, of `rax` and `rdx` around `idiv`, and of
+/// the registers a call clobbers. Then:
+///
+/// 1. **Coalesce**: the two sides of a `mov` that do not interfere are
+///    merged into one node when that cannot make the graph harder to color:
+///    the merged node has fewer than `K` neighbors of significant degree
+///    (Briggs), or, when merging into a register, every neighbor of the
+///    pseudo already interferes with the register or has a low degree
+///    (George). The `mov` then disappears.
+/// 2. **Simplify**: nodes with fewer than `K` neighbors are removed from
+///    the graph one after the other, as they can always be colored once
+///    their neighbors are. When only nodes of higher degree remain, the
+///    one with the lowest spill cost for its degree is removed anyway,
+///    hoping it will still find a color.
+/// 3. **Select**: nodes are put back in reverse order, each taking a color
+///    none of its neighbors has. A node without one is spilled.
+///
+/// The spill cost of a pseudo counts its uses and definitions, each weighed
+/// by ten to the power of its loop depth.
+///
+/// Returns the register assigned to each pseudo that is not spilled.
+pub(super) fn allocate(
+    instructions: &[Instruction],
+    registers: &[PhyRegister],
+) -> HashMap<String, PhyRegister> {
+    let mut graph = InterferenceGraph::build(instructions, registers);
+    graph.coalesce();
+    graph.color()
+}
+
+#[derive(Default)]
+struct InterferenceGraph<'a> {
+    /// Colors, i.e. registers available to pseudos
+    registers: &'a [PhyRegister],
+    adjacent: HashMap<Loc, HashSet<Loc>>,
+    /// Operands of the moves between two nodes, candidates for coalescing
+    moves: Vec<(Loc, Loc)>,
+    spill_costs: HashMap<String, f64>,
+    /// Node each coalesced node was merged into
+    alias: HashMap<Loc, Loc>,
+}
+
+impl<'a> InterferenceGraph<'a> {
+    fn build(instructions: &[Instruction], registers: &'a [PhyRegister]) -> Self {
+        let live_out = live_out(instructions);
+        let depths = loop_depths(instructions);
+        let mut graph = InterferenceGraph {
+            registers,
+            ..Default::default()
+        };
+        for &reg in registers {
+            graph.adjacent.entry(Loc::Reg(reg)).or_default();
+        }
+
+        for (idx, inst) in instructions.iter().enumerate() {
+            let (uses, defs) = uses_and_defs(inst);
+            let weight = 10f64.powi(depths[idx] as i32);
+            for loc in uses.iter().chain(&defs) {
+                if let Loc::Pseudo(name) = loc {
+                    *graph.spill_costs.entry(name.clone()).or_default() += weight;
+                    graph.adjacent.entry(loc.clone()).or_default();
+                }
+            }
+
+            // The destination of a move may share a register with its
+            // source, as they hold the same value
+            let move_src = match inst {
+                Instruction::Mov { src, dst, .. } => {
+                    match (as_node(src, registers), as_node(dst, registers)) {
+                        (Some(src), Some(dst)) => {
+                            graph.moves.push((src.clone(), dst));
+                            Some(src)
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+
+            for def in defs.iter().filter(|loc| is_node(loc, registers)) {
+                for live in live_out[idx].iter().filter(|loc| is_node(loc, registers)) {
+                    if live != def && Some(live) != move_src.as_ref() {
+                        graph.add_edge(def, live);
+                    }
+                }
+            }
+        }
+        graph
+    }
+
+    /// Number of colors
+    fn k(&self) -> usize {
+        self.registers.len()
+    }
+
+    fn add_edge(&mut self, a: &Loc, b: &Loc) {
+        self.adjacent
+            .entry(a.clone())
+            .or_default()
+            .insert(b.clone());
+        self.adjacent
+            .entry(b.clone())
+            .or_default()
+            .insert(a.clone());
+    }
+
+    fn resolve(&self, loc: &Loc) -> Loc {
+        let mut loc = loc;
+        while let Some(next) = self.alias.get(loc) {
+            loc = next;
+        }
+        loc.clone()
+    }
+
+    fn degree(&self, loc: &Loc) -> usize {
+        match loc {
+            Loc::Reg(_) => usize::MAX,
+            Loc::Pseudo(_) => self.adjacent[loc].len(),
+        }
+    }
+
+    /// Merges move-related nodes until no move can be coalesced safely.
+    fn coalesce(&mut self) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for idx in 0..self.moves.len() {
+                let (a, b) = &self.moves[idx];
+                let (a, b) = (self.resolve(a), self.resolve(b));
+                if a == b || self.adjacent[&a].contains(&b) {
+                    continue;
+                }
+                // Keep registers as the surviving node
+                let (keep, merge) = match (&a, &b) {
+                    (Loc::Reg(_), Loc::Reg(_)) => continue,
+                    (_, Loc::Reg(_)) => (b, a),
+                    _ => (a, b),
+                };
+                let safe = match keep {
+                    Loc::Reg(_) => self.george(&keep, &merge),
+                    Loc::Pseudo(_) => self.briggs(&keep, &merge),
+                };
+                if safe {
+                    self.combine(keep, merge);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    /// Whether every neighbor of `merge` either interferes with `keep`
+    /// already, has a low degree or is a register, whose color is fixed.
+    fn george(&self, keep: &Loc, merge: &Loc) -> bool {
+        self.adjacent[merge].iter().all(|neighbor| {
+            self.adjacent[keep].contains(neighbor)
+                || matches!(neighbor, Loc::Reg(_))
+                || self.degree(neighbor) < self.k()
+        })
+    }
+
+    /// Whether the merged node would have fewer than `K` neighbors of
+    /// significant degree.
+    fn briggs(&self, a: &Loc, b: &Loc) -> bool {
+        let neighbors: HashSet<&Loc> = self.adjacent[a].iter().chain(&self.adjacent[b]).collect();
+        let significant = neighbors
+            .into_iter()
+            .filter(|neighbor| {
+                // A common neighbor loses one edge in the merge
+                let shared =
+                    self.adjacent[a].contains(*neighbor) && self.adjacent[b].contains(*neighbor);
+                self.degree(neighbor).saturating_sub(shared as usize) >= self.k()
+            })
+            .count();
+        significant < self.k()
+    }
+
+    fn combine(&mut self, keep: Loc, merge: Loc) {
+        let neighbors = self.adjacent.remove(&merge).unwrap_or_default();
+        for neighbor in neighbors {
+            let adjacent = self.adjacent.get_mut(&neighbor).unwrap();
+            adjacent.remove(&merge);
+            adjacent.insert(keep.clone());
+            self.adjacent.get_mut(&keep).unwrap().insert(neighbor);
+        }
+        if let (Loc::Pseudo(keep), Loc::Pseudo(merge)) = (&keep, &merge) {
+            let cost = self.spill_costs.remove(merge).unwrap_or_default();
+            *self.spill_costs.entry(keep.clone()).or_default() += cost;
+        }
+        self.alias.insert(merge, keep);
+    }
+
+    fn color(&self) -> HashMap<String, PhyRegister> {
+        let mut remaining: HashSet<&Loc> = self
+            .adjacent
+            .keys()
+            .filter(|loc| matches!(loc, Loc::Pseudo(_)))
+            .collect();
+        let mut degrees: HashMap<&Loc, usize> = self
+            .adjacent
+            .iter()
+            .map(|(loc, adjacent)| (loc, adjacent.len()))
+            .collect();
+
+        let mut stack = Vec::with_capacity(remaining.len());
+        while !remaining.is_empty() {
+            let low_degree = remaining
+                .iter()
+                .filter(|loc| degrees[*loc] < self.k())
+                .min_by(|a, b| by_name(a, b))
+                .copied();
+            let node = low_degree.unwrap_or_else(|| {
+                let spill_metric = |loc: &Loc| match loc {
+                    Loc::Pseudo(name) => self.spill_costs[name] / degrees[loc] as f64,
+                    Loc::Reg(_) => f64::INFINITY,
+                };
+                remaining
+                    .iter()
+                    .copied()
+                    .min_by(|a, b| {
+                        spill_metric(a)
+                            .total_cmp(&spill_metric(b))
+                            .then_with(|| by_name(a, b))
+                    })
+                    .unwrap()
+            });
+
+            remaining.remove(node);
+            for neighbor in &self.adjacent[node] {
+                if let Some(degree) = degrees.get_mut(neighbor) {
+                    *degree -= 1;
+                }
+            }
+            stack.push(node);
+        }
+
+        let mut colors: HashMap<&Loc, PhyRegister> = self
+            .registers
+            .iter()
+            .map(|reg| {
+                let loc = self.adjacent.get_key_value(&Loc::Reg(*reg)).unwrap().0;
+                (loc, *reg)
+            })
+            .collect();
+        while let Some(node) = stack.pop() {
+            let taken: HashSet<PhyRegister> = self.adjacent[node]
+                .iter()
+                .filter_map(|neighbor| colors.get(neighbor).copied())
+                .collect();
+            if let Some(reg) = self
+                .registers
+                .iter()
+                .copied()
+                .find(|reg| !taken.contains(reg))
+            {
+                colors.insert(node, reg);
+            }
+        }
+
+        self.spill_costs
+            .keys()
+            .chain(self.alias.keys().filter_map(|loc| match loc {
+                Loc::Pseudo(name) => Some(name),
+                Loc::Reg(_) => None,
+            }))
+            .filter_map(|name| {
+                let node = self.resolve(&Loc::Pseudo(name.clone()));
+                let reg = match node {
+                    Loc::Reg(reg) => reg,
+                    node => *colors.get(&node)?,
+                };
+                Some((name.clone(), reg))
+            })
+            .collect()
+    }
+}
+
+/// Deterministic order between nodes, so the output does not depend on
+/// hashing.
+fn by_name(a: &Loc, b: &Loc) -> std::cmp::Ordering {
+    match (a, b) {
+        (Loc::Pseudo(a), Loc::Pseudo(b)) => a.cmp(b),
+        _ => std::cmp::Ordering::Equal,
+    }
+}
+
+fn as_node(operand: &Operand, registers: &[PhyRegister]) -> Option<Loc> {
+    let loc = match operand {
+        Operand::Pseudo(name) => Loc::Pseudo(name.clone()),
+        Operand::Register(reg) => Loc::Reg(*reg),
+        _ => return None,
+    };
+    is_node(&loc, registers).then_some(loc)
+}
+
+/// Whether `loc` takes part in allocation: stack and scratch registers
+/// do not.
+fn is_node(loc: &Loc, registers: &[PhyRegister]) -> bool {
+    match loc {
+        Loc::Pseudo(_) => true,
+        Loc::Reg(reg) => registers.contains(reg),
+    }
+}
+
+/// Loop nesting depth of each instruction, from the backward jumps that
+/// enclose it.
+fn loop_depths(instructions: &[Instruction]) -> Vec<usize> {
+    let labels: HashMap<&str, usize> = instructions
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, inst)| match inst {
+            Instruction::Label(label) => Some((label.as_str(), idx)),
+            _ => None,
+        })
+        .collect();
+    let mut depths = vec![0; instructions.len()];
+    for (idx, inst) in instructions.iter().enumerate() {
+        if let Instruction::Jmp(target) | Instruction::JmpCC { target, .. } = inst {
+            let head = labels[target.as_str()];
+            if head <= idx {
+                for depth in &mut depths[head..=idx] {
+                    *depth += 1;
+                }
+            }
+        }
+    }
+    depths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{codegen_base::regalloc::ALLOCATABLE, ir_base::Size, r};
+
+    const K: usize = ALLOCATABLE.len();
+
+    fn pseudo(name: &str) -> Operand {
+        Operand::Pseudo(name.into())
+    }
+
+    fn mov(src: Operand, dst: Operand) -> Instruction {
+        Instruction::Mov {
+            size: Size::Long,
+            src,
+            dst,
+        }
+    }
+
+    fn add(src: Operand, dst: Operand) -> Instruction {
+        Instruction::Add { src, dst }
+    }
+
+    #[test]
+    fn test_moves_are_coalesced_into_argument_and_return_registers() {
+        // x = edi; y = x; y += 1; eax = y; ret
+        let instructions = vec![
+            mov(Operand::Register(r!("rdi")), pseudo("x")),
+            mov(pseudo("x"), pseudo("y")),
+            add(Operand::Immediate(1), pseudo("y")),
+            mov(pseudo("y"), Operand::Register(r!("rax"))),
+            Instruction::Ret,
+        ];
+        // `x` is merged with `rdi` and `y` with `x`; only one of the moves
+        // in and out of `y` can go away
+        let assignment = allocate(&instructions, &ALLOCATABLE);
+        assert_eq!(assignment["x"], r!("rdi"));
+        assert_eq!(assignment["y"], r!("rdi"));
+    }
+
+    #[test]
+    fn test_interfering_values_get_different_registers() {
+        let instructions = vec![
+            mov(Operand::Immediate(1), pseudo("a")),
+            mov(Operand::Immediate(2), pseudo("b")),
+            add(pseudo("b"), pseudo("a")),
+            mov(pseudo("a"), Operand::Register(r!("rax"))),
+            Instruction::Ret,
+        ];
+        let assignment = allocate(&instructions, &ALLOCATABLE);
+        assert_ne!(assignment["a"], assignment["b"]);
+    }
+
+    #[test]
+    fn test_divisor_avoids_rax_and_rdx() {
+        let instructions = vec![
+            mov(Operand::Register(r!("rdi")), pseudo("n")),
+            mov(Operand::Register(r!("rsi")), pseudo("d")),
+            mov(pseudo("n"), Operand::Register(r!("rax"))),
+            Instruction::Cdq,
+            Instruction::IDiv {
+                divisor: pseudo("d"),
+            },
+            Instruction::Ret,
+        ];
+        let assignment = allocate(&instructions, &ALLOCATABLE);
+        assert_ne!(assignment["d"], r!("rax"));
+        assert_ne!(assignment["d"], r!("rdx"));
+    }
+
+    #[test]
+    fn test_value_live_across_call_is_not_in_caller_saved_register() {
+        let instructions = vec![
+            mov(Operand::Immediate(1), pseudo("x")),
+            Instruction::Call {
+                name: "f".into(),
+                arg_registers: 0,
+            },
+            mov(pseudo("x"), Operand::Register(r!("rax"))),
+            Instruction::Ret,
+        ];
+        let assignment = allocate(&instructions, &ALLOCATABLE);
+        assert!(crate::codegen_base::regalloc::CALLEE_SAVED.contains(&assignment["x"]));
+    }
+
+    #[test]
+    fn test_cheapest_value_is_spilled() {
+        // K + 1 values live at once; `cold` is used once, the others in a loop
+        let mut instructions = vec![mov(Operand::Immediate(0), pseudo("cold"))];
+        for idx in 0..K {
+            instructions.push(mov(
+                Operand::Immediate(idx as i64),
+                pseudo(&format!("hot{}", idx)),
+            ));
+        }
+        instructions.push(Instruction::Label("loop".into()));
+        for idx in 0..K {
+            instructions.push(add(Operand::Immediate(1), pseudo(&format!("hot{}", idx))));
+        }
+        instructions.push(Instruction::Cmp {
+            src: Operand::Immediate(0),
+            dst: pseudo("hot0"),
+        });
+        instructions.push(Instruction::JmpCC {
+            cond: crate::ir_base::CondCode::NE,
+            target: "loop".into(),
+        });
+        instructions.push(add(pseudo("cold"), pseudo("hot0")));
+        for idx in 1..K {
+            instructions.push(add(pseudo(&format!("hot{}", idx)), pseudo("hot0")));
+        }
+        instructions.push(mov(pseudo("hot0"), Operand::Register(r!("rax"))));
+        instructions.push(Instruction::Ret);
+
+        let assignment = allocate(&instructions, &ALLOCATABLE);
+        assert!(!assignment.contains_key("cold"));
+        assert!((0..K).all(|idx| assignment.contains_key(&format!("hot{}", idx))));
+    }
+}
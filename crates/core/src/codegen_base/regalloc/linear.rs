@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use crate::{codegen_base::regalloc::liveness::LiveRanges, ir_base::reg::PhyRegister};
+
+/// Linear-scan register allocation (Poletto and Sarkar).
+///
+/// Pseudo registers are visited by increasing start of their live range.
+/// Each one takes a register that no pseudo still live holds and that is
+/// not otherwise in use during its range, trying first the registers it is
+/// moved from or to. When there is none, whichever of the pseudo and the
+/// live pseudos ends last, and could use the register, goes to the stack,
+/// as it would hold its register the longest.
+///
+/// Returns the register of `registers` assigned to each pseudo that is not
+/// spilled.
+pub(super) fn allocate(
+    ranges: &LiveRanges,
+    registers: &[PhyRegister],
+) -> HashMap<String, PhyRegister> {
+    let mut order: Vec<(&str, (usize, usize))> = ranges
+        .pseudos
+        .iter()
+        .map(|(name, range)| (name.as_str(), *range))
+        .collect();
+    order.sort_by_key(|&(name, (start, end))| (start, end, name));
+
+    let mut assignment: HashMap<String, PhyRegister> = HashMap::new();
+    // Pseudos currently holding a register, with the end of their range
+    let mut active: Vec<(&str, usize, PhyRegister)> = Vec::new();
+
+    for (name, range) in order {
+        active.retain(|&(_, end, _)| end >= range.0);
+
+        let hints = ranges.hints.get(name).into_iter().flatten();
+        let free = hints.chain(registers).copied().find(|&reg| {
+            registers.contains(&reg)
+                && !active.iter().any(|&(_, _, taken)| taken == reg)
+                && !ranges.register_busy(reg, range)
+        });
+        if let Some(reg) = free {
+            assignment.insert(name.to_string(), reg);
+            active.push((name, range.1, reg));
+            continue;
+        }
+
+        let victim = active
+            .iter()
+            .enumerate()
+            .filter(|&(_, &(_, end, reg))| end > range.1 && !ranges.register_busy(reg, range))
+            .max_by_key(|&(_, &(_, end, _))| end)
+            .map(|(idx, _)| idx);
+        if let Some(idx) = victim {
+            let (spilled, _, reg) = active.swap_remove(idx);
+            assignment.remove(spilled);
+            assignment.insert(name.to_string(), reg);
+            active.push((name, range.1, reg));
+        }
+    }
+
+    assignment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{codegen_base::regalloc::ALLOCATABLE, r};
+
+    fn ranges(pseudos: &[(&str, usize, usize)]) -> LiveRanges {
+        LiveRanges {
+            pseudos: pseudos
+                .iter()
+                .map(|&(name, start, end)| (name.to_string(), (start, end)))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_disjoint_ranges_share_a_register() {
+        let assignment = allocate(&ranges(&[("a", 1, 4), ("b", 5, 8)]), &ALLOCATABLE);
+        assert_eq!(assignment["a"], assignment["b"]);
+    }
+
+    #[test]
+    fn test_overlapping_ranges_get_different_registers() {
+        let assignment = allocate(&ranges(&[("a", 1, 6), ("b", 3, 8)]), &ALLOCATABLE);
+        assert_ne!(assignment["a"], assignment["b"]);
+    }
+
+    #[test]
+    fn test_longest_range_is_spilled() {
+        // One more pseudo than there are registers, all live together
+        let mut pseudos: Vec<(String, usize, usize)> = (0..ALLOCATABLE.len())
+            .map(|idx| (format!("short{}", idx), 2 + idx, 100))
+            .collect();
+        pseudos.push(("long".into(), 1, 1000));
+        let pseudos: Vec<(&str, usize, usize)> = pseudos
+            .iter()
+            .map(|(name, start, end)| (name.as_str(), *start, *end))
+            .collect();
+
+        let assignment = allocate(&ranges(&pseudos), &ALLOCATABLE);
+        assert_eq!(assignment.len(), ALLOCATABLE.len());
+        assert!(!assignment.contains_key("long"));
+    }
+
+    #[test]
+    fn test_busy_register_is_avoided_and_hint_is_followed() {
+        let mut ranges = ranges(&[("a", 1, 6), ("b", 7, 9)]);
+        ranges.registers.insert(ALLOCATABLE[0], [4].into());
+        ranges.hints.insert("b".into(), vec![r!("rdi")]);
+        let assignment = allocate(&ranges, &ALLOCATABLE);
+        assert_ne!(assignment["a"], ALLOCATABLE[0]);
+        assert_eq!(assignment["b"], r!("rdi"));
+    }
+}
@@ -0,0 +1,300 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::{
+    codegen_base::{
+        ARG_REGISTERS,
+        regalloc::{CALLER_SAVED, Loc},
+    },
+    ir_base::{Instruction, Operand, reg::PhyRegister},
+    r,
+};
+
+/// Locations read and written by `inst`. Registers that `inst` uses
+/// implicitly, like `rax` and `rdx` for `idiv` or the registers a call
+/// clobbers, are included.
+pub(in crate::codegen_base) fn uses_and_defs(inst: &Instruction) -> (Vec<Loc>, Vec<Loc>) {
+    let mut uses = Vec::new();
+    let mut defs = Vec::new();
+    let reg = |reg: PhyRegister| Loc::Reg(reg);
+
+    match inst {
+        Instruction::Mov { src, dst, .. } => {
+            read(src, &mut uses);
+            write(dst, &mut uses, &mut defs);
+        }
+        Instruction::Add { src, dst }
+        | Instruction::Sub { src, dst }
+        | Instruction::IMul { src, dst }
+        | Instruction::And { src, dst }
+        | Instruction::Or { src, dst }
+        | Instruction::Xor { src, dst }
+        | Instruction::Sal { count: src, dst }
+        | Instruction::Sar { count: src, dst }
+        | Instruction::Cmov { src, dst, .. } => {
+            read(src, &mut uses);
+            read(dst, &mut uses);
+            write(dst, &mut uses, &mut defs);
+        }
+        Instruction::Lea {
+            base, index, dst, ..
+        } => {
+            for operand in base.iter().chain(index) {
+                read(operand, &mut uses);
+            }
+            write(dst, &mut uses, &mut defs);
+        }
+        Instruction::Cmp { src, dst } | Instruction::Test { src, dst } => {
+            read(src, &mut uses);
+            read(dst, &mut uses);
+        }
+        // `set` only writes the low byte, so the rest of `dst` is kept
+        Instruction::SetCC { dst, .. } | Instruction::Neg { dst } | Instruction::Not { dst } => {
+            read(dst, &mut uses);
+            write(dst, &mut uses, &mut defs);
+        }
+        Instruction::IDiv { divisor } => {
+            read(divisor, &mut uses);
+            uses.extend([reg(r!("rax")), reg(r!("rdx"))]);
+            defs.extend([reg(r!("rax")), reg(r!("rdx"))]);
+        }
+        Instruction::Cdq => {
+            uses.push(reg(r!("rax")));
+            defs.push(reg(r!("rdx")));
+        }
+        Instruction::Push(operand) => read(operand, &mut uses),
+        Instruction::Pop(operand) => write(operand, &mut uses, &mut defs),
+        Instruction::Call { arg_registers, .. } => {
+            uses.extend(ARG_REGISTERS[..*arg_registers].iter().copied().map(reg));
+            defs.extend(CALLER_SAVED.iter().copied().map(reg));
+        }
+        Instruction::TailCall { arg_registers, .. } => {
+            uses.extend(ARG_REGISTERS[..*arg_registers].iter().copied().map(reg));
+        }
+        Instruction::Ret => uses.push(reg(r!("rax"))),
+        Instruction::Jmp(_)
+        | Instruction::JmpCC { .. }
+        | Instruction::Label(_)
+        | Instruction::AllocateStack(_)
+        | Instruction::DeallocateStack(_)
+        | Instruction::Loc { .. } => {}
+    }
+    (uses, defs)
+}
+
+fn read(operand: &Operand, uses: &mut Vec<Loc>) {
+    match operand {
+        Operand::Pseudo(name) => uses.push(Loc::Pseudo(name.clone())),
+        Operand::Register(reg) => uses.push(Loc::Reg(*reg)),
+        Operand::Memory { base, index, .. } => {
+            uses.extend(base.iter().chain(index).map(|reg| Loc::Reg(*reg)))
+        }
+        Operand::Immediate(_) => {}
+    }
+}
+
+fn write(operand: &Operand, uses: &mut Vec<Loc>, defs: &mut Vec<Loc>) {
+    match operand {
+        Operand::Pseudo(name) => defs.push(Loc::Pseudo(name.clone())),
+        Operand::Register(reg) => defs.push(Loc::Reg(*reg)),
+        // Writing to memory reads the address
+        operand => read(operand, uses),
+    }
+}
+
+/// Successors of each instruction.
+fn successors(instructions: &[Instruction]) -> Vec<Vec<usize>> {
+    let labels: HashMap<&str, usize> = instructions
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, inst)| match inst {
+            Instruction::Label(label) => Some((label.as_str(), idx)),
+            _ => None,
+        })
+        .collect();
+    let next = |idx: usize| (idx + 1 < instructions.len()).then_some(idx + 1);
+
+    instructions
+        .iter()
+        .enumerate()
+        .map(|(idx, inst)| match inst {
+            Instruction::Jmp(target) => vec![labels[target.as_str()]],
+            Instruction::JmpCC { target, .. } => {
+                let mut succs = vec![labels[target.as_str()]];
+                succs.extend(next(idx));
+                succs
+            }
+            Instruction::Ret | Instruction::TailCall { .. } => vec![],
+            _ => next(idx).into_iter().collect(),
+        })
+        .collect()
+}
+
+/// Locations live right after each instruction.
+pub(super) fn live_out(instructions: &[Instruction]) -> Vec<HashSet<Loc>> {
+    let succs = successors(instructions);
+    let uses_defs: Vec<_> = instructions.iter().map(uses_and_defs).collect();
+    let mut live_in: Vec<HashSet<Loc>> = vec![HashSet::new(); instructions.len()];
+    let mut live_out: Vec<HashSet<Loc>> = vec![HashSet::new(); instructions.len()];
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for idx in (0..instructions.len()).rev() {
+            let out: HashSet<Loc> = succs[idx]
+                .iter()
+                .flat_map(|&succ| live_in[succ].iter().cloned())
+                .collect();
+            let (uses, defs) = &uses_defs[idx];
+            let mut inn: HashSet<Loc> = out
+                .iter()
+                .filter(|loc| !defs.contains(loc))
+                .cloned()
+                .collect();
+            inn.extend(uses.iter().cloned());
+
+            if inn != live_in[idx] {
+                live_in[idx] = inn;
+                changed = true;
+            }
+            live_out[idx] = out;
+        }
+    }
+    live_out
+}
+
+/// Where values live, on a numbering of points with two points per
+/// instruction: instruction `i` reads its operands at `2i` and writes its
+/// results at `2i + 1`. A value written by one instruction and read by the
+/// next thus does not overlap with a value read by the first instruction or
+/// written by the second, and can share its register.
+#[derive(Debug, Default)]
+pub(super) struct LiveRanges {
+    /// First and last point of each pseudo register. Holes in between are
+    /// ignored.
+    pub pseudos: HashMap<String, (usize, usize)>,
+    /// Every point at which a physical register holds a value or is written
+    pub registers: HashMap<PhyRegister, BTreeSet<usize>>,
+    /// Registers a pseudo is moved from or to, where assigning it the same
+    /// register makes the move unnecessary
+    pub hints: HashMap<String, Vec<PhyRegister>>,
+}
+
+impl LiveRanges {
+    pub fn compute(instructions: &[Instruction]) -> Self {
+        let live_out = live_out(instructions);
+        let mut ranges = LiveRanges::default();
+
+        for (idx, inst) in instructions.iter().enumerate() {
+            let (uses, defs) = uses_and_defs(inst);
+            let (read_at, write_at) = (2 * idx, 2 * idx + 1);
+            for loc in &uses {
+                ranges.add(loc, read_at);
+            }
+            for loc in defs.iter().chain(&live_out[idx]) {
+                ranges.add(loc, write_at);
+            }
+            // Live on entry to the next instruction as well
+            for loc in &live_out[idx] {
+                ranges.add(loc, write_at + 1);
+            }
+
+            if let Instruction::Mov { src, dst, .. } = inst {
+                match (src, dst) {
+                    (Operand::Pseudo(name), Operand::Register(reg))
+                    | (Operand::Register(reg), Operand::Pseudo(name)) => {
+                        ranges.hints.entry(name.clone()).or_default().push(*reg);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        ranges
+    }
+
+    fn add(&mut self, loc: &Loc, point: usize) {
+        match loc {
+            Loc::Pseudo(name) => {
+                let range = self.pseudos.entry(name.clone()).or_insert((point, point));
+                range.0 = range.0.min(point);
+                range.1 = range.1.max(point);
+            }
+            Loc::Reg(reg) => {
+                self.registers.entry(*reg).or_default().insert(point);
+            }
+        }
+    }
+
+    /// Whether `reg` is in use anywhere between `start` and `end`.
+    pub fn register_busy(&self, reg: PhyRegister, (start, end): (usize, usize)) -> bool {
+        self.registers
+            .get(&reg)
+            .is_some_and(|points| points.range(start..=end).next().is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir_base::Size;
+
+    fn pseudo(name: &str) -> Operand {
+        Operand::Pseudo(name.into())
+    }
+
+    fn mov(src: Operand, dst: Operand) -> Instruction {
+        Instruction::Mov {
+            size: Size::Long,
+            src,
+            dst,
+        }
+    }
+
+    #[test]
+    fn test_value_is_live_around_loop() {
+        // 0: x = 0   1: head:   2: x += 1   3: cmp   4: jl head   5: eax = x
+        let instructions = vec![
+            mov(Operand::Immediate(0), pseudo("x")),
+            Instruction::Label("head".into()),
+            Instruction::Add {
+                src: Operand::Immediate(1),
+                dst: pseudo("x"),
+            },
+            Instruction::Cmp {
+                src: Operand::Immediate(10),
+                dst: pseudo("x"),
+            },
+            Instruction::JmpCC {
+                cond: crate::ir_base::CondCode::L,
+                target: "head".into(),
+            },
+            mov(pseudo("x"), Operand::Register(r!("rax"))),
+            Instruction::Ret,
+        ];
+        let live_out = live_out(&instructions);
+        assert!(live_out[4].contains(&Loc::Pseudo("x".into())));
+        assert!(!live_out[5].contains(&Loc::Pseudo("x".into())));
+
+        let ranges = LiveRanges::compute(&instructions);
+        assert_eq!(ranges.pseudos["x"], (1, 10));
+        assert_eq!(ranges.hints["x"], [r!("rax")]);
+    }
+
+    #[test]
+    fn test_call_clobbers_caller_saved_registers() {
+        let instructions = vec![
+            mov(Operand::Immediate(1), pseudo("x")),
+            mov(Operand::Immediate(2), Operand::Register(r!("rdi"))),
+            Instruction::Call {
+                name: "f".into(),
+                arg_registers: 1,
+            },
+            mov(pseudo("x"), Operand::Register(r!("rax"))),
+            Instruction::Ret,
+        ];
+        let ranges = LiveRanges::compute(&instructions);
+        let x = ranges.pseudos["x"];
+        assert!(ranges.register_busy(r!("rcx"), x));
+        assert!(ranges.register_busy(r!("rdi"), x));
+        assert!(!ranges.register_busy(r!("rbx"), x));
+    }
+}
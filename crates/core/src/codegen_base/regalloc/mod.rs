@@ -0,0 +1,172 @@
+//! Register allocation: assigns pseudo registers to physical registers,
+//! leaving the ones that do not fit to be spilled to the stack.
+
+mod graph;
+mod linear;
+mod liveness;
+
+use std::str::FromStr;
+
+pub(super) use liveness::uses_and_defs;
+
+use crate::{
+    codegen_base::CodegenOptions,
+    ir_base::{Instruction, Operand, reg::PhyRegister},
+    r,
+};
+
+/// Registers a call may overwrite (System V ABI)
+pub const CALLER_SAVED: [PhyRegister; 9] = [
+    r!("rax"),
+    r!("rcx"),
+    r!("rdx"),
+    r!("rsi"),
+    r!("rdi"),
+    r!("r8"),
+    r!("r9"),
+    r!("r10"),
+    r!("r11"),
+];
+
+/// Registers a function must restore before returning (System V ABI)
+pub const CALLEE_SAVED: [PhyRegister; 6] = [
+    r!("rbx"),
+    r!("rbp"),
+    r!("r12"),
+    r!("r13"),
+    r!("r14"),
+    r!("r15"),
+];
+
+/// Registers handed out to pseudos, in order of preference: caller-saved
+/// ones first, as they need not be saved in the prologue. `r10` and `r11`
+/// are kept as scratch registers for fixing up operands afterwards.
+const ALLOCATABLE: [PhyRegister; 12] = [
+    r!("rcx"),
+    r!("rsi"),
+    r!("rdi"),
+    r!("r8"),
+    r!("r9"),
+    r!("rdx"),
+    r!("rax"),
+    r!("rbx"),
+    r!("r12"),
+    r!("r13"),
+    r!("r14"),
+    r!("r15"),
+];
+
+/// The registers `options` leave for pseudos: [`ALLOCATABLE`], and `rbp`
+/// when it does not hold the frame pointer.
+pub(super) fn allocatable(options: &CodegenOptions) -> Vec<PhyRegister> {
+    let mut registers = ALLOCATABLE.to_vec();
+    if options.omit_frame_pointer {
+        registers.push(r!("rbp"));
+    }
+    registers
+}
+
+/// Register allocation algorithm, selected with `-fregalloc=`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegAllocator {
+    /// Linear scan: fast, used up to `-O1`
+    Linear,
+    /// Graph coloring with coalescing: slower, but removes more moves and
+    /// picks better values to spill. Used at `-O2`
+    Graph,
+}
+
+impl FromStr for RegAllocator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(RegAllocator::Linear),
+            "graph" => Ok(RegAllocator::Graph),
+            _ => Err(format!("unknown register allocator `{}`", s)),
+        }
+    }
+}
+
+/// What the register allocator works on: pseudo registers and the physical
+/// registers they must not clash with.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) enum Loc {
+    Pseudo(String),
+    Reg(PhyRegister),
+}
+
+/// Replaces the pseudo registers of a function body with the physical
+/// `registers` where `allocator` finds one. The others are left for
+/// [`super::stack`] to place in the frame.
+///
+/// Returns the callee-saved registers now in use, which the prologue has to
+/// save.
+pub(super) fn allocate_registers(
+    instructions: &mut Vec<Instruction>,
+    allocator: RegAllocator,
+    registers: &[PhyRegister],
+) -> Vec<PhyRegister> {
+    let assignment = match allocator {
+        RegAllocator::Linear => {
+            linear::allocate(&liveness::LiveRanges::compute(instructions), registers)
+        }
+        RegAllocator::Graph => graph::allocate(instructions, registers),
+    };
+
+    for inst in instructions.iter_mut() {
+        for operand in operands_mut(inst) {
+            if let Operand::Pseudo(name) = operand
+                && let Some(reg) = assignment.get(name)
+            {
+                *operand = Operand::Register(*reg);
+            }
+        }
+    }
+    // Moves between a pseudo and the register it was given
+    instructions.retain(|inst| !matches!(inst, Instruction::Mov { src, dst, .. } if src == dst));
+
+    registers
+        .iter()
+        .copied()
+        .filter(|reg| CALLEE_SAVED.contains(reg))
+        .filter(|reg| assignment.values().any(|assigned| assigned == reg))
+        .collect()
+}
+
+/// The operands of `inst`, of any kind.
+pub(super) fn operands_mut(inst: &mut Instruction) -> Vec<&mut Operand> {
+    match inst {
+        Instruction::Mov { src, dst, .. }
+        | Instruction::Add { src, dst }
+        | Instruction::Sub { src, dst }
+        | Instruction::IMul { src, dst }
+        | Instruction::And { src, dst }
+        | Instruction::Or { src, dst }
+        | Instruction::Xor { src, dst }
+        | Instruction::Sal { count: src, dst }
+        | Instruction::Sar { count: src, dst }
+        | Instruction::Cmov { src, dst, .. }
+        | Instruction::Cmp { src, dst }
+        | Instruction::Test { src, dst } => vec![src, dst],
+        Instruction::Lea {
+            base, index, dst, ..
+        } => base.iter_mut().chain(index).chain([dst]).collect(),
+        Instruction::Push(operand)
+        | Instruction::Pop(operand)
+        | Instruction::IDiv { divisor: operand }
+        | Instruction::Neg { dst: operand }
+        | Instruction::Not { dst: operand }
+        | Instruction::SetCC { dst: operand, .. } => vec![operand],
+        Instruction::Cdq
+        | Instruction::Jmp(_)
+        | Instruction::JmpCC { .. }
+        | Instruction::Label(_)
+        | Instruction::AllocateStack(_)
+        | Instruction::DeallocateStack(_)
+        | Instruction::Call { .. }
+        | Instruction::TailCall { .. }
+        | Instruction::Ret
+        | Instruction::Loc { .. } => vec![],
+    }
+}
@@ -0,0 +1,483 @@
+//! Local instruction scheduling.
+//!
+//! Reorders the instructions between two scheduling barriers so that
+//! instructions waiting on a slow result, like a load from the stack frame
+//! or a multiplication, are separated from it by independent work. Runs on
+//! the final instructions of a function, after register allocation and
+//! operand fixup.
+
+use std::collections::HashSet;
+
+use crate::{
+    codegen_base::regalloc::{Loc, uses_and_defs},
+    ir_base::{Instruction, Operand, reg::PhyRegister},
+    r,
+};
+
+/// Reorders each region between barriers with a list scheduler.
+///
+/// Within a region, instructions form a dependency DAG over registers,
+/// the flags and stack memory: an instruction stays after every earlier one
+/// it reads a result of, overwrites an input of, or writes the same
+/// location as. So a comparison keeps feeding the `set` or jump after it,
+/// and the values left in registers and memory at the end of the region
+/// are unchanged. Ready instructions are then issued one per cycle, by
+/// length of the longest latency path to the end of the region, so that
+/// the long chains start first.
+pub fn schedule(instructions: &mut Vec<Instruction>) {
+    let mut scheduled = Vec::with_capacity(instructions.len());
+    let mut region = Vec::new();
+    for inst in std::mem::take(instructions) {
+        if is_barrier(&inst) {
+            scheduled.extend(schedule_region(std::mem::take(&mut region)));
+            scheduled.push(inst);
+        } else {
+            region.push(inst);
+        }
+    }
+    scheduled.extend(schedule_region(region));
+    *instructions = scheduled;
+}
+
+/// Whether `inst` must keep its place: control flow, calls, and anything
+/// that moves the stack or frame pointer, which memory operands are
+/// relative to. Without a frame pointer, other writes to `rbp` are
+/// ordinary.
+fn is_barrier(inst: &Instruction) -> bool {
+    match inst {
+        Instruction::Label(_)
+        | Instruction::Jmp(_)
+        | Instruction::JmpCC { .. }
+        | Instruction::Call { .. }
+        | Instruction::TailCall { .. }
+        | Instruction::Ret
+        | Instruction::Push(_)
+        | Instruction::Pop(_)
+        | Instruction::AllocateStack(_)
+        | Instruction::DeallocateStack(_) => true,
+        Instruction::Mov {
+            src: Operand::Register(r!("rsp")),
+            dst: Operand::Register(r!("rbp")),
+            ..
+        } => true,
+        inst => {
+            let (_, defs) = uses_and_defs(inst);
+            defs.contains(&Loc::Reg(r!("rsp")))
+        }
+    }
+}
+
+/// Cycles before the result of `inst` can be used, roughly as on recent
+/// x86-64 cores.
+fn latency(inst: &Instruction) -> usize {
+    let compute = match inst {
+        Instruction::IMul { .. } => 3,
+        Instruction::IDiv { .. } => 26,
+        _ => 1,
+    };
+    let load = if accesses(inst).iter().any(|access| !access.write) {
+        4
+    } else {
+        0
+    };
+    compute + load
+}
+
+/// What an instruction reads and writes, for ordering purposes.
+struct Effects {
+    uses: HashSet<Resource>,
+    defs: HashSet<Resource>,
+    memory: Vec<Access>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Resource {
+    Loc(Loc),
+    Flags,
+}
+
+/// A read or write of memory
+struct Access {
+    address: Operand,
+    write: bool,
+}
+
+impl Access {
+    fn conflicts_with(&self, other: &Access) -> bool {
+        (self.write || other.write) && may_overlap(&self.address, &other.address)
+    }
+}
+
+/// Whether two memory operands may refer to overlapping bytes. Stack slots
+/// at distinct offsets from the frame or stack pointer, which stays put
+/// within a region, are at most 8 bytes wide; anything else may alias.
+fn may_overlap(a: &Operand, b: &Operand) -> bool {
+    let frame_slot = |operand: &Operand| match operand {
+        Operand::Memory {
+            base: Some(base @ (PhyRegister::RBP | PhyRegister::RSP)),
+            index: None,
+            offset,
+            ..
+        } => Some((*base, *offset)),
+        _ => None,
+    };
+    match (frame_slot(a), frame_slot(b)) {
+        (Some((a_base, a)), Some((b_base, b))) if a_base == b_base => (a - b).abs() < 8,
+        _ => true,
+    }
+}
+
+fn effects(inst: &Instruction) -> Effects {
+    let (uses, defs) = uses_and_defs(inst);
+    let mut uses: HashSet<Resource> = uses.into_iter().map(Resource::Loc).collect();
+    let mut defs: HashSet<Resource> = defs.into_iter().map(Resource::Loc).collect();
+    if reads_flags(inst) {
+        uses.insert(Resource::Flags);
+    }
+    if writes_flags(inst) {
+        defs.insert(Resource::Flags);
+    }
+    Effects {
+        uses,
+        defs,
+        memory: accesses(inst),
+    }
+}
+
+fn reads_flags(inst: &Instruction) -> bool {
+    matches!(
+        inst,
+        Instruction::SetCC { .. } | Instruction::Cmov { .. } | Instruction::JmpCC { .. }
+    )
+}
+
+fn writes_flags(inst: &Instruction) -> bool {
+    matches!(
+        inst,
+        Instruction::Add { .. }
+            | Instruction::Sub { .. }
+            | Instruction::IMul { .. }
+            | Instruction::IDiv { .. }
+            | Instruction::Neg { .. }
+            | Instruction::And { .. }
+            | Instruction::Or { .. }
+            | Instruction::Xor { .. }
+            | Instruction::Sal { .. }
+            | Instruction::Sar { .. }
+            | Instruction::Cmp { .. }
+            | Instruction::Test { .. }
+    )
+}
+
+/// The memory operands of `inst` and whether each is written.
+fn accesses(inst: &Instruction) -> Vec<Access> {
+    let operands: Vec<(&Operand, bool)> = match inst {
+        Instruction::Mov { src, dst, .. } => vec![(src, false), (dst, true)],
+        Instruction::Add { src, dst }
+        | Instruction::Sub { src, dst }
+        | Instruction::IMul { src, dst }
+        | Instruction::And { src, dst }
+        | Instruction::Or { src, dst }
+        | Instruction::Xor { src, dst }
+        | Instruction::Sal { count: src, dst }
+        | Instruction::Sar { count: src, dst }
+        | Instruction::Cmov { src, dst, .. } => vec![(src, false), (dst, false), (dst, true)],
+        Instruction::Cmp { src, dst } | Instruction::Test { src, dst } => {
+            vec![(src, false), (dst, false)]
+        }
+        Instruction::SetCC { dst, .. } | Instruction::Neg { dst } | Instruction::Not { dst } => {
+            vec![(dst, false), (dst, true)]
+        }
+        Instruction::IDiv { divisor } => vec![(divisor, false)],
+        Instruction::Push(operand) => vec![(operand, false)],
+        Instruction::Pop(operand) => vec![(operand, true)],
+        // `lea` computes an address without accessing it
+        Instruction::Lea { .. }
+        | Instruction::Cdq
+        | Instruction::Jmp(_)
+        | Instruction::JmpCC { .. }
+        | Instruction::Label(_)
+        | Instruction::AllocateStack(_)
+        | Instruction::DeallocateStack(_)
+        | Instruction::Call { .. }
+        | Instruction::TailCall { .. }
+        | Instruction::Ret
+        | Instruction::Loc { .. } => vec![],
+    };
+    operands
+        .into_iter()
+        .filter(|(operand, _)| operand.is_memory())
+        .map(|(operand, write)| Access {
+            address: operand.clone(),
+            write,
+        })
+        .collect()
+}
+
+/// Dependencies of a region: for each instruction, the earlier ones it must
+/// follow and how many cycles after their issue it can start.
+fn dependencies(region: &[Instruction]) -> Vec<Vec<(usize, usize)>> {
+    let effects: Vec<Effects> = region.iter().map(effects).collect();
+    (0..region.len())
+        .map(|later| {
+            let after = &effects[later];
+            (0..later)
+                .filter_map(|earlier| {
+                    let before = &effects[earlier];
+                    let reads_result = !before.defs.is_disjoint(&after.uses)
+                        || before.memory.iter().any(|write| {
+                            write.write
+                                && after
+                                    .memory
+                                    .iter()
+                                    .any(|read| !read.write && write.conflicts_with(read))
+                        });
+                    let ordered = !before.defs.is_disjoint(&after.defs)
+                        || !before.uses.is_disjoint(&after.defs)
+                        || before
+                            .memory
+                            .iter()
+                            .any(|a| after.memory.iter().any(|b| a.conflicts_with(b)));
+                    if reads_result {
+                        Some((earlier, latency(&region[earlier])))
+                    } else if ordered {
+                        Some((earlier, 0))
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn schedule_region(region: Vec<Instruction>) -> Vec<Instruction> {
+    // A `.loc` directive moves along with the instruction after it, so that
+    // debug info leaves the schedule unchanged
+    let mut locs = Vec::new();
+    let mut attached = Vec::new();
+    let mut instructions = Vec::with_capacity(region.len());
+    for inst in region {
+        if matches!(inst, Instruction::Loc { .. }) {
+            locs.push(inst);
+        } else {
+            attached.push(std::mem::take(&mut locs));
+            instructions.push(inst);
+        }
+    }
+
+    let order = issue_order(&instructions);
+    let mut pending: Vec<Option<(Vec<Instruction>, Instruction)>> =
+        attached.into_iter().zip(instructions).map(Some).collect();
+    let mut scheduled = Vec::new();
+    for idx in order {
+        let (locs, inst) = pending[idx]
+            .take()
+            .expect("each instruction is issued once");
+        scheduled.extend(locs);
+        scheduled.push(inst);
+    }
+    scheduled.extend(locs);
+    scheduled
+}
+
+/// The order to issue the instructions of a region in, as indices.
+fn issue_order(region: &[Instruction]) -> Vec<usize> {
+    if region.len() < 2 {
+        return (0..region.len()).collect();
+    }
+    let preds = dependencies(region);
+
+    // Longest latency path from each instruction to the end of the region
+    let mut priority: Vec<usize> = region.iter().map(latency).collect();
+    for later in (0..region.len()).rev() {
+        for &(earlier, delay) in &preds[later] {
+            priority[earlier] = priority[earlier].max(delay + priority[later]);
+        }
+    }
+
+    let mut issued: Vec<Option<usize>> = vec![None; region.len()];
+    let mut order = Vec::with_capacity(region.len());
+    let mut cycle = 0;
+    while order.len() < region.len() {
+        // Instructions whose dependencies have issued, with the cycle at
+        // which their inputs are available
+        let ready = (0..region.len()).filter_map(|idx| {
+            if issued[idx].is_some() {
+                return None;
+            }
+            preds[idx]
+                .iter()
+                .try_fold(0, |start: usize, &(pred, delay)| {
+                    issued[pred].map(|at| start.max(at + delay))
+                })
+                .map(|start| (idx, start))
+        });
+        // Prefer what can start now, then the longest path, then the
+        // original order
+        let (next, start) = ready
+            .min_by_key(|&(idx, start)| (start.max(cycle), std::cmp::Reverse(priority[idx]), idx))
+            .expect("dependencies only point to earlier instructions");
+        cycle = start.max(cycle);
+        issued[next] = Some(cycle);
+        order.push(next);
+        cycle += 1;
+    }
+
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir_base::{CondCode, Size};
+
+    fn reg(name: PhyRegister) -> Operand {
+        Operand::Register(name)
+    }
+
+    fn load(offset: i64, dst: PhyRegister) -> Instruction {
+        Instruction::Mov {
+            size: Size::Long,
+            src: Operand::memory(r!("rbp"), offset),
+            dst: reg(dst),
+        }
+    }
+
+    #[test]
+    fn test_independent_load_is_hoisted_above_arithmetic() {
+        let mut instructions = vec![
+            Instruction::Add {
+                src: Operand::Immediate(1),
+                dst: reg(r!("rcx")),
+            },
+            Instruction::Add {
+                src: Operand::Immediate(2),
+                dst: reg(r!("rcx")),
+            },
+            load(-4, r!("rax")),
+            Instruction::Add {
+                src: reg(r!("rcx")),
+                dst: reg(r!("rax")),
+            },
+        ];
+        schedule(&mut instructions);
+        assert_eq!(instructions[0], load(-4, r!("rax")));
+        assert!(matches!(
+            instructions[3],
+            Instruction::Add {
+                dst: Operand::Register(r!("rax")),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_line_markers_move_with_their_instruction() {
+        let loc = Instruction::Loc { line: 3, column: 5 };
+        let mut instructions = vec![
+            Instruction::Add {
+                src: Operand::Immediate(1),
+                dst: reg(r!("rcx")),
+            },
+            Instruction::Add {
+                src: Operand::Immediate(2),
+                dst: reg(r!("rcx")),
+            },
+            loc.clone(),
+            load(-4, r!("rax")),
+            Instruction::Add {
+                src: reg(r!("rcx")),
+                dst: reg(r!("rax")),
+            },
+        ];
+        schedule(&mut instructions);
+        assert_eq!(instructions[0], loc);
+        assert_eq!(instructions[1], load(-4, r!("rax")));
+    }
+
+    #[test]
+    fn test_flags_stay_between_compare_and_set() {
+        let set = Instruction::SetCC {
+            cond: CondCode::L,
+            dst: reg(r!("rdx")),
+        };
+        let mut instructions = vec![
+            Instruction::Cmp {
+                src: reg(r!("rsi")),
+                dst: reg(r!("rdi")),
+            },
+            Instruction::Mov {
+                size: Size::Long,
+                src: Operand::Immediate(0),
+                dst: reg(r!("rdx")),
+            },
+            set.clone(),
+            load(-4, r!("rax")),
+            Instruction::IMul {
+                src: Operand::Immediate(3),
+                dst: reg(r!("rax")),
+            },
+        ];
+        schedule(&mut instructions);
+        let position = |inst: &Instruction| instructions.iter().position(|i| i == inst).unwrap();
+        let cmp = instructions
+            .iter()
+            .position(|inst| matches!(inst, Instruction::Cmp { .. }))
+            .unwrap();
+        let imul = instructions
+            .iter()
+            .position(|inst| matches!(inst, Instruction::IMul { .. }))
+            .unwrap();
+        // `imul` sets the flags too, so it cannot land between them
+        assert!(imul < cmp || imul > position(&set));
+        assert!(cmp < position(&set));
+    }
+
+    #[test]
+    fn test_instructions_do_not_cross_a_call() {
+        let call = Instruction::Call {
+            name: "f".into(),
+            arg_registers: 0,
+        };
+        let mut instructions = vec![
+            Instruction::Add {
+                src: Operand::Immediate(1),
+                dst: reg(r!("rbx")),
+            },
+            call.clone(),
+            load(-4, r!("rcx")),
+        ];
+        let expected = instructions.clone();
+        schedule(&mut instructions);
+        assert_eq!(instructions, expected);
+        assert_eq!(instructions[1], call);
+    }
+
+    #[test]
+    fn test_store_and_load_of_same_slot_keep_their_order() {
+        let store = Instruction::Mov {
+            size: Size::Long,
+            src: reg(r!("rcx")),
+            dst: Operand::memory(r!("rbp"), -8),
+        };
+        let mut instructions = vec![
+            Instruction::IMul {
+                src: Operand::Immediate(3),
+                dst: reg(r!("rdx")),
+            },
+            store.clone(),
+            load(-8, r!("rax")),
+            load(-16, r!("rsi")),
+        ];
+        schedule(&mut instructions);
+        let store_at = instructions.iter().position(|i| *i == store).unwrap();
+        let load_at = instructions
+            .iter()
+            .position(|i| *i == load(-8, r!("rax")))
+            .unwrap();
+        assert!(store_at < load_at);
+        // Both loads start before the shorter multiplication
+        assert!(matches!(instructions[3], Instruction::IMul { .. }));
+    }
+}
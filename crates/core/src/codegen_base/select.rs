@@ -0,0 +1,595 @@
+//! Instruction selection over expression trees.
+//!
+//! TACKY computes one operation per instruction, while a single x86
+//! instruction can often do more: `lea` adds a register, a scaled register
+//! and a constant at once, and a conditional jump can test the flags of a
+//! comparison directly instead of a value `set` from them. To find such
+//! cases, a temporary that is defined once and read once, later in the same
+//! block and with the operands of its definition unchanged in between, is
+//! treated as computed at its use. The instructions then form trees, which
+//! [`select`] covers with patterns of several nodes where one matches.
+//!
+//! A branch that does nothing but pick one of two values for a variable can
+//! be replaced with `cmov` as well, which is not always faster: both values
+//! are computed whichever is picked, so it is only done when optimizing.
+
+use std::collections::HashMap;
+
+use crate::{
+    grammar::BinaryOp,
+    ir_base::CondCode,
+    tacky_base::{Instruction, Value},
+};
+
+/// How the code generator translates one TACKY instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Selection {
+    /// On its own
+    Single,
+    /// Not at all: the instruction that reads its result computes it
+    Folded,
+    /// `dst = base + index * scale + offset`, with `lea`
+    Address {
+        base: Option<Value>,
+        index: Option<(Value, u8)>,
+        offset: i64,
+        dst: String,
+    },
+    /// A jump to `target` if `lhs` and `rhs` compare according to `cond`
+    CompareAndBranch {
+        cond: CondCode,
+        lhs: Value,
+        rhs: Value,
+        target: String,
+    },
+    /// `dst = then_value` if `lhs` and `rhs` compare according to `cond`,
+    /// `dst = else_value` otherwise, with `cmov`
+    ConditionalMove {
+        cond: CondCode,
+        lhs: Value,
+        rhs: Value,
+        then_value: Value,
+        else_value: Value,
+        dst: String,
+    },
+}
+
+/// The condition code of a comparison operator.
+fn condition_code(op: &BinaryOp) -> Option<CondCode> {
+    match op {
+        BinaryOp::LessThan => Some(CondCode::L),
+        BinaryOp::GreaterThan => Some(CondCode::G),
+        BinaryOp::Equal => Some(CondCode::E),
+        BinaryOp::NotEqual => Some(CondCode::NE),
+        BinaryOp::LessThanOrEqual => Some(CondCode::LE),
+        BinaryOp::GreaterThanOrEqual => Some(CondCode::GE),
+        BinaryOp::Add
+        | BinaryOp::Subtract
+        | BinaryOp::Multiply
+        | BinaryOp::Divide
+        | BinaryOp::Remainder
+        | BinaryOp::BitwiseAnd
+        | BinaryOp::BitwiseOr
+        | BinaryOp::BitwiseXor
+        | BinaryOp::LeftShift
+        | BinaryOp::RightShift => None,
+    }
+}
+
+/// Chooses how to translate each instruction of `body`, using conditional
+/// moves if `conditional_moves` is set.
+///
+/// Trees are covered from their root, so the last instructions of a block
+/// are visited first.
+pub(super) fn select(body: &[Instruction], conditional_moves: bool) -> Vec<Selection> {
+    let mut selector = Selector {
+        body,
+        foldable: foldable_temporaries(body),
+        selection: vec![Selection::Single; body.len()],
+        conditional_moves,
+    };
+    for at in (0..body.len()).rev() {
+        if selector.selection[at] == Selection::Single {
+            selector.select_root(at);
+        }
+    }
+    selector.selection
+}
+
+/// Where each variable defined by a binary operation and read exactly once
+/// is defined.
+fn foldable_temporaries(body: &[Instruction]) -> HashMap<&str, usize> {
+    let mut defs: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut uses: HashMap<&str, usize> = HashMap::new();
+    for (idx, inst) in body.iter().enumerate() {
+        if let Some(dst) = inst.dst() {
+            defs.entry(dst).or_default().push(idx);
+        }
+        for name in inst.operands().into_iter().filter_map(Value::as_var) {
+            *uses.entry(name).or_default() += 1;
+        }
+    }
+
+    defs.into_iter()
+        .filter_map(|(name, defs)| match defs[..] {
+            [idx]
+                if uses.get(name) == Some(&1)
+                    && matches!(body[idx], Instruction::Binary { .. }) =>
+            {
+                Some((name, idx))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// An address computation matched so far.
+#[derive(Debug, Default)]
+struct Address {
+    base: Option<Value>,
+    index: Option<(Value, u8)>,
+    offset: i64,
+    /// Definitions folded into it
+    folded: Vec<usize>,
+}
+
+impl Address {
+    /// The sum of two addresses, if it still fits in one.
+    fn add(self, other: Address) -> Option<Address> {
+        let mut registers: Vec<Value> = self.base.into_iter().chain(other.base).collect();
+        let index = match (self.index, other.index) {
+            (Some(_), Some(_)) => return None,
+            (Some(index), None) | (None, Some(index)) => Some(index),
+            // A second base register goes into the index
+            (None, None) if registers.len() == 2 => registers.pop().map(|reg| (reg, 1)),
+            (None, None) => None,
+        };
+        if registers.len() > 1 {
+            return None;
+        }
+
+        let mut folded = self.folded;
+        folded.extend(other.folded);
+        Some(Address {
+            base: registers.pop(),
+            index,
+            offset: wrap(self.offset + other.offset),
+            folded,
+        })
+    }
+}
+
+/// Wraps `value` to the 32-bit range the generated code computes in.
+fn wrap(value: i64) -> i64 {
+    value as i32 as i64
+}
+
+struct Selector<'a> {
+    body: &'a [Instruction],
+    foldable: HashMap<&'a str, usize>,
+    selection: Vec<Selection>,
+    conditional_moves: bool,
+}
+
+impl Selector<'_> {
+    /// Tries the patterns rooted at the instruction at `at`.
+    fn select_root(&mut self, at: usize) {
+        match &self.body[at] {
+            Instruction::Binary { op, lhs, rhs, dst } => {
+                let Some(address) = self.match_operation(op, lhs, rhs, at) else {
+                    return;
+                };
+                // A lone addition is as short with `add`, which also lets the
+                // register allocator give the result the register of an
+                // operand. A lone multiplication saves an `imul`.
+                let scaled = address.index.as_ref().is_some_and(|&(_, scale)| scale > 1);
+                let has_register = address.base.is_some() || address.index.is_some();
+                if address.folded.is_empty() && !scaled || !has_register {
+                    return;
+                }
+                for &idx in &address.folded {
+                    self.selection[idx] = Selection::Folded;
+                }
+                self.selection[at] = Selection::Address {
+                    base: address.base,
+                    index: address.index,
+                    offset: address.offset,
+                    dst: dst.clone(),
+                };
+            }
+            Instruction::JumpIfZero { cond, target }
+            | Instruction::JumpIfNotZero { cond, target } => {
+                if self.conditional_moves
+                    && let Some((then_value, else_value, dst)) = self.match_select(at)
+                {
+                    let (code, lhs, rhs) = self.fold_comparison(cond, at).unwrap_or((
+                        CondCode::NE,
+                        cond.clone(),
+                        Value::Constant(0),
+                    ));
+                    // The then value is picked when the jump is not taken
+                    let code = match self.body[at] {
+                        Instruction::JumpIfZero { .. } => code,
+                        _ => code.negate(),
+                    };
+                    for selection in &mut self.selection[at + 1..at + 6] {
+                        *selection = Selection::Folded;
+                    }
+                    self.selection[at] = Selection::ConditionalMove {
+                        cond: code,
+                        lhs,
+                        rhs,
+                        then_value,
+                        else_value,
+                        dst,
+                    };
+                    return;
+                }
+                let Some((mut code, lhs, rhs)) = self.fold_comparison(cond, at) else {
+                    return;
+                };
+                if matches!(self.body[at], Instruction::JumpIfZero { .. }) {
+                    code = code.negate();
+                }
+                self.selection[at] = Selection::CompareAndBranch {
+                    cond: code,
+                    lhs,
+                    rhs,
+                    target: target.clone(),
+                };
+            }
+            _ => {}
+        }
+    }
+
+    /// Folds the definition of `cond` into the instruction at `at` if it is
+    /// a comparison, returning the condition under which it is nonzero and
+    /// the values compared.
+    fn fold_comparison(&mut self, cond: &Value, at: usize) -> Option<(CondCode, Value, Value)> {
+        let def = self.fold(cond, at)?;
+        let Instruction::Binary { op, lhs, rhs, .. } = &self.body[def] else {
+            return None;
+        };
+        let code = condition_code(op)?;
+        self.selection[def] = Selection::Folded;
+        Some((code, lhs.clone(), rhs.clone()))
+    }
+
+    /// Matches the conditional jump at `at` with the branches it starts if
+    /// each only copies a value into the same variable:
+    ///
+    /// ```text
+    ///     jump to else if ...
+    ///     dst = then_value
+    ///     jump to end
+    /// else:
+    ///     dst = else_value
+    /// end:
+    /// ```
+    ///
+    /// and nothing else jumps to the two labels. Returns `then_value`,
+    /// which is copied when the jump is not taken, `else_value` and `dst`.
+    fn match_select(&self, at: usize) -> Option<(Value, Value, String)> {
+        let [
+            Instruction::JumpIfZero { target, .. } | Instruction::JumpIfNotZero { target, .. },
+            Instruction::Copy {
+                src: then_value,
+                dst,
+            },
+            Instruction::Jump(end),
+            Instruction::Label(else_label),
+            Instruction::Copy {
+                src: else_value,
+                dst: else_dst,
+            },
+            Instruction::Label(end_label),
+        ] = self.body.get(at..at + 6)?
+        else {
+            return None;
+        };
+        let jumps_to = |label: &str| {
+            self.body
+                .iter()
+                .filter(|inst| match inst {
+                    Instruction::Jump(target)
+                    | Instruction::JumpIfZero { target, .. }
+                    | Instruction::JumpIfNotZero { target, .. } => target == label,
+                    _ => false,
+                })
+                .count()
+        };
+        // `dst` is written with the else value before the then value is
+        // moved in
+        let matches = target == else_label
+            && end == end_label
+            && dst == else_dst
+            && then_value.as_var() != Some(dst)
+            && jumps_to(else_label) == 1
+            && jumps_to(end_label) == 1;
+        matches.then(|| (then_value.clone(), else_value.clone(), dst.clone()))
+    }
+
+    /// The definition of `value` if it can be computed by the instruction at
+    /// `at` instead.
+    fn fold(&self, value: &Value, at: usize) -> Option<usize> {
+        let def = *self.foldable.get(value.as_var()?)?;
+        if def >= at || self.selection[def] != Selection::Single {
+            return None;
+        }
+        let inputs: Vec<&str> = self.body[def]
+            .operands()
+            .into_iter()
+            .filter_map(Value::as_var)
+            .collect();
+        let unchanged = self.body[def + 1..at].iter().all(|inst| {
+            let ends_block = matches!(
+                inst,
+                Instruction::Label(_)
+                    | Instruction::Jump(_)
+                    | Instruction::JumpIfZero { .. }
+                    | Instruction::JumpIfNotZero { .. }
+                    | Instruction::Return(_)
+                    | Instruction::TailCall { .. }
+            );
+            !ends_block && inst.dst().is_none_or(|dst| !inputs.contains(&dst))
+        });
+        unchanged.then_some(def)
+    }
+
+    /// `value` as an address, folding its definition in if that is an
+    /// address computation as well.
+    fn match_value(&self, value: &Value, at: usize) -> Address {
+        if let Value::Constant(constant) = value {
+            return Address {
+                offset: wrap(*constant),
+                ..Default::default()
+            };
+        }
+        if let Some(def) = self.fold(value, at)
+            && let Instruction::Binary { op, lhs, rhs, .. } = &self.body[def]
+            && let Some(mut address) = self.match_operation(op, lhs, rhs, at)
+        {
+            address.folded.push(def);
+            return address;
+        }
+        Address {
+            base: Some(value.clone()),
+            ..Default::default()
+        }
+    }
+
+    /// `lhs op rhs` as an address.
+    fn match_operation(
+        &self,
+        op: &BinaryOp,
+        lhs: &Value,
+        rhs: &Value,
+        at: usize,
+    ) -> Option<Address> {
+        match (op, lhs, rhs) {
+            (BinaryOp::Add, _, _) => self.match_value(lhs, at).add(self.match_value(rhs, at)),
+            (BinaryOp::Subtract, _, Value::Constant(constant)) => {
+                let mut address = self.match_value(lhs, at);
+                address.offset = wrap(address.offset - constant);
+                Some(address)
+            }
+            (BinaryOp::Multiply, var @ Value::Var(_), Value::Constant(factor))
+            | (BinaryOp::Multiply, Value::Constant(factor), var @ Value::Var(_)) => {
+                match factor {
+                    1 | 2 | 4 | 8 => Some(Address {
+                        index: Some((var.clone(), *factor as u8)),
+                        ..Default::default()
+                    }),
+                    // x * 3 = x + x * 2
+                    3 | 5 | 9 => Some(Address {
+                        base: Some(var.clone()),
+                        index: Some((var.clone(), *factor as u8 - 1)),
+                        ..Default::default()
+                    }),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(name: &str) -> Value {
+        Value::Var(name.into())
+    }
+
+    fn binary(op: BinaryOp, lhs: Value, rhs: Value, dst: &str) -> Instruction {
+        Instruction::Binary {
+            op,
+            lhs,
+            rhs,
+            dst: dst.into(),
+        }
+    }
+
+    #[test]
+    fn test_scaled_sum_becomes_one_address() {
+        // a + b * 4 + 8
+        let body = vec![
+            binary(BinaryOp::Multiply, var("b"), Value::Constant(4), "t1"),
+            binary(BinaryOp::Add, var("a"), var("t1"), "t2"),
+            binary(BinaryOp::Add, var("t2"), Value::Constant(8), "t3"),
+            Instruction::Return(var("t3")),
+        ];
+        let selection = select(&body, true);
+        assert_eq!(selection[0], Selection::Folded);
+        assert_eq!(selection[1], Selection::Folded);
+        assert_eq!(
+            selection[2],
+            Selection::Address {
+                base: Some(var("a")),
+                index: Some((var("b"), 4)),
+                offset: 8,
+                dst: "t3".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_lone_addition_is_left_to_add_and_multiplication_uses_lea() {
+        let body = vec![
+            binary(BinaryOp::Add, var("a"), var("b"), "c"),
+            binary(BinaryOp::Multiply, Value::Constant(5), var("a"), "d"),
+            binary(BinaryOp::Add, Value::Constant(2), Value::Constant(3), "e"),
+            binary(BinaryOp::Add, var("e"), Value::Constant(1), "f"),
+            Instruction::Return(var("c")),
+        ];
+        let selection = select(&body, true);
+        assert_eq!(selection[0], Selection::Single);
+        assert_eq!(
+            selection[1],
+            Selection::Address {
+                base: Some(var("a")),
+                index: Some((var("a"), 4)),
+                offset: 0,
+                dst: "d".into(),
+            }
+        );
+        // No register to address from
+        assert_eq!(selection[2], Selection::Single);
+        assert_eq!(selection[3], Selection::Single);
+    }
+
+    #[test]
+    fn test_comparison_is_fused_with_branch() {
+        let body = vec![
+            binary(BinaryOp::LessThan, var("a"), Value::Constant(10), "t"),
+            Instruction::JumpIfZero {
+                cond: var("t"),
+                target: "end".into(),
+            },
+            Instruction::Return(Value::Constant(1)),
+            Instruction::Label("end".into()),
+            Instruction::Return(Value::Constant(0)),
+        ];
+        let selection = select(&body, true);
+        assert_eq!(selection[0], Selection::Folded);
+        assert_eq!(
+            selection[1],
+            Selection::CompareAndBranch {
+                cond: CondCode::GE,
+                lhs: var("a"),
+                rhs: Value::Constant(10),
+                target: "end".into(),
+            }
+        );
+    }
+
+    /// `dst = a < 10 ? a : 10`, branching on `t`
+    fn select_minimum(jump: Instruction) -> Vec<Instruction> {
+        vec![
+            binary(BinaryOp::LessThan, var("a"), Value::Constant(10), "t"),
+            jump,
+            Instruction::Copy {
+                src: var("a"),
+                dst: "dst".into(),
+            },
+            Instruction::Jump("end".into()),
+            Instruction::Label("else".into()),
+            Instruction::Copy {
+                src: Value::Constant(10),
+                dst: "dst".into(),
+            },
+            Instruction::Label("end".into()),
+            Instruction::Return(var("dst")),
+        ]
+    }
+
+    #[test]
+    fn test_branch_between_two_values_becomes_conditional_move() {
+        let body = select_minimum(Instruction::JumpIfZero {
+            cond: var("t"),
+            target: "else".into(),
+        });
+        let selection = select(&body, true);
+        assert_eq!(
+            selection[1],
+            Selection::ConditionalMove {
+                cond: CondCode::L,
+                lhs: var("a"),
+                rhs: Value::Constant(10),
+                then_value: var("a"),
+                else_value: Value::Constant(10),
+                dst: "dst".into(),
+            }
+        );
+        assert!(
+            selection[..7]
+                .iter()
+                .enumerate()
+                .all(|(idx, selection)| idx == 1 || *selection == Selection::Folded)
+        );
+        assert_eq!(selection[7], Selection::Single);
+
+        // Only when optimizing
+        let selection = select(&body, false);
+        assert!(matches!(selection[1], Selection::CompareAndBranch { .. }));
+    }
+
+    #[test]
+    fn test_conditional_move_tests_jump_condition() {
+        let mut body = select_minimum(Instruction::JumpIfNotZero {
+            cond: var("c"),
+            target: "else".into(),
+        });
+        body.remove(0);
+        assert_eq!(
+            select(&body, true)[0],
+            Selection::ConditionalMove {
+                cond: CondCode::E,
+                lhs: var("c"),
+                rhs: Value::Constant(0),
+                then_value: var("a"),
+                else_value: Value::Constant(10),
+                dst: "dst".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_branch_to_shared_label_is_kept() {
+        let mut body = select_minimum(Instruction::JumpIfZero {
+            cond: var("t"),
+            target: "else".into(),
+        });
+        body.push(Instruction::Jump("end".into()));
+        let selection = select(&body, true);
+        assert!(matches!(selection[1], Selection::CompareAndBranch { .. }));
+        assert_eq!(selection[2], Selection::Single);
+    }
+
+    #[test]
+    fn test_temporary_is_not_folded_past_a_change_of_its_operands() {
+        let body = vec![
+            binary(BinaryOp::Multiply, var("a"), Value::Constant(4), "t"),
+            Instruction::Copy {
+                src: Value::Constant(0),
+                dst: "a".into(),
+            },
+            binary(BinaryOp::Add, var("t"), var("b"), "c"),
+            binary(BinaryOp::LessThan, var("a"), var("b"), "d"),
+            Instruction::Label("next".into()),
+            Instruction::JumpIfZero {
+                cond: var("d"),
+                target: "next".into(),
+            },
+            Instruction::Return(var("c")),
+        ];
+        let selection = select(&body, true);
+        // `t` gets its own `lea`, from `a` before the copy
+        assert!(matches!(selection[0], Selection::Address { .. }));
+        assert_eq!(selection[2], Selection::Single);
+        // Across a label
+        assert_eq!(selection[3], Selection::Single);
+        assert_eq!(selection[5], Selection::Single);
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use crate::ir_base::{Instruction, Operand, reg::PhyRegister};
+
+/// Size of the stack slot given to every pseudo register
+const SLOT_SIZE: i64 = 4;
+
+/// Replaces every pseudo register with a slot in the current stack frame,
+/// below `base`. Returns the number of bytes the frame needs, and the
+/// offset from `base` of each pseudo register's slot.
+pub(super) fn replace_pseudo_registers(
+    instructions: &mut [Instruction],
+    base: PhyRegister,
+) -> (i64, HashMap<String, i64>) {
+    let mut slots: HashMap<String, i64> = HashMap::new();
+    let mut stack_size = 0;
+
+    let mut replace = |operand: &mut Operand| {
+        if let Operand::Pseudo(name) = operand {
+            let offset = *slots.entry(name.clone()).or_insert_with(|| {
+                stack_size += SLOT_SIZE;
+                -stack_size
+            });
+            *operand = Operand::memory(base, offset);
+        }
+    };
+
+    for inst in instructions.iter_mut() {
+        match inst {
+            Instruction::Mov { src, dst, .. }
+            | Instruction::Add { src, dst }
+            | Instruction::Sub { src, dst }
+            | Instruction::IMul { src, dst }
+            | Instruction::And { src, dst }
+            | Instruction::Or { src, dst }
+            | Instruction::Xor { src, dst }
+            | Instruction::Sal { count: src, dst }
+            | Instruction::Sar { count: src, dst }
+            | Instruction::Cmov { src, dst, .. }
+            | Instruction::Cmp { src, dst }
+            | Instruction::Test { src, dst } => {
+                replace(src);
+                replace(dst);
+            }
+            Instruction::Lea {
+                base, index, dst, ..
+            } => {
+                for operand in base.iter_mut().chain(index) {
+                    replace(operand);
+                }
+                replace(dst);
+            }
+            Instruction::Push(operand)
+            | Instruction::Pop(operand)
+            | Instruction::IDiv { divisor: operand }
+            | Instruction::Neg { dst: operand }
+            | Instruction::Not { dst: operand }
+            | Instruction::SetCC { dst: operand, .. } => replace(operand),
+            Instruction::Cdq
+            | Instruction::Jmp(_)
+            | Instruction::JmpCC { .. }
+            | Instruction::Label(_)
+            | Instruction::AllocateStack(_)
+            | Instruction::DeallocateStack(_)
+            | Instruction::Call { .. }
+            | Instruction::TailCall { .. }
+            | Instruction::Ret
+            | Instruction::Loc { .. } => {}
+        }
+    }
+
+    (stack_size, slots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ir_base::Size, r};
+
+    #[test]
+    fn test_each_pseudo_gets_its_own_slot() {
+        let mut instructions = vec![
+            Instruction::Mov {
+                size: Size::Long,
+                src: Operand::Pseudo("a".into()),
+                dst: Operand::Pseudo("b".into()),
+            },
+            Instruction::Neg {
+                dst: Operand::Pseudo("a".into()),
+            },
+        ];
+        let (stack_size, slots) = replace_pseudo_registers(&mut instructions, r!("rbp"));
+        assert_eq!(stack_size, 8);
+        assert_eq!(slots["a"], -4);
+        assert_eq!(slots["b"], -8);
+        assert_eq!(
+            instructions[0],
+            Instruction::Mov {
+                size: Size::Long,
+                src: Operand::memory(r!("rbp"), -4),
+                dst: Operand::memory(r!("rbp"), -8),
+            }
+        );
+        assert_eq!(
+            instructions[1],
+            Instruction::Neg {
+                dst: Operand::memory(r!("rbp"), -4),
+            }
+        );
+    }
+}
@@ -0,0 +1,330 @@
+//! DWARF 4 `.debug_abbrev` and `.debug_info` sections.
+//!
+//! The line table is left to the assembler, which builds `.debug_line`
+//! from the `.file` and `.loc` directives.
+
+use std::fmt::Write;
+
+use crate::{
+    debug_base::{DebugInfo, FunctionInfo, VariableInfo},
+    ir_base::{IRFuncDef, IRProgram, LOCAL_LABEL_PREFIX},
+};
+
+const PRODUCER: &str = "my_first_compiler";
+
+const DW_LANG_C99: u8 = 0x0c;
+const DW_ATE_SIGNED: u8 = 0x05;
+/// Frame base plus a signed offset
+const DW_OP_FBREG: u8 = 0x91;
+/// The canonical frame address, as computed from the call frame information
+const DW_OP_CALL_FRAME_CFA: u8 = 0x9c;
+
+const DW_TAG_FORMAL_PARAMETER: u16 = 0x05;
+const DW_TAG_COMPILE_UNIT: u16 = 0x11;
+const DW_TAG_BASE_TYPE: u16 = 0x24;
+const DW_TAG_SUBPROGRAM: u16 = 0x2e;
+const DW_TAG_VARIABLE: u16 = 0x34;
+
+const DW_AT_LOCATION: u16 = 0x02;
+const DW_AT_NAME: u16 = 0x03;
+const DW_AT_BYTE_SIZE: u16 = 0x0b;
+const DW_AT_STMT_LIST: u16 = 0x10;
+const DW_AT_LOW_PC: u16 = 0x11;
+const DW_AT_HIGH_PC: u16 = 0x12;
+const DW_AT_LANGUAGE: u16 = 0x13;
+const DW_AT_COMP_DIR: u16 = 0x1b;
+const DW_AT_PRODUCER: u16 = 0x25;
+const DW_AT_DECL_FILE: u16 = 0x3a;
+const DW_AT_DECL_LINE: u16 = 0x3b;
+const DW_AT_ENCODING: u16 = 0x3e;
+const DW_AT_EXTERNAL: u16 = 0x3f;
+const DW_AT_FRAME_BASE: u16 = 0x40;
+const DW_AT_TYPE: u16 = 0x49;
+
+const DW_FORM_ADDR: u8 = 0x01;
+const DW_FORM_DATA4: u8 = 0x06;
+const DW_FORM_STRING: u8 = 0x08;
+const DW_FORM_DATA1: u8 = 0x0b;
+const DW_FORM_FLAG: u8 = 0x0c;
+const DW_FORM_REF4: u8 = 0x13;
+const DW_FORM_SEC_OFFSET: u8 = 0x17;
+const DW_FORM_EXPRLOC: u8 = 0x18;
+
+/// The kinds of entries we write, numbered as their abbreviation codes
+#[derive(Debug, Clone, Copy)]
+enum Abbrev {
+    CompileUnit = 1,
+    BaseType,
+    Subprogram,
+    VoidSubprogram,
+    FormalParameter,
+    Variable,
+}
+
+impl Abbrev {
+    const ALL: [Abbrev; 6] = [
+        Abbrev::CompileUnit,
+        Abbrev::BaseType,
+        Abbrev::Subprogram,
+        Abbrev::VoidSubprogram,
+        Abbrev::FormalParameter,
+        Abbrev::Variable,
+    ];
+
+    fn tag(self) -> u16 {
+        match self {
+            Abbrev::CompileUnit => DW_TAG_COMPILE_UNIT,
+            Abbrev::BaseType => DW_TAG_BASE_TYPE,
+            Abbrev::Subprogram | Abbrev::VoidSubprogram => DW_TAG_SUBPROGRAM,
+            Abbrev::FormalParameter => DW_TAG_FORMAL_PARAMETER,
+            Abbrev::Variable => DW_TAG_VARIABLE,
+        }
+    }
+
+    fn has_children(self) -> bool {
+        matches!(
+            self,
+            Abbrev::CompileUnit | Abbrev::Subprogram | Abbrev::VoidSubprogram
+        )
+    }
+
+    /// `(attribute, form)` pairs, in the order the entry's values follow
+    fn attributes(self) -> &'static [(u16, u8)] {
+        match self {
+            Abbrev::CompileUnit => &[
+                (DW_AT_PRODUCER, DW_FORM_STRING),
+                (DW_AT_LANGUAGE, DW_FORM_DATA1),
+                (DW_AT_NAME, DW_FORM_STRING),
+                (DW_AT_COMP_DIR, DW_FORM_STRING),
+                (DW_AT_LOW_PC, DW_FORM_ADDR),
+                (DW_AT_HIGH_PC, DW_FORM_DATA4),
+                (DW_AT_STMT_LIST, DW_FORM_SEC_OFFSET),
+            ],
+            Abbrev::BaseType => &[
+                (DW_AT_BYTE_SIZE, DW_FORM_DATA1),
+                (DW_AT_ENCODING, DW_FORM_DATA1),
+                (DW_AT_NAME, DW_FORM_STRING),
+            ],
+            Abbrev::Subprogram => &[
+                (DW_AT_EXTERNAL, DW_FORM_FLAG),
+                (DW_AT_NAME, DW_FORM_STRING),
+                (DW_AT_DECL_FILE, DW_FORM_DATA1),
+                (DW_AT_DECL_LINE, DW_FORM_DATA4),
+                (DW_AT_TYPE, DW_FORM_REF4),
+                (DW_AT_LOW_PC, DW_FORM_ADDR),
+                (DW_AT_HIGH_PC, DW_FORM_DATA4),
+                (DW_AT_FRAME_BASE, DW_FORM_EXPRLOC),
+            ],
+            Abbrev::VoidSubprogram => &[
+                (DW_AT_EXTERNAL, DW_FORM_FLAG),
+                (DW_AT_NAME, DW_FORM_STRING),
+                (DW_AT_DECL_FILE, DW_FORM_DATA1),
+                (DW_AT_DECL_LINE, DW_FORM_DATA4),
+                (DW_AT_LOW_PC, DW_FORM_ADDR),
+                (DW_AT_HIGH_PC, DW_FORM_DATA4),
+                (DW_AT_FRAME_BASE, DW_FORM_EXPRLOC),
+            ],
+            Abbrev::FormalParameter | Abbrev::Variable => &[
+                (DW_AT_NAME, DW_FORM_STRING),
+                (DW_AT_DECL_FILE, DW_FORM_DATA1),
+                (DW_AT_DECL_LINE, DW_FORM_DATA4),
+                (DW_AT_TYPE, DW_FORM_REF4),
+                (DW_AT_LOCATION, DW_FORM_EXPRLOC),
+            ],
+        }
+    }
+}
+
+/// Label at the start of the program's code
+pub(crate) fn text_start() -> String {
+    format!("{}text0", LOCAL_LABEL_PREFIX)
+}
+
+/// Label at the end of the program's code
+pub(crate) fn text_end() -> String {
+    format!("{}etext0", LOCAL_LABEL_PREFIX)
+}
+
+/// Label right after the last instruction of the function `name`
+pub(crate) fn function_end(name: &str) -> String {
+    format!("{}{}.end", LOCAL_LABEL_PREFIX, name)
+}
+
+/// Writes the DWARF sections describing `program`. The emitter must have
+/// placed the labels of [`text_start`], [`text_end`] and [`function_end`].
+pub(crate) fn write_sections(out: &mut String, info: &DebugInfo, program: &IRProgram) {
+    write_abbrevs(out);
+    write_info(out, info, program);
+
+    // The assembler appends the line table generated from `.loc`
+    writeln!(out, "{}", section("debug_line")).unwrap();
+    writeln!(out, "{}debug_line0:", LOCAL_LABEL_PREFIX).unwrap();
+}
+
+fn write_abbrevs(out: &mut String) {
+    writeln!(out, "{}", section("debug_abbrev")).unwrap();
+    writeln!(out, "{}debug_abbrev0:", LOCAL_LABEL_PREFIX).unwrap();
+    for abbrev in Abbrev::ALL {
+        writeln!(out, "    .uleb128 {}    # {:?}", abbrev as u8, abbrev).unwrap();
+        writeln!(out, "    .uleb128 {:#x}", abbrev.tag()).unwrap();
+        writeln!(out, "    .byte {}", abbrev.has_children() as u8).unwrap();
+        for (attribute, form) in abbrev.attributes() {
+            writeln!(out, "    .uleb128 {:#x}", attribute).unwrap();
+            writeln!(out, "    .uleb128 {:#x}", form).unwrap();
+        }
+        writeln!(out, "    .byte 0").unwrap();
+        writeln!(out, "    .byte 0").unwrap();
+    }
+    writeln!(out, "    .byte 0").unwrap();
+}
+
+fn write_info(out: &mut String, info: &DebugInfo, program: &IRProgram) {
+    let prefix = LOCAL_LABEL_PREFIX;
+    writeln!(out, "{}", section("debug_info")).unwrap();
+    writeln!(out, "{}debug_info0:", prefix).unwrap();
+    writeln!(
+        out,
+        "    .long {}debug_info_end-{}debug_info_start",
+        prefix, prefix
+    )
+    .unwrap();
+    writeln!(out, "{}debug_info_start:", prefix).unwrap();
+    writeln!(out, "    .short 4").unwrap();
+    writeln!(out, "    .long {}debug_abbrev0", prefix).unwrap();
+    writeln!(out, "    .byte 8").unwrap();
+
+    writeln!(out, "    .uleb128 {}", Abbrev::CompileUnit as u8).unwrap();
+    writeln!(out, "    .string {}", quoted(PRODUCER)).unwrap();
+    writeln!(out, "    .byte {:#x}", DW_LANG_C99).unwrap();
+    writeln!(out, "    .string {}", quoted(&info.file)).unwrap();
+    writeln!(out, "    .string {}", quoted(&info.directory)).unwrap();
+    writeln!(out, "    .quad {}", text_start()).unwrap();
+    writeln!(out, "    .long {}-{}", text_end(), text_start()).unwrap();
+    writeln!(out, "    .long {}debug_line0", prefix).unwrap();
+
+    writeln!(out, "{}debug_type_int:", prefix).unwrap();
+    writeln!(out, "    .uleb128 {}", Abbrev::BaseType as u8).unwrap();
+    writeln!(out, "    .byte 4").unwrap();
+    writeln!(out, "    .byte {:#x}", DW_ATE_SIGNED).unwrap();
+    writeln!(out, "    .string \"int\"").unwrap();
+
+    for function in &info.functions {
+        let name = IRFuncDef::platfrom_mangle_name(&function.name);
+        if let Some(func) = program.functions.iter().find(|func| func.name == name) {
+            write_function(out, function, func);
+        }
+    }
+
+    writeln!(out, "    .byte 0").unwrap();
+    writeln!(out, "{}debug_info_end:", prefix).unwrap();
+}
+
+fn write_function(out: &mut String, function: &FunctionInfo, func: &IRFuncDef) {
+    let abbrev = match function.returns_value {
+        true => Abbrev::Subprogram,
+        false => Abbrev::VoidSubprogram,
+    };
+    writeln!(out, "    .uleb128 {}", abbrev as u8).unwrap();
+    writeln!(out, "    .byte {}", func.is_global as u8).unwrap();
+    writeln!(out, "    .string {}", quoted(&function.name)).unwrap();
+    writeln!(out, "    .byte 1").unwrap();
+    writeln!(out, "    .long {}", function.line).unwrap();
+    if function.returns_value {
+        write_int_type(out);
+    }
+    writeln!(out, "    .quad {}", func.name).unwrap();
+    writeln!(out, "    .long {}-{}", function_end(&func.name), func.name).unwrap();
+    write_expression(out, &[DW_OP_CALL_FRAME_CFA]);
+
+    for variable in &function.variables {
+        write_variable(out, variable, func);
+    }
+    writeln!(out, "    .byte 0").unwrap();
+}
+
+fn write_variable(out: &mut String, variable: &VariableInfo, func: &IRFuncDef) {
+    let abbrev = match variable.is_param {
+        true => Abbrev::FormalParameter,
+        false => Abbrev::Variable,
+    };
+    writeln!(out, "    .uleb128 {}", abbrev as u8).unwrap();
+    writeln!(out, "    .string {}", quoted(&variable.name)).unwrap();
+    writeln!(out, "    .byte 1").unwrap();
+    writeln!(out, "    .long {}", variable.line).unwrap();
+    write_int_type(out);
+
+    // A variable that was optimized away or lives in registers gets an
+    // empty location, which debuggers show as optimized out
+    let mut location = Vec::new();
+    if let Some(&offset) = func.stack_slots.get(&variable.tacky_name) {
+        location.push(DW_OP_FBREG);
+        location.extend(sleb128(offset));
+    }
+    write_expression(out, &location);
+}
+
+fn write_int_type(out: &mut String) {
+    writeln!(
+        out,
+        "    .long {}debug_type_int-{}debug_info0",
+        LOCAL_LABEL_PREFIX, LOCAL_LABEL_PREFIX
+    )
+    .unwrap();
+}
+
+/// A DWARF expression, preceded by its length
+fn write_expression(out: &mut String, expression: &[u8]) {
+    writeln!(out, "    .uleb128 {}", expression.len()).unwrap();
+    if !expression.is_empty() {
+        let bytes: Vec<String> = expression.iter().map(|b| format!("{:#x}", b)).collect();
+        writeln!(out, "    .byte {}", bytes.join(", ")).unwrap();
+    }
+}
+
+fn sleb128(mut value: i64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn section(name: &str) -> String {
+    format!(".section __DWARF,__{},regular,debug", name)
+}
+#[cfg(not(target_os = "macos"))]
+fn section(name: &str) -> String {
+    format!(".section .{},\"\",@progbits", name)
+}
+
+/// `s` as an assembler string literal
+pub(crate) fn quoted(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sleb128() {
+        assert_eq!(sleb128(0), vec![0x00]);
+        assert_eq!(sleb128(2), vec![0x02]);
+        assert_eq!(sleb128(-4), vec![0x7c]);
+        assert_eq!(sleb128(-64), vec![0x40]);
+        assert_eq!(sleb128(-65), vec![0xbf, 0x7f]);
+        assert_eq!(sleb128(64), vec![0xc0, 0x00]);
+    }
+
+    #[test]
+    fn test_quoted_escapes() {
+        assert_eq!(quoted(r#"a "b"\c"#), r#""a \"b\"\\c""#);
+    }
+}
@@ -0,0 +1,43 @@
+//! Source-level information for debuggers.
+//!
+//! The TACKY generator records the functions and variables of the program
+//! as it lowers them; the emitter then combines this with the stack slots
+//! that code generation picked and writes it out as DWARF, next to the
+//! `.loc` directives that map instructions back to source lines.
+
+mod dwarf;
+
+pub(crate) use dwarf::{function_end, quoted, text_end, text_start, write_sections};
+
+/// Debug info for a whole translation unit
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugInfo {
+    /// Source file name, as given on the command line
+    pub file: String,
+    /// Directory the compiler ran in, which `file` is relative to
+    pub directory: String,
+    pub functions: Vec<FunctionInfo>,
+}
+
+/// A function definition
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionInfo {
+    pub name: String,
+    pub line: usize,
+    pub column: usize,
+    /// Whether the function returns `int` rather than `void`
+    pub returns_value: bool,
+    /// Parameters first, then locals in order of declaration
+    pub variables: Vec<VariableInfo>,
+}
+
+/// A parameter or local variable
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariableInfo {
+    /// Name in the source
+    pub name: String,
+    /// Unique name of the variable in TACKY
+    pub tacky_name: String,
+    pub line: usize,
+    pub is_param: bool,
+}
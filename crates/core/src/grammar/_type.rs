@@ -1,3 +1,5 @@
+use std::fmt;
+
 use crate::{grammar::TokenType, t};
 
 #[derive(Debug, Clone, PartialEq)]
@@ -15,3 +17,12 @@ impl Type {
         }
     }
 }
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Void => write!(f, "void"),
+        }
+    }
+}
@@ -1,9 +1,16 @@
 use std::borrow::Cow;
 
-use crate::grammar::operator::*;
+use crate::grammar::{Span, operator::*};
 
+/// An expression and the span of source it was parsed from
 #[derive(Debug, Clone, PartialEq)]
-pub enum Expression<'a> {
+pub struct Expression<'a> {
+    pub kind: ExprKind<'a>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind<'a> {
     Constant(i64),
     Variable(Cow<'a, str>),
     Grouped(Box<Expression<'a>>),
@@ -16,13 +23,50 @@ pub enum Expression<'a> {
         op: UnaryOp,
         expr: Box<Expression<'a>>,
     },
+    /// `++expr`, `--expr`, `expr++` or `expr--`
+    IncDec {
+        op: IncDecOp,
+        expr: Box<Expression<'a>>,
+    },
+    Logical {
+        op: LogicalOp,
+        lhs: Box<Expression<'a>>,
+        rhs: Box<Expression<'a>>,
+    },
+    /// `cond ? then_expr : else_expr`
+    Conditional {
+        cond: Box<Expression<'a>>,
+        then_expr: Box<Expression<'a>>,
+        else_expr: Box<Expression<'a>>,
+    },
     Assignment {
         op: AssignOp,
         lhs: Box<Expression<'a>>,
         rhs: Box<Expression<'a>>,
     },
+    /// `lhs, rhs`: evaluates `lhs` for its side effects, then `rhs`
+    Comma {
+        lhs: Box<Expression<'a>>,
+        rhs: Box<Expression<'a>>,
+    },
     FunctionCall {
         callee: Box<Expression<'a>>,
         args: Vec<Expression<'a>>,
     },
 }
+
+impl<'a> Expression<'a> {
+    pub fn new(kind: ExprKind<'a>, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// Whether the expression designates an object, which can then be
+    /// assigned to.
+    pub fn is_lvalue(&self) -> bool {
+        match &self.kind {
+            ExprKind::Variable(_) => true,
+            ExprKind::Grouped(inner) => inner.is_lvalue(),
+            _ => false,
+        }
+    }
+}
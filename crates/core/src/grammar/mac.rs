@@ -26,6 +26,9 @@ macro_rules! t {
     ("for") => {
         $crate::grammar::TokenType::Static($crate::grammar::StaticToken::For)
     };
+    ("goto") => {
+        $crate::grammar::TokenType::Static($crate::grammar::StaticToken::Goto)
+    };
     ("break") => {
         $crate::grammar::TokenType::Static($crate::grammar::StaticToken::Break)
     };
@@ -1,12 +1,20 @@
 use crate::{grammar::TokenType, t};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum BinaryOp {
     // arithmetic
     Add,
     Subtract,
     Multiply,
     Divide,
+    Remainder,
+
+    // bitwise
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    LeftShift,
+    RightShift,
 
     // relational
     LessThan,
@@ -21,9 +29,13 @@ impl BinaryOp {
     pub const fn binding_power(&self) -> BindingPower {
         match self {
             // Multiplicative operators (highest precedence)
-            BinaryOp::Multiply | BinaryOp::Divide => BindingPower::Multiplicative,
+            BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Remainder => {
+                BindingPower::Multiplicative
+            }
             // Additive operators
             BinaryOp::Add | BinaryOp::Subtract => BindingPower::Additive,
+            // Shift operators
+            BinaryOp::LeftShift | BinaryOp::RightShift => BindingPower::Shift,
             // Relational operators
             BinaryOp::LessThan
             | BinaryOp::GreaterThan
@@ -31,6 +43,10 @@ impl BinaryOp {
             | BinaryOp::GreaterThanOrEqual => BindingPower::Relational,
             // Equality operators
             BinaryOp::Equal | BinaryOp::NotEqual => BindingPower::Equality,
+            // Bitwise operators, each at its own level
+            BinaryOp::BitwiseAnd => BindingPower::BitwiseAnd,
+            BinaryOp::BitwiseXor => BindingPower::BitwiseXor,
+            BinaryOp::BitwiseOr => BindingPower::BitwiseOr,
         }
     }
 
@@ -40,6 +56,12 @@ impl BinaryOp {
             t!("-") => Some(BinaryOp::Subtract),
             t!("*") => Some(BinaryOp::Multiply),
             t!("/") => Some(BinaryOp::Divide),
+            t!("%") => Some(BinaryOp::Remainder),
+            t!("&") => Some(BinaryOp::BitwiseAnd),
+            t!("|") => Some(BinaryOp::BitwiseOr),
+            t!("^") => Some(BinaryOp::BitwiseXor),
+            t!("<<") => Some(BinaryOp::LeftShift),
+            t!(">>") => Some(BinaryOp::RightShift),
             t!("<") => Some(BinaryOp::LessThan),
             t!(">") => Some(BinaryOp::GreaterThan),
             t!("<=") => Some(BinaryOp::LessThanOrEqual),
@@ -51,10 +73,11 @@ impl BinaryOp {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum UnaryOp {
     Negate,
     Not,
+    Complement,
 }
 
 impl UnaryOp {
@@ -62,6 +85,7 @@ impl UnaryOp {
         match token {
             t!("!") => Some(UnaryOp::Not),
             t!("-") => Some(UnaryOp::Negate),
+            t!("~") => Some(UnaryOp::Complement),
             _ => None,
         }
     }
@@ -71,6 +95,79 @@ impl UnaryOp {
     }
 }
 
+/// `++` and `--`, which add or subtract 1 and store the result back into
+/// their operand. The prefix forms give the new value, the postfix forms
+/// the old one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IncDecOp {
+    PreIncrement,
+    PreDecrement,
+    PostIncrement,
+    PostDecrement,
+}
+
+impl IncDecOp {
+    pub const fn prefix_from_token_type(token: &TokenType) -> Option<Self> {
+        match token {
+            t!("++") => Some(IncDecOp::PreIncrement),
+            t!("--") => Some(IncDecOp::PreDecrement),
+            _ => None,
+        }
+    }
+
+    pub const fn postfix_from_token_type(token: &TokenType) -> Option<Self> {
+        match token {
+            t!("++") => Some(IncDecOp::PostIncrement),
+            t!("--") => Some(IncDecOp::PostDecrement),
+            _ => None,
+        }
+    }
+
+    pub const fn binding_power(&self) -> BindingPower {
+        match self {
+            IncDecOp::PreIncrement | IncDecOp::PreDecrement => BindingPower::Unary,
+            IncDecOp::PostIncrement | IncDecOp::PostDecrement => BindingPower::Postfix,
+        }
+    }
+
+    pub const fn is_postfix(&self) -> bool {
+        matches!(self, IncDecOp::PostIncrement | IncDecOp::PostDecrement)
+    }
+
+    /// The operation applied to the operand
+    pub const fn binary_op(&self) -> BinaryOp {
+        match self {
+            IncDecOp::PreIncrement | IncDecOp::PostIncrement => BinaryOp::Add,
+            IncDecOp::PreDecrement | IncDecOp::PostDecrement => BinaryOp::Subtract,
+        }
+    }
+}
+
+/// `&&` and `||`, which only evaluate their right operand when the left
+/// one does not decide the result
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LogicalOp {
+    And,
+    Or,
+}
+
+impl LogicalOp {
+    pub const fn binding_power(&self) -> BindingPower {
+        match self {
+            LogicalOp::And => BindingPower::LogicalAnd,
+            LogicalOp::Or => BindingPower::LogicalOr,
+        }
+    }
+
+    pub const fn from_token_type(token: &TokenType) -> Option<Self> {
+        match token {
+            t!("&&") => Some(LogicalOp::And),
+            t!("||") => Some(LogicalOp::Or),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum AssignOp {
     Assign,       // =
@@ -92,6 +189,24 @@ impl AssignOp {
         BindingPower::Assignment
     }
 
+    /// The operation a compound assignment applies to its target, or
+    /// `None` for plain `=`
+    pub const fn binary_op(&self) -> Option<BinaryOp> {
+        match self {
+            AssignOp::Assign => None,
+            AssignOp::PlusAssign => Some(BinaryOp::Add),
+            AssignOp::MinusAssign => Some(BinaryOp::Subtract),
+            AssignOp::MulAssign => Some(BinaryOp::Multiply),
+            AssignOp::DivAssign => Some(BinaryOp::Divide),
+            AssignOp::ModAssign => Some(BinaryOp::Remainder),
+            AssignOp::AndAssign => Some(BinaryOp::BitwiseAnd),
+            AssignOp::OrAssign => Some(BinaryOp::BitwiseOr),
+            AssignOp::XorAssign => Some(BinaryOp::BitwiseXor),
+            AssignOp::LShiftAssign => Some(BinaryOp::LeftShift),
+            AssignOp::RShiftAssign => Some(BinaryOp::RightShift),
+        }
+    }
+
     pub const fn from_token_type(token: &TokenType) -> Option<Self> {
         match token {
             t!("=") => Some(AssignOp::Assign),
@@ -257,7 +372,16 @@ impl BindingPower {
             op.binding_power()
         } else if let Some(op) = BinaryOp::from_token_type(token_type) {
             op.binding_power()
-        } else if matches!(token_type, t!("[") | t!("(") | t!(".")) {
+        } else if let Some(op) = LogicalOp::from_token_type(token_type) {
+            op.binding_power()
+        } else if matches!(token_type, t!("?")) {
+            BindingPower::Conditional
+        } else if matches!(token_type, t!(",")) {
+            BindingPower::Comma
+        } else if matches!(
+            token_type,
+            t!("[") | t!("(") | t!(".") | t!("++") | t!("--")
+        ) {
             BindingPower::Postfix
         } else {
             BindingPower::Base
@@ -1,31 +1,35 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, marker::PhantomData};
 
 use compiler_macros::statement_enum;
 
-use crate::grammar::{Expression, Type};
+use crate::grammar::{Expression, Span, Type};
 
-/// A function definition
+/// A function declaration, which is a definition when it has a body
 #[derive(Debug)]
 pub struct FuncDef<'a> {
     pub return_type: Type,
     pub name: Cow<'a, str>,
     pub params: Vec<(Type, Cow<'a, str>)>,
-    pub body: BlockStmt<'a>,
+    pub body: Option<BlockStmt<'a>>,
+    pub span: Span,
 }
 
 statement_enum! {
     #[derive(Debug, Clone)]
     pub struct BlockStmt<'a> {
         pub statements: Vec<Statement<'a>>,
+        pub span: Span,
     }
 
     #[derive(Debug, Clone, Default)]
     pub struct BreakStmt<'a> {
+        pub span: Span,
         _m: std::marker::PhantomData< &'a()> ,
     }
 
     #[derive(Debug, Clone, Default)]
     pub struct ContinueStmt<'a> {
+        pub span: Span,
         _m: std::marker::PhantomData< &'a()> ,
     }
 
@@ -34,17 +38,20 @@ statement_enum! {
         pub var_type: Type,
         pub name: Cow<'a, str>,
         pub initializer: Option<Expression<'a>>,
+        pub span: Span,
     }
 
     #[derive(Debug, Clone)]
     pub struct DoWhileStmt<'a> {
         pub cond: Expression<'a>,
         pub body: Box<Statement<'a>>,
+        pub span: Span,
     }
 
     #[derive(Debug, Clone)]
     pub struct ExprStmt<'a> {
         pub expr: Expression<'a>,
+        pub span: Span,
     }
 
     #[derive(Debug, Clone)]
@@ -53,6 +60,14 @@ statement_enum! {
         pub cond: Option<Expression<'a>>,
         pub post: Option<Expression<'a>>,
         pub body: Box<Statement<'a>>,
+        pub span: Span,
+    }
+
+    /// `goto label;`
+    #[derive(Debug, Clone)]
+    pub struct GotoStmt<'a> {
+        pub label: Cow<'a, str>,
+        pub span: Span,
     }
 
     #[derive(Debug, Clone)]
@@ -60,22 +75,82 @@ statement_enum! {
         pub cond: Expression<'a>,
         pub then_block: Box<Statement<'a>>,
         pub else_block: Option<Box<Statement<'a>>>,
+        pub span: Span,
+    }
+
+    /// `label: stmt`
+    #[derive(Debug, Clone)]
+    pub struct LabeledStmt<'a> {
+        pub label: Cow<'a, str>,
+        pub stmt: Box<Statement<'a>>,
+        pub span: Span,
     }
 
     /// Represents a statement with one semicolon.
     #[derive(Debug, Clone, Default)]
     pub struct NullStmt<'a> {
+        pub span: Span,
         _m: std::marker::PhantomData< &'a()>,
     }
 
     #[derive(Debug, Clone)]
     pub struct ReturnStmt<'a> {
-        pub expr: Expression<'a>,
+        pub expr: Option<Expression<'a>>,
+        pub span: Span,
     }
 
     #[derive(Debug, Clone)]
     pub struct WhileStmt<'a> {
         pub cond: Expression<'a>,
         pub body: Box<Statement<'a>>,
+        pub span: Span,
+    }
+}
+
+impl BreakStmt<'_> {
+    pub fn new(span: Span) -> Self {
+        Self {
+            span,
+            _m: PhantomData,
+        }
+    }
+}
+
+impl ContinueStmt<'_> {
+    pub fn new(span: Span) -> Self {
+        Self {
+            span,
+            _m: PhantomData,
+        }
+    }
+}
+
+impl NullStmt<'_> {
+    pub fn new(span: Span) -> Self {
+        Self {
+            span,
+            _m: PhantomData,
+        }
+    }
+}
+
+impl Statement<'_> {
+    /// Source location covered by this statement.
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Block(s) => s.span,
+            Statement::Break(s) => s.span,
+            Statement::Continue(s) => s.span,
+            Statement::Declaration(s) => s.span,
+            Statement::DoWhile(s) => s.span,
+            Statement::Expr(s) => s.span,
+            Statement::For(s) => s.span,
+            Statement::Goto(s) => s.span,
+            Statement::If(s) => s.span,
+            Statement::Labeled(s) => s.span,
+            Statement::Null(s) => s.span,
+            Statement::Return(s) => s.span,
+            Statement::While(s) => s.span,
+        }
     }
 }
@@ -9,6 +9,7 @@ pub enum StaticToken {
     Do,
     Else,
     For,
+    Goto,
     If,
     Int,
     Return,
@@ -91,6 +92,7 @@ impl StaticToken {
             StaticToken::Do => "do",
             StaticToken::Else => "else",
             StaticToken::For => "for",
+            StaticToken::Goto => "goto",
             StaticToken::If => "if",
             StaticToken::Int => "int",
             StaticToken::Return => "return",
@@ -172,6 +174,7 @@ pub const ALL_KEYWORDS: &[StaticToken] = &[
     StaticToken::Do,
     StaticToken::Else,
     StaticToken::For,
+    StaticToken::Goto,
     StaticToken::If,
     StaticToken::Int,
     StaticToken::Return,
@@ -240,7 +243,7 @@ pub const ONE_CHAR_PUNCTUATORS: &[StaticToken] = &[
 ];
 
 /// Span represents the location information of a token in the source code
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct Span {
     /// byte offset in source (inclusive)
     pub start: usize,
@@ -262,6 +265,14 @@ impl Span {
         }
     }
 
+    /// Returns a `Span` that starts at `self` and ends where `end` ends.
+    pub fn to(self, end: Span) -> Self {
+        Self {
+            end: end.end.max(self.start),
+            ..self
+        }
+    }
+
     /// Returns a `Span` representing the end-of-file position of the given
     /// source.
     pub fn eof(src: &str) -> Self {
@@ -0,0 +1,354 @@
+//! Call frame information.
+//!
+//! `.cfi_*` directives tell unwinders, and so debuggers and profilers, how
+//! to get from any instruction of a function back to its caller: which
+//! register plus offset gives the canonical frame address (the value of
+//! `rsp` before the call), and where callee-saved registers were stored.
+//! They are derived from the final instructions rather than from how the
+//! frame was built, so they stay right for every frame shape, including
+//! register saves that the scheduler moved.
+
+use std::collections::HashSet;
+
+use crate::{
+    ir_base::{Instruction, Operand, Size, reg::PhyRegister},
+    r,
+};
+
+/// Tracks the frame through the instructions of one function.
+pub(super) struct Cfi {
+    /// Register the canonical frame address is relative to
+    cfa_register: PhyRegister,
+    /// Offset of the canonical frame address from `cfa_register`
+    cfa_offset: i64,
+    /// Registers whose save slot was announced
+    saved: HashSet<PhyRegister>,
+    /// Indices of the instructions that start releasing the frame before a
+    /// return
+    epilogues: HashSet<usize>,
+    /// Frame state saved with `.cfi_remember_state` at the current
+    /// epilogue
+    remembered: Option<(PhyRegister, i64)>,
+    /// Whether the frame is still being set up
+    in_prologue: bool,
+}
+
+impl Cfi {
+    pub(super) fn new(instructions: &[Instruction]) -> Self {
+        Self {
+            // The call has just pushed the return address
+            cfa_register: r!("rsp"),
+            cfa_offset: 8,
+            saved: HashSet::new(),
+            epilogues: Self::find_epilogues(instructions),
+            remembered: None,
+            in_prologue: true,
+        }
+    }
+
+    /// Every epilogue is followed by a return, after which code continues
+    /// with the frame of the function body. Remembering the state at the
+    /// start of the epilogue lets us go back to it once the return is past.
+    fn find_epilogues(instructions: &[Instruction]) -> HashSet<usize> {
+        let releases_frame = |inst: &Instruction| {
+            matches!(
+                inst,
+                Instruction::Mov {
+                    size: Size::Quad,
+                    src: Operand::Register(r!("rbp")),
+                    dst: Operand::Register(r!("rsp")),
+                } | Instruction::Pop(Operand::Register(r!("rbp")))
+                    | Instruction::DeallocateStack(_)
+            )
+        };
+        let mut epilogues = HashSet::new();
+        for (idx, inst) in instructions.iter().enumerate() {
+            if !matches!(inst, Instruction::Ret | Instruction::TailCall { .. }) {
+                continue;
+            }
+            // Stack arguments released after a call are not part of it
+            let start = (0..idx)
+                .rev()
+                .find(|&pos| {
+                    !releases_frame(&instructions[pos])
+                        || pos > 0 && matches!(instructions[pos - 1], Instruction::Call { .. })
+                })
+                .map_or(0, |pos| pos + 1);
+            if start < idx {
+                epilogues.insert(start);
+            }
+        }
+        epilogues
+    }
+
+    /// Directives that go right before the instruction at `idx`.
+    pub(super) fn before(&mut self, idx: usize) -> Vec<String> {
+        if self.epilogues.contains(&idx) {
+            self.remembered = Some((self.cfa_register, self.cfa_offset));
+            vec![".cfi_remember_state".to_string()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Directives that describe the frame after `inst` executed.
+    /// `is_last` tells whether it ends the function.
+    pub(super) fn after(&mut self, inst: &Instruction, is_last: bool) -> Vec<String> {
+        let mut directives = Vec::new();
+        self.in_prologue &= Self::sets_up_frame(inst);
+        match inst {
+            Instruction::Push(operand) => {
+                self.adjust_rsp(8, &mut directives);
+                if let Operand::Register(reg) = operand
+                    && self.in_prologue
+                    && is_callee_saved(*reg)
+                    && self.saved.insert(*reg)
+                {
+                    // The push stored it right below the old `rsp`
+                    directives.push(format!(
+                        ".cfi_offset %{}, {}",
+                        reg.as_str(),
+                        -self.cfa_offset
+                    ));
+                }
+            }
+            Instruction::Pop(_) => self.adjust_rsp(-8, &mut directives),
+            Instruction::AllocateStack(bytes) => self.adjust_rsp(*bytes, &mut directives),
+            Instruction::DeallocateStack(bytes) => self.adjust_rsp(-*bytes, &mut directives),
+            Instruction::Mov {
+                size: Size::Quad,
+                src: Operand::Register(src),
+                dst: Operand::Register(dst),
+            } if *src == self.cfa_register && matches!(*dst, r!("rsp") | r!("rbp")) => {
+                self.cfa_register = *dst;
+                directives.push(format!(".cfi_def_cfa_register %{}", dst.as_str()));
+            }
+            // Only the saves of callee-saved registers move whole 64-bit
+            // registers into the frame
+            Instruction::Mov {
+                size: Size::Quad,
+                src: Operand::Register(reg),
+                dst:
+                    Operand::Memory {
+                        base: Some(base),
+                        index: None,
+                        offset,
+                        ..
+                    },
+            } if is_callee_saved(*reg) && *base == self.cfa_register && self.saved.insert(*reg) => {
+                directives.push(format!(
+                    ".cfi_offset %{}, {}",
+                    reg.as_str(),
+                    offset - self.cfa_offset
+                ));
+            }
+            Instruction::Ret | Instruction::TailCall { .. } => {
+                if let Some((register, offset)) = self.remembered.take()
+                    && !is_last
+                {
+                    self.cfa_register = register;
+                    self.cfa_offset = offset;
+                    directives.push(".cfi_restore_state".to_string());
+                }
+            }
+            _ => {}
+        }
+        directives
+    }
+
+    /// Accounts for `rsp` moving down by `bytes`.
+    fn adjust_rsp(&mut self, bytes: i64, directives: &mut Vec<String>) {
+        if self.cfa_register == r!("rsp") {
+            self.cfa_offset += bytes;
+            directives.push(format!(".cfi_def_cfa_offset {}", self.cfa_offset));
+        }
+    }
+
+    /// Whether `inst` can be part of a prologue
+    fn sets_up_frame(inst: &Instruction) -> bool {
+        matches!(
+            inst,
+            Instruction::Push(Operand::Register(_))
+                | Instruction::AllocateStack(_)
+                | Instruction::Mov {
+                    size: Size::Quad,
+                    src: Operand::Register(_),
+                    ..
+                }
+        )
+    }
+}
+
+fn is_callee_saved(reg: PhyRegister) -> bool {
+    matches!(
+        reg,
+        r!("rbx") | r!("rbp") | r!("r12") | r!("r13") | r!("r14") | r!("r15")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each instruction with the directives around it
+    fn annotate(instructions: &[Instruction]) -> Vec<String> {
+        let mut cfi = Cfi::new(instructions);
+        let mut lines = Vec::new();
+        for (idx, inst) in instructions.iter().enumerate() {
+            lines.extend(cfi.before(idx));
+            lines.push(inst.as_assembly_inline());
+            lines.extend(cfi.after(inst, idx + 1 == instructions.len()));
+        }
+        lines
+    }
+
+    fn prologue() -> Vec<Instruction> {
+        vec![
+            Instruction::Push(Operand::Register(r!("rbp"))),
+            Instruction::Mov {
+                size: Size::Quad,
+                src: Operand::Register(r!("rsp")),
+                dst: Operand::Register(r!("rbp")),
+            },
+        ]
+    }
+
+    fn epilogue() -> Vec<Instruction> {
+        vec![
+            Instruction::Mov {
+                size: Size::Quad,
+                src: Operand::Register(r!("rbp")),
+                dst: Operand::Register(r!("rsp")),
+            },
+            Instruction::Pop(Operand::Register(r!("rbp"))),
+            Instruction::Ret,
+        ]
+    }
+
+    #[test]
+    fn test_frame_pointer_prologue_and_epilogue() {
+        let mut instructions = prologue();
+        instructions.push(Instruction::AllocateStack(16));
+        instructions.extend(epilogue());
+        assert_eq!(
+            annotate(&instructions),
+            vec![
+                "pushq %rbp",
+                ".cfi_def_cfa_offset 16",
+                ".cfi_offset %rbp, -16",
+                "movq %rsp, %rbp",
+                ".cfi_def_cfa_register %rbp",
+                "subq $16, %rsp",
+                ".cfi_remember_state",
+                "movq %rbp, %rsp",
+                ".cfi_def_cfa_register %rsp",
+                "popq %rbp",
+                ".cfi_def_cfa_offset 8",
+                "retq",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_callee_saved_registers_are_located_from_the_cfa() {
+        let mut instructions = prologue();
+        instructions.extend([
+            Instruction::AllocateStack(16),
+            Instruction::Mov {
+                size: Size::Quad,
+                src: Operand::Register(r!("rbx")),
+                dst: Operand::memory(r!("rbp"), -8),
+            },
+        ]);
+        instructions.extend(epilogue());
+        let lines = annotate(&instructions);
+        // `rbp` is 16 bytes below the frame address
+        assert!(lines.contains(&".cfi_offset %rbx, -24".to_string()));
+    }
+
+    #[test]
+    fn test_state_is_restored_after_an_early_return() {
+        let mut instructions = prologue();
+        instructions.extend(epilogue());
+        instructions.push(Instruction::Label("other".into()));
+        instructions.extend(epilogue());
+        let lines = annotate(&instructions);
+        let remembers = lines.iter().filter(|l| *l == ".cfi_remember_state").count();
+        assert_eq!(remembers, 2);
+        // Only the return that is followed by more code restores the state
+        let restores: Vec<usize> = (0..lines.len())
+            .filter(|&idx| lines[idx] == ".cfi_restore_state")
+            .collect();
+        assert_eq!(restores.len(), 1);
+        assert_eq!(lines[restores[0] - 1], "retq");
+        // The second epilogue starts from the `rbp`-based frame again
+        let second = &lines[restores[0]..];
+        assert!(second.contains(&".cfi_def_cfa_register %rsp".to_string()));
+    }
+
+    #[test]
+    fn test_argument_cleanup_is_not_part_of_the_epilogue() {
+        let instructions = vec![
+            Instruction::AllocateStack(8),
+            Instruction::Push(Operand::Immediate(1)),
+            Instruction::Call {
+                name: "f".into(),
+                arg_registers: 0,
+            },
+            Instruction::DeallocateStack(8),
+            Instruction::DeallocateStack(8),
+            Instruction::Ret,
+            Instruction::Label("other".into()),
+            Instruction::DeallocateStack(8),
+            Instruction::Ret,
+        ];
+        let lines = annotate(&instructions);
+        // The state remembered, and restored after the first return, is the
+        // one of the function body
+        let remember = lines
+            .iter()
+            .position(|l| l == ".cfi_remember_state")
+            .unwrap();
+        assert_eq!(lines[remember - 1], ".cfi_def_cfa_offset 16");
+        let restore = lines
+            .iter()
+            .position(|l| l == ".cfi_restore_state")
+            .unwrap();
+        assert_eq!(
+            lines[restore..]
+                .iter()
+                .find(|l| l.starts_with(".cfi_def_cfa_offset")),
+            Some(&".cfi_def_cfa_offset 8".to_string())
+        );
+    }
+
+    #[test]
+    fn test_rsp_based_frame_tracks_every_adjustment() {
+        let instructions = vec![
+            Instruction::AllocateStack(24),
+            Instruction::Push(Operand::Immediate(1)),
+            Instruction::Call {
+                name: "f".into(),
+                arg_registers: 0,
+            },
+            Instruction::DeallocateStack(8),
+            Instruction::DeallocateStack(24),
+            Instruction::Ret,
+        ];
+        let lines = annotate(&instructions);
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|l| l.starts_with(".cfi_def_cfa_offset"))
+                .collect::<Vec<_>>(),
+            vec![
+                ".cfi_def_cfa_offset 32",
+                ".cfi_def_cfa_offset 40",
+                ".cfi_def_cfa_offset 32",
+                ".cfi_def_cfa_offset 8",
+            ]
+        );
+        // An argument push is not a register save
+        assert!(!lines.iter().any(|l| l.starts_with(".cfi_offset")));
+    }
+}
@@ -1,25 +1,54 @@
 use std::fmt::Write;
 
-use crate::ir_base::{
-    IRProgram,
-    instruction::{IRFuncDef, Instruction},
+use crate::{
+    debug_base::{self, DebugInfo},
+    ir_base::{
+        IRProgram,
+        cfi::Cfi,
+        instruction::{IRFuncDef, Instruction},
+    },
 };
 
 pub struct Emitter {
     pub output: String,
+    /// Source-level information to emit DWARF for, if any
+    debug_info: Option<DebugInfo>,
 }
 impl Emitter {
     pub fn new() -> Self {
         Self {
             output: String::new(),
+            debug_info: None,
         }
     }
 
+    /// Emits `.loc` directives and DWARF sections describing `debug_info`
+    /// along with the code.
+    pub fn with_debug_info(mut self, debug_info: DebugInfo) -> Self {
+        self.debug_info = Some(debug_info);
+        self
+    }
+
     pub fn emit_program(&mut self, program: &IRProgram) -> String {
+        if let Some(info) = &self.debug_info {
+            let file = debug_base::quoted(&info.file);
+            writeln!(self.output, "    .file 1 {}", file).unwrap();
+            writeln!(self.output, "    .text").unwrap();
+            writeln!(self.output, "{}:", debug_base::text_start()).unwrap();
+        }
         for func in &program.functions {
             self.emit_function(func);
             self.output.push('\n');
         }
+        if let Some(info) = &self.debug_info {
+            writeln!(self.output, "{}:", debug_base::text_end()).unwrap();
+            debug_base::write_sections(&mut self.output, info, program);
+        }
+        // Mark the stack as non-executable for the GNU linker
+        #[cfg(target_os = "linux")]
+        if !program.functions.is_empty() {
+            writeln!(self.output, "    .section .note.GNU-stack,\"\",@progbits").unwrap();
+        }
         std::mem::take(&mut self.output)
     }
 
@@ -28,21 +57,65 @@ impl Emitter {
             writeln!(self.output, "    .global {}", func.name).unwrap();
         }
         writeln!(self.output, "{}:", func.name).unwrap();
+        // The prologue belongs to the line of the function's name
+        let info = self.debug_info.as_ref().and_then(|info| {
+            info.functions
+                .iter()
+                .find(|function| IRFuncDef::platfrom_mangle_name(&function.name) == func.name)
+        });
+        if let Some(function) = info {
+            writeln!(
+                self.output,
+                "    .loc 1 {} {}",
+                function.line, function.column
+            )
+            .unwrap();
+        }
 
-        for inst in &func.instructions {
+        writeln!(self.output, "    .cfi_startproc").unwrap();
+        let mut cfi = Cfi::new(&func.instructions);
+        for (idx, inst) in func.instructions.iter().enumerate() {
+            for directive in cfi.before(idx) {
+                writeln!(self.output, "    {}", directive).unwrap();
+            }
             self.emit_instruction(inst);
+            for directive in cfi.after(inst, idx + 1 == func.instructions.len()) {
+                writeln!(self.output, "    {}", directive).unwrap();
+            }
+        }
+        writeln!(self.output, "    .cfi_endproc").unwrap();
+
+        if self.debug_info.is_some() {
+            writeln!(self.output, "{}:", debug_base::function_end(&func.name)).unwrap();
         }
     }
 
     fn emit_instruction(&mut self, inst: &Instruction) {
-        writeln!(self.output, "    {}", inst.as_assembly_inline()).unwrap();
+        match inst {
+            Instruction::Label(_) => writeln!(self.output, "{}", inst.as_assembly_inline()),
+            _ => writeln!(self.output, "    {}", inst.as_assembly_inline()),
+        }
+        .unwrap();
+    }
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{ir_base::Operand, r};
+    use crate::{
+        ir_base::{CondCode, LOCAL_LABEL_PREFIX, Operand, Size},
+        r,
+    };
+
+    fn mangled(name: &str) -> String {
+        IRFuncDef::platfrom_mangle_name(name)
+    }
 
     fn normalize_whitespace(s: &str) -> String {
         s.split_whitespace().collect::<Vec<_>>().join(" ")
@@ -66,10 +139,12 @@ mod tests {
         let instructions = vec![
             Instruction::Push(Operand::Register(r!("rbp"))),
             Instruction::Mov {
+                size: Size::Quad,
                 src: Operand::Register(r!("rsp")),
                 dst: Operand::Register(r!("rbp")),
             },
             Instruction::Mov {
+                size: Size::Quad,
                 src: Operand::Immediate(42),
                 dst: Operand::Register(r!("rax")),
             },
@@ -85,8 +160,14 @@ mod tests {
         let assembly = emitter.emit_program(&program);
         println!("{}", assembly);
 
-        assert!(contains_normalized(&assembly, ".global _main"));
-        assert!(contains_normalized(&assembly, "_main:"));
+        assert!(contains_normalized(
+            &assembly,
+            &format!(".global {}", mangled("main"))
+        ));
+        assert!(contains_normalized(
+            &assembly,
+            &format!("{}:", mangled("main"))
+        ));
         assert!(contains_normalized(&assembly, "pushq   %rbp"));
         assert!(contains_normalized(&assembly, "movq    %rsp, %rbp"));
         assert!(contains_normalized(&assembly, "movq    $42, %rax"));
@@ -96,15 +177,21 @@ mod tests {
 
     #[test]
     fn test_emit_non_global_function() {
-        let func = IRFuncDef::new("helper".into(), false, &vec![Instruction::Ret]);
+        let func = IRFuncDef::new("helper".into(), false, &[Instruction::Ret]);
         let mut program = IRProgram::new();
         program.add_function(func);
 
         let mut emitter = Emitter::new();
         let assembly = emitter.emit_program(&program);
 
-        assert!(contains_normalized(&assembly, "_helper:"));
-        assert!(!contains_normalized(&assembly, ".global _helper"));
+        assert!(contains_normalized(
+            &assembly,
+            &format!("{}:", mangled("helper"))
+        ));
+        assert!(!contains_normalized(
+            &assembly,
+            &format!(".global {}", mangled("helper"))
+        ));
         assert!(contains_normalized(&assembly, "retq"));
     }
 
@@ -112,18 +199,22 @@ mod tests {
     fn test_emit_mov_sizes() {
         let instructions = vec![
             Instruction::Mov {
+                size: Size::Quad,
                 src: Operand::Immediate(1),
                 dst: Operand::Register(r!("rax")),
             },
             Instruction::Mov {
+                size: Size::Quad,
                 src: Operand::Immediate(2),
                 dst: Operand::Register(r!("rbx")),
             },
             Instruction::Mov {
+                size: Size::Quad,
                 src: Operand::Immediate(3),
                 dst: Operand::Register(r!("rcx")),
             },
             Instruction::Mov {
+                size: Size::Quad,
                 src: Operand::Immediate(4),
                 dst: Operand::Register(r!("rdx")),
             },
@@ -166,9 +257,9 @@ mod tests {
         let mut emitter = Emitter::new();
         let assembly = emitter.emit_program(&program);
 
-        assert!(contains_normalized(&assembly, "addl    $5, %rax"));
-        assert!(contains_normalized(&assembly, "subl    %rcx, %rax"));
-        assert!(contains_normalized(&assembly, "imull   $3, %rax"));
+        assert!(contains_normalized(&assembly, "addl    $5, %eax"));
+        assert!(contains_normalized(&assembly, "subl    %ecx, %eax"));
+        assert!(contains_normalized(&assembly, "imull   $3, %eax"));
     }
 
     #[test]
@@ -189,15 +280,44 @@ mod tests {
         let mut emitter = Emitter::new();
         let assembly = emitter.emit_program(&program);
 
-        assert!(contains_normalized(&assembly, "negl    %rax"));
-        assert!(contains_normalized(&assembly, "notl    %rbx"));
+        assert!(contains_normalized(&assembly, "negl    %eax"));
+        assert!(contains_normalized(&assembly, "notl    %ebx"));
+    }
+
+    #[test]
+    fn test_emit_shifts() {
+        let instructions = vec![
+            Instruction::Sal {
+                count: Operand::Register(r!("rcx")),
+                dst: Operand::Register(r!("rax")),
+            },
+            Instruction::Sar {
+                count: Operand::Immediate(3),
+                dst: Operand::memory(r!("rbp"), -4),
+            },
+        ];
+
+        let func = IRFuncDef::new("shifts".into(), true, &instructions);
+        let mut program = IRProgram::new();
+        program.add_function(func);
+
+        let assembly = Emitter::new().emit_program(&program);
+
+        assert!(contains_normalized(&assembly, "sall    %cl, %eax"));
+        assert!(contains_normalized(&assembly, "sarl    $3, -4(%rbp)"));
     }
 
     #[test]
     fn test_emit_call() {
         let instructions = vec![
-            Instruction::Call("printf".to_string()),
-            Instruction::Call("_helper".to_string()),
+            Instruction::Call {
+                name: "printf".to_string(),
+                arg_registers: 1,
+            },
+            Instruction::Call {
+                name: "_helper".to_string(),
+                arg_registers: 0,
+            },
         ];
 
         let func = IRFuncDef::new("caller".into(), true, &instructions);
@@ -213,9 +333,9 @@ mod tests {
 
     #[test]
     fn test_emit_multiple_functions() {
-        let func1 = IRFuncDef::new("main".into(), true, &vec![Instruction::Ret]);
+        let func1 = IRFuncDef::new("main".into(), true, &[Instruction::Ret]);
 
-        let func2 = IRFuncDef::new("helper".into(), false, &vec![Instruction::Ret]);
+        let func2 = IRFuncDef::new("helper".into(), false, &[Instruction::Ret]);
 
         let mut program = IRProgram::new();
         program.add_function(func1);
@@ -224,10 +344,22 @@ mod tests {
         let mut emitter = Emitter::new();
         let assembly = emitter.emit_program(&program);
         // Check both functions are present
-        assert!(contains_normalized(&assembly, "_main:"));
-        assert!(contains_normalized(&assembly, "_helper:"));
-        assert!(contains_normalized(&assembly, ".global _main"));
-        assert!(!contains_normalized(&assembly, ".global _helper"));
+        assert!(contains_normalized(
+            &assembly,
+            &format!("{}:", mangled("main"))
+        ));
+        assert!(contains_normalized(
+            &assembly,
+            &format!("{}:", mangled("helper"))
+        ));
+        assert!(contains_normalized(
+            &assembly,
+            &format!(".global {}", mangled("main"))
+        ));
+        assert!(!contains_normalized(
+            &assembly,
+            &format!(".global {}", mangled("helper"))
+        ));
 
         // Check they're separated by blank line
         let functions: Vec<&str> = assembly.split("\n\n").collect();
@@ -238,18 +370,14 @@ mod tests {
     fn test_emit_memory_operands() {
         let instructions = vec![
             Instruction::Mov {
-                src: Operand::Memory {
-                    base: Some(r!("rbp")),
-                    offset: -8,
-                },
+                size: Size::Quad,
+                src: Operand::memory(r!("rbp"), -8),
                 dst: Operand::Register(r!("rax")),
             },
             Instruction::Mov {
+                size: Size::Quad,
                 src: Operand::Register(r!("rcx")),
-                dst: Operand::Memory {
-                    base: Some(r!("rsp")),
-                    offset: 0,
-                },
+                dst: Operand::memory(r!("rsp"), 0),
             },
         ];
 
@@ -263,4 +391,197 @@ mod tests {
         assert!(contains_normalized(&assembly, "movq    -8(%rbp), %rax"));
         assert!(contains_normalized(&assembly, "movq    %rcx, 0(%rsp)"));
     }
+
+    #[test]
+    fn test_emit_lea_with_index_register() {
+        let instructions = vec![
+            Instruction::Lea {
+                base: Some(Operand::Register(r!("rdi"))),
+                index: Some(Operand::Register(r!("rsi"))),
+                scale: 4,
+                offset: 8,
+                dst: Operand::Register(r!("rax")),
+            },
+            Instruction::Lea {
+                base: None,
+                index: Some(Operand::Register(r!("rdi"))),
+                scale: 8,
+                offset: -1,
+                dst: Operand::Register(r!("rcx")),
+            },
+            Instruction::Test {
+                src: Operand::Register(r!("rax")),
+                dst: Operand::Register(r!("rax")),
+            },
+        ];
+
+        let func = IRFuncDef::new("lea".into(), true, &instructions);
+        let mut program = IRProgram::new();
+        program.add_function(func);
+
+        let mut emitter = Emitter::new();
+        let assembly = emitter.emit_program(&program);
+
+        assert!(contains_normalized(
+            &assembly,
+            "leal    8(%rdi,%rsi,4), %eax"
+        ));
+        assert!(contains_normalized(&assembly, "leal    -1(,%rdi,8), %ecx"));
+        assert!(contains_normalized(&assembly, "testl    %eax, %eax"));
+    }
+
+    #[test]
+    fn test_emit_long_mov_uses_32bit_registers() {
+        let instructions = vec![Instruction::Mov {
+            size: Size::Long,
+            src: Operand::Immediate(7),
+            dst: Operand::Register(r!("rax")),
+        }];
+
+        let func = IRFuncDef::new("long".into(), true, &instructions);
+        let mut program = IRProgram::new();
+        program.add_function(func);
+
+        let mut emitter = Emitter::new();
+        let assembly = emitter.emit_program(&program);
+
+        assert!(contains_normalized(&assembly, "movl    $7, %eax"));
+    }
+
+    #[test]
+    fn test_emit_control_flow() {
+        let instructions = vec![
+            Instruction::Cmp {
+                src: Operand::Immediate(0),
+                dst: Operand::Register(r!("rcx")),
+            },
+            Instruction::JmpCC {
+                cond: CondCode::E,
+                target: "end".to_string(),
+            },
+            Instruction::SetCC {
+                cond: CondCode::LE,
+                dst: Operand::Register(r!("rax")),
+            },
+            Instruction::Cmov {
+                cond: CondCode::G,
+                src: Operand::memory(r!("rbp"), -4),
+                dst: Operand::Register(r!("rdx")),
+            },
+            Instruction::Jmp("end".to_string()),
+            Instruction::Label("end".to_string()),
+        ];
+
+        let func = IRFuncDef::new("branches".into(), true, &instructions);
+        let mut program = IRProgram::new();
+        program.add_function(func);
+
+        let mut emitter = Emitter::new();
+        let assembly = emitter.emit_program(&program);
+
+        assert!(contains_normalized(&assembly, "cmpl    $0, %ecx"));
+        assert!(contains_normalized(
+            &assembly,
+            &format!("je {}end", LOCAL_LABEL_PREFIX)
+        ));
+        assert!(contains_normalized(&assembly, "setle   %al"));
+        assert!(contains_normalized(&assembly, "cmovgl  -4(%rbp), %edx"));
+        assert!(contains_normalized(
+            &assembly,
+            &format!("jmp {}end", LOCAL_LABEL_PREFIX)
+        ));
+        assert!(assembly.contains(&format!("\n{}end:", LOCAL_LABEL_PREFIX)));
+    }
+
+    #[test]
+    fn test_emit_debug_info() {
+        let instructions = vec![
+            Instruction::Loc { line: 2, column: 5 },
+            Instruction::Mov {
+                size: Size::Long,
+                src: Operand::memory(r!("rbp"), -4),
+                dst: Operand::Register(r!("rax")),
+            },
+            Instruction::Ret,
+        ];
+        let mut func = IRFuncDef::new("main".into(), true, &instructions);
+        // `-4(%rbp)`, below the return address and the saved `rbp`
+        func.stack_slots.insert("x.0".into(), -20);
+        let mut program = IRProgram::new();
+        program.add_function(func);
+
+        let debug_info = DebugInfo {
+            file: "main.c".into(),
+            directory: "/src".into(),
+            functions: vec![debug_base::FunctionInfo {
+                name: "main".into(),
+                line: 1,
+                column: 1,
+                returns_value: true,
+                variables: vec![debug_base::VariableInfo {
+                    name: "x".into(),
+                    tacky_name: "x.0".into(),
+                    line: 2,
+                    is_param: false,
+                }],
+            }],
+        };
+        let assembly = Emitter::new()
+            .with_debug_info(debug_info)
+            .emit_program(&program);
+
+        assert!(assembly.contains(".file 1 \"main.c\""));
+        // The prologue is attributed to the function's own line
+        assert!(contains_normalized(
+            &assembly,
+            &format!("{}: .loc 1 1 1", mangled("main"))
+        ));
+        assert!(contains_normalized(
+            &assembly,
+            ".loc 1 2 5 movl -4(%rbp), %eax"
+        ));
+        assert!(assembly.contains(&debug_base::function_end(&mangled("main"))));
+        assert!(assembly.contains("debug_abbrev"));
+        assert!(assembly.contains("debug_info"));
+        assert!(assembly.contains(".string \"x\""));
+        // The frame base is the canonical frame address
+        assert!(contains_normalized(&assembly, ".uleb128 1 .byte 0x9c"));
+        // `x` lives at the frame base minus 20: DW_OP_fbreg -20
+        assert!(contains_normalized(
+            &assembly,
+            ".uleb128 2 .byte 0x91, 0x6c"
+        ));
+    }
+
+    #[test]
+    fn test_emit_call_frame_information() {
+        let instructions = vec![
+            Instruction::Push(Operand::Register(r!("rbp"))),
+            Instruction::Mov {
+                size: Size::Quad,
+                src: Operand::Register(r!("rsp")),
+                dst: Operand::Register(r!("rbp")),
+            },
+            Instruction::Mov {
+                size: Size::Quad,
+                src: Operand::Register(r!("rbp")),
+                dst: Operand::Register(r!("rsp")),
+            },
+            Instruction::Pop(Operand::Register(r!("rbp"))),
+            Instruction::Ret,
+        ];
+        let mut program = IRProgram::new();
+        program.add_function(IRFuncDef::new("framed".into(), true, &instructions));
+        let assembly = Emitter::new().emit_program(&program);
+
+        assert!(contains_normalized(
+            &assembly,
+            &format!("{}: .cfi_startproc pushq %rbp", mangled("framed"))
+        ));
+        assert!(contains_normalized(&assembly, "retq .cfi_endproc"));
+        assert!(contains_normalized(
+            &assembly,
+            "movq %rsp, %rbp .cfi_def_cfa_register %rbp"
+        ));
+    }
 }
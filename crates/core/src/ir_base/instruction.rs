@@ -1,42 +1,192 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::HashMap};
 
 use crate::ir_base::operand::Operand;
 
+/// Condition codes used by conditional jumps and `set` instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CondCode {
+    E,
+    NE,
+    L,
+    LE,
+    G,
+    GE,
+}
+impl CondCode {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            CondCode::E => "e",
+            CondCode::NE => "ne",
+            CondCode::L => "l",
+            CondCode::LE => "le",
+            CondCode::G => "g",
+            CondCode::GE => "ge",
+        }
+    }
+
+    /// The condition that holds exactly when `self` does not.
+    pub const fn negate(&self) -> Self {
+        match self {
+            CondCode::E => CondCode::NE,
+            CondCode::NE => CondCode::E,
+            CondCode::L => CondCode::GE,
+            CondCode::LE => CondCode::G,
+            CondCode::G => CondCode::LE,
+            CondCode::GE => CondCode::L,
+        }
+    }
+
+    /// The condition to test once the operands of the comparison are
+    /// swapped.
+    pub const fn swap(&self) -> Self {
+        match self {
+            CondCode::E => CondCode::E,
+            CondCode::NE => CondCode::NE,
+            CondCode::L => CondCode::G,
+            CondCode::LE => CondCode::GE,
+            CondCode::G => CondCode::L,
+            CondCode::GE => CondCode::LE,
+        }
+    }
+}
+
 /// Individual assembly instruction representation
 #[derive(Debug, Clone, PartialEq)]
 pub enum Instruction {
     // Data movement
-    Mov { src: Operand, dst: Operand },
+    Mov {
+        size: Size,
+        src: Operand,
+        dst: Operand,
+    },
     Push(Operand),
     Pop(Operand),
 
     // Arithmetic operations
-    Add { src: Operand, dst: Operand },
-    Sub { src: Operand, dst: Operand },
-    IMul { src: Operand, dst: Operand },
-    IDiv { divisor: Operand },
-    Neg { dst: Operand },
+    Add {
+        src: Operand,
+        dst: Operand,
+    },
+    Sub {
+        src: Operand,
+        dst: Operand,
+    },
+    IMul {
+        src: Operand,
+        dst: Operand,
+    },
+    IDiv {
+        divisor: Operand,
+    },
+    /// Sign-extends `eax` into `edx` before a division
+    Cdq,
+    Neg {
+        dst: Operand,
+    },
+    /// Computes `base + index * scale + offset` into `dst`, with the
+    /// addressing hardware. `base` and `index` must be registers by
+    /// emission.
+    Lea {
+        base: Option<Operand>,
+        index: Option<Operand>,
+        scale: u8,
+        offset: i64,
+        dst: Operand,
+    },
 
     // Logical operations
-    And { src: Operand, dst: Operand },
-    Or { src: Operand, dst: Operand },
-    Xor { src: Operand, dst: Operand },
-    Not { dst: Operand },
+    And {
+        src: Operand,
+        dst: Operand,
+    },
+    Or {
+        src: Operand,
+        dst: Operand,
+    },
+    Xor {
+        src: Operand,
+        dst: Operand,
+    },
+    Not {
+        dst: Operand,
+    },
+    /// Shifts `dst` left by `count`, which is an immediate or `cl`
+    Sal {
+        count: Operand,
+        dst: Operand,
+    },
+    /// Shifts `dst` right by `count`, copying the sign bit in
+    Sar {
+        count: Operand,
+        dst: Operand,
+    },
 
     // Comparison
-    Cmp { src: Operand, dst: Operand },
+    Cmp {
+        src: Operand,
+        dst: Operand,
+    },
+    /// Sets the flags from `src & dst`; `test x, x` compares `x` with 0
+    Test {
+        src: Operand,
+        dst: Operand,
+    },
+    SetCC {
+        cond: CondCode,
+        dst: Operand,
+    },
+    /// Copies `src` into `dst` if `cond` holds, leaving `dst` as it is
+    /// otherwise
+    Cmov {
+        cond: CondCode,
+        src: Operand,
+        dst: Operand,
+    },
 
     // Jumps
+    Jmp(String),
+    JmpCC {
+        cond: CondCode,
+        target: String,
+    },
+    Label(String),
+
+    // Stack management
+    AllocateStack(i64),
+    DeallocateStack(i64),
 
     // Function calls
-    Call(String),
+    /// `arg_registers` is the number of arguments passed in registers, which
+    /// the call reads
+    Call {
+        name: String,
+        arg_registers: usize,
+    },
+    /// Jumps to a function, which then returns to our own caller
+    TailCall {
+        name: String,
+        arg_registers: usize,
+    },
     Ret,
+
+    // Debug info
+    /// `.loc` directive: the code that follows was generated for the
+    /// statement at `line`:`column` of the source file
+    Loc {
+        line: usize,
+        column: usize,
+    },
 }
 impl Instruction {
     pub fn as_assembly_inline(&self) -> String {
         match self {
-            Instruction::Mov { src, dst } => {
-                format!("movq {}, {}", src, dst)
+            Instruction::Mov { size, src, dst } => {
+                format!(
+                    "mov{} {}, {}",
+                    size.suffix(),
+                    src.to_sized_string(*size),
+                    dst.to_sized_string(*size)
+                )
             }
             Instruction::Push(operand) => {
                 format!("pushq {}", operand)
@@ -44,55 +194,126 @@ impl Instruction {
             Instruction::Pop(operand) => {
                 format!("popq {}", operand)
             }
-            Instruction::Add { src, dst } => {
-                format!("addl {}, {}", src, dst)
+            Instruction::Add { src, dst } => Self::long_binary("addl", src, dst),
+            Instruction::Sub { src, dst } => Self::long_binary("subl", src, dst),
+            Instruction::IMul { src, dst } => Self::long_binary("imull", src, dst),
+            Instruction::IDiv { divisor } => {
+                format!("idivl {}", divisor.to_sized_string(Size::Long))
+            }
+            Instruction::Cdq => "cdq".to_string(),
+            Instruction::Neg { dst } => {
+                format!("negl {}", dst.to_sized_string(Size::Long))
             }
-            Instruction::Sub { src, dst } => {
-                format!("subl {}, {}", src, dst)
+            Instruction::Lea {
+                base,
+                index,
+                scale,
+                offset,
+                dst,
+            } => {
+                let register = |operand: &Operand| match operand {
+                    Operand::Register(reg) => *reg,
+                    operand => unreachable!("`lea` with address operand {}", operand),
+                };
+                let address = Operand::Memory {
+                    base: base.as_ref().map(register),
+                    index: index.as_ref().map(register),
+                    scale: *scale,
+                    offset: *offset,
+                };
+                format!("leal {}, {}", address, dst.to_sized_string(Size::Long))
             }
-            Instruction::IMul { src, dst } => {
-                format!("imull {}, {}", src, dst)
+            Instruction::And { src, dst } => Self::long_binary("andl", src, dst),
+            Instruction::Or { src, dst } => Self::long_binary("orl", src, dst),
+            Instruction::Xor { src, dst } => Self::long_binary("xorl", src, dst),
+            Instruction::Not { dst } => {
+                format!("notl {}", dst.to_sized_string(Size::Long))
             }
-            Instruction::IDiv { divisor } => {
-                format!("idivl {}", divisor)
+            Instruction::Sal { count, dst } => Self::shift("sall", count, dst),
+            Instruction::Sar { count, dst } => Self::shift("sarl", count, dst),
+            Instruction::Cmp { src, dst } => Self::long_binary("cmpl", src, dst),
+            Instruction::Test { src, dst } => Self::long_binary("testl", src, dst),
+            Instruction::SetCC { cond, dst } => {
+                format!("set{} {}", cond.as_str(), dst.to_sized_string(Size::Byte))
             }
-            Instruction::Neg { dst } => {
-                format!("negl {}", dst)
+            Instruction::Cmov { cond, src, dst } => {
+                Self::long_binary(&format!("cmov{}l", cond.as_str()), src, dst)
             }
-            Instruction::And { src, dst } => {
-                format!("andl {}, {}", src, dst)
+            Instruction::Jmp(target) => {
+                format!("jmp {}{}", LOCAL_LABEL_PREFIX, target)
             }
-            Instruction::Or { src, dst } => {
-                format!("orl {}, {}", src, dst)
+            Instruction::JmpCC { cond, target } => {
+                format!("j{} {}{}", cond.as_str(), LOCAL_LABEL_PREFIX, target)
             }
-            Instruction::Xor { src, dst } => {
-                format!("xorl {}, {}", src, dst)
+            Instruction::Label(label) => {
+                format!("{}{}:", LOCAL_LABEL_PREFIX, label)
             }
-            Instruction::Not { dst } => {
-                format!("notl {}", dst)
+            Instruction::AllocateStack(bytes) => {
+                format!("subq ${}, %rsp", bytes)
+            }
+            Instruction::DeallocateStack(bytes) => {
+                format!("addq ${}, %rsp", bytes)
             }
-            Instruction::Cmp { src, dst } => {
-                format!("cmpl {}, {}", src, dst)
+            Instruction::Call { name, .. } => {
+                format!("call {}", name)
             }
-            Instruction::Call(function) => {
-                format!("call {}", function)
+            Instruction::TailCall { name, .. } => {
+                format!("jmp {}", name)
             }
             Instruction::Ret => "retq".to_string(),
+            Instruction::Loc { line, column } => {
+                format!(".loc 1 {} {}", line, column)
+            }
         }
     }
+
+    /// Shift of a 32-bit value, by a count in a byte register
+    fn shift(mnemonic: &str, count: &Operand, dst: &Operand) -> String {
+        format!(
+            "{} {}, {}",
+            mnemonic,
+            count.to_sized_string(Size::Byte),
+            dst.to_sized_string(Size::Long)
+        )
+    }
+
+    /// Two-operand instruction on 32-bit values
+    fn long_binary(mnemonic: &str, src: &Operand, dst: &Operand) -> String {
+        format!(
+            "{} {}, {}",
+            mnemonic,
+            src.to_sized_string(Size::Long),
+            dst.to_sized_string(Size::Long)
+        )
+    }
 }
 
+/// Prefix that keeps labels local to the object file
+#[cfg(target_os = "macos")]
+pub const LOCAL_LABEL_PREFIX: &str = "L";
+#[cfg(not(target_os = "macos"))]
+pub const LOCAL_LABEL_PREFIX: &str = ".L";
+
 /// Size specifier for instructions
-/// TODO: Use this in instructions, but for now we will keep it simple, only
-/// 32-bit
-#[allow(dead_code)]
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Only `mov` is sized for now: arithmetic always works on 32-bit values,
+/// while stack and frame pointer manipulation uses 64-bit forms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Size {
     Byte,
     Word,
     Long,
     Quad,
 }
+impl Size {
+    pub const fn suffix(&self) -> char {
+        match self {
+            Size::Byte => 'b',
+            Size::Word => 'w',
+            Size::Long => 'l',
+            Size::Quad => 'q',
+        }
+    }
+}
 
 /// IR Function Definition
 #[derive(Debug, Clone)]
@@ -100,9 +321,12 @@ pub struct IRFuncDef<'a> {
     pub name: Cow<'a, str>,
     pub is_global: bool,
     pub instructions: Vec<Instruction>,
+    /// Offset from the canonical frame address of the stack slot each
+    /// pseudo register was given, for debug info
+    pub stack_slots: HashMap<String, i64>,
 }
 impl<'a> IRFuncDef<'a> {
-    fn platfrom_mangle_name(name: &str) -> String {
+    pub(crate) fn platfrom_mangle_name(name: &str) -> String {
         // On macos, symbol names should be prefixed with `_`
         #[cfg(target_os = "macos")]
         {
@@ -119,6 +343,7 @@ impl<'a> IRFuncDef<'a> {
             name: Cow::Owned(Self::platfrom_mangle_name(&name)),
             is_global,
             instructions: instructions.to_vec(),
+            stack_slots: HashMap::new(),
         }
     }
 }
@@ -26,27 +26,27 @@ macro_rules! r {
         $crate::ir_base::reg::PhyRegister::RBP
     };
     ("r8") => {
-        $crate::ir_base::Register::R8
+        $crate::ir_base::reg::PhyRegister::R8
     };
     ("r9") => {
-        $crate::ir_base::Register::R9
+        $crate::ir_base::reg::PhyRegister::R9
     };
     ("r10") => {
-        $crate::ir_base::Register::R10
+        $crate::ir_base::reg::PhyRegister::R10
     };
     ("r11") => {
-        $crate::ir_base::Register::R11
+        $crate::ir_base::reg::PhyRegister::R11
     };
     ("r12") => {
-        $crate::ir_base::Register::R12
+        $crate::ir_base::reg::PhyRegister::R12
     };
     ("r13") => {
-        $crate::ir_base::Register::R13
+        $crate::ir_base::reg::PhyRegister::R13
     };
     ("r14") => {
-        $crate::ir_base::Register::R14
+        $crate::ir_base::reg::PhyRegister::R14
     };
     ("r15") => {
-        $crate::ir_base::Register::R15
+        $crate::ir_base::reg::PhyRegister::R15
     };
 }
@@ -1,3 +1,4 @@
+mod cfi;
 mod emitter;
 mod instruction;
 mod mac;
@@ -23,6 +24,12 @@ impl<'a> IRProgram<'a> {
     }
 }
 
+impl Default for IRProgram<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -37,16 +44,16 @@ mod tests {
 
         let func = IRFuncDef::new("test".into(), true, &instructions);
 
-        assert_eq!(func.name, "_test");
+        assert_eq!(func.name, IRFuncDef::platfrom_mangle_name("test"));
         assert!(func.is_global);
         assert_eq!(func.instructions.len(), 2);
     }
 
     #[test]
     fn test_ir_func_def_non_global() {
-        let func = IRFuncDef::new("helper".into(), false, &vec![]);
+        let func = IRFuncDef::new("helper".into(), false, &[]);
 
-        assert_eq!(func.name, "_helper");
+        assert_eq!(func.name, IRFuncDef::platfrom_mangle_name("helper"));
         assert!(!func.is_global);
         assert_eq!(func.instructions.len(), 0);
     }
@@ -56,14 +63,20 @@ mod tests {
         let mut program = IRProgram::new();
         assert_eq!(program.functions.len(), 0);
 
-        let func1 = IRFuncDef::new("main".into(), true, &vec![]);
-        let func2 = IRFuncDef::new("helper".into(), false, &vec![]);
+        let func1 = IRFuncDef::new("main".into(), true, &[]);
+        let func2 = IRFuncDef::new("helper".into(), false, &[]);
 
         program.add_function(func1);
         program.add_function(func2);
 
         assert_eq!(program.functions.len(), 2);
-        assert_eq!(program.functions[0].name, "_main");
-        assert_eq!(program.functions[1].name, "_helper");
+        assert_eq!(
+            program.functions[0].name,
+            IRFuncDef::platfrom_mangle_name("main")
+        );
+        assert_eq!(
+            program.functions[1].name,
+            IRFuncDef::platfrom_mangle_name("helper")
+        );
     }
 }
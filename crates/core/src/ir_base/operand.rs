@@ -1,25 +1,72 @@
-use crate::ir_base::reg::PhyRegister;
+use crate::ir_base::{Size, reg::PhyRegister};
 /// Operand for instructions
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Operand {
     Immediate(i64),
     Register(PhyRegister),
+    /// `offset(base, index, scale)`: the address
+    /// `base + index * scale + offset`
     Memory {
         base: Option<PhyRegister>,
+        index: Option<PhyRegister>,
+        /// 1, 2, 4 or 8
+        scale: u8,
         offset: i64,
     },
+    /// Virtual register standing for a TACKY variable, replaced by a real
+    /// location before emission
+    Pseudo(String),
     // Label(String),
 }
+impl Operand {
+    /// Memory at `offset` bytes from the address in `base`
+    pub const fn memory(base: PhyRegister, offset: i64) -> Self {
+        Operand::Memory {
+            base: Some(base),
+            index: None,
+            scale: 1,
+            offset,
+        }
+    }
+
+    /// Formats the operand as used by an instruction operating on `size`
+    /// bytes. Only registers are affected by the size.
+    pub fn to_sized_string(&self, size: Size) -> String {
+        match self {
+            Operand::Register(reg) => format!("%{}", reg.as_sized_str(size)),
+            _ => self.to_string(),
+        }
+    }
+
+    pub fn is_memory(&self) -> bool {
+        matches!(self, Operand::Memory { .. })
+    }
+}
 impl std::fmt::Display for Operand {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Operand::Immediate(int) => write!(f, "${}", int),
             Operand::Register(reg) => write!(f, "%{}", reg.as_str()),
-            Operand::Memory { base, offset } => match base {
-                Some(reg) => write!(f, "{}(%{})", offset, reg.as_str()),
+            Operand::Memory {
+                base,
+                index,
+                scale,
+                offset,
+            } => match (base, index) {
+                (Some(base), None) => write!(f, "{}(%{})", offset, base.as_str()),
+                (base, Some(index)) => write!(
+                    f,
+                    "{}({},%{},{})",
+                    offset,
+                    base.map(|reg| format!("%{}", reg.as_str()))
+                        .unwrap_or_default(),
+                    index.as_str(),
+                    scale
+                ),
                 //TODO: Choose how to represent memory operands without base registers
-                None => write!(f, "{}(%rsp)", offset),
+                (None, None) => write!(f, "{}(%rsp)", offset),
             },
+            Operand::Pseudo(name) => write!(f, "{}", name),
         }
     }
 }
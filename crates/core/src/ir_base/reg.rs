@@ -1,6 +1,8 @@
+use crate::ir_base::Size;
+
 /// Physical CPU registers for x86-64 architecture.
 #[allow(dead_code)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PhyRegister {
     RAX,
     RBX,
@@ -41,4 +43,65 @@ impl PhyRegister {
             PhyRegister::R15 => "r15",
         }
     }
+
+    /// Name of the part of this register that holds an operand of `size`
+    pub const fn as_sized_str(&self, size: Size) -> &'static str {
+        match size {
+            Size::Quad => self.as_str(),
+            Size::Long => match self {
+                PhyRegister::RAX => "eax",
+                PhyRegister::RBX => "ebx",
+                PhyRegister::RCX => "ecx",
+                PhyRegister::RDX => "edx",
+                PhyRegister::RSI => "esi",
+                PhyRegister::RDI => "edi",
+                PhyRegister::RBP => "ebp",
+                PhyRegister::RSP => "esp",
+                PhyRegister::R8 => "r8d",
+                PhyRegister::R9 => "r9d",
+                PhyRegister::R10 => "r10d",
+                PhyRegister::R11 => "r11d",
+                PhyRegister::R12 => "r12d",
+                PhyRegister::R13 => "r13d",
+                PhyRegister::R14 => "r14d",
+                PhyRegister::R15 => "r15d",
+            },
+            Size::Word => match self {
+                PhyRegister::RAX => "ax",
+                PhyRegister::RBX => "bx",
+                PhyRegister::RCX => "cx",
+                PhyRegister::RDX => "dx",
+                PhyRegister::RSI => "si",
+                PhyRegister::RDI => "di",
+                PhyRegister::RBP => "bp",
+                PhyRegister::RSP => "sp",
+                PhyRegister::R8 => "r8w",
+                PhyRegister::R9 => "r9w",
+                PhyRegister::R10 => "r10w",
+                PhyRegister::R11 => "r11w",
+                PhyRegister::R12 => "r12w",
+                PhyRegister::R13 => "r13w",
+                PhyRegister::R14 => "r14w",
+                PhyRegister::R15 => "r15w",
+            },
+            Size::Byte => match self {
+                PhyRegister::RAX => "al",
+                PhyRegister::RBX => "bl",
+                PhyRegister::RCX => "cl",
+                PhyRegister::RDX => "dl",
+                PhyRegister::RSI => "sil",
+                PhyRegister::RDI => "dil",
+                PhyRegister::RBP => "bpl",
+                PhyRegister::RSP => "spl",
+                PhyRegister::R8 => "r8b",
+                PhyRegister::R9 => "r9b",
+                PhyRegister::R10 => "r10b",
+                PhyRegister::R11 => "r11b",
+                PhyRegister::R12 => "r12b",
+                PhyRegister::R13 => "r13b",
+                PhyRegister::R14 => "r14b",
+                PhyRegister::R15 => "r15b",
+            },
+        }
+    }
 }
@@ -1,6 +1,12 @@
 pub mod codegen_base;
+pub mod debug_base;
 pub mod error;
 pub mod grammar;
 pub mod ir_base;
 pub mod lexer_base;
+pub mod opt_base;
 pub mod parser_base;
+pub mod pass_base;
+pub mod sema_base;
+pub mod tacky_base;
+pub mod warning;
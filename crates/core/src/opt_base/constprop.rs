@@ -0,0 +1,194 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::tacky_base::{
+    Instruction, TackyFuncDef, Value,
+    cfg::Cfg,
+    interp::{eval_binary, eval_unary},
+};
+
+/// Variables known to hold a constant at some program point. A variable
+/// missing from the map may hold any value.
+type Constants = HashMap<String, i64>;
+
+/// Sparse conditional constant propagation.
+///
+/// Blocks are only analysed once an executable edge leads into them, and a
+/// conditional jump on a known value only makes the taken edge executable.
+/// A variable is constant at the start of a block when it has the same
+/// constant value at the end of every executable predecessor. Uses of
+/// constants are then replaced by their value, fully constant computations
+/// are folded into copies, constant branches become unconditional and
+/// blocks that never execute are dropped.
+pub fn propagate_constants(func: &mut TackyFuncDef) {
+    let mut cfg = Cfg::new(std::mem::take(&mut func.body));
+    let block_count = cfg.blocks.len();
+
+    let mut block_in: Vec<Option<Constants>> = vec![None; block_count];
+    let mut block_out: Vec<Option<Constants>> = vec![None; block_count];
+    let mut executable: HashSet<(usize, usize)> = HashSet::new();
+    let mut worklist = VecDeque::new();
+    if block_count > 0 {
+        worklist.push_back(0);
+    }
+
+    while let Some(idx) = worklist.pop_front() {
+        let input = if idx == 0 {
+            Constants::new()
+        } else {
+            meet(
+                cfg.blocks[idx]
+                    .predecessors
+                    .iter()
+                    .filter(|&&pred| executable.contains(&(pred, idx)))
+                    .filter_map(|&pred| block_out[pred].as_ref()),
+            )
+        };
+
+        let mut state = input.clone();
+        for inst in &cfg.blocks[idx].instructions {
+            transfer(inst, &mut state);
+        }
+        let taken = taken_successors(&cfg, idx, &state);
+
+        let first_visit = block_out[idx].is_none();
+        let out_changed = block_out[idx].as_ref() != Some(&state);
+        block_in[idx] = Some(input);
+        block_out[idx] = Some(state);
+
+        for succ in taken {
+            let new_edge = executable.insert((idx, succ));
+            if (new_edge || out_changed || first_visit) && !worklist.contains(&succ) {
+                worklist.push_back(succ);
+            }
+        }
+    }
+
+    for (block, input) in cfg.blocks.iter_mut().zip(&block_in) {
+        if let Some(input) = input {
+            rewrite_block(&mut block.instructions, input.clone());
+        }
+    }
+    let keep: Vec<bool> = block_in.iter().map(Option::is_some).collect();
+    cfg.retain_blocks(&keep);
+
+    func.body = cfg.into_instructions();
+}
+
+/// Intersection of the constants known at the end of each predecessor.
+fn meet<'c>(mut states: impl Iterator<Item = &'c Constants>) -> Constants {
+    let Some(first) = states.next() else {
+        return Constants::new();
+    };
+    let mut result = first.clone();
+    for state in states {
+        result.retain(|var, val| state.get(var) == Some(val));
+    }
+    result
+}
+
+fn value_of(value: &Value, state: &Constants) -> Option<i64> {
+    match value {
+        Value::Constant(val) => Some(*val),
+        Value::Var(name) => state.get(name).copied(),
+    }
+}
+
+/// The constant an instruction writes into its destination, if it is known.
+fn evaluate(inst: &Instruction, state: &Constants) -> Option<i64> {
+    match inst {
+        Instruction::Copy { src, .. } => value_of(src, state),
+        Instruction::Unary { op, src, .. } => Some(eval_unary(op, value_of(src, state)?)),
+        Instruction::Binary { op, lhs, rhs, .. } => {
+            eval_binary(op, value_of(lhs, state)?, value_of(rhs, state)?)
+        }
+        _ => None,
+    }
+}
+
+fn transfer(inst: &Instruction, state: &mut Constants) {
+    if let Some(dst) = inst.dst() {
+        match evaluate(inst, state) {
+            Some(val) => state.insert(dst.to_string(), val),
+            None => state.remove(dst),
+        };
+    }
+}
+
+/// Successors of block `idx` that can execute given the constants known at
+/// its end.
+fn taken_successors(cfg: &Cfg, idx: usize, state: &Constants) -> Vec<usize> {
+    let block = &cfg.blocks[idx];
+    let branch = match block.instructions.last() {
+        Some(Instruction::JumpIfZero { cond, target }) => {
+            value_of(cond, state).map(|val| (val == 0, target))
+        }
+        Some(Instruction::JumpIfNotZero { cond, target }) => {
+            value_of(cond, state).map(|val| (val != 0, target))
+        }
+        _ => None,
+    };
+
+    match branch {
+        Some((true, target)) => {
+            let labels = cfg.label_map();
+            vec![labels[target]]
+        }
+        Some((false, _)) => cfg.fallthrough_successor(idx).into_iter().collect(),
+        None => block.successors.clone(),
+    }
+}
+
+fn rewrite_block(instructions: &mut Vec<Instruction>, mut state: Constants) {
+    let mut rewritten = Vec::with_capacity(instructions.len());
+
+    for mut inst in instructions.drain(..) {
+        let folded = evaluate(&inst, &state);
+        replace_operands(&mut inst, &state);
+        transfer(&inst, &mut state);
+
+        let inst = match (inst, folded) {
+            (
+                Instruction::Copy { dst, .. }
+                | Instruction::Unary { dst, .. }
+                | Instruction::Binary { dst, .. },
+                Some(val),
+            ) => Instruction::Copy {
+                src: Value::Constant(val),
+                dst,
+            },
+            (
+                Instruction::JumpIfZero {
+                    cond: Value::Constant(val),
+                    target,
+                },
+                _,
+            ) => match val {
+                0 => Instruction::Jump(target),
+                _ => continue,
+            },
+            (
+                Instruction::JumpIfNotZero {
+                    cond: Value::Constant(val),
+                    target,
+                },
+                _,
+            ) => match val {
+                0 => continue,
+                _ => Instruction::Jump(target),
+            },
+            (inst, _) => inst,
+        };
+        rewritten.push(inst);
+    }
+
+    *instructions = rewritten;
+}
+
+/// Replaces the variables read by `inst` whose value is known.
+fn replace_operands(inst: &mut Instruction, state: &Constants) {
+    for value in inst.operands_mut() {
+        if let Some(val) = value_of(value, state) {
+            *value = Value::Constant(val);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+
+use crate::tacky_base::{Instruction, TackyFuncDef, Value, cfg::Cfg};
+
+/// Copies `dst = src` that hold at some program point.
+type Copies = HashSet<(String, Value)>;
+
+/// Global copy propagation.
+///
+/// A forward "must" dataflow analysis finds the copies `x = y` that reach a
+/// use of `x` along every path without `x` or `y` being reassigned in
+/// between; such uses then read `y` directly. Copies that end up assigning a
+/// variable to itself are removed.
+pub fn propagate_copies(func: &mut TackyFuncDef) {
+    let mut cfg = Cfg::new(std::mem::take(&mut func.body));
+    let block_count = cfg.blocks.len();
+
+    // `None` stands for "every copy", the starting point for blocks that
+    // have not been visited yet.
+    let mut block_out: Vec<Option<Copies>> = vec![None; block_count];
+    let order = cfg.reverse_postorder();
+
+    let block_in = |idx: usize, block_out: &[Option<Copies>]| -> Copies {
+        if idx == 0 {
+            return Copies::new();
+        }
+        let mut known = cfg.blocks[idx]
+            .predecessors
+            .iter()
+            .filter_map(|&pred| block_out[pred].as_ref());
+        let Some(first) = known.next() else {
+            return Copies::new();
+        };
+        let mut copies = first.clone();
+        for other in known {
+            copies.retain(|copy| other.contains(copy));
+        }
+        copies
+    };
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &idx in &order {
+            let mut copies = block_in(idx, &block_out);
+            for inst in &cfg.blocks[idx].instructions {
+                transfer(inst, &mut copies);
+            }
+            if block_out[idx].as_ref() != Some(&copies) {
+                block_out[idx] = Some(copies);
+                changed = true;
+            }
+        }
+    }
+
+    let inputs: Vec<Copies> = (0..block_count)
+        .map(|idx| block_in(idx, &block_out))
+        .collect();
+    for (block, mut copies) in cfg.blocks.iter_mut().zip(inputs) {
+        let mut rewritten = Vec::with_capacity(block.instructions.len());
+        for mut inst in block.instructions.drain(..) {
+            replace_operands(&mut inst, &copies);
+            transfer(&inst, &mut copies);
+            if let Instruction::Copy {
+                src: Value::Var(src),
+                dst,
+            } = &inst
+                && src == dst
+            {
+                continue;
+            }
+            rewritten.push(inst);
+        }
+        block.instructions = rewritten;
+    }
+
+    func.body = cfg.into_instructions();
+}
+
+fn transfer(inst: &Instruction, copies: &mut Copies) {
+    let Some(dst) = inst.dst() else {
+        return;
+    };
+    copies.retain(|(copy_dst, copy_src)| copy_dst != dst && copy_src.as_var() != Some(dst));
+    if let Instruction::Copy { src, dst } = inst
+        && src.as_var() != Some(dst)
+    {
+        copies.insert((dst.clone(), src.clone()));
+    }
+}
+
+fn replace_operands(inst: &mut Instruction, copies: &Copies) {
+    for value in inst.operands_mut() {
+        let Value::Var(var) = value else {
+            continue;
+        };
+        if let Some((_, src)) = copies.iter().find(|(dst, _)| dst == var) {
+            *value = src.clone();
+        }
+    }
+}
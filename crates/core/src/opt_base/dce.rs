@@ -0,0 +1,160 @@
+use crate::{
+    opt_base::liveness::{Liveness, transfer},
+    tacky_base::{Instruction, TackyFuncDef, cfg::Cfg},
+};
+
+/// Removes instructions whose results are never read.
+///
+/// Instructions without side effects are dropped when their destination is
+/// dead right after them; function calls are kept, but a dead result is no
+/// longer stored. Since removing one instruction can make the ones feeding
+/// it dead too, the analysis is repeated until nothing changes.
+pub fn eliminate_dead_stores(func: &mut TackyFuncDef) {
+    let mut cfg = Cfg::new(std::mem::take(&mut func.body));
+
+    loop {
+        let liveness = Liveness::compute(&cfg);
+        let mut changed = false;
+
+        for (block, live_out) in cfg.blocks.iter_mut().zip(liveness.live_out) {
+            let mut live = live_out;
+            let mut kept = Vec::with_capacity(block.instructions.len());
+
+            for mut inst in block.instructions.drain(..).rev() {
+                let is_dead = inst.dst().is_some_and(|dst| !live.contains(dst));
+                if is_dead {
+                    changed = true;
+                    match &mut inst {
+                        Instruction::FunCall { dst, .. } => *dst = None,
+                        _ => continue,
+                    }
+                }
+                transfer(&inst, &mut live);
+                kept.push(inst);
+            }
+
+            kept.reverse();
+            block.instructions = kept;
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    func.body = cfg.into_instructions();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{grammar::BinaryOp, tacky_base::Value};
+
+    fn func(body: Vec<Instruction>) -> TackyFuncDef {
+        TackyFuncDef {
+            name: "f".into(),
+            is_global: true,
+            params: vec!["a".into()],
+            body,
+        }
+    }
+
+    #[test]
+    fn test_unused_computation_chain_is_removed() {
+        let mut f = func(vec![
+            Instruction::Binary {
+                op: BinaryOp::Add,
+                lhs: Value::Var("a".into()),
+                rhs: Value::Constant(1),
+                dst: "t1".into(),
+            },
+            Instruction::Binary {
+                op: BinaryOp::Multiply,
+                lhs: Value::Var("t1".into()),
+                rhs: Value::Constant(2),
+                dst: "t2".into(),
+            },
+            Instruction::Return(Value::Var("a".into())),
+        ]);
+        eliminate_dead_stores(&mut f);
+        assert_eq!(f.body, vec![Instruction::Return(Value::Var("a".into()))]);
+    }
+
+    #[test]
+    fn test_overwritten_store_is_removed() {
+        let mut f = func(vec![
+            Instruction::Copy {
+                src: Value::Constant(1),
+                dst: "x".into(),
+            },
+            Instruction::Copy {
+                src: Value::Constant(2),
+                dst: "x".into(),
+            },
+            Instruction::Return(Value::Var("x".into())),
+        ]);
+        eliminate_dead_stores(&mut f);
+        assert_eq!(f.body.len(), 2);
+        assert_eq!(
+            f.body[0],
+            Instruction::Copy {
+                src: Value::Constant(2),
+                dst: "x".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_call_with_unused_result_is_kept() {
+        let mut f = func(vec![
+            Instruction::FunCall {
+                name: "g".into(),
+                args: vec![],
+                dst: Some("t".into()),
+            },
+            Instruction::Return(Value::Constant(0)),
+        ]);
+        eliminate_dead_stores(&mut f);
+        assert_eq!(
+            f.body[0],
+            Instruction::FunCall {
+                name: "g".into(),
+                args: vec![],
+                dst: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_store_read_in_loop_is_kept() {
+        let body = vec![
+            Instruction::Copy {
+                src: Value::Constant(0),
+                dst: "i".into(),
+            },
+            Instruction::Label("head".into()),
+            Instruction::Binary {
+                op: BinaryOp::LessThan,
+                lhs: Value::Var("i".into()),
+                rhs: Value::Constant(10),
+                dst: "c".into(),
+            },
+            Instruction::JumpIfZero {
+                cond: Value::Var("c".into()),
+                target: "out".into(),
+            },
+            Instruction::Binary {
+                op: BinaryOp::Add,
+                lhs: Value::Var("i".into()),
+                rhs: Value::Constant(1),
+                dst: "i".into(),
+            },
+            Instruction::Jump("head".into()),
+            Instruction::Label("out".into()),
+            Instruction::Return(Value::Var("i".into())),
+        ];
+        let mut f = func(body.clone());
+        eliminate_dead_stores(&mut f);
+        assert_eq!(f.body, body);
+    }
+}
@@ -0,0 +1,180 @@
+use crate::tacky_base::cfg::Cfg;
+
+/// Dominator tree of a [`Cfg`] whose blocks are all reachable from the
+/// entry, computed with the iterative algorithm of Cooper, Harvey and
+/// Kennedy.
+pub struct Dominators {
+    /// Immediate dominator of each block; `None` for the entry block.
+    idom: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+}
+
+impl Dominators {
+    pub fn compute(cfg: &Cfg) -> Self {
+        let block_count = cfg.blocks.len();
+        let order = cfg.reverse_postorder();
+        let mut rpo_index = vec![usize::MAX; block_count];
+        for (position, &block) in order.iter().enumerate() {
+            rpo_index[block] = position;
+        }
+
+        let mut idom: Vec<Option<usize>> = vec![None; block_count];
+        if block_count > 0 {
+            idom[0] = Some(0);
+        }
+
+        let intersect = |idom: &[Option<usize>], mut a: usize, mut b: usize| {
+            while a != b {
+                while rpo_index[a] > rpo_index[b] {
+                    a = idom[a].unwrap();
+                }
+                while rpo_index[b] > rpo_index[a] {
+                    b = idom[b].unwrap();
+                }
+            }
+            a
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in order.iter().skip(1) {
+                let new_idom = cfg.blocks[block]
+                    .predecessors
+                    .iter()
+                    .copied()
+                    .filter(|&pred| idom[pred].is_some())
+                    .reduce(|a, b| intersect(&idom, a, b));
+                if new_idom.is_some() && idom[block] != new_idom {
+                    idom[block] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        if block_count > 0 {
+            idom[0] = None;
+        }
+        let mut children = vec![Vec::new(); block_count];
+        for (block, parent) in idom.iter().enumerate() {
+            if let Some(parent) = parent {
+                children[*parent].push(block);
+            }
+        }
+
+        Self { idom, children }
+    }
+
+    pub fn idom(&self, block: usize) -> Option<usize> {
+        self.idom[block]
+    }
+
+    /// Blocks immediately dominated by `block`.
+    pub fn children(&self, block: usize) -> &[usize] {
+        &self.children[block]
+    }
+
+    /// Whether every path from the entry to `b` goes through `a`.
+    pub fn dominates(&self, a: usize, mut b: usize) -> bool {
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.idom(b) {
+                Some(parent) => b = parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// Dominance frontier of each block: the blocks where its dominance
+    /// ends, i.e. the join points that also have a predecessor it does not
+    /// dominate.
+    pub fn frontiers(&self, cfg: &Cfg) -> Vec<Vec<usize>> {
+        let mut frontiers = vec![Vec::new(); cfg.blocks.len()];
+        for (block, data) in cfg.blocks.iter().enumerate() {
+            if data.predecessors.len() < 2 {
+                continue;
+            }
+            for &pred in &data.predecessors {
+                let mut runner = Some(pred);
+                while let Some(current) = runner
+                    && Some(current) != self.idom(block)
+                {
+                    if !frontiers[current].contains(&block) {
+                        frontiers[current].push(block);
+                    }
+                    runner = self.idom(current);
+                }
+            }
+        }
+        frontiers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tacky_base::{Instruction, Value};
+
+    /// 0: jz x, else   1: jmp end   2: else:   3: end: ret
+    fn diamond() -> Cfg {
+        Cfg::new(vec![
+            Instruction::JumpIfZero {
+                cond: Value::Var("x".into()),
+                target: "else".into(),
+            },
+            Instruction::Jump("end".into()),
+            Instruction::Label("else".into()),
+            Instruction::Label("end".into()),
+            Instruction::Return(Value::Constant(0)),
+        ])
+    }
+
+    #[test]
+    fn test_diamond_dominators() {
+        let cfg = diamond();
+        let doms = Dominators::compute(&cfg);
+        assert_eq!(doms.idom(0), None);
+        assert_eq!(doms.idom(1), Some(0));
+        assert_eq!(doms.idom(2), Some(0));
+        assert_eq!(doms.idom(3), Some(0));
+        assert_eq!(doms.children(0), [1, 2, 3]);
+        assert!(doms.dominates(0, 3));
+        assert!(!doms.dominates(1, 3));
+    }
+
+    #[test]
+    fn test_diamond_frontiers() {
+        let cfg = diamond();
+        let frontiers = Dominators::compute(&cfg).frontiers(&cfg);
+        assert!(frontiers[0].is_empty());
+        assert_eq!(frontiers[1], [3]);
+        assert_eq!(frontiers[2], [3]);
+    }
+
+    #[test]
+    fn test_loop_header_is_in_its_own_frontier() {
+        // 0: x = 0   1: head: jz x, out   2: jmp head   3: out: ret
+        let cfg = Cfg::new(vec![
+            Instruction::Copy {
+                src: Value::Constant(0),
+                dst: "x".into(),
+            },
+            Instruction::Label("head".into()),
+            Instruction::JumpIfZero {
+                cond: Value::Var("x".into()),
+                target: "out".into(),
+            },
+            Instruction::Jump("head".into()),
+            Instruction::Label("out".into()),
+            Instruction::Return(Value::Constant(0)),
+        ]);
+        let doms = Dominators::compute(&cfg);
+        assert_eq!(doms.idom(2), Some(1));
+        assert_eq!(doms.idom(3), Some(1));
+        let frontiers = doms.frontiers(&cfg);
+        assert_eq!(frontiers[2], [1]);
+        assert_eq!(frontiers[1], [1]);
+    }
+}
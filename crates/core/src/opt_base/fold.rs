@@ -0,0 +1,184 @@
+use crate::{
+    grammar::{ExprKind, Expression, LogicalOp, Program, Statement},
+    tacky_base::interp::{eval_binary, eval_unary},
+};
+
+/// Constant folding on the AST.
+///
+/// Operators whose operands are all constants are replaced by their
+/// result, so `2 * 3 + x` reaches lowering as `6 + x` and needs no
+/// temporaries for the constant part. The pass runs before type checking,
+/// so it never drops an operand that is not a constant: a `void` call in a
+/// branch that can never run is still reported.
+pub fn fold_constants(program: &mut Program<'_>) {
+    for func in &mut program.functions {
+        if let Some(body) = &mut func.body {
+            for stmt in &mut body.statements {
+                fold_statement(stmt);
+            }
+        }
+    }
+}
+
+fn fold_statement(stmt: &mut Statement<'_>) {
+    match stmt {
+        Statement::Block(block) => block.statements.iter_mut().for_each(fold_statement),
+        Statement::Break(_) | Statement::Continue(_) | Statement::Goto(_) | Statement::Null(_) => {}
+        Statement::Declaration(decl) => decl.initializer.iter_mut().for_each(fold_expression),
+        Statement::DoWhile(do_while) => {
+            fold_statement(&mut do_while.body);
+            fold_expression(&mut do_while.cond);
+        }
+        Statement::Expr(expr_stmt) => fold_expression(&mut expr_stmt.expr),
+        Statement::For(for_stmt) => {
+            [&mut for_stmt.init, &mut for_stmt.cond, &mut for_stmt.post]
+                .into_iter()
+                .flatten()
+                .for_each(fold_expression);
+            fold_statement(&mut for_stmt.body);
+        }
+        Statement::If(if_stmt) => {
+            fold_expression(&mut if_stmt.cond);
+            fold_statement(&mut if_stmt.then_block);
+            if let Some(else_block) = &mut if_stmt.else_block {
+                fold_statement(else_block);
+            }
+        }
+        Statement::Labeled(labeled) => fold_statement(&mut labeled.stmt),
+        Statement::Return(ret) => ret.expr.iter_mut().for_each(fold_expression),
+        Statement::While(while_stmt) => {
+            fold_expression(&mut while_stmt.cond);
+            fold_statement(&mut while_stmt.body);
+        }
+    }
+}
+
+fn fold_expression(expr: &mut Expression<'_>) {
+    let folded = match &mut expr.kind {
+        ExprKind::Constant(_) | ExprKind::Variable(_) => None,
+        ExprKind::Grouped(inner) => {
+            fold_expression(inner);
+            constant(inner)
+        }
+        ExprKind::Unary { op, expr } => {
+            fold_expression(expr);
+            constant(expr).map(|value| eval_unary(op, value))
+        }
+        ExprKind::Binary { op, lhs, rhs } => {
+            fold_expression(lhs);
+            fold_expression(rhs);
+            // Leaves operations without a defined result, like `1 / 0`,
+            // to fail at run time as they would unoptimized
+            constant(lhs)
+                .zip(constant(rhs))
+                .and_then(|(lhs, rhs)| eval_binary(op, lhs, rhs))
+        }
+        ExprKind::Logical { op, lhs, rhs } => {
+            fold_expression(lhs);
+            fold_expression(rhs);
+            constant(lhs).zip(constant(rhs)).map(|(lhs, rhs)| match op {
+                LogicalOp::And => (lhs != 0 && rhs != 0) as i64,
+                LogicalOp::Or => (lhs != 0 || rhs != 0) as i64,
+            })
+        }
+        ExprKind::Conditional {
+            cond,
+            then_expr,
+            else_expr,
+        } => {
+            fold_expression(cond);
+            fold_expression(then_expr);
+            fold_expression(else_expr);
+            match (constant(cond), constant(then_expr), constant(else_expr)) {
+                (Some(cond), Some(then_value), Some(else_value)) => {
+                    Some(if cond != 0 { then_value } else { else_value })
+                }
+                _ => None,
+            }
+        }
+        ExprKind::Comma { lhs, rhs } => {
+            fold_expression(lhs);
+            fold_expression(rhs);
+            constant(lhs).and(constant(rhs))
+        }
+        // Only the values stored are folded, targets are variables
+        ExprKind::IncDec { .. } => None,
+        ExprKind::Assignment { rhs, .. } => {
+            fold_expression(rhs);
+            None
+        }
+        ExprKind::FunctionCall { args, .. } => {
+            args.iter_mut().for_each(fold_expression);
+            None
+        }
+    };
+    if let Some(value) = folded {
+        expr.kind = ExprKind::Constant(value);
+    }
+}
+
+fn constant(expr: &Expression<'_>) -> Option<i64> {
+    match expr.kind {
+        ExprKind::Constant(value) => Some(value),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{grammar::BinaryOp, lexer_base::Lexer, parser_base::Parser};
+
+    fn folded(input: &str) -> Program<'_> {
+        let mut program = Parser::new(Lexer::new(input)).parse().unwrap();
+        fold_constants(&mut program);
+        program
+    }
+
+    fn returned<'p>(program: &'p Program<'_>) -> &'p ExprKind<'p> {
+        let body = program.functions.last().unwrap().body.as_ref().unwrap();
+        match body.statements.last() {
+            Some(Statement::Return(ret)) => &ret.expr.as_ref().unwrap().kind,
+            other => panic!("expected a return, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_constant_operators_are_folded() {
+        let program = folded("int main(void) { return (2 * 3 + 1) << 2 | ~0 & 5; }");
+        assert_eq!(returned(&program), &ExprKind::Constant(29));
+        let program = folded("int main(void) { return (1, 2) ? !0 && 3 : 4 || 0; }");
+        assert_eq!(returned(&program), &ExprKind::Constant(1));
+    }
+
+    #[test]
+    fn test_constant_parts_of_an_expression_are_folded() {
+        let program = folded("int main(void) { int x = 1; return 2 * 3 + x; }");
+        let ExprKind::Binary {
+            op: BinaryOp::Add,
+            lhs,
+            ..
+        } = returned(&program)
+        else {
+            panic!("expected an addition");
+        };
+        assert_eq!(lhs.kind, ExprKind::Constant(6));
+    }
+
+    #[test]
+    fn test_operations_without_a_result_are_kept() {
+        let program = folded("int main(void) { return 1 / 0 + 1 << 32; }");
+        assert!(matches!(returned(&program), ExprKind::Binary { .. }));
+    }
+
+    #[test]
+    fn test_operands_that_are_not_constant_are_kept() {
+        // `f()` would be discarded by folding the condition, hiding that it
+        // is void
+        let input = "void f(void); int main(void) { return 0 && f(); }";
+        let program = folded(input);
+        assert!(matches!(returned(&program), ExprKind::Logical { .. }));
+        let program = folded("int f(void); int main(void) { return 1 ? 2 : f(); }");
+        assert!(matches!(returned(&program), ExprKind::Conditional { .. }));
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+
+use crate::{
+    grammar::{BinaryOp, UnaryOp},
+    opt_base::ssa::SsaFunction,
+    tacky_base::{Instruction, TackyFuncDef, Value},
+};
+
+/// Pure computation, with its operands replaced by the leaders of their
+/// value numbers.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Expr {
+    Unary(UnaryOp, Value),
+    Binary(BinaryOp, Value, Value),
+}
+
+impl Expr {
+    /// Brings equivalent computations into the same shape: operands of
+    /// commutative operators are sorted and `>`/`>=` become `<`/`<=`.
+    fn binary(op: BinaryOp, lhs: Value, rhs: Value) -> Self {
+        match op {
+            BinaryOp::Add
+            | BinaryOp::Multiply
+            | BinaryOp::BitwiseAnd
+            | BinaryOp::BitwiseOr
+            | BinaryOp::BitwiseXor
+            | BinaryOp::Equal
+            | BinaryOp::NotEqual
+                if rhs < lhs =>
+            {
+                Expr::Binary(op, rhs, lhs)
+            }
+            BinaryOp::GreaterThan => Expr::Binary(BinaryOp::LessThan, rhs, lhs),
+            BinaryOp::GreaterThanOrEqual => Expr::Binary(BinaryOp::LessThanOrEqual, rhs, lhs),
+            _ => Expr::Binary(op, lhs, rhs),
+        }
+    }
+}
+
+/// Result of an operator applied to two operands with the same value.
+fn fold_same_operands(op: &BinaryOp) -> Option<i64> {
+    match op {
+        BinaryOp::Subtract
+        | BinaryOp::BitwiseXor
+        | BinaryOp::LessThan
+        | BinaryOp::GreaterThan
+        | BinaryOp::NotEqual => Some(0),
+        BinaryOp::Equal | BinaryOp::LessThanOrEqual | BinaryOp::GreaterThanOrEqual => Some(1),
+        _ => None,
+    }
+}
+
+/// Global value numbering and common subexpression elimination.
+///
+/// Works on the SSA form, where a variable can no longer be reassigned:
+/// each assignment creates a new version, so a value computed from the old
+/// version is never confused with one computed from the new. The dominator
+/// tree is walked from the entry; a computation is redundant when the same
+/// operator was already applied to the same values in a dominating block,
+/// and is then replaced by a copy of the earlier result. Copies and
+/// φ-functions whose arguments all agree do not define new values.
+///
+/// Function calls are never numbered: two calls with the same arguments can
+/// return different results, and TACKY has no loads whose values a call
+/// could clobber, so no other value is affected by them.
+pub fn number_values(func: &mut TackyFuncDef) {
+    let mut ssa = SsaFunction::construct(func);
+    let mut numbering = ValueNumbering::default();
+    if !ssa.cfg.blocks.is_empty() {
+        numbering.visit(&mut ssa, 0);
+    }
+
+    for phi in ssa.phis.iter_mut().flatten() {
+        for arg in &mut phi.args {
+            *arg = numbering.leader(arg);
+        }
+    }
+    for inst in ssa
+        .cfg
+        .blocks
+        .iter_mut()
+        .flat_map(|block| &mut block.instructions)
+    {
+        for operand in inst.operands_mut() {
+            *operand = numbering.leader(operand);
+        }
+    }
+
+    ssa.destruct(func);
+}
+
+#[derive(Default)]
+struct ValueNumbering {
+    /// Variables known to hold the same value as an earlier one. Because
+    /// the earlier definition dominates every use, this holds globally.
+    leaders: HashMap<String, Value>,
+    /// Computations available in the current block, i.e. performed in one
+    /// of its dominators, and the variable holding their result.
+    available: HashMap<Expr, String>,
+}
+
+impl ValueNumbering {
+    fn leader(&self, value: &Value) -> Value {
+        let mut value = value;
+        while let Value::Var(var) = value
+            && let Some(leader) = self.leaders.get(var)
+        {
+            value = leader;
+        }
+        value.clone()
+    }
+
+    fn visit(&mut self, ssa: &mut SsaFunction, block: usize) {
+        for phi in &ssa.phis[block] {
+            let mut args = phi
+                .args
+                .iter()
+                .map(|arg| self.leader(arg))
+                .filter(|arg| arg.as_var() != Some(&phi.dst));
+            if let Some(first) = args.next()
+                && args.all(|arg| arg == first)
+            {
+                self.leaders.insert(phi.dst.clone(), first);
+            }
+        }
+
+        let mut inserted = Vec::new();
+        for inst in &mut ssa.cfg.blocks[block].instructions {
+            for operand in inst.operands_mut() {
+                *operand = self.leader(operand);
+            }
+
+            let (expr, dst) = match inst {
+                Instruction::Copy { src, dst } => {
+                    self.leaders.insert(dst.clone(), src.clone());
+                    continue;
+                }
+                Instruction::Unary { op, src, dst } => (Expr::Unary(op.clone(), src.clone()), dst),
+                Instruction::Binary { op, lhs, rhs, dst } => {
+                    if lhs == rhs
+                        && let Some(val) = fold_same_operands(op)
+                    {
+                        let dst = dst.clone();
+                        self.leaders.insert(dst.clone(), Value::Constant(val));
+                        *inst = Instruction::Copy {
+                            src: Value::Constant(val),
+                            dst,
+                        };
+                        continue;
+                    }
+                    (Expr::binary(op.clone(), lhs.clone(), rhs.clone()), dst)
+                }
+                _ => continue,
+            };
+
+            match self.available.get(&expr) {
+                Some(earlier) => {
+                    let src = Value::Var(earlier.clone());
+                    let dst = dst.clone();
+                    self.leaders.insert(dst.clone(), src.clone());
+                    *inst = Instruction::Copy { src, dst };
+                }
+                None => {
+                    self.available.insert(expr.clone(), dst.clone());
+                    inserted.push(expr);
+                }
+            }
+        }
+
+        for child in ssa.dominators.children(block).to_vec() {
+            self.visit(ssa, child);
+        }
+
+        for expr in inserted {
+            self.available.remove(&expr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        opt_base::{OptLevel, optimize},
+        pass_base::PassManager,
+        tacky_base::{TackyProgram, lower_source, transform_and_check},
+    };
+
+    fn optimized(input: &str, args: &[i64], expected: i64) -> TackyProgram {
+        transform_and_check(input, "f", args, expected, |program| {
+            optimize(program, OptLevel::O1)
+        })
+    }
+
+    fn count(program: &TackyProgram, pred: impl Fn(&Instruction) -> bool) -> usize {
+        program.functions[0]
+            .body
+            .iter()
+            .filter(|inst| pred(inst))
+            .count()
+    }
+
+    fn is_op(op: BinaryOp) -> impl Fn(&Instruction) -> bool {
+        move |inst| matches!(inst, Instruction::Binary { op: o, .. } if *o == op)
+    }
+
+    #[test]
+    fn test_repeated_product_is_computed_once() {
+        let program = optimized("int f(int a, int b) { return a * b + a * b; }", &[3, 4], 24);
+        assert_eq!(count(&program, is_op(BinaryOp::Multiply)), 1);
+    }
+
+    #[test]
+    fn test_commuted_operands_share_a_value() {
+        let program = optimized(
+            "int f(int a, int b) { return (a + b) * (b + a); }",
+            &[2, 3],
+            25,
+        );
+        assert_eq!(count(&program, is_op(BinaryOp::Add)), 1);
+    }
+
+    #[test]
+    fn test_comparing_equal_values_is_folded() {
+        let program = optimized("int f(int x) { return (x + 1) < (x + 1); }", &[5], 0);
+        assert_eq!(
+            program.functions[0].body,
+            [Instruction::Return(Value::Constant(0))]
+        );
+    }
+
+    #[test]
+    fn test_value_from_dominating_block_is_reused() {
+        let program = optimized(
+            "int f(int a, int b) {
+                 int x = a * b;
+                 int y = 0;
+                 if (a > 0) y = a * b;
+                 return x + y;
+             }",
+            &[2, 5],
+            20,
+        );
+        assert_eq!(count(&program, is_op(BinaryOp::Multiply)), 1);
+    }
+
+    #[test]
+    fn test_value_from_sibling_branch_is_not_reused() {
+        let program = optimized(
+            "int f(int a, int b) {
+                 int y = 0;
+                 if (a > 0) y = a * b; else y = a * b + 1;
+                 return y;
+             }",
+            &[2, 5],
+            10,
+        );
+        assert_eq!(count(&program, is_op(BinaryOp::Multiply)), 2);
+    }
+
+    #[test]
+    fn test_assignment_kills_value() {
+        let program = optimized(
+            "int f(int a, int b) {
+                 int x = a * b;
+                 a = a + 1;
+                 int y = a * b;
+                 return x + y;
+             }",
+            &[2, 5],
+            25,
+        );
+        assert_eq!(count(&program, is_op(BinaryOp::Multiply)), 2);
+    }
+
+    #[test]
+    fn test_assignment_in_loop_kills_value() {
+        optimized(
+            "int f(int a, int b) {
+                 int s = 0;
+                 int i;
+                 for (i = 0; i < 3; i = i + 1) {
+                     s = s + a * b;
+                     a = a + 1;
+                 }
+                 return s + a * b;
+             }",
+            &[1, 2],
+            20,
+        );
+    }
+
+    #[test]
+    fn test_calls_are_not_merged() {
+        let mut program = lower_source(
+            "int g(int x) { return x; }
+             int f(int a) { return g(a) + g(a); }",
+        );
+        let mut passes = PassManager::new(OptLevel::O1);
+        passes.disable("inline").unwrap();
+        passes.run_ir(&mut program);
+        let calls = program.functions[1]
+            .body
+            .iter()
+            .filter(|inst| matches!(inst, Instruction::FunCall { .. }))
+            .count();
+        assert_eq!(calls, 2);
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::tacky_base::{Instruction, TackyFuncDef, TackyProgram, Value};
+
+/// Size limit of inlined callees when none is given with `-finline-limit`.
+pub const DEFAULT_INLINE_LIMIT: usize = 40;
+
+/// Function inlining.
+///
+/// A call is replaced by a copy of the callee's body when the callee is
+/// defined in the program, is not part of a recursive cycle, and its size
+/// (see [`cost`]) is at most `limit`. Functions are processed callees
+/// first, so a callee has already received its own inlined calls when it is
+/// copied into its callers.
+///
+/// Every variable and label of the copy is renamed with a suffix unique to
+/// the call site, the arguments are copied into the renamed parameters, and
+/// each `return` becomes a copy into the call's destination followed by a
+/// jump past the end of the copy.
+pub fn inline_functions(program: &mut TackyProgram, limit: usize) {
+    let index: HashMap<String, usize> = program
+        .functions
+        .iter()
+        .enumerate()
+        .map(|(idx, func)| (func.name.clone(), idx))
+        .collect();
+    let callees: Vec<Vec<usize>> = program
+        .functions
+        .iter()
+        .map(|func| {
+            func.body
+                .iter()
+                .filter_map(|inst| match inst {
+                    Instruction::FunCall { name, .. } | Instruction::TailCall { name, .. } => {
+                        index.get(name).copied()
+                    }
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect();
+    let recursive: Vec<bool> = (0..callees.len())
+        .map(|func| reaches(&callees, func, func))
+        .collect();
+
+    let mut inliner = Inliner { instance: 0 };
+    for func in postorder(&callees) {
+        let caller = program.functions[func].name.clone();
+        let mut body = std::mem::take(&mut program.functions[func].body);
+        let mut inlined = Vec::with_capacity(body.len());
+        for inst in body.drain(..) {
+            let callee = match &inst {
+                Instruction::FunCall { name, .. } => index
+                    .get(name)
+                    .copied()
+                    .filter(|&callee| !recursive[callee])
+                    .filter(|&callee| cost(&program.functions[callee]) <= limit),
+                _ => None,
+            };
+            match (callee, inst) {
+                (Some(callee), Instruction::FunCall { args, dst, .. }) => {
+                    let callee = &program.functions[callee];
+                    if args.len() == callee.params.len() {
+                        inliner.expand(&caller, callee, args, dst, &mut inlined);
+                    } else {
+                        inlined.push(Instruction::FunCall {
+                            name: callee.name.clone(),
+                            args,
+                            dst,
+                        });
+                    }
+                }
+                (_, inst) => inlined.push(inst),
+            }
+        }
+        program.functions[func].body = inlined;
+    }
+}
+
+/// Size of `func` in the cost model: the number of instructions it
+/// executes straight-line, labels and line markers excluded. This stands
+/// in for the code that a copy adds to each caller.
+fn cost(func: &TackyFuncDef) -> usize {
+    func.body
+        .iter()
+        .filter(|inst| !matches!(inst, Instruction::Label(_) | Instruction::Loc { .. }))
+        .count()
+}
+
+/// Whether `to` can be called, directly or not, from `from`.
+fn reaches(callees: &[Vec<usize>], from: usize, to: usize) -> bool {
+    let mut visited = HashSet::new();
+    let mut worklist = callees[from].clone();
+    while let Some(func) = worklist.pop() {
+        if func == to {
+            return true;
+        }
+        if visited.insert(func) {
+            worklist.extend(&callees[func]);
+        }
+    }
+    false
+}
+
+/// Functions ordered so that callees come before their callers, except
+/// along the edges closing a cycle.
+fn postorder(callees: &[Vec<usize>]) -> Vec<usize> {
+    fn visit(func: usize, callees: &[Vec<usize>], visited: &mut [bool], order: &mut Vec<usize>) {
+        if std::mem::replace(&mut visited[func], true) {
+            return;
+        }
+        for &callee in &callees[func] {
+            visit(callee, callees, visited, order);
+        }
+        order.push(func);
+    }
+
+    let mut visited = vec![false; callees.len()];
+    let mut order = Vec::with_capacity(callees.len());
+    for func in 0..callees.len() {
+        visit(func, callees, &mut visited, &mut order);
+    }
+    order
+}
+
+struct Inliner {
+    /// Counter of inlined call sites, shared by the whole program so that
+    /// the labels of every copy are unique
+    instance: usize,
+}
+
+impl Inliner {
+    /// Appends to `out` a copy of `callee` computing the call
+    /// `dst = callee(args)` made from `caller`.
+    fn expand(
+        &mut self,
+        caller: &str,
+        callee: &TackyFuncDef,
+        args: Vec<Value>,
+        dst: Option<String>,
+        out: &mut Vec<Instruction>,
+    ) {
+        let suffix = format!("{}.inline.{}", caller, self.instance);
+        self.instance += 1;
+        let rename = |name: &str| format!("{}.{}", name, suffix);
+        let end_label = format!("{}.end", suffix);
+
+        for (param, arg) in callee.params.iter().zip(args) {
+            out.push(Instruction::Copy {
+                src: arg,
+                dst: rename(param),
+            });
+        }
+
+        let last = callee.body.len().saturating_sub(1);
+        let mut jumps_to_end = false;
+        for (idx, inst) in callee.body.iter().enumerate() {
+            let mut inst = inst.clone();
+            for operand in inst.operands_mut() {
+                if let Value::Var(var) = operand {
+                    *var = rename(var);
+                }
+            }
+            if let Some(dst) = inst.dst_mut() {
+                *dst = rename(dst);
+            }
+            match &mut inst {
+                Instruction::Label(label)
+                | Instruction::Jump(label)
+                | Instruction::JumpIfZero { target: label, .. }
+                | Instruction::JumpIfNotZero { target: label, .. } => *label = rename(label),
+                _ => {}
+            }
+
+            let returned = match inst {
+                Instruction::Return(value) => {
+                    if let Some(dst) = &dst {
+                        out.push(Instruction::Copy {
+                            src: value,
+                            dst: dst.clone(),
+                        });
+                    }
+                    true
+                }
+                // Back to an ordinary call, as the copy no longer ends a function
+                Instruction::TailCall { name, args } => {
+                    out.push(Instruction::FunCall {
+                        name,
+                        args,
+                        dst: dst.clone(),
+                    });
+                    true
+                }
+                inst => {
+                    out.push(inst);
+                    false
+                }
+            };
+            // The final return falls through to the end of the copy
+            if returned && idx != last {
+                out.push(Instruction::Jump(end_label.clone()));
+                jumps_to_end = true;
+            }
+        }
+        if jumps_to_end {
+            out.push(Instruction::Label(end_label));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tacky_base::transform_and_check;
+
+    fn inlined(input: &str, limit: usize, expected: i64) -> TackyProgram {
+        transform_and_check(input, "main", &[], expected, |program| {
+            inline_functions(program, limit)
+        })
+    }
+
+    fn calls_in(program: &TackyProgram, func: &str) -> Vec<String> {
+        program
+            .functions
+            .iter()
+            .find(|f| f.name == func)
+            .unwrap()
+            .body
+            .iter()
+            .filter_map(|inst| match inst {
+                Instruction::FunCall { name, .. } => Some(name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_small_helper_is_inlined() {
+        let program = inlined(
+            "int square(int x) { return x * x; }
+             int main() { return square(3) + square(4); }",
+            DEFAULT_INLINE_LIMIT,
+            25,
+        );
+        assert!(calls_in(&program, "main").is_empty());
+    }
+
+    #[test]
+    fn test_early_returns_jump_to_the_end() {
+        let program = inlined(
+            "int abs(int x) { if (x < 0) return -x; return x; }
+             int main() { return abs(-4) * 10 + abs(2); }",
+            DEFAULT_INLINE_LIMIT,
+            42,
+        );
+        assert!(calls_in(&program, "main").is_empty());
+    }
+
+    #[test]
+    fn test_labels_are_unique_across_copies() {
+        let program = inlined(
+            "int sum(int n) { int s = 0; while (n > 0) { s = s + n; n = n - 1; } return s; }
+             int twice(int n) { return sum(n) + sum(n); }
+             int main() { return twice(3) + sum(4); }",
+            DEFAULT_INLINE_LIMIT,
+            22,
+        );
+        assert!(calls_in(&program, "main").is_empty());
+
+        let mut labels = HashSet::new();
+        for inst in program.functions.iter().flat_map(|func| &func.body) {
+            if let Instruction::Label(label) = inst {
+                assert!(labels.insert(label.clone()), "duplicate label {}", label);
+            }
+        }
+    }
+
+    #[test]
+    fn test_callee_over_limit_is_called() {
+        let program = inlined(
+            "int f(int x) { x = x + 1; x = x * 2; x = x - 3; return x; }
+             int main() { return f(5); }",
+            2,
+            9,
+        );
+        assert_eq!(calls_in(&program, "main"), ["f"]);
+    }
+
+    #[test]
+    fn test_recursive_cycles_are_not_inlined() {
+        let program = inlined(
+            "int odd(int n);
+             int even(int n) { if (n == 0) return 1; return odd(n - 1); }
+             int odd(int n) { if (n == 0) return 0; return even(n - 1); }
+             int fact(int n) { if (n < 2) return 1; return n * fact(n - 1); }
+             int main() { return even(6) + fact(4); }",
+            DEFAULT_INLINE_LIMIT,
+            25,
+        );
+        assert_eq!(calls_in(&program, "main"), ["even", "fact"]);
+        assert_eq!(calls_in(&program, "fact"), ["fact"]);
+        assert_eq!(calls_in(&program, "even"), ["odd"]);
+    }
+}
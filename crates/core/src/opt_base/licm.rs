@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use crate::{
+    grammar::BinaryOp,
+    opt_base::{
+        loops::{find_loops, insert_preheaders},
+        ssa::SsaFunction,
+    },
+    tacky_base::{Instruction, TackyFuncDef, Value},
+};
+
+/// Loop-invariant code motion.
+///
+/// A computation inside a loop is invariant when every operand is a
+/// constant or a variable defined outside the loop, or by another invariant
+/// computation. Such computations are moved into the loop's preheader, so
+/// they run once instead of on every iteration. Inner loops are handled
+/// first, so a value can move out of several loops in turn.
+///
+/// This is done on the SSA form, where moving a definition can never
+/// clobber another assignment to the same variable. Only pure computations
+/// move, and a division only when its divisor is a constant that cannot
+/// make it fault, since the hoisted code also runs when the loop body does
+/// not.
+pub fn hoist_loop_invariants(func: &mut TackyFuncDef) {
+    insert_preheaders(func);
+    let mut ssa = SsaFunction::construct(func);
+    let loops = find_loops(&ssa.cfg, &ssa.dominators);
+    let mut def_blocks = ssa.def_blocks();
+
+    for lp in &loops {
+        let Some(preheader) = lp.preheader(&ssa.cfg) else {
+            continue;
+        };
+        let is_invariant = |value: &Value, def_blocks: &HashMap<String, usize>| match value {
+            Value::Constant(_) => true,
+            Value::Var(var) => def_blocks.get(var).is_none_or(|&block| !lp.contains(block)),
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in &lp.blocks {
+                let mut idx = 0;
+                while idx < ssa.cfg.blocks[block].instructions.len() {
+                    let inst = &ssa.cfg.blocks[block].instructions[idx];
+                    let hoistable = is_movable(inst)
+                        && inst
+                            .operands()
+                            .into_iter()
+                            .all(|operand| is_invariant(operand, &def_blocks));
+                    if !hoistable {
+                        idx += 1;
+                        continue;
+                    }
+
+                    let inst = ssa.cfg.blocks[block].instructions.remove(idx);
+                    def_blocks.insert(inst.dst().unwrap().to_string(), preheader);
+                    ssa.append_to_block(preheader, inst);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    ssa.destruct(func);
+}
+
+/// Whether `inst` can be executed speculatively.
+fn is_movable(inst: &Instruction) -> bool {
+    match inst {
+        Instruction::Copy { .. } | Instruction::Unary { .. } => true,
+        Instruction::Binary {
+            op: BinaryOp::Divide | BinaryOp::Remainder,
+            rhs,
+            ..
+        } => matches!(rhs, Value::Constant(divisor) if *divisor != 0 && *divisor != -1),
+        Instruction::Binary { .. } => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tacky_base::{TackyProgram, transform_and_check};
+
+    fn hoisted(input: &str, args: &[i64], expected: i64) -> TackyProgram {
+        transform_and_check(input, "f", args, expected, |program| {
+            hoist_loop_invariants(&mut program.functions[0])
+        })
+    }
+
+    /// Index of the first instruction that computes `op`, and of the
+    /// first label, which starts the loop.
+    fn position_of(program: &TackyProgram, op: BinaryOp) -> (usize, usize) {
+        let body = &program.functions[0].body;
+        let computation = body
+            .iter()
+            .position(|inst| matches!(inst, Instruction::Binary { op: o, .. } if *o == op))
+            .unwrap();
+        let label = body
+            .iter()
+            .position(|inst| matches!(inst, Instruction::Label(_)))
+            .unwrap();
+        (computation, label)
+    }
+
+    #[test]
+    fn test_invariant_product_is_hoisted() {
+        let program = hoisted(
+            "int f(int a, int b) {
+                 int s = 0;
+                 int i;
+                 for (i = 0; i < 5; i = i + 1) s = s + a * b;
+                 return s;
+             }",
+            &[3, 4],
+            60,
+        );
+        let (product, loop_start) = position_of(&program, BinaryOp::Multiply);
+        assert!(product < loop_start);
+    }
+
+    #[test]
+    fn test_chain_of_invariants_is_hoisted_in_order() {
+        let program = hoisted(
+            "int f(int a, int b) {
+                 int s = 0;
+                 while (s < 100) {
+                     int t = a * b;
+                     int u = t + 1;
+                     s = s + u - a;
+                 }
+                 return s;
+             }",
+            &[3, 4],
+            100,
+        );
+        let (product, loop_start) = position_of(&program, BinaryOp::Multiply);
+        assert!(product < loop_start);
+        let body = &program.functions[0].body;
+        let add_one = body
+            .iter()
+            .position(|inst| {
+                matches!(
+                    inst,
+                    Instruction::Binary {
+                        op: BinaryOp::Add,
+                        rhs: Value::Constant(1),
+                        ..
+                    }
+                )
+            })
+            .unwrap();
+        assert!(product < add_one && add_one < loop_start);
+    }
+
+    #[test]
+    fn test_variant_computation_stays_in_loop() {
+        let program = hoisted(
+            "int f(int a, int b) {
+                 int s = 0;
+                 int i;
+                 for (i = 0; i < 5; i = i + 1) s = s + a * i;
+                 return s;
+             }",
+            &[3, 4],
+            30,
+        );
+        let (product, loop_start) = position_of(&program, BinaryOp::Multiply);
+        assert!(product > loop_start);
+    }
+
+    #[test]
+    fn test_guarded_division_is_not_hoisted() {
+        // Hoisting `a / b` would divide by zero when `b` is 0
+        let program = hoisted(
+            "int f(int a, int b) {
+                 int s = 0;
+                 int i;
+                 for (i = 0; i < 3; i = i + 1) if (b != 0) s = s + a / b;
+                 return s;
+             }",
+            &[6, 0],
+            0,
+        );
+        let (division, loop_start) = position_of(&program, BinaryOp::Divide);
+        assert!(division > loop_start);
+    }
+
+    #[test]
+    fn test_invariant_moves_out_of_nested_loops() {
+        let program = hoisted(
+            "int f(int a, int b) {
+                 int s = 0;
+                 int i;
+                 int j;
+                 for (i = 0; i < 3; i = i + 1)
+                     for (j = 0; j < 3; j = j + 1)
+                         s = s + a * b;
+                 return s;
+             }",
+            &[2, 5],
+            90,
+        );
+        let (product, loop_start) = position_of(&program, BinaryOp::Multiply);
+        assert!(product < loop_start);
+    }
+}
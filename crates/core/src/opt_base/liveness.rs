@@ -0,0 +1,87 @@
+use std::collections::HashSet;
+
+use crate::tacky_base::{Instruction, cfg::Cfg};
+
+/// Result of a backward liveness analysis over a [`Cfg`]: the variables whose
+/// current value may still be read at the start and at the end of each block.
+pub struct Liveness {
+    pub live_in: Vec<HashSet<String>>,
+    pub live_out: Vec<HashSet<String>>,
+}
+
+impl Liveness {
+    pub fn compute(cfg: &Cfg) -> Self {
+        let block_count = cfg.blocks.len();
+        let mut live_in: Vec<HashSet<String>> = vec![HashSet::new(); block_count];
+        let mut live_out: Vec<HashSet<String>> = vec![HashSet::new(); block_count];
+
+        // Iterate to a fixed point, visiting blocks in reverse program order
+        // so most information flows in a single sweep.
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for idx in (0..block_count).rev() {
+                let out: HashSet<String> = cfg.blocks[idx]
+                    .successors
+                    .iter()
+                    .flat_map(|&succ| live_in[succ].iter().cloned())
+                    .collect();
+
+                let mut live = out.clone();
+                for inst in cfg.blocks[idx].instructions.iter().rev() {
+                    transfer(inst, &mut live);
+                }
+
+                if live != live_in[idx] {
+                    live_in[idx] = live;
+                    changed = true;
+                }
+                live_out[idx] = out;
+            }
+        }
+
+        Self { live_in, live_out }
+    }
+}
+
+/// Updates `live` from the set of variables live after `inst` to the set
+/// live before it.
+pub fn transfer(inst: &Instruction, live: &mut HashSet<String>) {
+    if let Some(dst) = inst.dst() {
+        live.remove(dst);
+    }
+    for operand in inst.operands() {
+        if let Some(var) = operand.as_var() {
+            live.insert(var.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tacky_base::Value;
+
+    #[test]
+    fn test_liveness_across_loop_back_edge() {
+        // x is read at the loop head, so it is live at the end of the body.
+        let cfg = Cfg::new(vec![
+            Instruction::Label("head".into()),
+            Instruction::JumpIfZero {
+                cond: Value::Var("x".into()),
+                target: "out".into(),
+            },
+            Instruction::Copy {
+                src: Value::Constant(0),
+                dst: "x".into(),
+            },
+            Instruction::Jump("head".into()),
+            Instruction::Label("out".into()),
+            Instruction::Return(Value::Constant(0)),
+        ]);
+        let liveness = Liveness::compute(&cfg);
+        assert!(liveness.live_out[1].contains("x"));
+        assert!(liveness.live_out[2].is_empty());
+        assert!(liveness.live_in[0].contains("x"));
+    }
+}
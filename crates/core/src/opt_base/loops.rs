@@ -0,0 +1,242 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    opt_base::dominators::Dominators,
+    tacky_base::{
+        Instruction, TackyFuncDef,
+        cfg::{BasicBlock, Cfg},
+    },
+};
+
+/// Natural loop: the blocks that can reach one of the back edges into
+/// `header` without going through `header` itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loop {
+    pub header: usize,
+    /// Sources of the back edges
+    pub latches: Vec<usize>,
+    /// Every block of the loop, including `header`
+    pub blocks: BTreeSet<usize>,
+}
+
+impl Loop {
+    pub fn contains(&self, block: usize) -> bool {
+        self.blocks.contains(&block)
+    }
+
+    /// The block outside the loop through which it is always entered, if the
+    /// loop has one: the only predecessor of the header that is not a latch,
+    /// and that has no other successor.
+    pub fn preheader(&self, cfg: &Cfg) -> Option<usize> {
+        let mut outside = cfg.blocks[self.header]
+            .predecessors
+            .iter()
+            .filter(|&&pred| !self.contains(pred));
+        let pred = *outside.next()?;
+        (outside.next().is_none() && cfg.blocks[pred].successors == [self.header]).then_some(pred)
+    }
+}
+
+/// Finds the natural loops of `cfg` from its back edges, i.e. the edges
+/// whose target dominates their source. Back edges sharing a header form a
+/// single loop. Inner loops come before the loops containing them.
+pub fn find_loops(cfg: &Cfg, dominators: &Dominators) -> Vec<Loop> {
+    let mut loops: Vec<Loop> = Vec::new();
+
+    for (latch, block) in cfg.blocks.iter().enumerate() {
+        for &header in &block.successors {
+            if !dominators.dominates(header, latch) {
+                continue;
+            }
+            let idx = match loops.iter().position(|lp| lp.header == header) {
+                Some(idx) => idx,
+                None => {
+                    loops.push(Loop {
+                        header,
+                        latches: Vec::new(),
+                        blocks: BTreeSet::from([header]),
+                    });
+                    loops.len() - 1
+                }
+            };
+            let lp = &mut loops[idx];
+            lp.latches.push(latch);
+
+            let mut worklist = vec![latch];
+            while let Some(block) = worklist.pop() {
+                if lp.blocks.insert(block) {
+                    worklist.extend(cfg.blocks[block].predecessors.iter().copied());
+                }
+            }
+        }
+    }
+
+    loops.sort_by_key(|lp| lp.blocks.len());
+    loops
+}
+
+/// Gives every loop of `func` a preheader, a block that is the only way into
+/// the loop from outside. Code hoisted out of the loop is placed there.
+///
+/// The preheader is inserted right before the header in program order, and
+/// jumps into the loop from outside are redirected to it.
+pub fn insert_preheaders(func: &mut TackyFuncDef) {
+    let mut cfg = Cfg::new(std::mem::take(&mut func.body));
+    let reachable = cfg.reachable_blocks();
+    cfg.retain_blocks(&reachable);
+
+    let dominators = Dominators::compute(&cfg);
+    let mut loops = find_loops(&cfg, &dominators);
+    loops.retain(|lp| lp.preheader(&cfg).is_none());
+    // Insert from the back so the indices of the remaining headers stay valid
+    loops.sort_by_key(|lp| std::cmp::Reverse(lp.header));
+
+    let prefix = format!("{}.preheader", func.name);
+    for lp in loops {
+        let header = lp.header;
+        let header_label = match cfg.blocks[header].label() {
+            Some(label) => label.to_string(),
+            None => {
+                let label = cfg.fresh_label(&prefix);
+                cfg.blocks[header]
+                    .instructions
+                    .insert(0, Instruction::Label(label.clone()));
+                label
+            }
+        };
+        let preheader_label = cfg.fresh_label(&prefix);
+
+        for &pred in &cfg.blocks[header].predecessors.clone() {
+            if lp.contains(pred) {
+                continue;
+            }
+            if let Some(
+                Instruction::Jump(target)
+                | Instruction::JumpIfZero { target, .. }
+                | Instruction::JumpIfNotZero { target, .. },
+            ) = cfg.blocks[pred].instructions.last_mut()
+                && *target == header_label
+            {
+                *target = preheader_label.clone();
+            }
+        }
+        // A latch right before the header must keep jumping into the loop
+        // instead of falling through into the preheader.
+        if header > 0
+            && lp.contains(header - 1)
+            && cfg.fallthrough_successor(header - 1) == Some(header)
+        {
+            cfg.blocks[header - 1]
+                .instructions
+                .push(Instruction::Jump(header_label));
+        }
+
+        cfg.blocks.insert(
+            header,
+            BasicBlock {
+                instructions: vec![Instruction::Label(preheader_label)],
+                ..Default::default()
+            },
+        );
+        cfg.compute_edges();
+    }
+
+    func.body = cfg.into_instructions();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tacky_base::Value;
+
+    fn func(body: Vec<Instruction>) -> TackyFuncDef {
+        TackyFuncDef {
+            name: "f".into(),
+            is_global: true,
+            params: vec!["n".into()],
+            body,
+        }
+    }
+
+    fn jz(cond: &str, target: &str) -> Instruction {
+        Instruction::JumpIfZero {
+            cond: Value::Var(cond.into()),
+            target: target.into(),
+        }
+    }
+
+    #[test]
+    fn test_nested_loops_inner_first() {
+        // 0: entry   1: outer: jz n, out   2: inner: jz n, next   3: jmp inner
+        // 4: next: jmp outer   5: out: ret
+        let cfg = Cfg::new(vec![
+            Instruction::Copy {
+                src: Value::Constant(0),
+                dst: "x".into(),
+            },
+            Instruction::Label("outer".into()),
+            jz("n", "out"),
+            Instruction::Label("inner".into()),
+            jz("n", "next"),
+            Instruction::Jump("inner".into()),
+            Instruction::Label("next".into()),
+            Instruction::Jump("outer".into()),
+            Instruction::Label("out".into()),
+            Instruction::Return(Value::Constant(0)),
+        ]);
+        let loops = find_loops(&cfg, &Dominators::compute(&cfg));
+        assert_eq!(loops.len(), 2);
+        assert_eq!(loops[0].header, 2);
+        assert_eq!(loops[0].blocks, BTreeSet::from([2, 3]));
+        assert_eq!(loops[1].header, 1);
+        assert_eq!(loops[1].blocks, BTreeSet::from([1, 2, 3, 4]));
+        assert_eq!(loops[1].preheader(&cfg), Some(0));
+        // the outer header also branches out of the loop, so the inner loop
+        // has no preheader yet
+        assert_eq!(loops[0].preheader(&cfg), None);
+    }
+
+    #[test]
+    fn test_preheader_is_inserted_for_loop_at_entry() {
+        let mut f = func(vec![
+            Instruction::Label("head".into()),
+            jz("n", "out"),
+            Instruction::Jump("head".into()),
+            Instruction::Label("out".into()),
+            Instruction::Return(Value::Constant(0)),
+        ]);
+        insert_preheaders(&mut f);
+        assert_eq!(f.body[0], Instruction::Label("f.preheader.0".into()));
+        assert_eq!(f.body[1], Instruction::Label("head".into()));
+
+        let cfg = Cfg::new(f.body);
+        let loops = find_loops(&cfg, &Dominators::compute(&cfg));
+        assert_eq!(loops[0].preheader(&cfg), Some(0));
+    }
+
+    #[test]
+    fn test_jumps_from_outside_are_redirected() {
+        // Two ways into the loop: falling through and a jump from `skip`
+        let mut f = func(vec![
+            jz("n", "skip"),
+            Instruction::Jump("head".into()),
+            Instruction::Label("skip".into()),
+            Instruction::Copy {
+                src: Value::Constant(1),
+                dst: "n".into(),
+            },
+            Instruction::Label("head".into()),
+            jz("n", "out"),
+            Instruction::Jump("head".into()),
+            Instruction::Label("out".into()),
+            Instruction::Return(Value::Constant(0)),
+        ]);
+        insert_preheaders(&mut f);
+        assert_eq!(f.body[1], Instruction::Jump("f.preheader.0".into()));
+
+        let cfg = Cfg::new(f.body);
+        let loops = find_loops(&cfg, &Dominators::compute(&cfg));
+        assert_eq!(loops.len(), 1);
+        assert!(loops[0].preheader(&cfg).is_some());
+    }
+}
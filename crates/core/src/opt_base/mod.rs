@@ -0,0 +1,241 @@
+//! Optimization passes over the AST and TACKY.
+
+mod constprop;
+mod copyprop;
+mod dce;
+mod dominators;
+mod fold;
+mod gvn;
+mod inline;
+mod licm;
+mod liveness;
+mod loops;
+mod ssa;
+mod strength;
+mod tailcall;
+mod unreachable;
+
+pub use constprop::propagate_constants;
+pub use copyprop::propagate_copies;
+pub use dce::eliminate_dead_stores;
+pub use fold::fold_constants;
+pub use gvn::number_values;
+pub use inline::{DEFAULT_INLINE_LIMIT, inline_functions};
+pub use licm::hoist_loop_invariants;
+pub use strength::reduce_strength;
+pub use tailcall::eliminate_tail_calls;
+pub use unreachable::eliminate_unreachable_blocks;
+
+use std::str::FromStr;
+
+use crate::{pass_base::PassManager, tacky_base::TackyProgram};
+
+/// Optimization level selected with `-O<level>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum OptLevel {
+    #[default]
+    O0,
+    O1,
+    O2,
+    /// Optimize for size
+    Os,
+}
+
+impl FromStr for OptLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(OptLevel::O0),
+            "1" => Ok(OptLevel::O1),
+            "2" => Ok(OptLevel::O2),
+            "s" => Ok(OptLevel::Os),
+            _ => Err(format!("unknown optimization level `{}`", s)),
+        }
+    }
+}
+
+/// Optimizer settings: the level and the `-f` flags refining it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    pub level: OptLevel,
+    /// Largest callee copied into its callers, set with `-finline-limit=N`
+    pub inline_limit: usize,
+}
+
+impl Options {
+    pub fn new(level: OptLevel) -> Self {
+        Self {
+            level,
+            inline_limit: DEFAULT_INLINE_LIMIT,
+        }
+    }
+
+    /// Size limit for inlining. When optimizing for size, only callees no
+    /// larger than the call sequence they replace are inlined.
+    pub(crate) fn effective_inline_limit(&self) -> usize {
+        match self.level {
+            OptLevel::Os => self.inline_limit.min(CALL_COST),
+            _ => self.inline_limit,
+        }
+    }
+}
+
+/// Rough size of a call in the inliner's cost model: the argument setup,
+/// the call itself and copying the result back.
+const CALL_COST: usize = 3;
+
+/// Runs the TACKY passes enabled at `level` over the program. See
+/// [`PassManager`] for finer control.
+pub fn optimize(program: &mut TackyProgram, level: OptLevel) {
+    PassManager::new(level).run_ir(program);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tacky_base::{Instruction, Value, lower_source, transform_and_check};
+
+    /// Optimizes `input` and checks that `main` still returns `expected`.
+    fn optimize_and_check(input: &str, expected: i64) -> TackyProgram {
+        transform_and_check(input, "main", &[], expected, |program| {
+            optimize(program, OptLevel::O1)
+        })
+    }
+
+    fn main_body(program: &TackyProgram) -> &[Instruction] {
+        &program
+            .functions
+            .iter()
+            .find(|func| func.name == "main")
+            .unwrap()
+            .body
+    }
+
+    #[test]
+    fn test_opt_level_from_str() {
+        assert_eq!("0".parse(), Ok(OptLevel::O0));
+        assert_eq!("2".parse(), Ok(OptLevel::O2));
+        assert_eq!("s".parse(), Ok(OptLevel::Os));
+        assert!("3".parse::<OptLevel>().is_err());
+    }
+
+    #[test]
+    fn test_o0_leaves_program_untouched() {
+        let mut program = lower_source("int main() { int x = 3; return x; }");
+        let before = program.clone();
+        optimize(&mut program, OptLevel::O0);
+        assert_eq!(program, before);
+    }
+
+    #[test]
+    fn test_constants_propagate_through_variables() {
+        let program = optimize_and_check("int main() { int x = 3; int y = x + 4; return y; }", 7);
+        assert_eq!(
+            main_body(&program),
+            [Instruction::Return(Value::Constant(7))]
+        );
+    }
+
+    #[test]
+    fn test_constant_branches_are_folded() {
+        let program = optimize_and_check(
+            "int main() {
+                 int x = 2;
+                 if (x > 1) x = x * 10; else x = 0;
+                 while (x < 5) x = x + 1;
+                 return x;
+             }",
+            20,
+        );
+        assert_eq!(
+            main_body(&program),
+            [Instruction::Return(Value::Constant(20))]
+        );
+    }
+
+    #[test]
+    fn test_loop_counter_is_not_constant() {
+        let program = optimize_and_check(
+            "int main() {
+                 int s = 0;
+                 int i;
+                 for (i = 0; i < 5; i = i + 1) s = s + 2;
+                 return s;
+             }",
+            10,
+        );
+        assert!(
+            main_body(&program)
+                .iter()
+                .any(|inst| matches!(inst, Instruction::JumpIfZero { .. }))
+        );
+    }
+
+    #[test]
+    fn test_copies_propagate_into_uses() {
+        let program = optimize_and_check(
+            "int id(int a) { int b = a; int c = b; return c; }
+             int main() { return id(5); }",
+            5,
+        );
+        assert_eq!(
+            program.functions[0].body,
+            [Instruction::Return(Value::Var("a.0".into()))]
+        );
+    }
+
+    #[test]
+    fn test_copy_is_killed_by_reassigned_source() {
+        optimize_and_check(
+            "int f(int a) {
+                 int b = a;
+                 a = a + 1;
+                 return b * 10 + a;
+             }
+             int main() { return f(4); }",
+            45,
+        );
+    }
+
+    #[test]
+    fn test_division_by_zero_is_not_folded() {
+        let program = lower_source("int main() { int z = 0; if (z) return 1 / z; return 2; }");
+        let mut optimized = program.clone();
+        optimize(&mut optimized, OptLevel::O1);
+        assert_eq!(
+            main_body(&optimized),
+            [Instruction::Return(Value::Constant(2))]
+        );
+
+        let mut program = lower_source("int main() { int z = 0; return 1 / z; }");
+        optimize(&mut program, OptLevel::O1);
+        assert!(
+            main_body(&program)
+                .iter()
+                .any(|inst| matches!(inst, Instruction::Binary { .. }))
+        );
+    }
+
+    #[test]
+    fn test_inlining_can_be_disabled() {
+        let input = "int inc(int x) { return x + 1; }
+                     int main() { return inc(inc(1)); }";
+        let mut program = lower_source(input);
+        optimize(&mut program, OptLevel::O1);
+        assert_eq!(
+            main_body(&program),
+            [Instruction::Return(Value::Constant(3))]
+        );
+
+        let mut program = lower_source(input);
+        let mut passes = PassManager::new(OptLevel::O1);
+        passes.disable("inline").unwrap();
+        passes.run_ir(&mut program);
+        assert!(
+            main_body(&program)
+                .iter()
+                .any(|inst| matches!(inst, Instruction::FunCall { .. }))
+        );
+    }
+}
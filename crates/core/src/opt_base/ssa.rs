@@ -0,0 +1,477 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{
+    opt_base::{dominators::Dominators, liveness::Liveness},
+    tacky_base::{
+        Instruction, TackyFuncDef, Value,
+        cfg::{BasicBlock, Cfg},
+    },
+};
+
+/// `dst = φ(args)`: takes the value of `args[k]` when its block is entered
+/// from the k-th predecessor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Phi {
+    pub dst: String,
+    pub args: Vec<Value>,
+}
+
+/// A function body in static single assignment form.
+///
+/// Every variable is assigned exactly once; its versions are named
+/// `<variable>#<n>`. Parameters keep their names on entry. φ-functions are
+/// kept next to the graph instead of in the instruction stream, so passes
+/// that do not care about them can treat each block like plain TACKY.
+pub struct SsaFunction {
+    name: String,
+    pub cfg: Cfg,
+    /// φ-functions at the start of each block
+    pub phis: Vec<Vec<Phi>>,
+    pub dominators: Dominators,
+    next_id: usize,
+}
+
+impl SsaFunction {
+    /// Converts the body of `func` into pruned SSA form: φ-functions are
+    /// placed on the iterated dominance frontier of each variable's
+    /// definitions, but only where the variable is live.
+    ///
+    /// Unreachable blocks are dropped, and an empty entry block is added
+    /// when the first block is the target of a jump, so that every
+    /// φ-function has a predecessor for each of its arguments.
+    pub fn construct(func: &mut TackyFuncDef) -> Self {
+        let mut cfg = Cfg::new(std::mem::take(&mut func.body));
+        let reachable = cfg.reachable_blocks();
+        cfg.retain_blocks(&reachable);
+        if cfg
+            .blocks
+            .first()
+            .is_some_and(|entry| !entry.predecessors.is_empty())
+        {
+            cfg.blocks.insert(0, BasicBlock::default());
+            cfg.compute_edges();
+        }
+
+        let next_id = 1 + cfg
+            .blocks
+            .iter()
+            .flat_map(|block| &block.instructions)
+            .filter_map(Instruction::dst)
+            .chain(func.params.iter().map(String::as_str))
+            .filter_map(|name| name.rsplit_once('#')?.1.parse::<usize>().ok())
+            .max()
+            .unwrap_or(0);
+
+        let dominators = Dominators::compute(&cfg);
+        let mut ssa = Self {
+            name: func.name.clone(),
+            phis: vec![Vec::new(); cfg.blocks.len()],
+            cfg,
+            dominators,
+            next_id,
+        };
+        let original = ssa.place_phis(&func.params);
+
+        let mut renamer = Renamer {
+            ssa: &mut ssa,
+            original: &original,
+            stacks: func
+                .params
+                .iter()
+                .map(|param| (param.clone(), vec![param.clone()]))
+                .collect(),
+        };
+        if !renamer.ssa.cfg.blocks.is_empty() {
+            renamer.rename_block(0);
+        }
+        ssa
+    }
+
+    /// Inserts φ-functions for every variable that needs one. Returns the
+    /// variable each φ-function merges, since renaming overwrites `dst`.
+    fn place_phis(&mut self, params: &[String]) -> Vec<Vec<String>> {
+        let liveness = Liveness::compute(&self.cfg);
+        let frontiers = self.dominators.frontiers(&self.cfg);
+
+        // Sorted so the generated names do not depend on hashing order
+        let mut defs: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for param in params {
+            defs.entry(param).or_default().push(0);
+        }
+        for (idx, block) in self.cfg.blocks.iter().enumerate() {
+            for dst in block.instructions.iter().filter_map(Instruction::dst) {
+                defs.entry(dst).or_default().push(idx);
+            }
+        }
+
+        let mut original = vec![Vec::new(); self.cfg.blocks.len()];
+        for (var, mut worklist) in defs {
+            let mut has_phi = HashSet::new();
+            while let Some(block) = worklist.pop() {
+                for &frontier in &frontiers[block] {
+                    if !liveness.live_in[frontier].contains(var) || !has_phi.insert(frontier) {
+                        continue;
+                    }
+                    let pred_count = self.cfg.blocks[frontier].predecessors.len();
+                    self.phis[frontier].push(Phi {
+                        dst: var.to_string(),
+                        args: vec![Value::Var(var.to_string()); pred_count],
+                    });
+                    original[frontier].push(var.to_string());
+                    worklist.push(frontier);
+                }
+            }
+        }
+        original
+    }
+
+    /// Block that defines each variable, with a φ-function or an
+    /// instruction. Parameters are not included.
+    pub fn def_blocks(&self) -> HashMap<String, usize> {
+        let mut def_blocks = HashMap::new();
+        for (idx, (block, phis)) in self.cfg.blocks.iter().zip(&self.phis).enumerate() {
+            let phi_defs = phis.iter().map(|phi| phi.dst.as_str());
+            let inst_defs = block.instructions.iter().filter_map(Instruction::dst);
+            for dst in phi_defs.chain(inst_defs) {
+                def_blocks.insert(dst.to_string(), idx);
+            }
+        }
+        def_blocks
+    }
+
+    /// Adds `inst` at the end of `block`, before the jump that ends it.
+    pub fn append_to_block(&mut self, block: usize, inst: Instruction) {
+        let instructions = &mut self.cfg.blocks[block].instructions;
+        let ends_in_jump = matches!(
+            instructions.last(),
+            Some(
+                Instruction::Jump(_)
+                    | Instruction::JumpIfZero { .. }
+                    | Instruction::JumpIfNotZero { .. }
+                    | Instruction::Return(_)
+                    | Instruction::TailCall { .. }
+            )
+        );
+        let at = instructions.len() - ends_in_jump as usize;
+        instructions.insert(at, inst);
+    }
+
+    /// A new version of `var` that is not used anywhere yet.
+    pub fn fresh_name(&mut self, var: &str) -> String {
+        let base = var.rsplit_once('#').map_or(var, |(base, _)| base);
+        let name = format!("{}#{}", base, self.next_id);
+        self.next_id += 1;
+        name
+    }
+
+    /// Translates the function out of SSA form and stores the result as the
+    /// body of `func`.
+    ///
+    /// Each φ-function becomes a copy at the end of every predecessor. When
+    /// the predecessor ends in a conditional jump the copies would also run
+    /// on its other edge, so the edge is split with a new block. Copies of
+    /// one edge happen simultaneously; if one of them reads a variable
+    /// written by another, all of them go through fresh temporaries.
+    pub fn destruct(mut self, func: &mut TackyFuncDef) {
+        let split_prefix = format!("{}.phi", self.name);
+
+        for block in 0..self.phis.len() {
+            let phis = std::mem::take(&mut self.phis[block]);
+            if phis.is_empty() {
+                continue;
+            }
+            let label = self.cfg.blocks[block]
+                .label()
+                .expect("a block with φ-functions is the target of a jump")
+                .to_string();
+
+            for (k, pred) in self.cfg.blocks[block]
+                .predecessors
+                .clone()
+                .into_iter()
+                .enumerate()
+            {
+                let copies: Vec<(String, Value)> = phis
+                    .iter()
+                    .map(|phi| (phi.dst.clone(), phi.args[k].clone()))
+                    .filter(|(dst, src)| src.as_var() != Some(dst))
+                    .collect();
+                if copies.is_empty() {
+                    continue;
+                }
+                let mut copies = self.sequentialize(copies);
+
+                let ends_in_branch = matches!(
+                    self.cfg.blocks[pred].instructions.last(),
+                    Some(Instruction::JumpIfZero { .. } | Instruction::JumpIfNotZero { .. })
+                );
+                if ends_in_branch {
+                    // New blocks go after all others, so the indices of the
+                    // existing ones stay valid.
+                    let split_label = self.cfg.fresh_label(&split_prefix);
+                    copies.insert(0, Instruction::Label(split_label.clone()));
+                    copies.push(Instruction::Jump(label.clone()));
+                    self.cfg.blocks.push(BasicBlock {
+                        instructions: copies,
+                        ..Default::default()
+                    });
+
+                    let instructions = &mut self.cfg.blocks[pred].instructions;
+                    if let Some(
+                        Instruction::JumpIfZero { target, .. }
+                        | Instruction::JumpIfNotZero { target, .. },
+                    ) = instructions.last_mut()
+                        && *target == label
+                    {
+                        *target = split_label.clone();
+                    }
+                    // The conditional jump also falls through into the
+                    // block that follows it.
+                    if pred + 1 == block {
+                        instructions.push(Instruction::Jump(split_label));
+                    }
+                } else {
+                    let instructions = &mut self.cfg.blocks[pred].instructions;
+                    let jump = instructions.pop_if(|inst| matches!(inst, Instruction::Jump(_)));
+                    instructions.extend(copies);
+                    instructions.extend(jump);
+                }
+            }
+        }
+
+        func.body = self.cfg.into_instructions();
+    }
+
+    fn sequentialize(&mut self, copies: Vec<(String, Value)>) -> Vec<Instruction> {
+        let dsts: HashSet<&str> = copies.iter().map(|(dst, _)| dst.as_str()).collect();
+        let overlapping = copies
+            .iter()
+            .any(|(_, src)| src.as_var().is_some_and(|var| dsts.contains(var)));
+
+        if !overlapping {
+            return copies
+                .into_iter()
+                .map(|(dst, src)| Instruction::Copy { src, dst })
+                .collect();
+        }
+
+        let temps: Vec<String> = copies.iter().map(|(dst, _)| self.fresh_name(dst)).collect();
+        let mut instructions = Vec::with_capacity(copies.len() * 2);
+        for ((_, src), temp) in copies.iter().zip(&temps) {
+            instructions.push(Instruction::Copy {
+                src: src.clone(),
+                dst: temp.clone(),
+            });
+        }
+        for ((dst, _), temp) in copies.into_iter().zip(temps) {
+            instructions.push(Instruction::Copy {
+                src: Value::Var(temp),
+                dst,
+            });
+        }
+        instructions
+    }
+}
+
+/// Renames every definition to a fresh version while walking the dominator
+/// tree, keeping a stack of the versions currently in scope per variable.
+struct Renamer<'s> {
+    ssa: &'s mut SsaFunction,
+    original: &'s [Vec<String>],
+    stacks: HashMap<String, Vec<String>>,
+}
+
+impl Renamer<'_> {
+    fn current(&self, var: &str) -> Option<&String> {
+        self.stacks.get(var).and_then(|stack| stack.last())
+    }
+
+    fn define(&mut self, var: &str) -> String {
+        let name = self.ssa.fresh_name(var);
+        self.stacks
+            .entry(var.to_string())
+            .or_default()
+            .push(name.clone());
+        name
+    }
+
+    fn rename_block(&mut self, block: usize) {
+        let mut defined = Vec::new();
+
+        for idx in 0..self.ssa.phis[block].len() {
+            let var = &self.original[block][idx];
+            self.ssa.phis[block][idx].dst = self.define(var);
+            defined.push(var.clone());
+        }
+
+        let mut instructions = std::mem::take(&mut self.ssa.cfg.blocks[block].instructions);
+        for inst in &mut instructions {
+            for operand in inst.operands_mut() {
+                if let Value::Var(var) = operand
+                    && let Some(current) = self.current(var)
+                {
+                    *var = current.clone();
+                }
+            }
+            if let Some(dst) = inst.dst_mut() {
+                let var = std::mem::take(dst);
+                *dst = self.define(&var);
+                defined.push(var);
+            }
+        }
+        self.ssa.cfg.blocks[block].instructions = instructions;
+
+        for succ in self.ssa.cfg.blocks[block].successors.clone() {
+            let k = self.ssa.cfg.blocks[succ]
+                .predecessors
+                .iter()
+                .position(|&pred| pred == block)
+                .unwrap();
+            for idx in 0..self.ssa.phis[succ].len() {
+                let var = &self.original[succ][idx];
+                if let Some(current) = self.current(var) {
+                    self.ssa.phis[succ][idx].args[k] = Value::Var(current.clone());
+                }
+            }
+        }
+
+        for child in self.ssa.dominators.children(block).to_vec() {
+            self.rename_block(child);
+        }
+
+        for var in defined {
+            self.stacks.get_mut(&var).unwrap().pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tacky_base::{TackyProgram, interp::Interpreter, lower_source};
+
+    fn round_trip(input: &str, expected: i64) {
+        let mut program = lower_source(input);
+        assert_eq!(Interpreter::new(&program).call("main", &[]), expected);
+        for func in &mut program.functions {
+            let ssa = SsaFunction::construct(func);
+
+            // every variable is assigned exactly once
+            let mut defined = HashSet::new();
+            let phi_defs = ssa.phis.iter().flatten().map(|phi| phi.dst.as_str());
+            let inst_defs = ssa
+                .cfg
+                .blocks
+                .iter()
+                .flat_map(|block| &block.instructions)
+                .filter_map(Instruction::dst);
+            for dst in phi_defs.chain(inst_defs) {
+                assert!(defined.insert(dst.to_string()), "{} defined twice", dst);
+            }
+
+            ssa.destruct(func);
+        }
+        assert_eq!(Interpreter::new(&program).call("main", &[]), expected);
+    }
+
+    #[test]
+    fn test_phi_is_placed_at_join() {
+        let mut program = lower_source(
+            "int main() {
+                 int x = 1;
+                 int c = 0;
+                 if (c) x = 2;
+                 return x;
+             }",
+        );
+        let ssa = SsaFunction::construct(&mut program.functions[0]);
+        let phis: Vec<&Phi> = ssa.phis.iter().flatten().collect();
+        assert_eq!(phis.len(), 1);
+        assert!(phis[0].dst.starts_with("x."));
+        assert_eq!(phis[0].args.len(), 2);
+    }
+
+    #[test]
+    fn test_dead_variable_gets_no_phi() {
+        let mut program = lower_source(
+            "int main() {
+                 int x = 1;
+                 int c = 0;
+                 if (c) x = 2;
+                 return c;
+             }",
+        );
+        let ssa = SsaFunction::construct(&mut program.functions[0]);
+        assert!(ssa.phis.iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn test_round_trip_loops() {
+        round_trip(
+            "int main() {
+                 int s = 0;
+                 int i;
+                 for (i = 0; i < 10; i = i + 1) {
+                     if (i == 3) continue;
+                     s = s + i;
+                 }
+                 return s;
+             }",
+            42,
+        );
+    }
+
+    #[test]
+    fn test_round_trip_swap_in_loop() {
+        // `a` and `b` are swapped every iteration, so the φ-copies on the
+        // back edge read each other's destination.
+        round_trip(
+            "int main() {
+                 int a = 1;
+                 int b = 2;
+                 int t;
+                 int n = 3;
+                 while (n > 0) {
+                     t = a;
+                     a = b;
+                     b = t;
+                     n = n - 1;
+                 }
+                 return a * 10 + b;
+             }",
+            21,
+        );
+    }
+
+    #[test]
+    fn test_round_trip_entry_block_is_loop_header() {
+        let mut func = TackyFuncDef {
+            name: "main".into(),
+            is_global: true,
+            params: vec!["n".into()],
+            body: vec![
+                Instruction::Label("head".into()),
+                Instruction::Binary {
+                    op: crate::grammar::BinaryOp::Subtract,
+                    lhs: Value::Var("n".into()),
+                    rhs: Value::Constant(1),
+                    dst: "n".into(),
+                },
+                Instruction::JumpIfNotZero {
+                    cond: Value::Var("n".into()),
+                    target: "head".into(),
+                },
+                Instruction::Return(Value::Var("n".into())),
+            ],
+        };
+        let ssa = SsaFunction::construct(&mut func);
+        assert!(ssa.cfg.blocks[0].instructions.is_empty());
+        assert_eq!(ssa.phis[1].len(), 1);
+        ssa.destruct(&mut func);
+
+        let program = TackyProgram {
+            functions: vec![func],
+        };
+        assert_eq!(Interpreter::new(&program).call("main", &[5]), 0);
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+
+use crate::{
+    grammar::BinaryOp,
+    opt_base::{
+        loops::{Loop, find_loops, insert_preheaders},
+        ssa::{Phi, SsaFunction},
+    },
+    tacky_base::{Instruction, TackyFuncDef, Value, interp::eval_binary},
+};
+
+/// Basic induction variable: a φ-function in the loop header that starts at
+/// `init` and grows by the constant `step` on every iteration.
+struct InductionVar {
+    phi: String,
+    init: Value,
+    /// Variable holding the value for the next iteration, `phi + step`
+    next: String,
+    step: i64,
+}
+
+/// Strength reduction of induction variable multiplies.
+///
+/// For a basic induction variable `i` and a loop-invariant `k`, `i * k`
+/// grows by `step * k` from one iteration to the next. The product is
+/// replaced by a new induction variable that starts at `init * k` and is
+/// updated with an addition right after `i` is. As everything wraps around
+/// at 32 bits, the two stay equal even when the products overflow.
+pub fn reduce_strength(func: &mut TackyFuncDef) {
+    insert_preheaders(func);
+    let mut ssa = SsaFunction::construct(func);
+    let loops = find_loops(&ssa.cfg, &ssa.dominators);
+
+    for lp in &loops {
+        let Some(preheader) = lp.preheader(&ssa.cfg) else {
+            continue;
+        };
+        let def_blocks = ssa.def_blocks();
+        let is_invariant = |value: &Value| match value {
+            Value::Constant(_) => true,
+            Value::Var(var) => def_blocks.get(var).is_none_or(|&block| !lp.contains(block)),
+        };
+
+        let induction_vars = find_induction_vars(&ssa, lp, preheader);
+        // Products that were already reduced, by induction variable and factor
+        let mut reduced: HashMap<(usize, Value), String> = HashMap::new();
+
+        let mut candidates = Vec::new();
+        for &block in &lp.blocks {
+            for inst in &ssa.cfg.blocks[block].instructions {
+                let Instruction::Binary {
+                    op: BinaryOp::Multiply,
+                    lhs,
+                    rhs,
+                    dst,
+                } = inst
+                else {
+                    continue;
+                };
+                let found = induction_vars.iter().enumerate().find_map(|(iv, var)| {
+                    let var = Value::Var(var.phi.clone());
+                    if *lhs == var && is_invariant(rhs) {
+                        Some((iv, rhs.clone()))
+                    } else if *rhs == var && is_invariant(lhs) {
+                        Some((iv, lhs.clone()))
+                    } else {
+                        None
+                    }
+                });
+                if let Some((iv, factor)) = found {
+                    candidates.push((block, dst.clone(), iv, factor));
+                }
+            }
+        }
+
+        for (block, dst, iv, factor) in candidates {
+            let product = reduced
+                .entry((iv, factor.clone()))
+                .or_insert_with(|| {
+                    add_induction_var(&mut ssa, lp, preheader, &induction_vars[iv], &factor, &dst)
+                })
+                .clone();
+            let inst = ssa.cfg.blocks[block]
+                .instructions
+                .iter_mut()
+                .find(|inst| inst.dst() == Some(dst.as_str()))
+                .unwrap();
+            *inst = Instruction::Copy {
+                src: Value::Var(product),
+                dst,
+            };
+        }
+    }
+
+    ssa.destruct(func);
+}
+
+/// Header φ-functions of the form `i = φ(init, i + c, ..., i + c)`, where
+/// `init` comes from the preheader and every latch passes the same
+/// `i + c` back, possibly through copies.
+fn find_induction_vars(ssa: &SsaFunction, lp: &Loop, preheader: usize) -> Vec<InductionVar> {
+    let preds = &ssa.cfg.blocks[lp.header].predecessors;
+    let entry = preds.iter().position(|&pred| pred == preheader).unwrap();
+
+    let definitions: HashMap<&str, &Instruction> = lp
+        .blocks
+        .iter()
+        .flat_map(|&block| &ssa.cfg.blocks[block].instructions)
+        .filter_map(|inst| Some((inst.dst()?, inst)))
+        .collect();
+
+    ssa.phis[lp.header]
+        .iter()
+        .filter_map(|Phi { dst, args }| {
+            let mut back_args = args
+                .iter()
+                .enumerate()
+                .filter(|&(k, _)| k != entry)
+                .map(|(_, arg)| arg);
+            let mut next = back_args.next()?.as_var()?;
+            if !back_args.all(|arg| arg.as_var() == Some(next)) {
+                return None;
+            }
+            // Look through copies of the incremented value
+            while let Some(Instruction::Copy {
+                src: Value::Var(src),
+                ..
+            }) = definitions.get(next)
+            {
+                next = src;
+            }
+
+            let phi = Value::Var(dst.clone());
+            let step = match definitions.get(next)? {
+                Instruction::Binary {
+                    op: BinaryOp::Add,
+                    lhs,
+                    rhs: Value::Constant(step),
+                    ..
+                } if *lhs == phi => *step,
+                Instruction::Binary {
+                    op: BinaryOp::Add,
+                    lhs: Value::Constant(step),
+                    rhs,
+                    ..
+                } if *rhs == phi => *step,
+                Instruction::Binary {
+                    op: BinaryOp::Subtract,
+                    lhs,
+                    rhs: Value::Constant(step),
+                    ..
+                } if *lhs == phi => (*step as i32).wrapping_neg() as i64,
+                _ => return None,
+            };
+
+            Some(InductionVar {
+                phi: dst.clone(),
+                init: args[entry].clone(),
+                next: next.to_string(),
+                step,
+            })
+        })
+        .collect()
+}
+
+/// Creates the induction variable tracking `iv * factor` and returns the
+/// name of its φ-function. `name` is the variable whose value it replaces.
+fn add_induction_var(
+    ssa: &mut SsaFunction,
+    lp: &Loop,
+    preheader: usize,
+    iv: &InductionVar,
+    factor: &Value,
+    name: &str,
+) -> String {
+    let multiply_in_preheader = |ssa: &mut SsaFunction, lhs: Value, rhs: &Value| {
+        if let (Value::Constant(lhs), Value::Constant(rhs)) = (&lhs, rhs) {
+            return Value::Constant(eval_binary(&BinaryOp::Multiply, *lhs, *rhs).unwrap());
+        }
+        let dst = ssa.fresh_name(name);
+        ssa.append_to_block(
+            preheader,
+            Instruction::Binary {
+                op: BinaryOp::Multiply,
+                lhs,
+                rhs: rhs.clone(),
+                dst: dst.clone(),
+            },
+        );
+        Value::Var(dst)
+    };
+    let init = multiply_in_preheader(ssa, iv.init.clone(), factor);
+    let step = multiply_in_preheader(ssa, Value::Constant(iv.step), factor);
+
+    let phi = ssa.fresh_name(name);
+    let next = ssa.fresh_name(name);
+
+    let args = ssa.cfg.blocks[lp.header]
+        .predecessors
+        .iter()
+        .map(|&pred| {
+            if pred == preheader {
+                init.clone()
+            } else {
+                Value::Var(next.clone())
+            }
+        })
+        .collect();
+    ssa.phis[lp.header].push(Phi {
+        dst: phi.clone(),
+        args,
+    });
+
+    // Update right after the original induction variable
+    let (block, idx) = lp
+        .blocks
+        .iter()
+        .find_map(|&block| {
+            let idx = ssa.cfg.blocks[block]
+                .instructions
+                .iter()
+                .position(|inst| inst.dst() == Some(iv.next.as_str()))?;
+            Some((block, idx))
+        })
+        .unwrap();
+    ssa.cfg.blocks[block].instructions.insert(
+        idx + 1,
+        Instruction::Binary {
+            op: BinaryOp::Add,
+            lhs: Value::Var(phi.clone()),
+            rhs: step,
+            dst: next,
+        },
+    );
+
+    phi
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tacky_base::{TackyProgram, transform_and_check};
+
+    fn reduced(input: &str, args: &[i64], expected: i64) -> TackyProgram {
+        transform_and_check(input, "f", args, expected, |program| {
+            reduce_strength(&mut program.functions[0])
+        })
+    }
+
+    /// Multiplications left after the first label, i.e. inside the loop.
+    fn multiplies_in_loop(program: &TackyProgram) -> usize {
+        program.functions[0]
+            .body
+            .iter()
+            .skip_while(|inst| !matches!(inst, Instruction::Label(_)))
+            .filter(|inst| {
+                matches!(
+                    inst,
+                    Instruction::Binary {
+                        op: BinaryOp::Multiply,
+                        ..
+                    }
+                )
+            })
+            .count()
+    }
+
+    #[test]
+    fn test_multiply_by_constant_becomes_add() {
+        let program = reduced(
+            "int f(int n) {
+                 int s = 0;
+                 int i;
+                 for (i = 0; i < n; i = i + 1) s = s + i * 4;
+                 return s;
+             }",
+            &[10],
+            180,
+        );
+        assert_eq!(multiplies_in_loop(&program), 0);
+    }
+
+    #[test]
+    fn test_multiply_by_invariant_variable() {
+        let program = reduced(
+            "int f(int n, int k) {
+                 int s = 0;
+                 int i;
+                 for (i = n; i > 0; i = i - 2) s = s + k * i;
+                 return s;
+             }",
+            &[9, 3],
+            75,
+        );
+        assert_eq!(multiplies_in_loop(&program), 0);
+    }
+
+    #[test]
+    fn test_multiply_by_variant_factor_is_kept() {
+        let program = reduced(
+            "int f(int n) {
+                 int s = 0;
+                 int i;
+                 for (i = 0; i < n; i = i + 1) s = s + i * s;
+                 return s;
+             }",
+            &[5],
+            0,
+        );
+        assert_eq!(multiplies_in_loop(&program), 1);
+    }
+
+    #[test]
+    fn test_products_wrap_like_multiplication() {
+        reduced(
+            "int f(int n) {
+                 int s = 0;
+                 int i;
+                 for (i = 0; i < n; i = i + 1000) s = s + i * 100000;
+                 return s;
+             }",
+            &[50000],
+            -2_054_051_584,
+        );
+    }
+}
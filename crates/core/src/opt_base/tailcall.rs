@@ -0,0 +1,196 @@
+use crate::{
+    codegen_base::ARG_REGISTERS,
+    tacky_base::{Instruction, TackyFuncDef, Value},
+};
+
+/// Tail-call optimization.
+///
+/// A call is in tail position when its result is returned right away. A
+/// tail call to the function itself becomes a loop: the arguments are
+/// assigned to the parameters and control jumps back to the top of the
+/// body, so deep recursion runs in constant stack space. Other tail calls
+/// become [`Instruction::TailCall`], which the code generator lowers to a
+/// jump after releasing the frame, provided all arguments are passed in
+/// registers: stack arguments would have to be written over the caller's
+/// own incoming arguments, which may not leave enough room.
+pub fn eliminate_tail_calls(func: &mut TackyFuncDef) {
+    let entry_label = format!("{}.tail_entry", func.name);
+    let mut loops_to_entry = false;
+    let mut site = 0;
+
+    let mut body = Vec::with_capacity(func.body.len());
+    let mut instructions = std::mem::take(&mut func.body).into_iter().peekable();
+    while let Some(inst) = instructions.next() {
+        let Instruction::FunCall {
+            name,
+            args,
+            dst: Some(dst),
+        } = &inst
+        else {
+            body.push(inst);
+            continue;
+        };
+        // Debug line markers in between don't keep the call from being in
+        // tail position
+        let mut markers = Vec::new();
+        while let Some(marker) =
+            instructions.next_if(|next| matches!(next, Instruction::Loc { .. }))
+        {
+            markers.push(marker);
+        }
+        let returns_result = matches!(
+            instructions.peek(),
+            Some(Instruction::Return(Value::Var(var))) if var == dst
+        );
+        if !returns_result {
+            body.push(inst);
+            body.extend(markers);
+            continue;
+        }
+
+        if *name == func.name {
+            // Evaluate every argument before assigning any parameter, as an
+            // argument can read a parameter that comes earlier in the list
+            let temps: Vec<String> = func
+                .params
+                .iter()
+                .map(|param| format!("{}.tail.{}", param, site))
+                .collect();
+            site += 1;
+            for (arg, temp) in args.iter().zip(&temps) {
+                body.push(Instruction::Copy {
+                    src: arg.clone(),
+                    dst: temp.clone(),
+                });
+            }
+            for (temp, param) in temps.into_iter().zip(&func.params) {
+                body.push(Instruction::Copy {
+                    src: Value::Var(temp),
+                    dst: param.clone(),
+                });
+            }
+            body.push(Instruction::Jump(entry_label.clone()));
+            loops_to_entry = true;
+        } else if args.len() <= ARG_REGISTERS.len() {
+            body.push(Instruction::TailCall {
+                name: name.clone(),
+                args: args.clone(),
+            });
+        } else {
+            body.push(inst);
+            body.extend(markers);
+            continue;
+        }
+        // Drop the return of the call's result
+        instructions.next();
+    }
+
+    if loops_to_entry {
+        body.insert(0, Instruction::Label(entry_label));
+    }
+    func.body = body;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tacky_base::{TackyProgram, transform_and_check};
+
+    fn optimized(input: &str, func: &str, args: &[i64], expected: i64) -> TackyProgram {
+        transform_and_check(input, func, args, expected, |program| {
+            program.functions.iter_mut().for_each(eliminate_tail_calls)
+        })
+    }
+
+    fn body<'p>(program: &'p TackyProgram, func: &str) -> &'p [Instruction] {
+        &program
+            .functions
+            .iter()
+            .find(|f| f.name == func)
+            .unwrap()
+            .body
+    }
+
+    #[test]
+    fn test_self_recursion_becomes_loop() {
+        let program = optimized(
+            "int fact_acc(int n, int acc) {
+                 if (n < 2) return acc;
+                 return fact_acc(n - 1, acc * n);
+             }",
+            "fact_acc",
+            &[5, 1],
+            120,
+        );
+        let body = body(&program, "fact_acc");
+        assert_eq!(body[0], Instruction::Label("fact_acc.tail_entry".into()));
+        assert!(
+            !body
+                .iter()
+                .any(|inst| matches!(inst, Instruction::FunCall { .. }))
+        );
+    }
+
+    #[test]
+    fn test_swapped_arguments_are_evaluated_first() {
+        optimized(
+            "int gcd(int a, int b) {
+                 if (b == 0) return a;
+                 return gcd(b, a - a / b * b);
+             }",
+            "gcd",
+            &[84, 36],
+            12,
+        );
+    }
+
+    #[test]
+    fn test_sibling_call_becomes_tail_call() {
+        let program = optimized(
+            "int twice(int x) { return x * 2; }
+             int f(int x) { return twice(x + 1); }",
+            "f",
+            &[4],
+            10,
+        );
+        assert!(
+            body(&program, "f")
+                .iter()
+                .any(|inst| matches!(inst, Instruction::TailCall { name, .. } if name == "twice"))
+        );
+    }
+
+    #[test]
+    fn test_call_whose_result_is_used_is_kept() {
+        let program = optimized(
+            "int fact(int n) { if (n < 2) return 1; return n * fact(n - 1); }",
+            "fact",
+            &[5],
+            120,
+        );
+        let body = body(&program, "fact");
+        assert!(
+            body.iter()
+                .any(|inst| matches!(inst, Instruction::FunCall { .. }))
+        );
+        assert!(!matches!(body[0], Instruction::Label(_)));
+    }
+
+    #[test]
+    fn test_call_with_stack_arguments_is_kept() {
+        let program = optimized(
+            "int sum(int a, int b, int c, int d, int e, int f, int g) {
+                 return a + b + c + d + e + f + g;
+             }
+             int f(int x) { return sum(x, x, x, x, x, x, x); }",
+            "f",
+            &[2],
+            14,
+        );
+        assert!(
+            body(&program, "f")
+                .iter()
+                .any(|inst| matches!(inst, Instruction::FunCall { .. }))
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::HashSet;
+
+use crate::tacky_base::{Instruction, TackyFuncDef, cfg::Cfg};
+
+/// Removes basic blocks that cannot be reached from the function entry,
+/// then drops the jumps and labels that became redundant.
+pub fn eliminate_unreachable_blocks(func: &mut TackyFuncDef) {
+    let mut cfg = Cfg::new(std::mem::take(&mut func.body));
+
+    let reachable = cfg.reachable_blocks();
+    cfg.retain_blocks(&reachable);
+    remove_redundant_jumps(&mut cfg);
+    remove_redundant_labels(&mut cfg);
+
+    func.body = cfg.into_instructions();
+}
+
+/// Removes jumps whose every target is the block that follows in program
+/// order anyway.
+fn remove_redundant_jumps(cfg: &mut Cfg) {
+    let block_count = cfg.blocks.len();
+    for idx in 0..block_count.saturating_sub(1) {
+        let block = &mut cfg.blocks[idx];
+        let is_jump = matches!(
+            block.instructions.last(),
+            Some(
+                Instruction::Jump(_)
+                    | Instruction::JumpIfZero { .. }
+                    | Instruction::JumpIfNotZero { .. }
+            )
+        );
+        if is_jump && block.successors.iter().all(|&succ| succ == idx + 1) {
+            block.instructions.pop();
+        }
+    }
+    cfg.compute_edges();
+}
+
+/// Removes labels that no jump refers to; such blocks are only ever entered
+/// by falling through from the previous block.
+fn remove_redundant_labels(cfg: &mut Cfg) {
+    let targets: HashSet<String> = cfg
+        .blocks
+        .iter()
+        .filter_map(|block| match block.instructions.last() {
+            Some(
+                Instruction::Jump(target)
+                | Instruction::JumpIfZero { target, .. }
+                | Instruction::JumpIfNotZero { target, .. },
+            ) => Some(target.clone()),
+            _ => None,
+        })
+        .collect();
+
+    for block in &mut cfg.blocks {
+        if block.label().is_some_and(|label| !targets.contains(label)) {
+            block.instructions.remove(0);
+        }
+    }
+    cfg.compute_edges();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tacky_base::Value;
+
+    fn func(body: Vec<Instruction>) -> TackyFuncDef {
+        TackyFuncDef {
+            name: "f".into(),
+            is_global: true,
+            params: vec![],
+            body,
+        }
+    }
+
+    #[test]
+    fn test_code_after_return_is_removed() {
+        let mut f = func(vec![
+            Instruction::Return(Value::Constant(1)),
+            Instruction::Copy {
+                src: Value::Constant(2),
+                dst: "x".into(),
+            },
+            Instruction::Return(Value::Constant(0)),
+        ]);
+        eliminate_unreachable_blocks(&mut f);
+        assert_eq!(f.body, vec![Instruction::Return(Value::Constant(1))]);
+    }
+
+    #[test]
+    fn test_jump_to_next_block_and_its_label_are_removed() {
+        let mut f = func(vec![
+            Instruction::Jump("next".into()),
+            Instruction::Label("next".into()),
+            Instruction::Return(Value::Constant(0)),
+        ]);
+        eliminate_unreachable_blocks(&mut f);
+        assert_eq!(f.body, vec![Instruction::Return(Value::Constant(0))]);
+    }
+
+    #[test]
+    fn test_loop_labels_are_kept() {
+        let body = vec![
+            Instruction::Label("head".into()),
+            Instruction::JumpIfZero {
+                cond: Value::Var("x".into()),
+                target: "out".into(),
+            },
+            Instruction::Jump("head".into()),
+            Instruction::Label("out".into()),
+            Instruction::Return(Value::Constant(0)),
+        ];
+        let mut f = func(body.clone());
+        eliminate_unreachable_blocks(&mut f);
+        assert_eq!(f.body, body);
+    }
+
+    #[test]
+    fn test_else_branch_of_constant_condition_is_removed() {
+        // `if (1) return 1; else return 2;` after the condition is folded
+        let mut f = func(vec![
+            Instruction::Jump("then".into()),
+            Instruction::Label("else".into()),
+            Instruction::Return(Value::Constant(2)),
+            Instruction::Label("then".into()),
+            Instruction::Return(Value::Constant(1)),
+        ]);
+        eliminate_unreachable_blocks(&mut f);
+        assert_eq!(f.body, vec![Instruction::Return(Value::Constant(1))]);
+    }
+}
@@ -15,6 +15,11 @@ pub enum ParseError {
     #[error("Expected {expected}, but found {found}")]
     UnexpectedToken { expected: String, found: String },
 
+    /// An operator that stores into its operand applied to something that
+    /// cannot be stored into
+    #[error("Invalid operand of {0}, which must be an lvalue")]
+    InvalidLvalue(String),
+
     /// Expected a specific token, but reached end of input
     #[error("Expected {expected}, but reached end of file")]
     UnexpectedEof { expected: String },
@@ -11,12 +11,20 @@ impl<'a> Parser<'a> {
         self.parse_expr_with_min_bp(0)
     }
 
+    /// Parses an expression without a comma operator at its top level, for
+    /// the places where commas separate expressions instead: function call
+    /// arguments and initializers.
+    pub(super) fn parse_assignment_expression(&mut self) -> ParseResult<Expression<'a>> {
+        self.parse_expr_with_min_bp(BindingPower::Assignment.left_associative())
+    }
+
     fn parse_expr_with_min_bp(&mut self, min_bp: u8) -> ParseResult<Expression<'a>> {
         let lhs = self.parse_prefix()?;
         self.parse_with_lhs_within_bp(lhs, min_bp)
     }
 
     fn parse_prefix(&mut self) -> ParseResult<Expression<'a>> {
+        let start = self.peek_span()?;
         if let Some(op) = self
             .peek_token_type()?
             .as_ref()
@@ -25,55 +33,115 @@ impl<'a> Parser<'a> {
             self.next_token()?;
             let rbp = op.binding_power().right_associative();
             let rhs = self.parse_expr_with_min_bp(rbp)?;
-            Ok(Expression::Unary {
+            let kind = ExprKind::Unary {
                 op,
                 expr: Box::new(rhs),
-            })
+            };
+            Ok(Expression::new(kind, self.span_from(start)))
+        } else if let Some(token) = self.peek_token()?
+            && let Some(op) = IncDecOp::prefix_from_token_type(&token.kind)
+        {
+            self.next_token()?;
+            let rbp = op.binding_power().right_associative();
+            let operand = self.parse_expr_with_min_bp(rbp)?;
+            self.expect_lvalue(&operand, &token.kind)?;
+            let kind = ExprKind::IncDec {
+                op,
+                expr: Box::new(operand),
+            };
+            Ok(Expression::new(kind, self.span_from(start)))
         } else {
             self.parse_unit_expression()
         }
     }
 
+    /// Extends `lhs` with every operator that binds at least as tightly as
+    /// `min_bp`.
     fn parse_with_lhs_within_bp(
         &mut self,
         mut lhs: Expression<'a>,
         min_bp: u8,
     ) -> ParseResult<Expression<'a>> {
         while let Some(token) = self.peek_token()? {
+            let start = lhs.span;
             let (lbp, rbp) = BindingPower::infer_from_token_type(&token.kind).as_tuple();
             if min_bp > lbp {
                 break;
             }
 
-            lhs = if matches!(token.kind, t!("(")) {
-                Expression::FunctionCall {
+            let kind = if matches!(token.kind, t!("(")) {
+                ExprKind::FunctionCall {
                     callee: Box::new(lhs),
                     args: self.parse_function_call_arguments()?,
                 }
+            } else if let Some(op) = IncDecOp::postfix_from_token_type(&token.kind) {
+                self.expect_lvalue(&lhs, &token.kind)?;
+                self.next_token()?;
+                ExprKind::IncDec {
+                    op,
+                    expr: Box::new(lhs),
+                }
             } else if let Some(op) = BinaryOp::from_token_type(&token.kind) {
                 self.next_token()?;
                 let rhs = self.parse_expr_with_min_bp(rbp)?;
-                Expression::Binary {
+                ExprKind::Binary {
+                    op,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                }
+            } else if let Some(op) = LogicalOp::from_token_type(&token.kind) {
+                self.next_token()?;
+                let rhs = self.parse_expr_with_min_bp(rbp)?;
+                ExprKind::Logical {
                     op,
                     lhs: Box::new(lhs),
                     rhs: Box::new(rhs),
                 }
+            } else if matches!(token.kind, t!("?")) {
+                self.next_token()?;
+                // Whatever is between `?` and `:` is the middle operand, so
+                // it is parsed as a full expression
+                let then_expr = self.parse_expression()?;
+                self.expect_token(t!(":"))?;
+                let else_expr = self.parse_expr_with_min_bp(rbp)?;
+                ExprKind::Conditional {
+                    cond: Box::new(lhs),
+                    then_expr: Box::new(then_expr),
+                    else_expr: Box::new(else_expr),
+                }
+            } else if matches!(token.kind, t!(",")) {
+                self.next_token()?;
+                let rhs = self.parse_expr_with_min_bp(rbp)?;
+                ExprKind::Comma {
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                }
             } else if let Some(op) = AssignOp::from_token_type(&token.kind) {
+                self.expect_lvalue(&lhs, &token.kind)?;
                 self.next_token()?;
                 let rhs = self.parse_expr_with_min_bp(rbp)?;
-                Expression::Assignment {
+                ExprKind::Assignment {
                     op,
                     lhs: Box::new(lhs),
                     rhs: Box::new(rhs),
                 }
             } else {
                 break;
-            }
+            };
+            lhs = Expression::new(kind, self.span_from(start));
         }
 
         Ok(lhs)
     }
 
+    /// Checks that `expr` can be the operand of `op`, which stores into it.
+    fn expect_lvalue(&self, expr: &Expression<'a>, op: &TokenType<'a>) -> ParseResult<()> {
+        match expr.is_lvalue() {
+            true => Ok(()),
+            false => Err(ParseError::InvalidLvalue(op.to_string()).with_span(expr.span)),
+        }
+    }
+
     /// Parses a unit expression, which can be a constant, variable, or grouped
     /// expression
     ///
@@ -83,17 +151,17 @@ impl<'a> Parser<'a> {
             Some(Token { kind: t!("("), .. }) => self.parse_grouped_expression(),
             Some(Token {
                 kind: TokenType::Constant(value),
-                ..
+                span,
             }) => {
                 self.next_token()?;
-                Ok(Expression::Constant(value))
+                Ok(Expression::new(ExprKind::Constant(value), span))
             }
             Some(Token {
                 kind: TokenType::Identifier(name),
-                ..
+                span,
             }) => {
                 self.next_token()?;
-                Ok(Expression::Variable(name.clone()))
+                Ok(Expression::new(ExprKind::Variable(name.clone()), span))
             }
             else_token => Err(ParseError::unexpected(
                 "expression",
@@ -107,10 +175,12 @@ impl<'a> Parser<'a> {
     ///
     /// (expr)
     fn parse_grouped_expression(&mut self) -> ParseResult<Expression<'a>> {
+        let start = self.peek_span()?;
         self.expect_token(t!("("))?;
         let expr = self.parse_expression()?;
         self.expect_token(t!(")"))?;
-        Ok(Expression::Grouped(Box::new(expr)))
+        let kind = ExprKind::Grouped(Box::new(expr));
+        Ok(Expression::new(kind, self.span_from(start)))
     }
 
     /// Parses function call arguments, with parentheses
@@ -125,7 +195,7 @@ impl<'a> Parser<'a> {
         }
 
         loop {
-            args.push(self.parse_expression()?);
+            args.push(self.parse_assignment_expression()?);
             if self.eat(t!(")"))? {
                 break;
             }
@@ -165,7 +235,7 @@ mod tests {
     use super::*;
     use crate::lexer_base::Lexer;
 
-    fn parse_expr(input: &str) -> ParseResult<Expression<'_>> {
+    fn parse_spanned(input: &str) -> ParseResult<Expression<'_>> {
         let lexer = Lexer::new(input);
         let mut parser = Parser::new(lexer);
         parser
@@ -174,21 +244,64 @@ mod tests {
             .inspect_err(|e| eprintln!("{e:?}"))
     }
 
+    /// Parses `input` with every span cleared, so that the result can be
+    /// compared against trees built with [`node`].
+    fn parse_expr(input: &str) -> ParseResult<Expression<'_>> {
+        parse_spanned(input).map(|mut expr| {
+            clear_spans(&mut expr);
+            expr
+        })
+    }
+
+    fn clear_spans(expr: &mut Expression<'_>) {
+        expr.span = Span::default();
+        match &mut expr.kind {
+            ExprKind::Constant(_) | ExprKind::Variable(_) => {}
+            ExprKind::Grouped(expr)
+            | ExprKind::Unary { expr, .. }
+            | ExprKind::IncDec { expr, .. } => clear_spans(expr),
+            ExprKind::Binary { lhs, rhs, .. }
+            | ExprKind::Logical { lhs, rhs, .. }
+            | ExprKind::Assignment { lhs, rhs, .. }
+            | ExprKind::Comma { lhs, rhs } => {
+                clear_spans(lhs);
+                clear_spans(rhs);
+            }
+            ExprKind::Conditional {
+                cond,
+                then_expr,
+                else_expr,
+            } => {
+                clear_spans(cond);
+                clear_spans(then_expr);
+                clear_spans(else_expr);
+            }
+            ExprKind::FunctionCall { callee, args } => {
+                clear_spans(callee);
+                args.iter_mut().for_each(clear_spans);
+            }
+        }
+    }
+
+    fn node(kind: ExprKind<'_>) -> Expression<'_> {
+        Expression::new(kind, Span::default())
+    }
+
     // === Unit Expression Tests ===
 
     #[test]
     fn test_parse_constant() {
         let result = parse_expr("42");
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), Expression::Constant(42));
+        assert_eq!(result.unwrap().kind, ExprKind::Constant(42));
     }
 
     #[test]
     fn test_parse_variable() {
         let result = parse_expr("foo");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Variable(name) => assert_eq!(name, "foo"),
+        match result.unwrap().kind {
+            ExprKind::Variable(name) => assert_eq!(name, "foo"),
             _ => panic!("Expected variable"),
         }
     }
@@ -197,10 +310,10 @@ mod tests {
     fn test_parse_unary_negate() {
         let result = parse_expr("-5");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Unary { op, expr } => {
+        match result.unwrap().kind {
+            ExprKind::Unary { op, expr } => {
                 assert_eq!(op, UnaryOp::Negate);
-                assert_eq!(*expr, Expression::Constant(5));
+                assert_eq!(expr.kind, ExprKind::Constant(5));
             }
             _ => panic!("Expected unary negation"),
         }
@@ -210,9 +323,9 @@ mod tests {
     fn test_parse_grouped_expression() {
         let result = parse_expr("(42)");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Grouped(inner) => {
-                assert_eq!(*inner, Expression::Constant(42));
+        match result.unwrap().kind {
+            ExprKind::Grouped(inner) => {
+                assert_eq!(inner.kind, ExprKind::Constant(42));
             }
             _ => panic!("Expected grouped expression"),
         }
@@ -224,11 +337,11 @@ mod tests {
     fn test_parse_binary_addition() {
         let result = parse_expr("1 + 2");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Binary { op, lhs, rhs } => {
+        match result.unwrap().kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert_eq!(op, BinaryOp::Add);
-                assert_eq!(*lhs, Expression::Constant(1));
-                assert_eq!(*rhs, Expression::Constant(2));
+                assert_eq!(lhs.kind, ExprKind::Constant(1));
+                assert_eq!(rhs.kind, ExprKind::Constant(2));
             }
             _ => panic!("Expected binary addition"),
         }
@@ -238,11 +351,11 @@ mod tests {
     fn test_parse_binary_subtraction() {
         let result = parse_expr("5 - 3");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Binary { op, lhs, rhs } => {
+        match result.unwrap().kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert_eq!(op, BinaryOp::Subtract);
-                assert_eq!(*lhs, Expression::Constant(5));
-                assert_eq!(*rhs, Expression::Constant(3));
+                assert_eq!(lhs.kind, ExprKind::Constant(5));
+                assert_eq!(rhs.kind, ExprKind::Constant(3));
             }
             _ => panic!("Expected binary subtraction"),
         }
@@ -252,11 +365,11 @@ mod tests {
     fn test_parse_binary_multiplication() {
         let result = parse_expr("3 * 4");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Binary { op, lhs, rhs } => {
+        match result.unwrap().kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert_eq!(op, BinaryOp::Multiply);
-                assert_eq!(*lhs, Expression::Constant(3));
-                assert_eq!(*rhs, Expression::Constant(4));
+                assert_eq!(lhs.kind, ExprKind::Constant(3));
+                assert_eq!(rhs.kind, ExprKind::Constant(4));
             }
             _ => panic!("Expected binary multiplication"),
         }
@@ -266,11 +379,11 @@ mod tests {
     fn test_parse_binary_division() {
         let result = parse_expr("10 / 2");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Binary { op, lhs, rhs } => {
+        match result.unwrap().kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert_eq!(op, BinaryOp::Divide);
-                assert_eq!(*lhs, Expression::Constant(10));
-                assert_eq!(*rhs, Expression::Constant(2));
+                assert_eq!(lhs.kind, ExprKind::Constant(10));
+                assert_eq!(rhs.kind, ExprKind::Constant(2));
             }
             _ => panic!("Expected binary division"),
         }
@@ -283,15 +396,15 @@ mod tests {
         // 2 + 3 * 4 should be parsed as 2 + (3 * 4)
         let result = parse_expr("2 + 3 * 4");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Binary { op, lhs, rhs } => {
+        match result.unwrap().kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert_eq!(op, BinaryOp::Add);
-                assert_eq!(*lhs, Expression::Constant(2));
-                match *rhs {
-                    Expression::Binary { op, lhs, rhs } => {
+                assert_eq!(lhs.kind, ExprKind::Constant(2));
+                match rhs.kind {
+                    ExprKind::Binary { op, lhs, rhs } => {
                         assert_eq!(op, BinaryOp::Multiply);
-                        assert_eq!(*lhs, Expression::Constant(3));
-                        assert_eq!(*rhs, Expression::Constant(4));
+                        assert_eq!(lhs.kind, ExprKind::Constant(3));
+                        assert_eq!(rhs.kind, ExprKind::Constant(4));
                     }
                     _ => panic!("Expected multiplication on right side"),
                 }
@@ -305,15 +418,15 @@ mod tests {
         // 10 - 6 / 2 should be parsed as 10 - (6 / 2)
         let result = parse_expr("10 - 6 / 2");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Binary { op, lhs, rhs } => {
+        match result.unwrap().kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert_eq!(op, BinaryOp::Subtract);
-                assert_eq!(*lhs, Expression::Constant(10));
-                match *rhs {
-                    Expression::Binary { op, lhs, rhs } => {
+                assert_eq!(lhs.kind, ExprKind::Constant(10));
+                match rhs.kind {
+                    ExprKind::Binary { op, lhs, rhs } => {
                         assert_eq!(op, BinaryOp::Divide);
-                        assert_eq!(*lhs, Expression::Constant(6));
-                        assert_eq!(*rhs, Expression::Constant(2));
+                        assert_eq!(lhs.kind, ExprKind::Constant(6));
+                        assert_eq!(rhs.kind, ExprKind::Constant(2));
                     }
                     _ => panic!("Expected division on right side"),
                 }
@@ -327,15 +440,15 @@ mod tests {
         // 5 - 3 - 1 should be parsed as (5 - 3) - 1
         let result = parse_expr("5 - 3 - 1");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Binary { op, lhs, rhs } => {
+        match result.unwrap().kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert_eq!(op, BinaryOp::Subtract);
-                assert_eq!(*rhs, Expression::Constant(1));
-                match *lhs {
-                    Expression::Binary { op, lhs, rhs } => {
+                assert_eq!(rhs.kind, ExprKind::Constant(1));
+                match lhs.kind {
+                    ExprKind::Binary { op, lhs, rhs } => {
                         assert_eq!(op, BinaryOp::Subtract);
-                        assert_eq!(*lhs, Expression::Constant(5));
-                        assert_eq!(*rhs, Expression::Constant(3));
+                        assert_eq!(lhs.kind, ExprKind::Constant(5));
+                        assert_eq!(rhs.kind, ExprKind::Constant(3));
                     }
                     _ => panic!("Expected subtraction on left side"),
                 }
@@ -349,16 +462,16 @@ mod tests {
         // (2 + 3) * 4 should respect parentheses
         let result = parse_expr("(2 + 3) * 4");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Binary { op, lhs, rhs } => {
+        match result.unwrap().kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert_eq!(op, BinaryOp::Multiply);
-                assert_eq!(*rhs, Expression::Constant(4));
-                match *lhs {
-                    Expression::Grouped(inner) => match *inner {
-                        Expression::Binary { op, lhs, rhs } => {
+                assert_eq!(rhs.kind, ExprKind::Constant(4));
+                match lhs.kind {
+                    ExprKind::Grouped(inner) => match inner.kind {
+                        ExprKind::Binary { op, lhs, rhs } => {
                             assert_eq!(op, BinaryOp::Add);
-                            assert_eq!(*lhs, Expression::Constant(2));
-                            assert_eq!(*rhs, Expression::Constant(3));
+                            assert_eq!(lhs.kind, ExprKind::Constant(2));
+                            assert_eq!(rhs.kind, ExprKind::Constant(3));
                         }
                         _ => panic!("Expected addition inside grouped expression"),
                     },
@@ -374,14 +487,14 @@ mod tests {
         // -3 + 5 should be parsed as (-3) + 5
         let result = parse_expr("-3 + 5");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Binary { op, lhs, rhs } => {
+        match result.unwrap().kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert_eq!(op, BinaryOp::Add);
-                assert_eq!(*rhs, Expression::Constant(5));
-                match *lhs {
-                    Expression::Unary { op, expr } => {
+                assert_eq!(rhs.kind, ExprKind::Constant(5));
+                match lhs.kind {
+                    ExprKind::Unary { op, expr } => {
                         assert_eq!(op, UnaryOp::Negate);
-                        assert_eq!(*expr, Expression::Constant(3));
+                        assert_eq!(expr.kind, ExprKind::Constant(3));
                     }
                     _ => panic!("Expected unary negation on left side"),
                 }
@@ -396,11 +509,11 @@ mod tests {
     fn test_parse_less_than() {
         let result = parse_expr("1 < 2");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Binary { op, lhs, rhs } => {
+        match result.unwrap().kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert_eq!(op, BinaryOp::LessThan);
-                assert_eq!(*lhs, Expression::Constant(1));
-                assert_eq!(*rhs, Expression::Constant(2));
+                assert_eq!(lhs.kind, ExprKind::Constant(1));
+                assert_eq!(rhs.kind, ExprKind::Constant(2));
             }
             _ => panic!("Expected less than comparison"),
         }
@@ -410,11 +523,11 @@ mod tests {
     fn test_parse_greater_than() {
         let result = parse_expr("5 > 3");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Binary { op, lhs, rhs } => {
+        match result.unwrap().kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert_eq!(op, BinaryOp::GreaterThan);
-                assert_eq!(*lhs, Expression::Constant(5));
-                assert_eq!(*rhs, Expression::Constant(3));
+                assert_eq!(lhs.kind, ExprKind::Constant(5));
+                assert_eq!(rhs.kind, ExprKind::Constant(3));
             }
             _ => panic!("Expected greater than comparison"),
         }
@@ -424,11 +537,11 @@ mod tests {
     fn test_parse_less_than_or_equal() {
         let result = parse_expr("1 <= 2");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Binary { op, lhs, rhs } => {
+        match result.unwrap().kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert_eq!(op, BinaryOp::LessThanOrEqual);
-                assert_eq!(*lhs, Expression::Constant(1));
-                assert_eq!(*rhs, Expression::Constant(2));
+                assert_eq!(lhs.kind, ExprKind::Constant(1));
+                assert_eq!(rhs.kind, ExprKind::Constant(2));
             }
             _ => panic!("Expected less than or equal comparison"),
         }
@@ -438,11 +551,11 @@ mod tests {
     fn test_parse_greater_than_or_equal() {
         let result = parse_expr("5 >= 3");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Binary { op, lhs, rhs } => {
+        match result.unwrap().kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert_eq!(op, BinaryOp::GreaterThanOrEqual);
-                assert_eq!(*lhs, Expression::Constant(5));
-                assert_eq!(*rhs, Expression::Constant(3));
+                assert_eq!(lhs.kind, ExprKind::Constant(5));
+                assert_eq!(rhs.kind, ExprKind::Constant(3));
             }
             _ => panic!("Expected greater than or equal comparison"),
         }
@@ -452,11 +565,11 @@ mod tests {
     fn test_parse_equal_equal() {
         let result = parse_expr("1 == 1");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Binary { op, lhs, rhs } => {
+        match result.unwrap().kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert_eq!(op, BinaryOp::Equal);
-                assert_eq!(*lhs, Expression::Constant(1));
-                assert_eq!(*rhs, Expression::Constant(1));
+                assert_eq!(lhs.kind, ExprKind::Constant(1));
+                assert_eq!(rhs.kind, ExprKind::Constant(1));
             }
             _ => panic!("Expected equality comparison"),
         }
@@ -466,11 +579,11 @@ mod tests {
     fn test_parse_not_equal() {
         let result = parse_expr("1 != 2");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Binary { op, lhs, rhs } => {
+        match result.unwrap().kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert_eq!(op, BinaryOp::NotEqual);
-                assert_eq!(*lhs, Expression::Constant(1));
-                assert_eq!(*rhs, Expression::Constant(2));
+                assert_eq!(lhs.kind, ExprKind::Constant(1));
+                assert_eq!(rhs.kind, ExprKind::Constant(2));
             }
             _ => panic!("Expected not equal comparison"),
         }
@@ -481,19 +594,19 @@ mod tests {
         // 1 + 2 < 3 * 4 should be parsed as (1 + 2) < (3 * 4)
         let result = parse_expr("1 + 2 < 3 * 4");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Binary { op, lhs, rhs } => {
+        match result.unwrap().kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert_eq!(op, BinaryOp::LessThan);
                 // Left side should be (1 + 2)
-                match *lhs {
-                    Expression::Binary { op, .. } => {
+                match lhs.kind {
+                    ExprKind::Binary { op, .. } => {
                         assert_eq!(op, BinaryOp::Add);
                     }
                     _ => panic!("Expected addition on left side"),
                 }
                 // Right side should be (3 * 4)
-                match *rhs {
-                    Expression::Binary { op, .. } => {
+                match rhs.kind {
+                    ExprKind::Binary { op, .. } => {
                         assert_eq!(op, BinaryOp::Multiply);
                     }
                     _ => panic!("Expected multiplication on right side"),
@@ -508,23 +621,23 @@ mod tests {
         // Test that 1 < 2 == 3 < 4 parses as (1 < 2) == (3 < 4)
         let result = parse_expr("1 < 2 == 3 < 4");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Binary { op, lhs, rhs } => {
+        match result.unwrap().kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert_eq!(op, BinaryOp::Equal);
                 assert_eq!(
-                    *lhs,
-                    Expression::Binary {
+                    lhs.kind,
+                    ExprKind::Binary {
                         op: BinaryOp::LessThan,
-                        lhs: Box::new(Expression::Constant(1)),
-                        rhs: Box::new(Expression::Constant(2)),
+                        lhs: Box::new(node(ExprKind::Constant(1))),
+                        rhs: Box::new(node(ExprKind::Constant(2))),
                     }
                 );
                 assert_eq!(
-                    *rhs,
-                    Expression::Binary {
+                    rhs.kind,
+                    ExprKind::Binary {
                         op: BinaryOp::LessThan,
-                        lhs: Box::new(Expression::Constant(3)),
-                        rhs: Box::new(Expression::Constant(4)),
+                        lhs: Box::new(node(ExprKind::Constant(3))),
+                        rhs: Box::new(node(ExprKind::Constant(4))),
                     }
                 );
             }
@@ -534,6 +647,305 @@ mod tests {
 
     // === Error Tests ===
 
+    // === Bitwise, Shift and Remainder Tests ===
+
+    fn binary<'a>(op: BinaryOp, lhs: Expression<'a>, rhs: Expression<'a>) -> Expression<'a> {
+        node(ExprKind::Binary {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        })
+    }
+
+    fn var(name: &str) -> Expression<'_> {
+        node(ExprKind::Variable(name.into()))
+    }
+
+    #[test]
+    fn test_parse_remainder_with_multiplicative_precedence() {
+        // a % b * c should be parsed as (a % b) * c
+        assert_eq!(
+            parse_expr("a % b * c").unwrap(),
+            binary(
+                BinaryOp::Multiply,
+                binary(BinaryOp::Remainder, var("a"), var("b")),
+                var("c")
+            )
+        );
+    }
+
+    #[test]
+    fn test_shift_between_additive_and_relational() {
+        // a << b + 1 < c should be parsed as (a << (b + 1)) < c
+        assert_eq!(
+            parse_expr("a << b + 1 < c").unwrap(),
+            binary(
+                BinaryOp::LessThan,
+                binary(
+                    BinaryOp::LeftShift,
+                    var("a"),
+                    binary(BinaryOp::Add, var("b"), node(ExprKind::Constant(1)))
+                ),
+                var("c")
+            )
+        );
+        // Shifts are left-associative
+        assert_eq!(
+            parse_expr("a >> b >> c").unwrap(),
+            binary(
+                BinaryOp::RightShift,
+                binary(BinaryOp::RightShift, var("a"), var("b")),
+                var("c")
+            )
+        );
+    }
+
+    #[test]
+    fn test_bitwise_precedence() {
+        // a | b ^ c & d == e should be parsed as a | (b ^ (c & (d == e)))
+        assert_eq!(
+            parse_expr("a | b ^ c & d == e").unwrap(),
+            binary(
+                BinaryOp::BitwiseOr,
+                var("a"),
+                binary(
+                    BinaryOp::BitwiseXor,
+                    var("b"),
+                    binary(
+                        BinaryOp::BitwiseAnd,
+                        var("c"),
+                        binary(BinaryOp::Equal, var("d"), var("e"))
+                    )
+                )
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_complement() {
+        assert_eq!(
+            parse_expr("~a & 3").unwrap(),
+            binary(
+                BinaryOp::BitwiseAnd,
+                node(ExprKind::Unary {
+                    op: UnaryOp::Complement,
+                    expr: Box::new(var("a")),
+                }),
+                node(ExprKind::Constant(3))
+            )
+        );
+    }
+
+    fn logical<'a>(op: LogicalOp, lhs: Expression<'a>, rhs: Expression<'a>) -> Expression<'a> {
+        node(ExprKind::Logical {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        })
+    }
+
+    #[test]
+    fn test_logical_and_binds_tighter_than_or() {
+        // a || b && c | d should be parsed as a || (b && (c | d))
+        assert_eq!(
+            parse_expr("a || b && c | d").unwrap(),
+            logical(
+                LogicalOp::Or,
+                var("a"),
+                logical(
+                    LogicalOp::And,
+                    var("b"),
+                    binary(BinaryOp::BitwiseOr, var("c"), var("d"))
+                )
+            )
+        );
+    }
+
+    #[test]
+    fn test_logical_operators_are_left_associative() {
+        assert_eq!(
+            parse_expr("a && b && c").unwrap(),
+            logical(
+                LogicalOp::And,
+                logical(LogicalOp::And, var("a"), var("b")),
+                var("c")
+            )
+        );
+        // Assignment binds looser
+        assert!(matches!(
+            parse_expr("x = a || b").unwrap().kind,
+            ExprKind::Assignment { .. }
+        ));
+    }
+
+    fn conditional<'a>(
+        cond: Expression<'a>,
+        then_expr: Expression<'a>,
+        else_expr: Expression<'a>,
+    ) -> Expression<'a> {
+        node(ExprKind::Conditional {
+            cond: Box::new(cond),
+            then_expr: Box::new(then_expr),
+            else_expr: Box::new(else_expr),
+        })
+    }
+
+    #[test]
+    fn test_conditional_is_right_associative() {
+        // a ? b : c ? d : e should be parsed as a ? b : (c ? d : e)
+        assert_eq!(
+            parse_expr("a ? b : c ? d : e").unwrap(),
+            conditional(
+                var("a"),
+                var("b"),
+                conditional(var("c"), var("d"), var("e"))
+            )
+        );
+        // and a ? b ? c : d : e as a ? (b ? c : d) : e
+        assert_eq!(
+            parse_expr("a ? b ? c : d : e").unwrap(),
+            conditional(
+                var("a"),
+                conditional(var("b"), var("c"), var("d")),
+                var("e")
+            )
+        );
+    }
+
+    #[test]
+    fn test_conditional_precedence() {
+        // x = a || b ? c : d should be parsed as x = ((a || b) ? c : d)
+        assert_eq!(
+            parse_expr("x = a || b ? c : d").unwrap(),
+            node(ExprKind::Assignment {
+                op: AssignOp::Assign,
+                lhs: Box::new(var("x")),
+                rhs: Box::new(conditional(
+                    logical(LogicalOp::Or, var("a"), var("b")),
+                    var("c"),
+                    var("d")
+                )),
+            })
+        );
+        // The middle operand can be an assignment
+        assert_eq!(
+            parse_expr("a ? x = 1 : 2").unwrap(),
+            conditional(
+                var("a"),
+                node(ExprKind::Assignment {
+                    op: AssignOp::Assign,
+                    lhs: Box::new(var("x")),
+                    rhs: Box::new(node(ExprKind::Constant(1))),
+                }),
+                node(ExprKind::Constant(2))
+            )
+        );
+    }
+
+    #[test]
+    fn test_error_conditional_without_colon() {
+        assert!(parse_expr("a ? b").is_err());
+        assert!(parse_expr("a ? b ; c").is_err());
+    }
+
+    fn comma<'a>(lhs: Expression<'a>, rhs: Expression<'a>) -> Expression<'a> {
+        node(ExprKind::Comma {
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        })
+    }
+
+    #[test]
+    fn test_comma_is_left_associative_and_binds_loosest() {
+        // a = 1, b, c ? d : e should be parsed as ((a = 1), b), (c ? d : e)
+        assert_eq!(
+            parse_expr("a = 1, b, c ? d : e").unwrap(),
+            comma(
+                comma(
+                    node(ExprKind::Assignment {
+                        op: AssignOp::Assign,
+                        lhs: Box::new(var("a")),
+                        rhs: Box::new(node(ExprKind::Constant(1))),
+                    }),
+                    var("b")
+                ),
+                conditional(var("c"), var("d"), var("e"))
+            )
+        );
+    }
+
+    #[test]
+    fn test_commas_separate_call_arguments() {
+        assert_eq!(
+            parse_expr("f(a, (b, c))").unwrap(),
+            node(ExprKind::FunctionCall {
+                callee: Box::new(var("f")),
+                args: vec![
+                    var("a"),
+                    node(ExprKind::Grouped(Box::new(comma(var("b"), var("c")))))
+                ],
+            })
+        );
+    }
+
+    fn inc_dec(op: IncDecOp, expr: Expression<'_>) -> Expression<'_> {
+        node(ExprKind::IncDec {
+            op,
+            expr: Box::new(expr),
+        })
+    }
+
+    #[test]
+    fn test_parse_prefix_and_postfix_inc_dec() {
+        assert_eq!(
+            parse_expr("++x").unwrap(),
+            inc_dec(IncDecOp::PreIncrement, var("x"))
+        );
+        assert_eq!(
+            parse_expr("x--").unwrap(),
+            inc_dec(IncDecOp::PostDecrement, var("x"))
+        );
+        // -x++ should be parsed as -(x++), and a++ + --b as (a++) + (--b)
+        assert_eq!(
+            parse_expr("-x++").unwrap(),
+            node(ExprKind::Unary {
+                op: UnaryOp::Negate,
+                expr: Box::new(inc_dec(IncDecOp::PostIncrement, var("x"))),
+            })
+        );
+        assert_eq!(
+            parse_expr("a++ + --b").unwrap(),
+            binary(
+                BinaryOp::Add,
+                inc_dec(IncDecOp::PostIncrement, var("a")),
+                inc_dec(IncDecOp::PreDecrement, var("b"))
+            )
+        );
+        assert_eq!(
+            parse_expr("(x)++").unwrap(),
+            inc_dec(
+                IncDecOp::PostIncrement,
+                node(ExprKind::Grouped(Box::new(var("x"))))
+            )
+        );
+    }
+
+    #[test]
+    fn test_error_inc_dec_of_non_lvalue() {
+        for input in ["++1", "x++ ++", "--(a + b)", "++x++", "f()--", "(x = 1)++"] {
+            let err = parse_expr(input).unwrap_err();
+            assert!(
+                matches!(err.error, ParseError::InvalidLvalue(_)),
+                "{}: {:?}",
+                input,
+                err
+            );
+        }
+        let err = parse_expr("++(a + b)").unwrap_err();
+        assert_eq!(err.error, ParseError::InvalidLvalue("'++'".into()));
+        assert_eq!(err.span.column, 3);
+    }
+
     #[test]
     fn test_error_unexpected_token() {
         let result = parse_expr("+");
@@ -552,24 +964,46 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_expressions_span_their_source() {
+        let input = "x = -(a + 1) * f(b++, c)";
+        let source = |span: Span| &input[span.start..span.end];
+        let expr = parse_spanned(input).unwrap();
+        assert_eq!(source(expr.span), input);
+        let ExprKind::Assignment { rhs, .. } = expr.kind else {
+            panic!("Expected assignment expression");
+        };
+        assert_eq!(source(rhs.span), "-(a + 1) * f(b++, c)");
+        let ExprKind::Binary { lhs, rhs, .. } = rhs.kind else {
+            panic!("Expected binary expression");
+        };
+        assert_eq!(source(lhs.span), "-(a + 1)");
+        assert_eq!(source(rhs.span), "f(b++, c)");
+        let ExprKind::FunctionCall { args, .. } = rhs.kind else {
+            panic!("Expected function call expression");
+        };
+        assert_eq!(source(args[0].span), "b++");
+        assert_eq!(args[1].span.column, 23);
+    }
+
     // === Assignment Expression Tests ===
 
     #[test]
     fn test_parse_simple_assignment() {
         let result = parse_expr("x = 5");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Assignment {
+        match result.unwrap().kind {
+            ExprKind::Assignment {
                 op,
                 lhs: lvalue,
                 rhs: rvalue,
             } => {
                 assert_eq!(op, AssignOp::Assign);
-                match *lvalue {
-                    Expression::Variable(name) => assert_eq!(name, "x"),
+                match lvalue.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "x"),
                     _ => panic!("Expected variable on left side"),
                 }
-                assert_eq!(*rvalue, Expression::Constant(5));
+                assert_eq!(rvalue.kind, ExprKind::Constant(5));
             }
             _ => panic!("Expected assignment expression"),
         }
@@ -580,31 +1014,31 @@ mod tests {
         // a = b = c should be parsed as a = (b = c)
         let result = parse_expr("a = b = c");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Assignment {
+        match result.unwrap().kind {
+            ExprKind::Assignment {
                 op,
                 lhs: lvalue,
                 rhs: rvalue,
             } => {
                 assert_eq!(op, AssignOp::Assign);
-                match *lvalue {
-                    Expression::Variable(name) => assert_eq!(name, "a"),
+                match lvalue.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "a"),
                     _ => panic!("Expected variable 'a' on left side"),
                 }
                 // Right side should be another assignment (b = c)
-                match *rvalue {
-                    Expression::Assignment {
+                match rvalue.kind {
+                    ExprKind::Assignment {
                         op,
                         lhs: lvalue,
                         rhs: rvalue,
                     } => {
                         assert_eq!(op, AssignOp::Assign);
-                        match *lvalue {
-                            Expression::Variable(name) => assert_eq!(name, "b"),
+                        match lvalue.kind {
+                            ExprKind::Variable(name) => assert_eq!(name, "b"),
                             _ => panic!("Expected variable 'b'"),
                         }
-                        match *rvalue {
-                            Expression::Variable(name) => assert_eq!(name, "c"),
+                        match rvalue.kind {
+                            ExprKind::Variable(name) => assert_eq!(name, "c"),
                             _ => panic!("Expected variable 'c'"),
                         }
                     }
@@ -620,20 +1054,20 @@ mod tests {
         // x = 1 + 2 * 3 should be parsed as x = (1 + (2 * 3))
         let result = parse_expr("x = 1 + 2 * 3");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Assignment {
+        match result.unwrap().kind {
+            ExprKind::Assignment {
                 op,
                 lhs: lvalue,
                 rhs: rvalue,
             } => {
                 assert_eq!(op, AssignOp::Assign);
-                match *lvalue {
-                    Expression::Variable(name) => assert_eq!(name, "x"),
+                match lvalue.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "x"),
                     _ => panic!("Expected variable on left side"),
                 }
                 // Right side should be a binary expression
-                match *rvalue {
-                    Expression::Binary { op, .. } => {
+                match rvalue.kind {
+                    ExprKind::Binary { op, .. } => {
                         assert_eq!(op, BinaryOp::Add);
                     }
                     _ => panic!("Expected binary expression on right side"),
@@ -647,18 +1081,18 @@ mod tests {
     fn test_parse_plus_assign() {
         let result = parse_expr("x += 5");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Assignment {
+        match result.unwrap().kind {
+            ExprKind::Assignment {
                 op,
                 lhs: lvalue,
                 rhs: rvalue,
             } => {
                 assert_eq!(op, AssignOp::PlusAssign);
-                match *lvalue {
-                    Expression::Variable(name) => assert_eq!(name, "x"),
+                match lvalue.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "x"),
                     _ => panic!("Expected variable on left side"),
                 }
-                assert_eq!(*rvalue, Expression::Constant(5));
+                assert_eq!(rvalue.kind, ExprKind::Constant(5));
             }
             _ => panic!("Expected assignment expression"),
         }
@@ -668,18 +1102,18 @@ mod tests {
     fn test_parse_minus_assign() {
         let result = parse_expr("y -= 10");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Assignment {
+        match result.unwrap().kind {
+            ExprKind::Assignment {
                 op,
                 lhs: lvalue,
                 rhs: rvalue,
             } => {
                 assert_eq!(op, AssignOp::MinusAssign);
-                match *lvalue {
-                    Expression::Variable(name) => assert_eq!(name, "y"),
+                match lvalue.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "y"),
                     _ => panic!("Expected variable on left side"),
                 }
-                assert_eq!(*rvalue, Expression::Constant(10));
+                assert_eq!(rvalue.kind, ExprKind::Constant(10));
             }
             _ => panic!("Expected assignment expression"),
         }
@@ -689,18 +1123,18 @@ mod tests {
     fn test_parse_mul_assign() {
         let result = parse_expr("z *= 3");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Assignment {
+        match result.unwrap().kind {
+            ExprKind::Assignment {
                 op,
                 lhs: lvalue,
                 rhs: rvalue,
             } => {
                 assert_eq!(op, AssignOp::MulAssign);
-                match *lvalue {
-                    Expression::Variable(name) => assert_eq!(name, "z"),
+                match lvalue.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "z"),
                     _ => panic!("Expected variable on left side"),
                 }
-                assert_eq!(*rvalue, Expression::Constant(3));
+                assert_eq!(rvalue.kind, ExprKind::Constant(3));
             }
             _ => panic!("Expected assignment expression"),
         }
@@ -710,18 +1144,18 @@ mod tests {
     fn test_parse_div_assign() {
         let result = parse_expr("a /= 2");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Assignment {
+        match result.unwrap().kind {
+            ExprKind::Assignment {
                 op,
                 lhs: lvalue,
                 rhs: rvalue,
             } => {
                 assert_eq!(op, AssignOp::DivAssign);
-                match *lvalue {
-                    Expression::Variable(name) => assert_eq!(name, "a"),
+                match lvalue.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "a"),
                     _ => panic!("Expected variable on left side"),
                 }
-                assert_eq!(*rvalue, Expression::Constant(2));
+                assert_eq!(rvalue.kind, ExprKind::Constant(2));
             }
             _ => panic!("Expected assignment expression"),
         }
@@ -731,18 +1165,18 @@ mod tests {
     fn test_parse_mod_assign() {
         let result = parse_expr("b %= 5");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Assignment {
+        match result.unwrap().kind {
+            ExprKind::Assignment {
                 op,
                 lhs: lvalue,
                 rhs: rvalue,
             } => {
                 assert_eq!(op, AssignOp::ModAssign);
-                match *lvalue {
-                    Expression::Variable(name) => assert_eq!(name, "b"),
+                match lvalue.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "b"),
                     _ => panic!("Expected variable on left side"),
                 }
-                assert_eq!(*rvalue, Expression::Constant(5));
+                assert_eq!(rvalue.kind, ExprKind::Constant(5));
             }
             _ => panic!("Expected assignment expression"),
         }
@@ -752,18 +1186,18 @@ mod tests {
     fn test_parse_and_assign() {
         let result = parse_expr("c &= 7");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Assignment {
+        match result.unwrap().kind {
+            ExprKind::Assignment {
                 op,
                 lhs: lvalue,
                 rhs: rvalue,
             } => {
                 assert_eq!(op, AssignOp::AndAssign);
-                match *lvalue {
-                    Expression::Variable(name) => assert_eq!(name, "c"),
+                match lvalue.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "c"),
                     _ => panic!("Expected variable on left side"),
                 }
-                assert_eq!(*rvalue, Expression::Constant(7));
+                assert_eq!(rvalue.kind, ExprKind::Constant(7));
             }
             _ => panic!("Expected assignment expression"),
         }
@@ -773,18 +1207,18 @@ mod tests {
     fn test_parse_or_assign() {
         let result = parse_expr("d |= 8");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Assignment {
+        match result.unwrap().kind {
+            ExprKind::Assignment {
                 op,
                 lhs: lvalue,
                 rhs: rvalue,
             } => {
                 assert_eq!(op, AssignOp::OrAssign);
-                match *lvalue {
-                    Expression::Variable(name) => assert_eq!(name, "d"),
+                match lvalue.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "d"),
                     _ => panic!("Expected variable on left side"),
                 }
-                assert_eq!(*rvalue, Expression::Constant(8));
+                assert_eq!(rvalue.kind, ExprKind::Constant(8));
             }
             _ => panic!("Expected assignment expression"),
         }
@@ -794,18 +1228,18 @@ mod tests {
     fn test_parse_xor_assign() {
         let result = parse_expr("e ^= 9");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Assignment {
+        match result.unwrap().kind {
+            ExprKind::Assignment {
                 op,
                 lhs: lvalue,
                 rhs: rvalue,
             } => {
                 assert_eq!(op, AssignOp::XorAssign);
-                match *lvalue {
-                    Expression::Variable(name) => assert_eq!(name, "e"),
+                match lvalue.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "e"),
                     _ => panic!("Expected variable on left side"),
                 }
-                assert_eq!(*rvalue, Expression::Constant(9));
+                assert_eq!(rvalue.kind, ExprKind::Constant(9));
             }
             _ => panic!("Expected assignment expression"),
         }
@@ -815,18 +1249,18 @@ mod tests {
     fn test_parse_left_shift_assign() {
         let result = parse_expr("f <<= 2");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Assignment {
+        match result.unwrap().kind {
+            ExprKind::Assignment {
                 op,
                 lhs: lvalue,
                 rhs: rvalue,
             } => {
                 assert_eq!(op, AssignOp::LShiftAssign);
-                match *lvalue {
-                    Expression::Variable(name) => assert_eq!(name, "f"),
+                match lvalue.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "f"),
                     _ => panic!("Expected variable on left side"),
                 }
-                assert_eq!(*rvalue, Expression::Constant(2));
+                assert_eq!(rvalue.kind, ExprKind::Constant(2));
             }
             _ => panic!("Expected assignment expression"),
         }
@@ -836,42 +1270,76 @@ mod tests {
     fn test_parse_right_shift_assign() {
         let result = parse_expr("g >>= 3");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Assignment {
+        match result.unwrap().kind {
+            ExprKind::Assignment {
                 op,
                 lhs: lvalue,
                 rhs: rvalue,
             } => {
                 assert_eq!(op, AssignOp::RShiftAssign);
-                match *lvalue {
-                    Expression::Variable(name) => assert_eq!(name, "g"),
+                match lvalue.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "g"),
                     _ => panic!("Expected variable on left side"),
                 }
-                assert_eq!(*rvalue, Expression::Constant(3));
+                assert_eq!(rvalue.kind, ExprKind::Constant(3));
             }
             _ => panic!("Expected assignment expression"),
         }
     }
 
+    #[test]
+    fn test_error_assignment_to_non_lvalue() {
+        for input in [
+            "1 = 2",
+            "(a + b) += 3",
+            "-a = 1",
+            "f() = 1",
+            "a = 1 = 2",
+            "a ? b : c = 1",
+        ] {
+            let err = parse_expr(input).unwrap_err();
+            assert!(
+                matches!(err.error, ParseError::InvalidLvalue(_)),
+                "{}: {:?}",
+                input,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_invalid_lvalue_spans_the_target() {
+        let err = parse_expr("x + (a + b) *= 3").unwrap_err();
+        assert_eq!(err.error, ParseError::InvalidLvalue("'*='".into()));
+        assert_eq!(err.span.column, 1);
+        assert_eq!(err.span.end - err.span.start, 11);
+    }
+
+    #[test]
+    fn test_parse_assignment_to_parenthesized_variable() {
+        let result = parse_expr("(a) = 1").unwrap();
+        assert!(matches!(result.kind, ExprKind::Assignment { .. }));
+    }
+
     #[test]
     fn test_assignment_lower_precedence_than_comparison() {
         // x = 1 < 2 should be parsed as x = (1 < 2)
         let result = parse_expr("x = 1 < 2");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Assignment {
+        match result.unwrap().kind {
+            ExprKind::Assignment {
                 op,
                 lhs: lvalue,
                 rhs: rvalue,
             } => {
                 assert_eq!(op, AssignOp::Assign);
-                match *lvalue {
-                    Expression::Variable(name) => assert_eq!(name, "x"),
+                match lvalue.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "x"),
                     _ => panic!("Expected variable on left side"),
                 }
                 // Right side should be a comparison
-                match *rvalue {
-                    Expression::Binary { op, .. } => {
+                match rvalue.kind {
+                    ExprKind::Binary { op, .. } => {
                         assert_eq!(op, BinaryOp::LessThan);
                     }
                     _ => panic!("Expected comparison on right side"),
@@ -887,10 +1355,10 @@ mod tests {
     fn test_parse_function_call_no_args() {
         let result = parse_expr("foo()");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::FunctionCall { callee, args } => {
-                match *callee {
-                    Expression::Variable(name) => assert_eq!(name, "foo"),
+        match result.unwrap().kind {
+            ExprKind::FunctionCall { callee, args } => {
+                match callee.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "foo"),
                     _ => panic!("Expected function name"),
                 }
                 assert_eq!(args.len(), 0);
@@ -903,14 +1371,14 @@ mod tests {
     fn test_parse_function_call_single_arg() {
         let result = parse_expr("bar(42)");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::FunctionCall { callee, args } => {
-                match *callee {
-                    Expression::Variable(name) => assert_eq!(name, "bar"),
+        match result.unwrap().kind {
+            ExprKind::FunctionCall { callee, args } => {
+                match callee.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "bar"),
                     _ => panic!("Expected function name"),
                 }
                 assert_eq!(args.len(), 1);
-                assert_eq!(args[0], Expression::Constant(42));
+                assert_eq!(args[0].kind, ExprKind::Constant(42));
             }
             _ => panic!("Expected function call expression"),
         }
@@ -920,16 +1388,16 @@ mod tests {
     fn test_parse_function_call_multiple_args() {
         let result = parse_expr("add(1, 2, 3)");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::FunctionCall { callee, args } => {
-                match *callee {
-                    Expression::Variable(name) => assert_eq!(name, "add"),
+        match result.unwrap().kind {
+            ExprKind::FunctionCall { callee, args } => {
+                match callee.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "add"),
                     _ => panic!("Expected function name"),
                 }
                 assert_eq!(args.len(), 3);
-                assert_eq!(args[0], Expression::Constant(1));
-                assert_eq!(args[1], Expression::Constant(2));
-                assert_eq!(args[2], Expression::Constant(3));
+                assert_eq!(args[0].kind, ExprKind::Constant(1));
+                assert_eq!(args[1].kind, ExprKind::Constant(2));
+                assert_eq!(args[2].kind, ExprKind::Constant(3));
             }
             _ => panic!("Expected function call expression"),
         }
@@ -939,31 +1407,31 @@ mod tests {
     fn test_parse_function_call_with_expression_args() {
         let result = parse_expr("calculate(1 + 2, x * 3)");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::FunctionCall { callee, args } => {
-                match *callee {
-                    Expression::Variable(name) => assert_eq!(name, "calculate"),
+        match result.unwrap().kind {
+            ExprKind::FunctionCall { callee, args } => {
+                match callee.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "calculate"),
                     _ => panic!("Expected function name"),
                 }
                 assert_eq!(args.len(), 2);
                 // First argument: 1 + 2
-                match &args[0] {
-                    Expression::Binary { op, lhs, rhs } => {
+                match &args[0].kind {
+                    ExprKind::Binary { op, lhs, rhs } => {
                         assert_eq!(op, &BinaryOp::Add);
-                        assert_eq!(**lhs, Expression::Constant(1));
-                        assert_eq!(**rhs, Expression::Constant(2));
+                        assert_eq!(lhs.kind, ExprKind::Constant(1));
+                        assert_eq!(rhs.kind, ExprKind::Constant(2));
                     }
                     _ => panic!("Expected binary expression as first argument"),
                 }
                 // Second argument: x * 3
-                match &args[1] {
-                    Expression::Binary { op, lhs, rhs } => {
+                match &args[1].kind {
+                    ExprKind::Binary { op, lhs, rhs } => {
                         assert_eq!(op, &BinaryOp::Multiply);
-                        match &**lhs {
-                            Expression::Variable(name) => assert_eq!(name, "x"),
+                        match &lhs.kind {
+                            ExprKind::Variable(name) => assert_eq!(name, "x"),
                             _ => panic!("Expected variable in second argument"),
                         }
-                        assert_eq!(**rhs, Expression::Constant(3));
+                        assert_eq!(rhs.kind, ExprKind::Constant(3));
                     }
                     _ => panic!("Expected binary expression as second argument"),
                 }
@@ -977,22 +1445,22 @@ mod tests {
         // outer(inner(5))
         let result = parse_expr("outer(inner(5))");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::FunctionCall { callee, args } => {
-                match *callee {
-                    Expression::Variable(name) => assert_eq!(name, "outer"),
+        match result.unwrap().kind {
+            ExprKind::FunctionCall { callee, args } => {
+                match callee.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "outer"),
                     _ => panic!("Expected outer function name"),
                 }
                 assert_eq!(args.len(), 1);
                 // Argument should be inner(5)
-                match &args[0] {
-                    Expression::FunctionCall { callee, args } => {
-                        match &**callee {
-                            Expression::Variable(name) => assert_eq!(name, "inner"),
+                match &args[0].kind {
+                    ExprKind::FunctionCall { callee, args } => {
+                        match &callee.kind {
+                            ExprKind::Variable(name) => assert_eq!(name, "inner"),
                             _ => panic!("Expected inner function name"),
                         }
                         assert_eq!(args.len(), 1);
-                        assert_eq!(args[0], Expression::Constant(5));
+                        assert_eq!(args[0].kind, ExprKind::Constant(5));
                     }
                     _ => panic!("Expected nested function call"),
                 }
@@ -1006,30 +1474,30 @@ mod tests {
         // foo(1) + bar(2) should be parsed as (foo(1)) + (bar(2))
         let result = parse_expr("foo(1) + bar(2)");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Binary { op, lhs, rhs } => {
+        match result.unwrap().kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert_eq!(op, BinaryOp::Add);
                 // Left side: foo(1)
-                match *lhs {
-                    Expression::FunctionCall { callee, args } => {
-                        match *callee {
-                            Expression::Variable(name) => assert_eq!(name, "foo"),
+                match lhs.kind {
+                    ExprKind::FunctionCall { callee, args } => {
+                        match callee.kind {
+                            ExprKind::Variable(name) => assert_eq!(name, "foo"),
                             _ => panic!("Expected foo function"),
                         }
                         assert_eq!(args.len(), 1);
-                        assert_eq!(args[0], Expression::Constant(1));
+                        assert_eq!(args[0].kind, ExprKind::Constant(1));
                     }
                     _ => panic!("Expected function call on left side"),
                 }
                 // Right side: bar(2)
-                match *rhs {
-                    Expression::FunctionCall { callee, args } => {
-                        match *callee {
-                            Expression::Variable(name) => assert_eq!(name, "bar"),
+                match rhs.kind {
+                    ExprKind::FunctionCall { callee, args } => {
+                        match callee.kind {
+                            ExprKind::Variable(name) => assert_eq!(name, "bar"),
                             _ => panic!("Expected bar function"),
                         }
                         assert_eq!(args.len(), 1);
-                        assert_eq!(args[0], Expression::Constant(2));
+                        assert_eq!(args[0].kind, ExprKind::Constant(2));
                     }
                     _ => panic!("Expected function call on right side"),
                 }
@@ -1043,26 +1511,26 @@ mod tests {
         // x = getValue(10) should be parsed as x = (getValue(10))
         let result = parse_expr("x = getValue(10)");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Assignment {
+        match result.unwrap().kind {
+            ExprKind::Assignment {
                 op,
                 lhs: lvalue,
                 rhs: rvalue,
             } => {
                 assert_eq!(op, AssignOp::Assign);
-                match *lvalue {
-                    Expression::Variable(name) => assert_eq!(name, "x"),
+                match lvalue.kind {
+                    ExprKind::Variable(name) => assert_eq!(name, "x"),
                     _ => panic!("Expected variable on left side"),
                 }
                 // Right side should be getValue(10)
-                match *rvalue {
-                    Expression::FunctionCall { callee, args } => {
-                        match *callee {
-                            Expression::Variable(name) => assert_eq!(name, "getValue"),
+                match rvalue.kind {
+                    ExprKind::FunctionCall { callee, args } => {
+                        match callee.kind {
+                            ExprKind::Variable(name) => assert_eq!(name, "getValue"),
                             _ => panic!("Expected getValue function"),
                         }
                         assert_eq!(args.len(), 1);
-                        assert_eq!(args[0], Expression::Constant(10));
+                        assert_eq!(args[0].kind, ExprKind::Constant(10));
                     }
                     _ => panic!("Expected function call on right side"),
                 }
@@ -1083,23 +1551,23 @@ mod tests {
         // should be parsed as !(foo(1, !2))
         let result = parse_expr("!foo(1, !2)");
         assert!(result.is_ok());
-        match result.unwrap() {
-            Expression::Unary {
+        match result.unwrap().kind {
+            ExprKind::Unary {
                 op: UnaryOp::Not,
                 expr,
-            } => match *expr {
-                Expression::FunctionCall { callee, args } => {
-                    match *callee {
-                        Expression::Variable(name) => assert_eq!(name, "foo"),
+            } => match expr.kind {
+                ExprKind::FunctionCall { callee, args } => {
+                    match callee.kind {
+                        ExprKind::Variable(name) => assert_eq!(name, "foo"),
                         _ => panic!("Expected function name"),
                     }
                     assert_eq!(args.len(), 2);
-                    assert_eq!(args[0], Expression::Constant(1));
+                    assert_eq!(args[0].kind, ExprKind::Constant(1));
                     assert_eq!(
-                        &args[1],
-                        &Expression::Unary {
+                        args[1].kind,
+                        ExprKind::Unary {
                             op: UnaryOp::Not,
-                            expr: Box::new(Expression::Constant(2)),
+                            expr: Box::new(node(ExprKind::Constant(2))),
                         }
                     );
                 }
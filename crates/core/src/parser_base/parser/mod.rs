@@ -5,7 +5,7 @@ mod util;
 use std::{borrow::Cow, iter::Peekable};
 
 use crate::{
-    error::IntoCompilerError,
+    error::{CompilerError, IntoCompilerError},
     grammar::*,
     lexer_base::Lexer,
     parser_base::{ParseError, error::ParseResult},
@@ -16,6 +16,8 @@ use crate::{
 pub struct Parser<'a> {
     lexer: Peekable<Lexer<'a>>,
     eof_span: Span,
+    /// Span of the most recently consumed token
+    last_span: Span,
 }
 
 impl<'a> Parser<'a> {
@@ -24,6 +26,7 @@ impl<'a> Parser<'a> {
         Self {
             lexer: lexer.peekable(),
             eof_span,
+            last_span: Span::default(),
         }
     }
 
@@ -46,17 +49,23 @@ impl<'a> Parser<'a> {
         Ok(Program { functions })
     }
 
-    /// Parse a function definition: int name(void) { ... }
+    /// Parse a function definition, `int name(void) { ... }`, or
+    /// declaration, `int name(void);`
     fn parse_function(&mut self) -> ParseResult<FuncDef<'a>> {
+        let start = self.peek_span()?;
         let (return_type, name) = self.parse_typed_identifier()?;
         let params = self.parse_function_parameters()?;
-        let body = self.parse_block_statement()?;
+        let body = match self.eat(t!(";"))? {
+            true => None,
+            false => Some(self.parse_block_statement()?),
+        };
 
         Ok(FuncDef {
             return_type,
             name,
             params,
             body,
+            span: self.span_from(start),
         })
     }
 
@@ -82,8 +91,20 @@ impl<'a> Parser<'a> {
             Some(Token {
                 kind: t!("break"), ..
             }) => self.parse_break_statement().map(Into::into),
+            // Outside a block, a declaration is the body of an `if`, a loop
+            // or a label. Several declarators are grouped in a block of
+            // their own.
             Some(Token { kind, .. }) if Type::from_token_type(&kind).is_some() => {
-                self.parse_declaration_statement().map(Into::into)
+                let start = self.peek_span()?;
+                let mut declarations = self.parse_declaration_statement()?;
+                match declarations.len() {
+                    1 => Ok(declarations.remove(0).into()),
+                    _ => Ok(BlockStmt {
+                        statements: declarations.into_iter().map(Into::into).collect(),
+                        span: self.span_from(start),
+                    }
+                    .into()),
+                }
             }
             Some(Token {
                 kind: t!("continue"),
@@ -93,6 +114,9 @@ impl<'a> Parser<'a> {
             Some(Token {
                 kind: t!("for"), ..
             }) => self.parse_for_statement().map(Into::into),
+            Some(Token {
+                kind: t!("goto"), ..
+            }) => self.parse_goto_statement().map(Into::into),
             Some(Token { kind: t!("if"), .. }) => self.parse_if_statement().map(Into::into),
             Some(Token {
                 kind: t!("return"), ..
@@ -102,12 +126,27 @@ impl<'a> Parser<'a> {
             }) => self.parse_while_statement().map(Into::into),
             Some(Token { kind: t!(";"), .. }) => self.parse_null_statement().map(Into::into),
             Some(Token { kind: t!("{"), .. }) => self.parse_block_statement().map(Into::into),
+            Some(_) if self.at_label()? => self.parse_labeled_statement().map(Into::into),
             Some(token) => {
-                let expr = self.parse_expression().map_err(|_| {
-                    ParseError::unexpected_token("statement", &token.kind).with_span(token.span)
-                })?;
+                // A token that cannot start an expression cannot start a
+                // statement either. Any error past it is the expression's own.
+                let expr = match self.parse_expression() {
+                    Ok(expr) => expr,
+                    Err(CompilerError {
+                        error: ParseError::UnexpectedToken { .. },
+                        ..
+                    }) if self.peek_span()? == token.span => {
+                        return Err(ParseError::unexpected_token("statement", &token.kind)
+                            .with_span(token.span));
+                    }
+                    Err(err) => return Err(err),
+                };
                 self.expect_token(t!(";"))?;
-                Ok(ExprStmt { expr }.into())
+                Ok(ExprStmt {
+                    expr,
+                    span: self.span_from(token.span),
+                }
+                .into())
             }
             None => Err(ParseError::unexpected_eof("statement").with_span(self.eof_span)),
         }
@@ -153,7 +192,10 @@ mod tests {
         assert_eq!(program.functions.len(), 1);
         assert_eq!(program.functions[0].return_type, Type::Int);
         assert_eq!(program.functions[0].name, "main");
-        assert_eq!(program.functions[0].body.statements.len(), 1);
+        assert_eq!(
+            program.functions[0].body.as_ref().unwrap().statements.len(),
+            1
+        );
     }
 
     #[test]
@@ -175,6 +217,16 @@ mod tests {
         assert_eq!(program.functions[2].return_type, Type::Void);
     }
 
+    #[test]
+    fn test_parse_function_declaration() {
+        let input = "int add(int a, int b); int main(void) { return add(1, 2); }";
+        let program = parse_program(input).unwrap();
+        assert_eq!(program.functions.len(), 2);
+        assert_eq!(program.functions[0].params.len(), 2);
+        assert!(program.functions[0].body.is_none());
+        assert!(program.functions[1].body.is_some());
+    }
+
     #[test]
     fn test_invalid_void_parameter() {
         let input = "int main(void int x) { return 0; }";
@@ -200,7 +252,10 @@ mod tests {
         let result = parse_program(input);
         assert!(result.is_ok());
         let program = result.unwrap();
-        assert_eq!(program.functions[0].body.statements.len(), 2);
+        assert_eq!(
+            program.functions[0].body.as_ref().unwrap().statements.len(),
+            2
+        );
     }
 
     #[test]
@@ -223,7 +278,10 @@ mod tests {
         let result = parse_program(input);
         assert!(result.is_ok());
         let program = result.unwrap();
-        assert_eq!(program.functions[0].body.statements.len(), 4);
+        assert_eq!(
+            program.functions[0].body.as_ref().unwrap().statements.len(),
+            4
+        );
     }
 
     #[test]
@@ -233,16 +291,17 @@ mod tests {
                 if (1) { x; }
                 while (1) { x; }
                 do { x; } while (1);
-                for (;;) { x; }
-                break;
-                continue;
+                for (;;) { x; break; continue; }
                 return 0;
             }
         "#;
         let result = parse_program(input);
         assert!(result.is_ok());
         let program = result.unwrap();
-        assert_eq!(program.functions[0].body.statements.len(), 7);
+        assert_eq!(
+            program.functions[0].body.as_ref().unwrap().statements.len(),
+            5
+        );
     }
 
     #[test]
@@ -289,11 +348,47 @@ mod tests {
 
     #[test]
     fn test_error_missing_function_body() {
-        let input = "int main(void);";
+        let input = "int main(void)";
         let result = parse_program(input);
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_error_statement_that_is_not_an_expression() {
+        let err = parse_program("int main(void) { ) }").unwrap_err();
+        assert_eq!(
+            err.error,
+            ParseError::unexpected_token("statement", &t!(")"))
+        );
+    }
+
+    #[test]
+    fn test_error_invalid_lvalue_in_statement() {
+        let err = parse_program("int main(void) { 1 = 2; return 0; }").unwrap_err();
+        assert_eq!(err.error, ParseError::InvalidLvalue("'='".into()));
+        assert_eq!((err.span.line, err.span.column), (1, 18));
+        assert_eq!(err.span.end - err.span.start, 1);
+
+        let err = parse_program("int main(void) { int a; (a + a) += 3; }").unwrap_err();
+        assert_eq!(err.error, ParseError::InvalidLvalue("'+='".into()));
+        assert_eq!(err.span.column, 25);
+        assert_eq!(err.span.end - err.span.start, 7);
+
+        for input in [
+            "int main(void) { int a; -a = 2; }",
+            "int main(void) { 5++; }",
+            "int main(void) { int a; a++ = 3; }",
+        ] {
+            let err = parse_program(input).unwrap_err();
+            assert!(
+                matches!(err.error, ParseError::InvalidLvalue(_)),
+                "{}: {:?}",
+                input,
+                err
+            );
+        }
+    }
+
     // === Edge Cases ===
 
     #[test]
@@ -318,6 +413,9 @@ mod tests {
         let result = parse_program(input);
         assert!(result.is_ok());
         let program = result.unwrap();
-        assert_eq!(program.functions[0].body.statements.len(), 0);
+        assert_eq!(
+            program.functions[0].body.as_ref().unwrap().statements.len(),
+            0
+        );
     }
 }
@@ -1,6 +1,6 @@
 use crate::{
     error::IntoCompilerError,
-    grammar::{BlockStmt, Token},
+    grammar::{BlockStmt, Statement, Token, Type},
     parser_base::{ParseError, Parser, error::ParseResult},
     t,
 };
@@ -8,6 +8,7 @@ use crate::{
 impl<'a> Parser<'a> {
     /// Parse a block: { statement* }
     pub(crate) fn parse_block_statement(&mut self) -> ParseResult<BlockStmt<'a>> {
+        let start = self.peek_span()?;
         self.expect_token(t!("{"))?;
 
         let mut statements = Vec::new();
@@ -17,7 +18,7 @@ impl<'a> Parser<'a> {
             match self.peek_token()? {
                 Some(Token { kind: t!("}"), .. }) => break,
                 Some(_) => {
-                    statements.push(self.parse_statement()?);
+                    statements.extend(self.parse_block_item()?);
                 }
                 None => {
                     return Err(
@@ -28,7 +29,25 @@ impl<'a> Parser<'a> {
         }
 
         self.expect_token(t!("}"))?;
-        Ok(BlockStmt { statements })
+        Ok(BlockStmt {
+            statements,
+            span: self.span_from(start),
+        })
+    }
+
+    /// Parses an item of a block into the statements it adds to the block.
+    /// Each declarator is a statement of the block, so the variables are in
+    /// its scope.
+    pub(crate) fn parse_block_item(&mut self) -> ParseResult<Vec<Statement<'a>>> {
+        match self.peek_token()? {
+            Some(Token { kind, .. }) if Type::from_token_type(&kind).is_some() => Ok(self
+                .parse_declaration_statement()?
+                .into_iter()
+                .map(Into::into)
+                .collect()),
+            Some(_) if self.at_label()? => self.parse_labeled_block_item(),
+            _ => Ok(vec![self.parse_statement()?]),
+        }
     }
 }
 
@@ -77,6 +96,20 @@ mod tests {
         assert_eq!(block.statements.len(), 1);
     }
 
+    #[test]
+    fn test_parse_block_labeled_declarators() {
+        // Only the first declarator is labeled, the others follow it in
+        // the block
+        let block = parse_block("{ l: int a = 1, b = 2; return a + b; }").unwrap();
+        let [Statement::Labeled(labeled), Statement::Declaration(b), _] = &block.statements[..]
+        else {
+            panic!("expected a label, a declaration and a return");
+        };
+        assert!(matches!(&*labeled.stmt, Statement::Declaration(a) if a.name == "a"));
+        assert_eq!(labeled.span.end, labeled.stmt.span().end);
+        assert_eq!(b.name.as_ref(), "b");
+    }
+
     #[test]
     fn test_parse_block_error_missing_closing_brace() {
         let result = parse_block("{ x;");
@@ -6,9 +6,10 @@ use crate::{
 
 impl<'a> Parser<'a> {
     pub(crate) fn parse_break_statement(&mut self) -> ParseResult<BreakStmt<'a>> {
+        let start = self.peek_span()?;
         self.expect_token(t!("break"))?;
         self.expect_token(t!(";"))?;
-        Ok(BreakStmt::default())
+        Ok(BreakStmt::new(self.span_from(start)))
     }
 }
 
@@ -6,9 +6,10 @@ use crate::{
 
 impl<'a> Parser<'a> {
     pub(crate) fn parse_continue_statement(&mut self) -> ParseResult<ContinueStmt<'a>> {
+        let start = self.peek_span()?;
         self.expect_token(t!("continue"))?;
         self.expect_token(t!(";"))?;
-        Ok(ContinueStmt::default())
+        Ok(ContinueStmt::new(self.span_from(start)))
     }
 }
 
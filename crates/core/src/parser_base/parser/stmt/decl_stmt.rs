@@ -5,20 +5,32 @@ use crate::{
 };
 
 impl<'a> Parser<'a> {
-    pub(crate) fn parse_declaration_statement(&mut self) -> ParseResult<DeclarationStmt<'a>> {
+    /// Parses a declaration with one or more declarators, `int a = 1, b;`,
+    /// into a declaration statement per declarator. The first spans the
+    /// type too, the others start at their name.
+    pub(crate) fn parse_declaration_statement(&mut self) -> ParseResult<Vec<DeclarationStmt<'a>>> {
+        let mut start = self.peek_span()?;
         let var_type = self.expect_with(Type::from_token_type, "type")?;
-        let name = self.parse_identifier()?;
-        let initializer = self
-            .eat(t!("="))?
-            .then(|| self.parse_expression())
-            .transpose()?;
+        let mut declarations = Vec::new();
+        loop {
+            let name = self.parse_identifier()?;
+            let initializer = self
+                .eat(t!("="))?
+                .then(|| self.parse_assignment_expression())
+                .transpose()?;
+            declarations.push(DeclarationStmt {
+                var_type: var_type.clone(),
+                name,
+                initializer,
+                span: self.span_from(start),
+            });
+            if !self.eat(t!(","))? {
+                break;
+            }
+            start = self.peek_span()?;
+        }
         self.expect_token(t!(";"))?;
-
-        Ok(DeclarationStmt {
-            var_type,
-            name,
-            initializer,
-        })
+        Ok(declarations)
     }
 }
 
@@ -28,6 +40,12 @@ mod tests {
     use crate::{grammar::Expression, lexer_base::Lexer};
 
     fn parse_declaration(input: &str) -> ParseResult<DeclarationStmt<'_>> {
+        let mut declarations = parse_declarations(input)?;
+        assert_eq!(declarations.len(), 1);
+        Ok(declarations.remove(0))
+    }
+
+    fn parse_declarations(input: &str) -> ParseResult<Vec<DeclarationStmt<'_>>> {
         let lexer = Lexer::new(input);
         let mut parser = Parser::new(lexer);
         parser.parse_declaration_statement()
@@ -50,7 +68,55 @@ mod tests {
         let stmt = result.unwrap();
         assert_eq!(stmt.var_type, Type::Int);
         assert_eq!(stmt.name.as_ref(), "x");
-        assert!(matches!(stmt.initializer, Some(Expression::Constant(42))));
+        assert!(matches!(
+            stmt.initializer,
+            Some(Expression {
+                kind: ExprKind::Constant(42),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_declaration_with_several_declarators() {
+        let declarations = parse_declarations("int x = 1, y, z = x + y;").unwrap();
+        assert_eq!(declarations.len(), 3);
+        assert_eq!(declarations[0].name.as_ref(), "x");
+        assert!(matches!(
+            declarations[0].initializer,
+            Some(Expression {
+                kind: ExprKind::Constant(1),
+                ..
+            })
+        ));
+        assert_eq!(declarations[1].name.as_ref(), "y");
+        assert!(declarations[1].initializer.is_none());
+        assert_eq!(declarations[2].name.as_ref(), "z");
+        assert!(matches!(
+            declarations[2].initializer,
+            Some(Expression {
+                kind: ExprKind::Binary { .. },
+                ..
+            })
+        ));
+        assert!(declarations.iter().all(|decl| decl.var_type == Type::Int));
+        assert_eq!(declarations[0].span.column, 1);
+        assert_eq!(declarations[1].span.column, 12);
+    }
+
+    #[test]
+    fn test_parse_declaration_initializer_stops_at_comma() {
+        // A comma in an initializer needs parentheses to be an operator
+        let result = parse_declaration("int x = (1, 2);");
+        assert!(matches!(
+            result.unwrap().initializer,
+            Some(Expression {
+                kind: ExprKind::Grouped(_),
+                ..
+            })
+        ));
+        assert!(parse_declarations("int x = 1, 2;").is_err());
+        assert!(parse_declarations("int x, ;").is_err());
     }
 
     #[test]
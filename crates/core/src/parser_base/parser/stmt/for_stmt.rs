@@ -6,6 +6,7 @@ use crate::{
 
 impl<'a> Parser<'a> {
     pub(crate) fn parse_for_statement(&mut self) -> ParseResult<ForStmt<'a>> {
+        let start = self.peek_span()?;
         self.expect_sequence_of_tokens([t!("for"), t!("(")])?;
 
         // Parse init expression (optional)
@@ -39,6 +40,7 @@ impl<'a> Parser<'a> {
             cond,
             post,
             body: Box::new(body),
+            span: self.span_from(start),
         })
     }
 }
@@ -67,6 +69,34 @@ mod tests {
         assert!(matches!(*stmt.body, Statement::Block(_)));
     }
 
+    #[test]
+    fn test_parse_for_with_comma_clauses() {
+        let result = parse_for("for (i = 0, j = 10; i < j; i = i + 1, j = j - 1) x;");
+        assert!(result.is_ok());
+        let stmt = result.unwrap();
+        assert!(matches!(
+            stmt.init,
+            Some(Expression {
+                kind: ExprKind::Comma { .. },
+                ..
+            })
+        ));
+        assert!(matches!(
+            stmt.cond,
+            Some(Expression {
+                kind: ExprKind::Binary { .. },
+                ..
+            })
+        ));
+        assert!(matches!(
+            stmt.post,
+            Some(Expression {
+                kind: ExprKind::Comma { .. },
+                ..
+            })
+        ));
+    }
+
     #[test]
     fn test_parse_for_empty_clauses() {
         let result = parse_for("for (;;) { x; }");
@@ -0,0 +1,49 @@
+use crate::{
+    grammar::GotoStmt,
+    parser_base::{Parser, error::ParseResult},
+    t,
+};
+
+impl<'a> Parser<'a> {
+    pub(crate) fn parse_goto_statement(&mut self) -> ParseResult<GotoStmt<'a>> {
+        let start = self.peek_span()?;
+        self.expect_token(t!("goto"))?;
+        let label = self.parse_identifier()?;
+        self.expect_token(t!(";"))?;
+        Ok(GotoStmt {
+            label,
+            span: self.span_from(start),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer_base::Lexer;
+
+    fn parse_goto(input: &str) -> ParseResult<GotoStmt<'_>> {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        parser.parse_goto_statement()
+    }
+
+    #[test]
+    fn test_parse_goto() {
+        let result = parse_goto("goto cleanup;");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().label.as_ref(), "cleanup");
+    }
+
+    #[test]
+    fn test_parse_goto_error_missing_label() {
+        assert!(parse_goto("goto;").is_err());
+        assert!(parse_goto("goto 1;").is_err());
+    }
+
+    #[test]
+    fn test_parse_goto_error_missing_semicolon() {
+        let result = parse_goto("goto cleanup");
+        assert!(result.is_err());
+    }
+}
@@ -7,6 +7,7 @@ use crate::{
 impl<'a> Parser<'a> {
     /// Parse an if statement: if (condition) { body } else { body }
     pub(crate) fn parse_if_statement(&mut self) -> ParseResult<IfStmt<'a>> {
+        let start = self.peek_span()?;
         self.expect_sequence_of_tokens([t!("if"), t!("(")])?;
         let condition = self.parse_expression()?;
         self.expect_token(t!(")"))?;
@@ -20,6 +21,7 @@ impl<'a> Parser<'a> {
             cond: condition,
             then_block: Box::new(then_block),
             else_block: else_block.map(Box::new),
+            span: self.span_from(start),
         })
     }
 }
@@ -42,7 +44,7 @@ mod tests {
         let result = parse_if("if (1) { return 0; }");
         assert!(result.is_ok());
         let stmt = result.unwrap();
-        assert!(matches!(stmt.cond, Expression::Constant(1)));
+        assert!(matches!(stmt.cond.kind, ExprKind::Constant(1)));
         assert!(matches!(*stmt.then_block, Statement::Block(_)));
         assert!(stmt.else_block.is_none());
     }
@@ -52,11 +54,11 @@ mod tests {
         let result = parse_if("if (x > 5) { x; }");
         assert!(result.is_ok());
         let stmt = result.unwrap();
-        match stmt.cond {
-            Expression::Binary { op, lhs, rhs } => {
+        match stmt.cond.kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert!(matches!(op, BinaryOp::GreaterThan));
-                assert!(matches!(*lhs, Expression::Variable(name) if name == "x"));
-                assert!(matches!(*rhs, Expression::Constant(5)));
+                assert!(matches!(lhs.kind, ExprKind::Variable(name) if name == "x"));
+                assert!(matches!(rhs.kind, ExprKind::Constant(5)));
             }
             _ => panic!("Expected binary comparison"),
         }
@@ -75,7 +77,7 @@ mod tests {
         let result = parse_if("if (1) { return 0; } else { return 1; }");
         assert!(result.is_ok());
         let stmt = result.unwrap();
-        assert!(matches!(stmt.cond, Expression::Constant(1)));
+        assert!(matches!(stmt.cond.kind, ExprKind::Constant(1)));
         assert!(matches!(*stmt.then_block, Statement::Block(_)));
         assert!(stmt.else_block.is_some());
         assert!(matches!(
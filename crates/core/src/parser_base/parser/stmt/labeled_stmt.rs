@@ -0,0 +1,102 @@
+use std::borrow::Cow;
+
+use crate::{
+    grammar::{LabeledStmt, Statement},
+    parser_base::{Parser, error::ParseResult},
+    t,
+};
+
+impl<'a> Parser<'a> {
+    pub(crate) fn parse_labeled_statement(&mut self) -> ParseResult<LabeledStmt<'a>> {
+        let start = self.peek_span()?;
+        let label = self.parse_label()?;
+        let stmt = self.parse_statement()?;
+        Ok(LabeledStmt {
+            label,
+            stmt: Box::new(stmt),
+            span: self.span_from(start),
+        })
+    }
+
+    /// Parses a labeled item of a block. A declaration with several
+    /// declarators gives a statement per declarator as anywhere else in a
+    /// block, and only the first carries the label.
+    pub(crate) fn parse_labeled_block_item(&mut self) -> ParseResult<Vec<Statement<'a>>> {
+        let start = self.peek_span()?;
+        let label = self.parse_label()?;
+        let mut items = self.parse_block_item()?;
+        let stmt = items.remove(0);
+        let labeled = LabeledStmt {
+            label,
+            span: start.to(stmt.span()),
+            stmt: Box::new(stmt),
+        };
+        items.insert(0, labeled.into());
+        Ok(items)
+    }
+
+    fn parse_label(&mut self) -> ParseResult<Cow<'a, str>> {
+        let label = self.parse_identifier()?;
+        self.expect_token(t!(":"))?;
+        Ok(label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        grammar::{ExprKind, Statement},
+        lexer_base::Lexer,
+    };
+
+    fn parse_labeled(input: &str) -> ParseResult<LabeledStmt<'_>> {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        parser.parse_labeled_statement()
+    }
+
+    fn parse_statement(input: &str) -> ParseResult<Statement<'_>> {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        parser.parse_statement()
+    }
+
+    #[test]
+    fn test_parse_labeled_statement() {
+        let result = parse_labeled("done: return 0;");
+        assert!(result.is_ok());
+        let stmt = result.unwrap();
+        assert_eq!(stmt.label.as_ref(), "done");
+        assert!(matches!(*stmt.stmt, Statement::Return(_)));
+    }
+
+    #[test]
+    fn test_parse_nested_labels() {
+        let stmt = parse_labeled("a: b: ;").unwrap();
+        let Statement::Labeled(inner) = *stmt.stmt else {
+            panic!("Expected a labeled statement");
+        };
+        assert_eq!(inner.label.as_ref(), "b");
+        assert!(matches!(*inner.stmt, Statement::Null(_)));
+    }
+
+    #[test]
+    fn test_identifier_followed_by_colon_starts_a_label() {
+        assert!(matches!(
+            parse_statement("x: x = 1;"),
+            Ok(Statement::Labeled(_))
+        ));
+        // Anything else starting with an identifier is an expression
+        let Ok(Statement::Expr(stmt)) = parse_statement("x = a ? b : c;") else {
+            panic!("Expected an expression statement");
+        };
+        assert!(matches!(stmt.expr.kind, ExprKind::Assignment { .. }));
+    }
+
+    #[test]
+    fn test_parse_labeled_statement_error_missing_statement() {
+        let result = parse_labeled("done:");
+        assert!(result.is_err());
+    }
+}
@@ -3,7 +3,9 @@ pub(super) mod break_stmt;
 pub(super) mod continue_stmt;
 pub(super) mod decl_stmt;
 pub(super) mod for_stmt;
+pub(super) mod goto_stmt;
 pub(super) mod if_stmt;
+pub(super) mod labeled_stmt;
 pub(super) mod null_stmt;
 pub(super) mod return_stmt;
 pub(super) mod while_stmt;
@@ -6,7 +6,8 @@ use crate::{
 
 impl<'a> Parser<'a> {
     pub(crate) fn parse_null_statement(&mut self) -> ParseResult<NullStmt<'a>> {
+        let start = self.peek_span()?;
         self.expect_token(t!(";"))?;
-        Ok(NullStmt::default())
+        Ok(NullStmt::new(self.span_from(start)))
     }
 }
@@ -5,19 +5,32 @@ use crate::{
 };
 
 impl<'a> Parser<'a> {
-    /// Parse a return statement: return expr;
+    /// Parse a return statement: return expr; or return;
     pub(crate) fn parse_return_statement(&mut self) -> ParseResult<ReturnStmt<'a>> {
+        let start = self.peek_span()?;
         self.expect_token(t!("return"))?;
-        let expr = self.parse_expression()?;
-        self.expect_token(t!(";"))?;
-        Ok(ReturnStmt { expr })
+        let expr = match self.eat(t!(";"))? {
+            true => None,
+            false => {
+                let expr = self.parse_expression()?;
+                self.expect_token(t!(";"))?;
+                Some(expr)
+            }
+        };
+        Ok(ReturnStmt {
+            expr,
+            span: self.span_from(start),
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{grammar::Expression, lexer_base::Lexer};
+    use crate::{
+        grammar::{ExprKind, Expression},
+        lexer_base::Lexer,
+    };
 
     fn parse_return(input: &str) -> ParseResult<ReturnStmt<'_>> {
         let lexer = Lexer::new(input);
@@ -30,7 +43,13 @@ mod tests {
         let result = parse_return("return 42;");
         assert!(result.is_ok());
         let stmt = result.unwrap();
-        assert!(matches!(stmt.expr, Expression::Constant(42)));
+        assert!(matches!(
+            stmt.expr,
+            Some(Expression {
+                kind: ExprKind::Constant(42),
+                ..
+            })
+        ));
     }
 
     #[test]
@@ -38,7 +57,9 @@ mod tests {
         let result = parse_return("return x;");
         assert!(result.is_ok());
         let stmt = result.unwrap();
-        assert!(matches!(stmt.expr, Expression::Variable(name) if name == "x"));
+        assert!(
+            matches!(stmt.expr, Some(Expression { kind: ExprKind::Variable(name), .. }) if name == "x")
+        );
     }
 
     #[test]
@@ -46,7 +67,13 @@ mod tests {
         let result = parse_return("return 1 + 2;");
         assert!(result.is_ok());
         let stmt = result.unwrap();
-        assert!(matches!(stmt.expr, Expression::Binary { .. }));
+        assert!(matches!(
+            stmt.expr,
+            Some(Expression {
+                kind: ExprKind::Binary { .. },
+                ..
+            })
+        ));
     }
 
     #[test]
@@ -56,8 +83,8 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_return_error_missing_expression() {
-        let result = parse_return("return;");
-        assert!(result.is_err());
+    fn test_parse_return_without_expression() {
+        let stmt = parse_return("return;").unwrap();
+        assert!(stmt.expr.is_none());
     }
 }
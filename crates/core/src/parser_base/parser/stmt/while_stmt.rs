@@ -6,6 +6,7 @@ use crate::{
 
 impl<'a> Parser<'a> {
     pub(crate) fn parse_do_while_statement(&mut self) -> ParseResult<DoWhileStmt<'a>> {
+        let start = self.peek_span()?;
         self.expect_token(t!("do"))?;
         let body = self.parse_statement()?;
         self.expect_sequence_of_tokens([t!("while"), t!("(")])?;
@@ -14,10 +15,12 @@ impl<'a> Parser<'a> {
         Ok(DoWhileStmt {
             body: Box::new(body),
             cond: condition,
+            span: self.span_from(start),
         })
     }
 
     pub(crate) fn parse_while_statement(&mut self) -> ParseResult<WhileStmt<'a>> {
+        let start = self.peek_span()?;
         self.expect_sequence_of_tokens([t!("while"), t!("(")])?;
         let cond = self.parse_expression()?;
         self.expect_token(t!(")"))?;
@@ -25,6 +28,7 @@ impl<'a> Parser<'a> {
         Ok(WhileStmt {
             cond,
             body: Box::new(body),
+            span: self.span_from(start),
         })
     }
 }
@@ -53,7 +57,7 @@ mod tests {
         let result = parse_while("while (1) { return 0; }");
         assert!(result.is_ok());
         let stmt = result.unwrap();
-        assert!(matches!(stmt.cond, Expression::Constant(1)));
+        assert!(matches!(stmt.cond.kind, ExprKind::Constant(1)));
         assert!(matches!(*stmt.body, Statement::Block(_)));
     }
 
@@ -62,11 +66,11 @@ mod tests {
         let result = parse_while("while (x < 10) { x; }");
         assert!(result.is_ok());
         let stmt = result.unwrap();
-        match stmt.cond {
-            Expression::Binary { op, lhs, rhs } => {
+        match stmt.cond.kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert!(matches!(op, BinaryOp::LessThan));
-                assert!(matches!(*lhs, Expression::Variable(name) if name == "x"));
-                assert!(matches!(*rhs, Expression::Constant(10)));
+                assert!(matches!(lhs.kind, ExprKind::Variable(name) if name == "x"));
+                assert!(matches!(rhs.kind, ExprKind::Constant(10)));
             }
             _ => panic!("Expected binary comparison"),
         }
@@ -116,7 +120,7 @@ mod tests {
         let result = parse_do_while("do { return 0; } while (1);");
         assert!(result.is_ok());
         let stmt = result.unwrap();
-        assert!(matches!(stmt.cond, Expression::Constant(1)));
+        assert!(matches!(stmt.cond.kind, ExprKind::Constant(1)));
         assert!(matches!(*stmt.body, Statement::Block(_)));
     }
 
@@ -125,11 +129,11 @@ mod tests {
         let result = parse_do_while("do { x; } while (x < 10);");
         assert!(result.is_ok());
         let stmt = result.unwrap();
-        match stmt.cond {
-            Expression::Binary { op, lhs, rhs } => {
+        match stmt.cond.kind {
+            ExprKind::Binary { op, lhs, rhs } => {
                 assert!(matches!(op, BinaryOp::LessThan));
-                assert!(matches!(*lhs, Expression::Variable(name) if name == "x"));
-                assert!(matches!(*rhs, Expression::Constant(10)));
+                assert!(matches!(lhs.kind, ExprKind::Variable(name) if name == "x"));
+                assert!(matches!(rhs.kind, ExprKind::Constant(10)));
             }
             _ => panic!("Expected binary comparison"),
         }
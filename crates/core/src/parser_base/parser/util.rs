@@ -3,6 +3,7 @@ use crate::{
     error::IntoCompilerError,
     grammar::*,
     parser_base::{ParseError, error::ParseResult},
+    t,
 };
 
 impl<'a> Parser<'a> {
@@ -97,7 +98,15 @@ impl<'a> Parser<'a> {
 
     /// Consumes the next token from the lexer.
     pub(super) fn next_token(&mut self) -> ParseResult<Option<Token<'a>>> {
-        self.lexer.next().transpose().map_err(|e| e.convert_error())
+        let token = self
+            .lexer
+            .next()
+            .transpose()
+            .map_err(|e| e.convert_error())?;
+        if let Some(token) = &token {
+            self.last_span = token.span;
+        }
+        Ok(token)
     }
 
     /// Peeks the next token from the lexer without consuming it.
@@ -109,7 +118,34 @@ impl<'a> Parser<'a> {
             .map_err(|e| e.convert_error())
     }
 
+    /// Peeks the token after the upcoming one. `Peekable` only looks one
+    /// token ahead, so this lexes on from a copy of the lexer.
+    pub(super) fn peek_second_token(&self) -> ParseResult<Option<Token<'a>>> {
+        let mut lexer = self.lexer.clone();
+        lexer.next();
+        lexer.next().transpose().map_err(|e| e.convert_error())
+    }
+
+    /// Whether the upcoming tokens are `ident:`, which starts a labeled
+    /// statement, while any other identifier starts an expression.
+    pub(super) fn at_label(&mut self) -> ParseResult<bool> {
+        Ok(
+            matches!(self.peek_token_type()?, Some(TokenType::Identifier(_)))
+                && matches!(self.peek_second_token()?, Some(Token { kind: t!(":"), .. })),
+        )
+    }
+
     pub(super) fn peek_token_type(&mut self) -> ParseResult<Option<TokenType<'a>>> {
         Ok(self.peek_token()?.map(|token| token.kind))
     }
+
+    /// Span of the upcoming token, or the end-of-file span if there is none.
+    pub(super) fn peek_span(&mut self) -> ParseResult<Span> {
+        Ok(self.peek_token()?.map_or(self.eof_span, |token| token.span))
+    }
+
+    /// Span from `start` up to the end of the most recently consumed token.
+    pub(super) fn span_from(&self, start: Span) -> Span {
+        start.to(self.last_span)
+    }
 }
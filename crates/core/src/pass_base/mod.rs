@@ -0,0 +1,477 @@
+//! The pass manager: runs the passes over each representation of the
+//! program, from the AST through TACKY to the assembly IR, in the order and
+//! at the optimization levels they are registered for.
+//!
+//! Each pass has a name, by which `-fno-<name>` turns it off,
+//! `-f<name>` turns it on at levels that do not run it, and
+//! `--print-after=<name>` dumps the program after it runs. In debug builds
+//! the program is checked by [`verify`] after every pass, so a pass that
+//! breaks an invariant is caught where it does so.
+
+pub mod verify;
+
+use std::{collections::HashSet, fmt::Debug};
+
+use crate::{
+    codegen_base::{self, CodeGenerator, CodegenOptions, RelocationModel},
+    grammar::Program,
+    ir_base::IRProgram,
+    opt_base::{
+        OptLevel, Options, eliminate_dead_stores, eliminate_tail_calls,
+        eliminate_unreachable_blocks, fold_constants, hoist_loop_invariants, inline_functions,
+        number_values, propagate_constants, propagate_copies, reduce_strength,
+    },
+    tacky_base::{TackyFuncDef, TackyProgram},
+};
+
+/// A named transformation of one representation of the program
+pub struct Pass<F> {
+    /// Name used by `-fno-<name>` and `--print-after=<name>`
+    pub name: &'static str,
+    /// Optimization levels that run the pass unless it is turned off
+    pub levels: &'static [OptLevel],
+    pub run: F,
+}
+
+/// Pass over the AST
+pub type AstPass = Pass<for<'a> fn(&mut Program<'a>, &Options)>;
+/// Pass over TACKY
+pub type IrPass = Pass<fn(&mut TackyProgram, &Options)>;
+/// Pass over the assembly IR, once registers and stack slots are assigned
+pub type MachinePass = Pass<fn(&mut IRProgram<'static>, &Options)>;
+
+/// An entry of the TACKY pipeline
+pub enum IrStep {
+    Once(IrPass),
+    /// Repeats the passes until the program stops changing
+    Fixpoint(&'static [IrPass]),
+}
+
+const OPTIMIZING: &[OptLevel] = &[OptLevel::O1, OptLevel::O2, OptLevel::Os];
+const O2: &[OptLevel] = &[OptLevel::O2];
+
+/// AST passes, in order
+pub const AST_PASSES: &[AstPass] = &[Pass {
+    name: "fold-constants",
+    levels: OPTIMIZING,
+    run: |program, _| fold_constants(program),
+}];
+
+/// Scalar cleanups. Each can expose new opportunities for the others, e.g.
+/// a folded branch makes blocks unreachable, which in turn lets more
+/// constants reach the join points.
+const SIMPLIFY: &[IrPass] = &[
+    Pass {
+        name: "constprop",
+        levels: OPTIMIZING,
+        run: |program, _| each_function(program, propagate_constants),
+    },
+    Pass {
+        name: "copyprop",
+        levels: OPTIMIZING,
+        run: |program, _| each_function(program, propagate_copies),
+    },
+    Pass {
+        name: "unreachable",
+        levels: OPTIMIZING,
+        run: |program, _| each_function(program, eliminate_unreachable_blocks),
+    },
+    Pass {
+        name: "dce",
+        levels: OPTIMIZING,
+        run: |program, _| each_function(program, eliminate_dead_stores),
+    },
+];
+
+/// TACKY passes, in order
+pub const IR_PASSES: &[IrStep] = &[
+    // Clean up callees first so their size reflects the code they would
+    // really add to their callers
+    IrStep::Fixpoint(SIMPLIFY),
+    IrStep::Once(Pass {
+        name: "inline",
+        levels: OPTIMIZING,
+        run: |program, options| inline_functions(program, options.effective_inline_limit()),
+    }),
+    IrStep::Fixpoint(SIMPLIFY),
+    IrStep::Once(Pass {
+        name: "gvn",
+        levels: OPTIMIZING,
+        run: |program, _| each_function(program, number_values),
+    }),
+    IrStep::Fixpoint(SIMPLIFY),
+    // Before the loop passes, which can then work on loops that used to be
+    // recursion
+    IrStep::Once(Pass {
+        name: "tail-calls",
+        levels: O2,
+        run: |program, _| each_function(program, eliminate_tail_calls),
+    }),
+    IrStep::Once(Pass {
+        name: "licm",
+        levels: O2,
+        run: |program, _| each_function(program, hoist_loop_invariants),
+    }),
+    IrStep::Once(Pass {
+        name: "strength-reduce",
+        levels: O2,
+        run: |program, _| each_function(program, reduce_strength),
+    }),
+    IrStep::Fixpoint(SIMPLIFY),
+];
+
+/// Assembly IR passes, in order
+pub const MACHINE_PASSES: &[MachinePass] = &[Pass {
+    name: "schedule-insns",
+    levels: O2,
+    run: |program, _| {
+        for func in &mut program.functions {
+            codegen_base::schedule(&mut func.instructions);
+        }
+    },
+}];
+
+fn each_function(program: &mut TackyProgram, pass: fn(&mut TackyFuncDef)) {
+    for func in &mut program.functions {
+        pass(func);
+    }
+}
+
+/// Names of all registered passes.
+pub fn pass_names() -> impl Iterator<Item = &'static str> {
+    let ir = IR_PASSES.iter().flat_map(|step| match step {
+        IrStep::Once(pass) => std::slice::from_ref(pass),
+        IrStep::Fixpoint(passes) => passes,
+    });
+    AST_PASSES
+        .iter()
+        .map(|pass| pass.name)
+        .chain(ir.map(|pass| pass.name))
+        .chain(MACHINE_PASSES.iter().map(|pass| pass.name))
+}
+
+/// Runs the passes selected by the optimization level and the command line.
+#[derive(Debug, Clone)]
+pub struct PassManager {
+    pub options: Options,
+    pub codegen_options: CodegenOptions,
+    /// Passes turned on with `-f<name>`
+    enabled: HashSet<String>,
+    /// Passes turned off with `-fno-<name>`
+    disabled: HashSet<String>,
+    /// Passes after which to print the program
+    print_after: HashSet<String>,
+    /// Whether to check the program after every pass
+    pub verify: bool,
+}
+
+impl PassManager {
+    /// The passes of `level`, verifying the program in debug builds.
+    pub fn new(level: OptLevel) -> Self {
+        Self {
+            options: Options::new(level),
+            codegen_options: CodegenOptions::new(level),
+            enabled: HashSet::new(),
+            disabled: HashSet::new(),
+            print_after: HashSet::new(),
+            verify: cfg!(debug_assertions),
+        }
+    }
+
+    /// Applies a `-f<flag>` option: `-f<pass>` or `-fno-<pass>`, or one of
+    /// the settings `-finline-limit=N`, `-fregalloc=linear|graph`,
+    /// `-f[no-]omit-frame-pointer` and `-fpic`, `-fpie` or `-fno-pic`.
+    pub fn apply_flag(&mut self, flag: &str) -> Result<(), String> {
+        if flag == "omit-frame-pointer" || flag == "no-omit-frame-pointer" {
+            self.codegen_options.omit_frame_pointer = !flag.starts_with("no-");
+        } else if let Some(model) = Self::relocation_model(flag) {
+            self.codegen_options.relocation_model = model;
+        } else if let Some(limit) = flag.strip_prefix("inline-limit=") {
+            self.options.inline_limit = limit
+                .parse()
+                .map_err(|_| format!("invalid inline limit `{}`", limit))?;
+        } else if let Some(allocator) = flag.strip_prefix("regalloc=") {
+            self.codegen_options.register_allocator = Some(allocator.parse()?);
+        } else if let Some(pass) = flag.strip_prefix("no-")
+            && Self::is_pass(pass)
+        {
+            self.disable(pass)?;
+        } else if Self::is_pass(flag) {
+            self.enable(flag)?;
+        } else {
+            return Err(format!("unknown flag `-f{}`", flag));
+        }
+        Ok(())
+    }
+
+    /// The relocation model `flag` selects. As on x86-64 there is no limit
+    /// on the size of the GOT, `-fPIC` and `-fPIE` are the same as `-fpic`
+    /// and `-fpie`.
+    fn relocation_model(flag: &str) -> Option<RelocationModel> {
+        match flag {
+            "no-pic" | "no-PIC" | "no-pie" | "no-PIE" => Some(RelocationModel::Static),
+            "pie" | "PIE" => Some(RelocationModel::Pie),
+            "pic" | "PIC" => Some(RelocationModel::Pic),
+            _ => None,
+        }
+    }
+
+    /// Applies a `-m<flag>` option about the target: `-m[no-]red-zone`.
+    pub fn apply_machine_flag(&mut self, flag: &str) -> Result<(), String> {
+        match flag {
+            "red-zone" => self.codegen_options.red_zone = true,
+            "no-red-zone" => self.codegen_options.red_zone = false,
+            _ => return Err(format!("unknown flag `-m{}`", flag)),
+        }
+        Ok(())
+    }
+
+    /// Runs `pass` even at levels that do not.
+    pub fn enable(&mut self, pass: &str) -> Result<(), String> {
+        let pass = Self::known(pass)?;
+        self.disabled.remove(pass);
+        self.enabled.insert(pass.to_string());
+        Ok(())
+    }
+
+    /// Skips `pass`.
+    pub fn disable(&mut self, pass: &str) -> Result<(), String> {
+        let pass = Self::known(pass)?;
+        self.enabled.remove(pass);
+        self.disabled.insert(pass.to_string());
+        Ok(())
+    }
+
+    /// Prints the program to stderr after each run of `pass`.
+    pub fn print_after(&mut self, pass: &str) -> Result<(), String> {
+        let pass = Self::known(pass)?;
+        self.print_after.insert(pass.to_string());
+        Ok(())
+    }
+
+    fn is_pass(name: &str) -> bool {
+        pass_names().any(|pass| pass == name)
+    }
+
+    fn known(name: &str) -> Result<&str, String> {
+        if Self::is_pass(name) {
+            Ok(name)
+        } else {
+            Err(format!("unknown pass `{}`", name))
+        }
+    }
+
+    /// Whether `pass` runs with the current settings.
+    pub fn is_enabled<F>(&self, pass: &Pass<F>) -> bool {
+        !self.disabled.contains(pass.name)
+            && (pass.levels.contains(&self.options.level) || self.enabled.contains(pass.name))
+    }
+
+    pub fn run_ast(&self, program: &mut Program<'_>) {
+        for pass in AST_PASSES.iter().filter(|pass| self.is_enabled(pass)) {
+            (pass.run)(program, &self.options);
+            self.after(pass.name, "AST", program, |_| Ok(()));
+        }
+    }
+
+    pub fn run_ir(&self, program: &mut TackyProgram) {
+        self.check("lowering", program, verify::verify_tacky);
+        for step in IR_PASSES {
+            match step {
+                IrStep::Once(pass) => {
+                    self.run_ir_pass(pass, program);
+                }
+                IrStep::Fixpoint(passes) => loop {
+                    let mut changed = false;
+                    for pass in *passes {
+                        changed |= self.run_ir_pass(pass, program);
+                    }
+                    if !changed {
+                        break;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Runs `pass` if enabled, returning whether it changed the program.
+    fn run_ir_pass(&self, pass: &IrPass, program: &mut TackyProgram) -> bool {
+        if !self.is_enabled(pass) {
+            return false;
+        }
+        let before = program.clone();
+        (pass.run)(program, &self.options);
+        self.after(pass.name, "TACKY", program, verify::verify_tacky);
+        *program != before
+    }
+
+    /// Generates the assembly IR for `program` and runs the machine passes
+    /// over it.
+    pub fn generate(&self, program: &TackyProgram) -> IRProgram<'static> {
+        let mut ir_program = CodeGenerator::new()
+            .with_options(self.codegen_options)
+            .generate(program);
+        self.check("code generation", &ir_program, verify::verify_machine);
+        for pass in MACHINE_PASSES.iter().filter(|pass| self.is_enabled(pass)) {
+            (pass.run)(&mut ir_program, &self.options);
+            self.after(pass.name, "IR", &ir_program, verify::verify_machine);
+        }
+        ir_program
+    }
+
+    fn after<T: Debug>(
+        &self,
+        pass: &str,
+        stage: &str,
+        program: &T,
+        verify: fn(&T) -> Result<(), verify::VerifyError>,
+    ) {
+        if self.print_after.contains(pass) {
+            eprintln!("*** {} after {} ***", stage, pass);
+            eprintln!("{:#?}", program);
+        }
+        self.check(&format!("`{}`", pass), program, verify);
+    }
+
+    /// Verifies `program`, if enabled. A failure is a bug in the compiler.
+    fn check<T>(&self, step: &str, program: &T, verify: fn(&T) -> Result<(), verify::VerifyError>) {
+        if self.verify
+            && let Err(err) = verify(program)
+        {
+            panic!("invalid program after {}: {}", step, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        grammar::{ExprKind, Statement},
+        lexer_base::Lexer,
+        parser_base::Parser,
+        tacky_base::{Instruction, interp::Interpreter, lower_source},
+    };
+
+    fn ir_pass(name: &str) -> &'static IrPass {
+        IR_PASSES
+            .iter()
+            .find_map(|step| match step {
+                IrStep::Once(pass) if pass.name == name => Some(pass),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_flags_select_passes() {
+        let mut passes = PassManager::new(OptLevel::O1);
+        assert!(passes.apply_flag("no-inline").is_ok());
+        assert!(passes.apply_flag("schedule-insns").is_ok());
+        assert!(passes.apply_flag("inline-limit=10").is_ok());
+        assert_eq!(passes.options.inline_limit, 10);
+        assert_eq!(
+            passes.apply_flag("no-such-pass"),
+            Err("unknown flag `-fno-such-pass`".into())
+        );
+        assert!(passes.print_after("licm").is_ok());
+        assert!(passes.print_after("parse").is_err());
+
+        assert!(!passes.is_enabled(ir_pass("inline")));
+        assert!(passes.is_enabled(ir_pass("gvn")));
+        assert!(!passes.is_enabled(ir_pass("licm")));
+        assert!(passes.is_enabled(&MACHINE_PASSES[0]));
+    }
+
+    #[test]
+    fn test_ast_passes_run_by_level() {
+        let input = "int main(void) { return 2 * 3; }";
+        let returned = |passes: &PassManager| {
+            let mut ast = Parser::new(Lexer::new(input)).parse().unwrap();
+            passes.run_ast(&mut ast);
+            let body = ast.functions[0].body.take().unwrap();
+            match body.statements.into_iter().next() {
+                Some(Statement::Return(ret)) => ret.expr.unwrap().kind,
+                other => panic!("expected a return, found {:?}", other),
+            }
+        };
+        assert_eq!(
+            returned(&PassManager::new(OptLevel::O1)),
+            ExprKind::Constant(6)
+        );
+        assert!(matches!(
+            returned(&PassManager::new(OptLevel::O0)),
+            ExprKind::Binary { .. }
+        ));
+        let mut passes = PassManager::new(OptLevel::O2);
+        passes.disable("fold-constants").unwrap();
+        assert!(matches!(returned(&passes), ExprKind::Binary { .. }));
+    }
+
+    #[test]
+    fn test_flags_select_code_generation_settings() {
+        let mut passes = PassManager::new(OptLevel::O2);
+        assert!(!passes.codegen_options.omit_frame_pointer);
+        assert!(passes.codegen_options.red_zone);
+        assert!(passes.apply_flag("omit-frame-pointer").is_ok());
+        assert!(passes.apply_machine_flag("no-red-zone").is_ok());
+        assert!(passes.codegen_options.omit_frame_pointer);
+        assert!(!passes.codegen_options.red_zone);
+        assert!(passes.apply_flag("no-omit-frame-pointer").is_ok());
+        assert!(!passes.codegen_options.omit_frame_pointer);
+        assert_eq!(
+            passes.codegen_options.relocation_model,
+            RelocationModel::Pie
+        );
+        assert!(passes.apply_flag("PIC").is_ok());
+        assert_eq!(
+            passes.codegen_options.relocation_model,
+            RelocationModel::Pic
+        );
+        assert!(passes.apply_flag("no-pie").is_ok());
+        assert_eq!(
+            passes.codegen_options.relocation_model,
+            RelocationModel::Static
+        );
+        assert_eq!(
+            passes.apply_machine_flag("sse"),
+            Err("unknown flag `-msse`".into())
+        );
+    }
+
+    #[test]
+    fn test_disabled_pass_does_not_run() {
+        let input = "int inc(int x) { return x + 1; }
+                     int main() { return inc(inc(1)); }";
+        let mut program = lower_source(input);
+        let mut passes = PassManager::new(OptLevel::O1);
+        passes.disable("inline").unwrap();
+        passes.run_ir(&mut program);
+        assert_eq!(Interpreter::new(&program).call("main", &[]), 3);
+        let main = program.functions.iter().find(|f| f.name == "main").unwrap();
+        assert!(
+            main.body
+                .iter()
+                .any(|inst| matches!(inst, Instruction::FunCall { .. }))
+        );
+    }
+
+    #[test]
+    fn test_o2_only_pass_can_be_enabled_at_o1() {
+        let input =
+            "int sum(int n, int acc) { if (n == 0) return acc; return sum(n - 1, acc + n); }
+                     int main() { return sum(10, 0); }";
+        let mut program = lower_source(input);
+        let mut passes = PassManager::new(OptLevel::O1);
+        passes.disable("inline").unwrap();
+        passes.enable("tail-calls").unwrap();
+        passes.run_ir(&mut program);
+        assert_eq!(Interpreter::new(&program).call("main", &[]), 55);
+        let sum = program.functions.iter().find(|f| f.name == "sum").unwrap();
+        assert!(
+            !sum.body
+                .iter()
+                .any(|inst| matches!(inst, Instruction::FunCall { .. }))
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! Consistency checks run between passes.
+
+use std::collections::HashSet;
+
+use thiserror::Error;
+
+use crate::{
+    ir_base::{IRProgram, Instruction as Asm, Operand},
+    r,
+    tacky_base::{Instruction, TackyProgram},
+};
+
+/// An invariant of the program that a pass broke
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum VerifyError {
+    #[error("label `{0}` is defined more than once")]
+    DuplicateLabel(String),
+    #[error("`{function}` jumps to undefined label `{label}`")]
+    UndefinedLabel { function: String, label: String },
+    #[error("`{0}` can run past the end of its body")]
+    MissingTerminator(String),
+    #[error("`{function}` still uses pseudo register `{name}`")]
+    PseudoRegister { function: String, name: String },
+    #[error("`{function}` has an instruction that cannot be encoded: {instruction}")]
+    InvalidOperands {
+        function: String,
+        instruction: String,
+    },
+}
+
+/// Checks that labels are unique across the program, as they are emitted
+/// into one assembly file, that jumps stay within their function, and that
+/// every function ends with a return or a jump.
+pub fn verify_tacky(program: &TackyProgram) -> Result<(), VerifyError> {
+    let mut labels = HashSet::new();
+    for func in &program.functions {
+        let mut local = HashSet::new();
+        for inst in &func.body {
+            if let Instruction::Label(label) = inst {
+                if !labels.insert(label.as_str()) {
+                    return Err(VerifyError::DuplicateLabel(label.clone()));
+                }
+                local.insert(label.as_str());
+            }
+        }
+        for inst in &func.body {
+            if let Instruction::Jump(target)
+            | Instruction::JumpIfZero { target, .. }
+            | Instruction::JumpIfNotZero { target, .. } = inst
+                && !local.contains(target.as_str())
+            {
+                return Err(VerifyError::UndefinedLabel {
+                    function: func.name.clone(),
+                    label: target.clone(),
+                });
+            }
+        }
+        if !matches!(
+            func.body.last(),
+            Some(Instruction::Return(_) | Instruction::Jump(_) | Instruction::TailCall { .. })
+        ) {
+            return Err(VerifyError::MissingTerminator(func.name.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Checks the assembly IR: the label and terminator rules of
+/// [`verify_tacky`], and that every instruction can be emitted, with no
+/// pseudo registers left and only operand combinations x86-64 encodes.
+pub fn verify_machine(program: &IRProgram) -> Result<(), VerifyError> {
+    let mut labels = HashSet::new();
+    for func in &program.functions {
+        let function = func.name.to_string();
+        let mut local = HashSet::new();
+        for inst in &func.instructions {
+            if let Asm::Label(label) = inst {
+                if !labels.insert(label.as_str()) {
+                    return Err(VerifyError::DuplicateLabel(label.clone()));
+                }
+                local.insert(label.as_str());
+            }
+        }
+
+        for inst in &func.instructions {
+            if let Asm::Jmp(target) | Asm::JmpCC { target, .. } = inst
+                && !local.contains(target.as_str())
+            {
+                return Err(VerifyError::UndefinedLabel {
+                    function,
+                    label: target.clone(),
+                });
+            }
+            if let Some(name) = operands(inst)
+                .into_iter()
+                .find_map(|operand| match operand {
+                    Operand::Pseudo(name) => Some(name),
+                    _ => None,
+                })
+            {
+                return Err(VerifyError::PseudoRegister {
+                    function,
+                    name: name.clone(),
+                });
+            }
+            if !is_encodable(inst) {
+                return Err(VerifyError::InvalidOperands {
+                    function,
+                    instruction: inst.as_assembly_inline(),
+                });
+            }
+        }
+
+        if !matches!(
+            func.instructions.last(),
+            Some(Asm::Ret | Asm::Jmp(_) | Asm::TailCall { .. })
+        ) {
+            return Err(VerifyError::MissingTerminator(function));
+        }
+    }
+    Ok(())
+}
+
+fn operands(inst: &Asm) -> Vec<&Operand> {
+    match inst {
+        Asm::Mov { src, dst, .. }
+        | Asm::Add { src, dst }
+        | Asm::Sub { src, dst }
+        | Asm::IMul { src, dst }
+        | Asm::And { src, dst }
+        | Asm::Or { src, dst }
+        | Asm::Xor { src, dst }
+        | Asm::Sal { count: src, dst }
+        | Asm::Sar { count: src, dst }
+        | Asm::Cmov { src, dst, .. }
+        | Asm::Cmp { src, dst }
+        | Asm::Test { src, dst } => vec![src, dst],
+        Asm::Lea {
+            base, index, dst, ..
+        } => base.iter().chain(index).chain([dst]).collect(),
+        Asm::Push(operand)
+        | Asm::Pop(operand)
+        | Asm::IDiv { divisor: operand }
+        | Asm::Neg { dst: operand }
+        | Asm::Not { dst: operand }
+        | Asm::SetCC { dst: operand, .. } => vec![operand],
+        Asm::Cdq
+        | Asm::Jmp(_)
+        | Asm::JmpCC { .. }
+        | Asm::Label(_)
+        | Asm::AllocateStack(_)
+        | Asm::DeallocateStack(_)
+        | Asm::Call { .. }
+        | Asm::TailCall { .. }
+        | Asm::Ret
+        | Asm::Loc { .. } => vec![],
+    }
+}
+
+/// Whether the operands of `inst` are a combination x86-64 has an encoding
+/// for.
+fn is_encodable(inst: &Asm) -> bool {
+    let immediate = |operand: &Operand| matches!(operand, Operand::Immediate(_));
+    let register = |operand: &Operand| matches!(operand, Operand::Register(_));
+    match inst {
+        Asm::Mov { src, dst, .. }
+        | Asm::Add { src, dst }
+        | Asm::Sub { src, dst }
+        | Asm::And { src, dst }
+        | Asm::Or { src, dst }
+        | Asm::Xor { src, dst }
+        | Asm::Cmp { src, dst }
+        | Asm::Test { src, dst } => !(immediate(dst) || src.is_memory() && dst.is_memory()),
+        Asm::IMul { dst, .. } => register(dst),
+        Asm::Cmov { src, dst, .. } => !immediate(src) && register(dst),
+        // The count of a shift is an immediate or `cl`
+        Asm::Sal { count, dst } | Asm::Sar { count, dst } => {
+            matches!(
+                count,
+                Operand::Immediate(0..=255) | Operand::Register(r!("rcx"))
+            ) && !immediate(dst)
+        }
+        Asm::IDiv { divisor } => !immediate(divisor),
+        Asm::Lea {
+            base, index, dst, ..
+        } => base.iter().chain(index).all(register) && register(dst),
+        Asm::Pop(dst) | Asm::Neg { dst } | Asm::Not { dst } | Asm::SetCC { dst, .. } => {
+            !immediate(dst)
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ir_base::{IRFuncDef, Size},
+        r,
+        tacky_base::{TackyFuncDef, Value},
+    };
+
+    fn tacky(body: Vec<Instruction>) -> TackyProgram {
+        TackyProgram {
+            functions: vec![TackyFuncDef {
+                name: "main".into(),
+                is_global: true,
+                params: vec![],
+                body,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_tacky_jump_to_missing_label_is_rejected() {
+        let program = tacky(vec![
+            Instruction::Jump("nowhere".into()),
+            Instruction::Return(Value::Constant(0)),
+        ]);
+        assert_eq!(
+            verify_tacky(&program),
+            Err(VerifyError::UndefinedLabel {
+                function: "main".into(),
+                label: "nowhere".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_tacky_body_must_end_in_terminator() {
+        let program = tacky(vec![Instruction::Label("end".into())]);
+        assert_eq!(
+            verify_tacky(&program),
+            Err(VerifyError::MissingTerminator("main".into()))
+        );
+        let program = tacky(vec![Instruction::Return(Value::Constant(0))]);
+        assert_eq!(verify_tacky(&program), Ok(()));
+    }
+
+    #[test]
+    fn test_machine_code_must_be_encodable() {
+        let mut program = IRProgram::new();
+        program.add_function(IRFuncDef::new(
+            "main".into(),
+            true,
+            &[
+                Asm::Mov {
+                    size: Size::Long,
+                    src: Operand::memory(r!("rbp"), -4),
+                    dst: Operand::memory(r!("rbp"), -8),
+                },
+                Asm::Ret,
+            ],
+        ));
+        assert!(matches!(
+            verify_machine(&program),
+            Err(VerifyError::InvalidOperands { .. })
+        ));
+
+        program.functions[0].instructions[0] = Asm::Mov {
+            size: Size::Long,
+            src: Operand::Pseudo("x".into()),
+            dst: Operand::Register(r!("rax")),
+        };
+        assert!(matches!(
+            verify_machine(&program),
+            Err(VerifyError::PseudoRegister { .. })
+        ));
+    }
+}
@@ -0,0 +1,82 @@
+use thiserror::Error;
+
+use crate::{
+    error::{CompilerError, IntoCompilerError},
+    grammar::Type,
+};
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum SemanticError {
+    #[error("use of undeclared identifier `{0}`")]
+    UndeclaredVariable(String),
+
+    #[error("call to undeclared function `{0}`")]
+    UndeclaredFunction(String),
+
+    /// A name declared twice in the same scope
+    #[error("redeclaration of `{0}`")]
+    DuplicateDeclaration(String),
+
+    /// A function with two bodies
+    #[error("redefinition of function `{0}`")]
+    DuplicateDefinition(String),
+
+    /// A function declared again with a different parameter list
+    #[error("conflicting declarations of function `{0}`")]
+    ConflictingDeclaration(String),
+
+    #[error("`break` statement not within a loop")]
+    BreakOutsideLoop,
+
+    #[error("`continue` statement not within a loop")]
+    ContinueOutsideLoop,
+
+    /// A label defined twice in one function
+    #[error("redefinition of label `{0}`")]
+    DuplicateLabel(String),
+
+    /// A `goto` to a label its function does not define
+    #[error("use of undeclared label `{0}`")]
+    UndefinedLabel(String),
+
+    #[error("function `{name}` takes {expected} argument(s), but {found} were given")]
+    ArgumentCount {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+
+    /// A call of something that is not a function
+    #[error("called object is not a function")]
+    NotAFunction,
+
+    #[error("function `{0}` used as a value")]
+    FunctionUsedAsValue(String),
+
+    #[error("variable `{0}` declared void")]
+    VoidVariable(String),
+
+    /// The result of a `void` call used where a value is needed
+    #[error("void value not ignored as it ought to be")]
+    VoidValue,
+
+    #[error("argument {position} of `{name}` should be `{expected}`, but is `{found}`")]
+    ArgumentType {
+        name: String,
+        position: usize,
+        expected: Type,
+        found: Type,
+    },
+
+    #[error("operands of `?:` have incompatible types `{0}` and `{1}`")]
+    ConditionalTypes(Type, Type),
+
+    #[error("`return` with a value in function `{0}` returning void")]
+    ReturnValueInVoidFunction(String),
+
+    #[error("`return` without a value in function `{0}` returning a value")]
+    MissingReturnValue(String),
+}
+
+impl IntoCompilerError for SemanticError {}
+pub type SemanticResult<T> = Result<T, Vec<CompilerError<SemanticError>>>;
@@ -0,0 +1,16 @@
+//! Semantic analysis of the AST.
+//!
+//! The parser only checks that a program is well formed; the passes here
+//! check that it also makes sense, such as every identifier referring to
+//! something that was declared, and report each problem with the span of
+//! the statement or declaration it was found in.
+
+mod error;
+mod resolve;
+mod typecheck;
+pub mod typed;
+
+pub use error::{SemanticError, SemanticResult};
+pub use resolve::resolve;
+pub(crate) use resolve::source_name;
+pub use typecheck::typecheck;
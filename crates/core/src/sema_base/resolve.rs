@@ -0,0 +1,545 @@
+//! Identifier resolution.
+//!
+//! Every use of a variable is matched with the declaration it refers to,
+//! following the block scopes of C, and every local is renamed to
+//! `{name}.{n}` so that later phases can tell variables apart by name
+//! alone. Function names are program-wide and are left as they are.
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+use crate::{
+    error::{CompilerError, IntoCompilerError},
+    grammar::*,
+    sema_base::{SemanticError, SemanticResult},
+};
+
+/// Name in the source of the local that the resolver renamed to
+/// `resolved`.
+pub(crate) fn source_name(resolved: &str) -> &str {
+    resolved.rsplit_once('.').map_or(resolved, |(name, _)| name)
+}
+
+/// Resolves the identifiers of `program` and renames its locals, or
+/// returns every error found.
+pub fn resolve(program: &mut Program<'_>) -> SemanticResult<()> {
+    let mut resolver = Resolver::default();
+    for func in &mut program.functions {
+        resolver.resolve_function(func);
+    }
+    match resolver.errors.is_empty() {
+        true => Ok(()),
+        false => Err(resolver.errors),
+    }
+}
+
+/// A function declared at file scope
+struct FunctionDecl {
+    return_type: Type,
+    params: usize,
+    defined: bool,
+}
+
+#[derive(Default)]
+struct Resolver {
+    functions: HashMap<String, FunctionDecl>,
+    /// Block scopes mapping source names to unique names, innermost last
+    scopes: Vec<HashMap<String, String>>,
+    /// Labels of the current function, which are in scope in all of it
+    labels: HashSet<String>,
+    /// Targets of the `goto`s of the current function, checked once all of
+    /// its labels are known
+    gotos: Vec<(String, Span)>,
+    /// Number of loops enclosing the current statement
+    loop_depth: usize,
+    counter: usize,
+    errors: Vec<CompilerError<SemanticError>>,
+}
+
+impl Resolver {
+    fn error(&mut self, error: SemanticError, span: Span) {
+        self.errors.push(error.with_span(span));
+    }
+
+    fn resolve_function(&mut self, func: &mut FuncDef<'_>) {
+        let defined = func.body.is_some();
+        match self.functions.get_mut(func.name.as_ref()) {
+            Some(decl)
+                if decl.return_type != func.return_type || decl.params != func.params.len() =>
+            {
+                self.error(
+                    SemanticError::ConflictingDeclaration(func.name.to_string()),
+                    func.span,
+                );
+            }
+            Some(decl) if decl.defined && defined => {
+                self.error(
+                    SemanticError::DuplicateDefinition(func.name.to_string()),
+                    func.span,
+                );
+            }
+            Some(decl) => decl.defined |= defined,
+            None => {
+                self.functions.insert(
+                    func.name.to_string(),
+                    FunctionDecl {
+                        return_type: func.return_type.clone(),
+                        params: func.params.len(),
+                        defined,
+                    },
+                );
+            }
+        }
+
+        // Parameters share the scope of the outermost block of the body
+        self.scopes.push(HashMap::new());
+        for (_, name) in &mut func.params {
+            self.declare(name, func.span);
+        }
+        if let Some(body) = &mut func.body {
+            for stmt in &mut body.statements {
+                self.resolve_statement(stmt);
+            }
+        }
+        self.scopes.pop();
+
+        // A `goto` may jump forward, so targets are only checked at the end
+        let labels = std::mem::take(&mut self.labels);
+        for (label, span) in std::mem::take(&mut self.gotos) {
+            if !labels.contains(&label) {
+                self.error(SemanticError::UndefinedLabel(label), span);
+            }
+        }
+    }
+
+    /// Enters `name` into the innermost scope and renames it.
+    fn declare(&mut self, name: &mut Cow<'_, str>, span: Span) {
+        let scope = self
+            .scopes
+            .last_mut()
+            .expect("declaration outside of any scope");
+        if scope.contains_key(name.as_ref()) {
+            self.error(SemanticError::DuplicateDeclaration(name.to_string()), span);
+            return;
+        }
+        let unique = format!("{}.{}", name, self.counter);
+        self.counter += 1;
+        scope.insert(name.to_string(), unique.clone());
+        *name = Cow::Owned(unique);
+    }
+
+    fn lookup(&self, name: &str) -> Option<&String> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn resolve_block(&mut self, block: &mut BlockStmt<'_>) {
+        self.scopes.push(HashMap::new());
+        for stmt in &mut block.statements {
+            self.resolve_statement(stmt);
+        }
+        self.scopes.pop();
+    }
+
+    /// Resolves the body of an `if` or a loop, which is a scope of its own
+    /// even when it is not a block.
+    fn resolve_substatement(&mut self, stmt: &mut Statement<'_>) {
+        self.scopes.push(HashMap::new());
+        self.resolve_statement(stmt);
+        self.scopes.pop();
+    }
+
+    /// Resolves the body of a loop, where `break` and `continue` may appear.
+    fn resolve_loop_body(&mut self, body: &mut Statement<'_>) {
+        self.loop_depth += 1;
+        self.resolve_substatement(body);
+        self.loop_depth -= 1;
+    }
+
+    fn resolve_statement(&mut self, stmt: &mut Statement<'_>) {
+        match stmt {
+            Statement::Block(block) => self.resolve_block(block),
+            Statement::Break(stmt) if self.loop_depth == 0 => {
+                self.error(SemanticError::BreakOutsideLoop, stmt.span)
+            }
+            Statement::Continue(stmt) if self.loop_depth == 0 => {
+                self.error(SemanticError::ContinueOutsideLoop, stmt.span)
+            }
+            Statement::Break(_) | Statement::Continue(_) | Statement::Null(_) => {}
+            Statement::Declaration(decl) => {
+                // The variable is in scope in its own initializer
+                self.declare(&mut decl.name, decl.span);
+                if let Some(init) = &mut decl.initializer {
+                    self.resolve_expression(init);
+                }
+            }
+            Statement::DoWhile(do_while) => {
+                self.resolve_loop_body(&mut do_while.body);
+                self.resolve_expression(&mut do_while.cond);
+            }
+            Statement::Expr(expr_stmt) => self.resolve_expression(&mut expr_stmt.expr),
+            Statement::Goto(goto) => self.gotos.push((goto.label.to_string(), goto.span)),
+            Statement::Labeled(labeled) => {
+                if !self.labels.insert(labeled.label.to_string()) {
+                    self.error(
+                        SemanticError::DuplicateLabel(labeled.label.to_string()),
+                        labeled.span,
+                    );
+                }
+                self.resolve_statement(&mut labeled.stmt);
+            }
+            Statement::For(for_stmt) => {
+                for expr in [&mut for_stmt.init, &mut for_stmt.cond, &mut for_stmt.post]
+                    .into_iter()
+                    .flatten()
+                {
+                    self.resolve_expression(expr);
+                }
+                self.resolve_loop_body(&mut for_stmt.body);
+            }
+            Statement::If(if_stmt) => {
+                self.resolve_expression(&mut if_stmt.cond);
+                self.resolve_substatement(&mut if_stmt.then_block);
+                if let Some(else_block) = &mut if_stmt.else_block {
+                    self.resolve_substatement(else_block);
+                }
+            }
+            Statement::Return(ret) => {
+                if let Some(expr) = &mut ret.expr {
+                    self.resolve_expression(expr);
+                }
+            }
+            Statement::While(while_stmt) => {
+                self.resolve_expression(&mut while_stmt.cond);
+                self.resolve_loop_body(&mut while_stmt.body);
+            }
+        }
+    }
+
+    /// Resolves the identifiers of `expr`.
+    fn resolve_expression(&mut self, expr: &mut Expression<'_>) {
+        let span = expr.span;
+        match &mut expr.kind {
+            ExprKind::Constant(_) => {}
+            ExprKind::Variable(name) => match self.lookup(name) {
+                Some(unique) => *name = Cow::Owned(unique.clone()),
+                None if self.functions.contains_key(name.as_ref()) => {
+                    self.error(SemanticError::FunctionUsedAsValue(name.to_string()), span)
+                }
+                None => self.error(SemanticError::UndeclaredVariable(name.to_string()), span),
+            },
+            ExprKind::Grouped(inner) => self.resolve_expression(inner),
+            ExprKind::Unary { expr, .. } | ExprKind::IncDec { expr, .. } => {
+                self.resolve_expression(expr)
+            }
+            ExprKind::Binary { lhs, rhs, .. }
+            | ExprKind::Logical { lhs, rhs, .. }
+            | ExprKind::Comma { lhs, rhs }
+            | ExprKind::Assignment { lhs, rhs, .. } => {
+                self.resolve_expression(lhs);
+                self.resolve_expression(rhs);
+            }
+            ExprKind::Conditional {
+                cond,
+                then_expr,
+                else_expr,
+            } => {
+                self.resolve_expression(cond);
+                self.resolve_expression(then_expr);
+                self.resolve_expression(else_expr);
+            }
+            ExprKind::FunctionCall { callee, args } => {
+                self.resolve_callee(callee, args.len(), span);
+                for arg in args {
+                    self.resolve_expression(arg);
+                }
+            }
+        }
+    }
+
+    /// Resolves the function called by the call at `span`, which passes
+    /// `found` arguments.
+    fn resolve_callee(&mut self, callee: &mut Expression<'_>, found: usize, span: Span) {
+        let ExprKind::Variable(name) = &mut callee.kind else {
+            self.resolve_expression(callee);
+            self.error(SemanticError::NotAFunction, span);
+            return;
+        };
+        if self.lookup(name).is_some() {
+            // A local hides the function of the same name
+            self.error(SemanticError::NotAFunction, span);
+            return;
+        }
+        let error = match self.functions.get(name.as_ref()) {
+            None => SemanticError::UndeclaredFunction(name.to_string()),
+            Some(decl) if decl.params != found => SemanticError::ArgumentCount {
+                name: name.to_string(),
+                expected: decl.params,
+                found,
+            },
+            Some(_) => return,
+        };
+        self.error(error, span);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer_base::Lexer, parser_base::Parser};
+
+    fn resolve_source(input: &str) -> SemanticResult<Program<'_>> {
+        let mut program = Parser::new(Lexer::new(input)).parse().unwrap();
+        resolve(&mut program)?;
+        Ok(program)
+    }
+
+    fn error_kinds(input: &str) -> Vec<SemanticError> {
+        match resolve_source(input) {
+            Ok(_) => Vec::new(),
+            Err(errors) => errors.into_iter().map(|e| e.error).collect(),
+        }
+    }
+
+    fn returned(stmt: &Statement<'_>) -> String {
+        match stmt {
+            Statement::Return(ReturnStmt {
+                expr:
+                    Some(Expression {
+                        kind: ExprKind::Variable(name),
+                        ..
+                    }),
+                ..
+            }) => name.to_string(),
+            other => panic!("expected `return <variable>`, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_locals_are_renamed_uniquely() {
+        let program =
+            resolve_source("int main(void) { int x = 1; { int x = 2; return x; } return x; }")
+                .unwrap();
+        let body = &program.functions[0].body.as_ref().unwrap().statements;
+        let Statement::Declaration(outer) = &body[0] else {
+            panic!("expected a declaration");
+        };
+        let Statement::Block(block) = &body[1] else {
+            panic!("expected a block");
+        };
+        let Statement::Declaration(inner) = &block.statements[0] else {
+            panic!("expected a declaration");
+        };
+        assert_ne!(outer.name, inner.name);
+        assert_eq!(returned(&block.statements[1]), inner.name);
+        assert_eq!(returned(&body[2]), outer.name);
+    }
+
+    #[test]
+    fn test_parameters_are_renamed() {
+        let program = resolve_source("int f(int a) { return a; }").unwrap();
+        let func = &program.functions[0];
+        assert_ne!(func.params[0].1, "a");
+        assert_eq!(
+            returned(&func.body.as_ref().unwrap().statements[0]),
+            func.params[0].1
+        );
+    }
+
+    #[test]
+    fn test_undeclared_variable() {
+        let input = "int main(void) {\n    int x = 1;\n    return y;\n}";
+        let errors = resolve_source(input).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].error,
+            SemanticError::UndeclaredVariable("y".into())
+        );
+        assert_eq!(errors[0].span.line, 3);
+        assert_eq!(errors[0].span.column, 12);
+    }
+
+    #[test]
+    fn test_variable_out_of_scope() {
+        assert_eq!(
+            error_kinds("int main(void) { { int x = 1; } return x; }"),
+            vec![SemanticError::UndeclaredVariable("x".into())]
+        );
+        assert_eq!(
+            error_kinds("int main(void) { if (1) int x = 1; return x; }"),
+            vec![SemanticError::UndeclaredVariable("x".into())]
+        );
+    }
+
+    #[test]
+    fn test_every_declarator_declares_a_variable() {
+        let program = resolve_source("int main(void) { int a = 1, b = a; return b; }").unwrap();
+        let body = &program.functions[0].body.as_ref().unwrap().statements;
+        let [Statement::Declaration(a), Statement::Declaration(b), ret] = &body[..] else {
+            panic!("expected two declarations and a return");
+        };
+        assert_eq!(
+            b.initializer.as_ref().map(|init| &init.kind),
+            Some(&ExprKind::Variable(a.name.clone()))
+        );
+        assert_eq!(returned(ret), b.name);
+        // In the body of an `if` they are in a scope of their own
+        assert_eq!(
+            error_kinds("int main(void) { if (1) int a = 1, b = a; return b; }"),
+            vec![SemanticError::UndeclaredVariable("b".into())]
+        );
+        assert_eq!(
+            error_kinds("int main(void) { int a, a; return 0; }"),
+            vec![SemanticError::DuplicateDeclaration("a".into())]
+        );
+    }
+
+    #[test]
+    fn test_labeled_declarators_are_declared_in_the_block() {
+        let input = "int main(void) { goto l; l: int a = 1, b = 2; return a + b; }";
+        assert!(error_kinds(input).is_empty());
+        // Under an `if` the label is on the block grouping them
+        assert_eq!(
+            error_kinds("int main(void) { if (1) l: int a = 1, b = a; return b; }"),
+            vec![SemanticError::UndeclaredVariable("b".into())]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_declaration_in_one_scope() {
+        let input = "int main(void) {\n    int x = 1;\n    int x = 2;\n    return x;\n}";
+        let errors = resolve_source(input).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].error,
+            SemanticError::DuplicateDeclaration("x".into())
+        );
+        assert_eq!(errors[0].span.line, 3);
+
+        // A parameter is in the same scope as the top of the body
+        assert_eq!(
+            error_kinds("int f(int a) { int a = 1; return a; }"),
+            vec![SemanticError::DuplicateDeclaration("a".into())]
+        );
+        assert_eq!(
+            error_kinds("int f(int a, int a) { return a; }"),
+            vec![SemanticError::DuplicateDeclaration("a".into())]
+        );
+    }
+
+    #[test]
+    fn test_shadowing_in_nested_scope_is_allowed() {
+        assert!(error_kinds("int f(int a) { { int a = 2; return a; } }").is_empty());
+    }
+
+    #[test]
+    fn test_undeclared_function() {
+        let errors = resolve_source("int main(void) {\n    return f(1);\n}").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].error,
+            SemanticError::UndeclaredFunction("f".into())
+        );
+        assert_eq!(errors[0].span.line, 2);
+    }
+
+    #[test]
+    fn test_functions_must_be_declared_before_use() {
+        assert_eq!(
+            error_kinds("int main(void) { return f(); } int f(void) { return 1; }"),
+            vec![SemanticError::UndeclaredFunction("f".into())]
+        );
+        assert!(
+            error_kinds("int f(void); int main(void) { return f(); } int f(void) { return 1; }")
+                .is_empty()
+        );
+        // A function can call itself
+        assert!(error_kinds("int f(int n) { return f(n); }").is_empty());
+    }
+
+    #[test]
+    fn test_wrong_argument_count() {
+        assert_eq!(
+            error_kinds("int f(int a, int b) { return a; } int main(void) { return f(1); }"),
+            vec![SemanticError::ArgumentCount {
+                name: "f".into(),
+                expected: 2,
+                found: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_conflicting_and_duplicate_functions() {
+        assert_eq!(
+            error_kinds("int f(int a); int f(void) { return 0; }"),
+            vec![SemanticError::ConflictingDeclaration("f".into())]
+        );
+        assert_eq!(
+            error_kinds("int f(void) { return 0; } int f(void) { return 1; }"),
+            vec![SemanticError::DuplicateDefinition("f".into())]
+        );
+    }
+
+    #[test]
+    fn test_variables_and_functions_do_not_mix() {
+        assert_eq!(
+            error_kinds("int main(void) { int f = 1; return f(); }"),
+            vec![SemanticError::NotAFunction]
+        );
+        assert_eq!(
+            error_kinds("int f(void) { return 0; } int main(void) { return f + 1; }"),
+            vec![SemanticError::FunctionUsedAsValue("f".into())]
+        );
+    }
+
+    #[test]
+    fn test_loop_control_outside_of_a_loop() {
+        let input = "int main(void) {\n    break;\n    continue;\n    return 0;\n}";
+        let errors = resolve_source(input).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].error, SemanticError::BreakOutsideLoop);
+        assert_eq!(errors[0].span.line, 2);
+        assert_eq!(errors[1].error, SemanticError::ContinueOutsideLoop);
+        assert_eq!(errors[1].span.line, 3);
+        // Leaving the loop ends the region where they are allowed
+        assert_eq!(
+            error_kinds("int main(void) { while (1) { if (1) break; } continue; }"),
+            vec![SemanticError::ContinueOutsideLoop]
+        );
+        assert!(
+            error_kinds("int main(void) { for (;;) { do continue; while (0); break; } }")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_labels_are_checked_per_function() {
+        // Jumps may go forward or backward, and names of labels do not clash
+        // with those of variables
+        assert!(
+            error_kinds("int main(void) { int x = 0; goto x; x: x = x + 1; goto x; }").is_empty()
+        );
+        assert_eq!(
+            error_kinds("int main(void) { a: ; { a: return 0; } }"),
+            vec![SemanticError::DuplicateLabel("a".into())]
+        );
+        assert_eq!(
+            error_kinds("int f(void) { a: return 0; } int main(void) { goto a; }"),
+            vec![SemanticError::UndefinedLabel("a".into())]
+        );
+        // The same label may be defined once in each function
+        assert!(
+            error_kinds("int f(void) { a: return 0; } int main(void) { a: return 1; }").is_empty()
+        );
+    }
+
+    #[test]
+    fn test_every_error_is_reported() {
+        assert_eq!(
+            error_kinds("int main(void) { a = b; return c(); }").len(),
+            3
+        );
+    }
+}
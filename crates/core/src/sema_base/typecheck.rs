@@ -0,0 +1,552 @@
+//! Type checking.
+//!
+//! Runs on a resolved program, so every name is known to refer to
+//! something declared and locals have unique names. Works out the type of
+//! every expression and builds the [`TypedProgram`] that TACKY generation
+//! consumes.
+
+use std::collections::HashMap;
+
+use crate::{
+    error::{CompilerError, IntoCompilerError},
+    grammar::*,
+    sema_base::{
+        SemanticError, SemanticResult, source_name,
+        typed::{
+            ExpressionKind, StatementKind, TypedExpression, TypedFunction, TypedProgram,
+            TypedStatement,
+        },
+    },
+};
+
+/// Checks the types of a resolved `program` and returns it typed, or
+/// returns every error found.
+pub fn typecheck(program: &Program<'_>) -> SemanticResult<TypedProgram> {
+    let mut checker = TypeChecker::default();
+    let functions = program
+        .functions
+        .iter()
+        .filter_map(|func| checker.check_function(func))
+        .collect();
+    match checker.errors.is_empty() {
+        true => Ok(TypedProgram { functions }),
+        false => Err(checker.errors),
+    }
+}
+
+/// Return and parameter types of a function
+struct Signature {
+    return_type: Type,
+    params: Vec<Type>,
+}
+
+#[derive(Default)]
+struct TypeChecker {
+    functions: HashMap<String, Signature>,
+    /// Types of the variables, by resolved name
+    variables: HashMap<String, Type>,
+    /// Name and return type of the function being checked
+    current: Option<(String, Type)>,
+    errors: Vec<CompilerError<SemanticError>>,
+}
+
+impl TypeChecker {
+    fn error(&mut self, error: SemanticError, span: Span) {
+        self.errors.push(error.with_span(span));
+    }
+
+    fn check_function(&mut self, func: &FuncDef<'_>) -> Option<TypedFunction> {
+        self.functions.insert(
+            func.name.to_string(),
+            Signature {
+                return_type: func.return_type.clone(),
+                params: func.params.iter().map(|(ty, _)| ty.clone()).collect(),
+            },
+        );
+        for (ty, name) in &func.params {
+            self.declare(ty, name, func.span);
+        }
+
+        let body = func.body.as_ref()?;
+        self.current = Some((func.name.to_string(), func.return_type.clone()));
+        let body = body
+            .statements
+            .iter()
+            .map(|stmt| self.check_statement(stmt))
+            .collect();
+        Some(TypedFunction {
+            name: func.name.to_string(),
+            return_type: func.return_type.clone(),
+            params: func
+                .params
+                .iter()
+                .map(|(_, name)| name.to_string())
+                .collect(),
+            body,
+            span: func.span,
+        })
+    }
+
+    fn declare(&mut self, ty: &Type, name: &str, span: Span) {
+        if *ty == Type::Void {
+            let name = source_name(name).to_string();
+            self.error(SemanticError::VoidVariable(name), span);
+        }
+        self.variables.insert(name.to_string(), ty.clone());
+    }
+
+    fn check_statement(&mut self, stmt: &Statement<'_>) -> TypedStatement {
+        let span = stmt.span();
+        let kind = match stmt {
+            Statement::Block(block) => StatementKind::Block(
+                block
+                    .statements
+                    .iter()
+                    .map(|stmt| self.check_statement(stmt))
+                    .collect(),
+            ),
+            Statement::Break(_) => StatementKind::Break,
+            Statement::Continue(_) => StatementKind::Continue,
+            Statement::Declaration(decl) => {
+                self.declare(&decl.var_type, &decl.name, span);
+                StatementKind::Declaration {
+                    name: decl.name.to_string(),
+                    initializer: decl
+                        .initializer
+                        .as_ref()
+                        .map(|init| self.convert(init, &decl.var_type)),
+                }
+            }
+            Statement::DoWhile(do_while) => StatementKind::DoWhile {
+                body: Box::new(self.check_statement(&do_while.body)),
+                cond: self.check_value(&do_while.cond),
+            },
+            // The value of an expression statement is discarded, so it may
+            // be `void`
+            Statement::Expr(expr_stmt) => {
+                StatementKind::Expr(self.check_expression(&expr_stmt.expr))
+            }
+            Statement::For(for_stmt) => StatementKind::For {
+                init: for_stmt
+                    .init
+                    .as_ref()
+                    .map(|init| self.check_expression(init)),
+                cond: for_stmt.cond.as_ref().map(|cond| self.check_value(cond)),
+                post: for_stmt
+                    .post
+                    .as_ref()
+                    .map(|post| self.check_expression(post)),
+                body: Box::new(self.check_statement(&for_stmt.body)),
+            },
+            Statement::Goto(goto) => StatementKind::Goto(goto.label.to_string()),
+            Statement::If(if_stmt) => StatementKind::If {
+                cond: self.check_value(&if_stmt.cond),
+                then_block: Box::new(self.check_statement(&if_stmt.then_block)),
+                else_block: if_stmt
+                    .else_block
+                    .as_ref()
+                    .map(|stmt| Box::new(self.check_statement(stmt))),
+            },
+            Statement::Labeled(labeled) => StatementKind::Labeled {
+                label: labeled.label.to_string(),
+                body: Box::new(self.check_statement(&labeled.stmt)),
+            },
+            Statement::Null(_) => StatementKind::Null,
+            Statement::Return(ret) => StatementKind::Return(self.check_return(ret)),
+            Statement::While(while_stmt) => StatementKind::While {
+                cond: self.check_value(&while_stmt.cond),
+                body: Box::new(self.check_statement(&while_stmt.body)),
+            },
+        };
+        TypedStatement { kind, span }
+    }
+
+    fn check_return(&mut self, ret: &ReturnStmt<'_>) -> Option<TypedExpression> {
+        let (name, return_type) = self.current.clone().expect("return outside of a function");
+        match (&ret.expr, return_type) {
+            (Some(expr), Type::Void) => {
+                // Still check the expression, for errors of its own
+                self.check_expression(expr);
+                self.error(SemanticError::ReturnValueInVoidFunction(name), ret.span);
+                None
+            }
+            (Some(expr), return_type) => Some(self.convert(expr, &return_type)),
+            (None, Type::Void) => None,
+            (None, _) => {
+                self.error(SemanticError::MissingReturnValue(name), ret.span);
+                None
+            }
+        }
+    }
+
+    /// Checks `expr` where its value is used, which rules out `void`.
+    fn check_value(&mut self, expr: &Expression<'_>) -> TypedExpression {
+        let typed = self.check_expression(expr);
+        if typed.ty == Type::Void {
+            self.error(SemanticError::VoidValue, expr.span);
+        }
+        typed
+    }
+
+    /// Checks `expr` where a value of type `ty` is expected, as for an
+    /// initializer or a returned value, and converts it to `ty`.
+    fn convert(&mut self, expr: &Expression<'_>, ty: &Type) -> TypedExpression {
+        let typed = self.check_value(expr);
+        convert_to(typed, ty)
+    }
+
+    fn check_expression(&mut self, expr: &Expression<'_>) -> TypedExpression {
+        match &expr.kind {
+            ExprKind::Constant(value) => {
+                TypedExpression::new(ExpressionKind::Constant(*value), Type::Int)
+            }
+            ExprKind::Variable(name) => {
+                let ty = self.variables[name.as_ref()].clone();
+                TypedExpression::new(ExpressionKind::Variable(name.to_string()), ty)
+            }
+            ExprKind::Grouped(inner) => self.check_expression(inner),
+            ExprKind::Unary { op, expr } => {
+                let expr = self.check_value(expr);
+                TypedExpression::new(
+                    ExpressionKind::Unary {
+                        op: op.clone(),
+                        expr: Box::new(expr),
+                    },
+                    Type::Int,
+                )
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let lhs = self.check_value(lhs);
+                let rhs = self.check_value(rhs);
+                TypedExpression::new(
+                    ExpressionKind::Binary {
+                        op: op.clone(),
+                        lhs: Box::new(lhs),
+                        rhs: Box::new(rhs),
+                    },
+                    Type::Int,
+                )
+            }
+            ExprKind::IncDec { op, expr } => {
+                let expr = self.check_value(expr);
+                let ty = expr.ty.clone();
+                TypedExpression::new(
+                    ExpressionKind::IncDec {
+                        op: op.clone(),
+                        expr: Box::new(expr),
+                    },
+                    ty,
+                )
+            }
+            ExprKind::Logical { op, lhs, rhs } => {
+                let lhs = self.check_value(lhs);
+                let rhs = self.check_value(rhs);
+                TypedExpression::new(
+                    ExpressionKind::Logical {
+                        op: op.clone(),
+                        lhs: Box::new(lhs),
+                        rhs: Box::new(rhs),
+                    },
+                    Type::Int,
+                )
+            }
+            // For a compound assignment, the usual arithmetic conversions
+            // would bring both operands to a common type, which the result
+            // is then converted back from. With `int` as the only arithmetic
+            // type, all of these leave the operands as they are.
+            ExprKind::Assignment { op, lhs, rhs } => {
+                let lhs = self.check_value(lhs);
+                let rhs = self.convert(rhs, &lhs.ty);
+                let ty = lhs.ty.clone();
+                TypedExpression::new(
+                    ExpressionKind::Assignment {
+                        op: op.clone(),
+                        lhs: Box::new(lhs),
+                        rhs: Box::new(rhs),
+                    },
+                    ty,
+                )
+            }
+            ExprKind::Conditional {
+                cond,
+                then_expr,
+                else_expr,
+            } => {
+                let cond = self.check_value(cond);
+                let then_expr = self.check_expression(then_expr);
+                let else_expr = self.check_expression(else_expr);
+                let ty = self.common_type(&then_expr.ty, &else_expr.ty, expr.span);
+                TypedExpression::new(
+                    ExpressionKind::Conditional {
+                        cond: Box::new(cond),
+                        then_expr: Box::new(convert_to(then_expr, &ty)),
+                        else_expr: Box::new(convert_to(else_expr, &ty)),
+                    },
+                    ty,
+                )
+            }
+            // Only the value of `rhs` is used
+            ExprKind::Comma { lhs, rhs } => {
+                let lhs = self.check_expression(lhs);
+                let rhs = self.check_expression(rhs);
+                let ty = rhs.ty.clone();
+                TypedExpression::new(
+                    ExpressionKind::Comma {
+                        lhs: Box::new(lhs),
+                        rhs: Box::new(rhs),
+                    },
+                    ty,
+                )
+            }
+            ExprKind::FunctionCall { callee, args } => self.check_call(callee, args),
+        }
+    }
+
+    /// The type both arms of a conditional are converted to. With `int` and
+    /// `void` as the only types, that requires both to be the same.
+    fn common_type(&mut self, lhs: &Type, rhs: &Type, span: Span) -> Type {
+        if lhs != rhs {
+            self.error(
+                SemanticError::ConditionalTypes(lhs.clone(), rhs.clone()),
+                span,
+            );
+        }
+        lhs.clone()
+    }
+
+    fn check_call(&mut self, callee: &Expression<'_>, args: &[Expression<'_>]) -> TypedExpression {
+        let ExprKind::Variable(name) = &callee.kind else {
+            unreachable!("the resolver only accepts calls of function names")
+        };
+        let signature = &self.functions[name.as_ref()];
+        let return_type = signature.return_type.clone();
+        let params = signature.params.clone();
+
+        let args = args
+            .iter()
+            .zip(&params)
+            .enumerate()
+            .map(|(idx, (arg, param))| {
+                let span = arg.span;
+                let arg = self.check_expression(arg);
+                if arg.ty != *param {
+                    self.error(
+                        SemanticError::ArgumentType {
+                            name: name.to_string(),
+                            position: idx + 1,
+                            expected: param.clone(),
+                            found: arg.ty.clone(),
+                        },
+                        span,
+                    );
+                }
+                // Arguments are converted as if assigned to the parameters
+                convert_to(arg, param)
+            })
+            .collect();
+        TypedExpression::new(
+            ExpressionKind::FunctionCall {
+                name: name.to_string(),
+                args,
+            },
+            return_type,
+        )
+    }
+}
+
+/// Wraps `expr` in a conversion to `ty` when it has another type. A `void`
+/// operand cannot be converted and is left as it is, for its error has
+/// been reported where it is used.
+fn convert_to(expr: TypedExpression, ty: &Type) -> TypedExpression {
+    match expr.ty == *ty || expr.ty == Type::Void {
+        true => expr,
+        false => TypedExpression::new(ExpressionKind::Convert(Box::new(expr)), ty.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer_base::Lexer, parser_base::Parser, sema_base::resolve};
+
+    fn check(input: &str) -> SemanticResult<TypedProgram> {
+        let mut program = Parser::new(Lexer::new(input)).parse().unwrap();
+        resolve(&mut program).unwrap();
+        typecheck(&program)
+    }
+
+    fn error_kinds(input: &str) -> Vec<SemanticError> {
+        match check(input) {
+            Ok(_) => Vec::new(),
+            Err(errors) => errors.into_iter().map(|e| e.error).collect(),
+        }
+    }
+
+    #[test]
+    fn test_every_expression_is_typed() {
+        let program = check("void g(void) { } int f(int a) { g(); return a + 1; }").unwrap();
+        let body = &program.functions[1].body;
+        let StatementKind::Expr(call) = &body[0].kind else {
+            panic!("expected an expression statement");
+        };
+        assert_eq!(call.ty, Type::Void);
+        let StatementKind::Return(Some(sum)) = &body[1].kind else {
+            panic!("expected a return with a value");
+        };
+        assert_eq!(sum.ty, Type::Int);
+        let ExpressionKind::Binary { lhs, .. } = &sum.kind else {
+            panic!("expected a binary expression");
+        };
+        assert_eq!(lhs.ty, Type::Int);
+    }
+
+    #[test]
+    fn test_declarations_are_dropped() {
+        let program = check("int f(void); int main(void) { return f(); }").unwrap();
+        assert_eq!(program.functions.len(), 1);
+        assert_eq!(program.functions[0].name, "main");
+    }
+
+    #[test]
+    fn test_return_value_in_void_function() {
+        let errors = check("void baz() {\n    return 0;\n}").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].error,
+            SemanticError::ReturnValueInVoidFunction("baz".into())
+        );
+        assert_eq!(errors[0].span.line, 2);
+        assert!(error_kinds("void baz() { return; }").is_empty());
+    }
+
+    #[test]
+    fn test_return_without_value_in_int_function() {
+        assert_eq!(
+            error_kinds("int main(void) { return; }"),
+            vec![SemanticError::MissingReturnValue("main".into())]
+        );
+    }
+
+    #[test]
+    fn test_void_values_cannot_be_used() {
+        let void_fn = "void g(void) { }";
+        for body in [
+            "int x = g(); return x;",
+            "return g();",
+            "return 1 + g();",
+            "if (g()) return 1; return 0;",
+            "while (g()) { } return 0;",
+        ] {
+            let input = format!("{} int main(void) {{ {} }}", void_fn, body);
+            assert_eq!(
+                error_kinds(&input),
+                vec![SemanticError::VoidValue],
+                "{}",
+                body
+            );
+        }
+        let input = format!(
+            "{} int main(void) {{ g(); for (g(); 1; g()) break; return 0; }}",
+            void_fn
+        );
+        assert!(error_kinds(&input).is_empty());
+    }
+
+    #[test]
+    fn test_errors_point_at_the_offending_expression() {
+        let columns = |input: &str| {
+            let errors = check(input).unwrap_err();
+            errors.iter().map(|e| e.span.column).collect::<Vec<_>>()
+        };
+        let void_fn = "void g(void) { }\n";
+        // `g()` in `    return 1 + g();`
+        assert_eq!(
+            columns(&format!(
+                "{}int main(void) {{\n    return 1 + g();\n}}",
+                void_fn
+            )),
+            vec![16]
+        );
+        // The whole conditional, and then the argument
+        assert_eq!(
+            columns(&format!(
+                "{}int f(int a) {{ return a; }}\nint main(void) {{\n    return 0 + (1 ? 2 : g()), f(g());\n}}",
+                void_fn
+            )),
+            vec![17, 33]
+        );
+    }
+
+    #[test]
+    fn test_conditional_arms_get_a_common_type() {
+        let program =
+            check("void g(void) { } int main(void) { 1 ? g() : g(); return 1 ? 2 : 3; }").unwrap();
+        let body = &program.functions[1].body;
+        let StatementKind::Expr(void) = &body[0].kind else {
+            panic!("expected an expression statement");
+        };
+        assert_eq!(void.ty, Type::Void);
+        let StatementKind::Return(Some(int)) = &body[1].kind else {
+            panic!("expected a return with a value");
+        };
+        assert_eq!(int.ty, Type::Int);
+
+        assert_eq!(
+            error_kinds("void g(void) { } int main(void) { 1 ? 2 : g(); return 0; }"),
+            vec![SemanticError::ConditionalTypes(Type::Int, Type::Void)]
+        );
+        assert_eq!(
+            error_kinds("void g(void) { } int main(void) { return g() ? 1 : 2; }"),
+            vec![SemanticError::VoidValue]
+        );
+    }
+
+    #[test]
+    fn test_conversions_are_inserted_where_types_differ() {
+        let int = TypedExpression::new(ExpressionKind::Constant(1), Type::Int);
+        assert_eq!(convert_to(int.clone(), &Type::Int), int);
+        let discarded = convert_to(int.clone(), &Type::Void);
+        assert_eq!(discarded.ty, Type::Void);
+        assert_eq!(discarded.kind, ExpressionKind::Convert(Box::new(int)));
+        let void = TypedExpression::new(
+            ExpressionKind::FunctionCall {
+                name: "g".into(),
+                args: Vec::new(),
+            },
+            Type::Void,
+        );
+        assert_eq!(convert_to(void.clone(), &Type::Int), void);
+    }
+
+    #[test]
+    fn test_comma_has_the_type_of_its_right_operand() {
+        let void_fn = "void g(void) { }";
+        let input = format!("{} int main(void) {{ return g(), 1; }}", void_fn);
+        assert!(error_kinds(&input).is_empty());
+        let input = format!("{} int main(void) {{ g(), g(); return 1, g(); }}", void_fn);
+        assert_eq!(error_kinds(&input), vec![SemanticError::VoidValue]);
+    }
+
+    #[test]
+    fn test_void_variables_are_rejected() {
+        assert_eq!(
+            error_kinds("int main(void) { void x; return 0; }"),
+            vec![SemanticError::VoidVariable("x".into())]
+        );
+    }
+
+    #[test]
+    fn test_argument_types_must_match_parameters() {
+        assert_eq!(
+            error_kinds(
+                "void g(void) { } int f(int a) { return a; } int main(void) { return f(g()); }"
+            ),
+            vec![SemanticError::ArgumentType {
+                name: "f".into(),
+                position: 1,
+                expected: Type::Int,
+                found: Type::Void,
+            }]
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! The typed AST.
+//!
+//! This is what the type checker hands to TACKY generation: the resolved
+//! program with the type of every expression spelled out. Parentheses are
+//! gone, calls name their function directly, and only functions with a
+//! body are kept.
+
+use crate::grammar::{AssignOp, BinaryOp, IncDecOp, LogicalOp, Span, Type, UnaryOp};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedProgram {
+    pub functions: Vec<TypedFunction>,
+}
+
+/// A function definition
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedFunction {
+    pub name: String,
+    pub return_type: Type,
+    /// Resolved names of the parameters
+    pub params: Vec<String>,
+    pub body: Vec<TypedStatement>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedStatement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatementKind {
+    Block(Vec<TypedStatement>),
+    Break,
+    Continue,
+    Declaration {
+        name: String,
+        initializer: Option<TypedExpression>,
+    },
+    DoWhile {
+        body: Box<TypedStatement>,
+        cond: TypedExpression,
+    },
+    Expr(TypedExpression),
+    For {
+        init: Option<TypedExpression>,
+        cond: Option<TypedExpression>,
+        post: Option<TypedExpression>,
+        body: Box<TypedStatement>,
+    },
+    Goto(String),
+    If {
+        cond: TypedExpression,
+        then_block: Box<TypedStatement>,
+        else_block: Option<Box<TypedStatement>>,
+    },
+    Labeled {
+        label: String,
+        body: Box<TypedStatement>,
+    },
+    Null,
+    /// `None` in functions returning `void`
+    Return(Option<TypedExpression>),
+    While {
+        cond: TypedExpression,
+        body: Box<TypedStatement>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedExpression {
+    pub kind: ExpressionKind,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpressionKind {
+    Constant(i64),
+    Variable(String),
+    Unary {
+        op: UnaryOp,
+        expr: Box<TypedExpression>,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Box<TypedExpression>,
+        rhs: Box<TypedExpression>,
+    },
+    IncDec {
+        op: IncDecOp,
+        expr: Box<TypedExpression>,
+    },
+    Logical {
+        op: LogicalOp,
+        lhs: Box<TypedExpression>,
+        rhs: Box<TypedExpression>,
+    },
+    Conditional {
+        cond: Box<TypedExpression>,
+        then_expr: Box<TypedExpression>,
+        else_expr: Box<TypedExpression>,
+    },
+    Comma {
+        lhs: Box<TypedExpression>,
+        rhs: Box<TypedExpression>,
+    },
+    Assignment {
+        op: AssignOp,
+        lhs: Box<TypedExpression>,
+        rhs: Box<TypedExpression>,
+    },
+    FunctionCall {
+        name: String,
+        args: Vec<TypedExpression>,
+    },
+    /// `expr` converted to the type of the enclosing expression
+    Convert(Box<TypedExpression>),
+}
+
+impl TypedExpression {
+    pub fn new(kind: ExpressionKind, ty: Type) -> Self {
+        Self { kind, ty }
+    }
+}
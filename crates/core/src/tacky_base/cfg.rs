@@ -0,0 +1,299 @@
+//! Control-flow graph over the body of a [`TackyFuncDef`].
+//!
+//! [`TackyFuncDef`]: crate::tacky_base::TackyFuncDef
+
+use std::collections::HashMap;
+
+use crate::tacky_base::Instruction;
+
+/// A maximal straight-line sequence of instructions. Only the first
+/// instruction may be a label and only the last one may transfer control.
+#[derive(Debug, Clone, Default)]
+pub struct BasicBlock {
+    pub instructions: Vec<Instruction>,
+    pub predecessors: Vec<usize>,
+    pub successors: Vec<usize>,
+}
+
+impl BasicBlock {
+    /// The label that starts this block, if any.
+    pub fn label(&self) -> Option<&str> {
+        match self.instructions.first() {
+            Some(Instruction::Label(label)) => Some(label),
+            _ => None,
+        }
+    }
+
+    /// Whether control can fall through from the end of this block into the
+    /// block that follows it in program order.
+    fn falls_through(&self) -> bool {
+        !matches!(
+            self.instructions.last(),
+            Some(Instruction::Jump(_) | Instruction::Return(_) | Instruction::TailCall { .. })
+        )
+    }
+}
+
+/// Control-flow graph whose entry is always the first block. Blocks are kept
+/// in program order, so flattening the graph back into a list of
+/// instructions preserves fallthrough edges.
+#[derive(Debug, Clone, Default)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+}
+
+impl Cfg {
+    /// Splits a function body into basic blocks and connects them.
+    pub fn new(instructions: Vec<Instruction>) -> Self {
+        let mut blocks = Vec::new();
+        let mut current = Vec::new();
+
+        for inst in instructions {
+            match inst {
+                Instruction::Label(_) => {
+                    if !current.is_empty() {
+                        blocks.push(std::mem::take(&mut current));
+                    }
+                    current.push(inst);
+                }
+                Instruction::Jump(_)
+                | Instruction::JumpIfZero { .. }
+                | Instruction::JumpIfNotZero { .. }
+                | Instruction::Return(_)
+                | Instruction::TailCall { .. } => {
+                    current.push(inst);
+                    blocks.push(std::mem::take(&mut current));
+                }
+                _ => current.push(inst),
+            }
+        }
+        if !current.is_empty() {
+            blocks.push(current);
+        }
+
+        let mut cfg = Self {
+            blocks: blocks
+                .into_iter()
+                .map(|instructions| BasicBlock {
+                    instructions,
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        cfg.compute_edges();
+        cfg
+    }
+
+    /// Flattens the graph back into a list of instructions.
+    pub fn into_instructions(self) -> Vec<Instruction> {
+        self.blocks
+            .into_iter()
+            .flat_map(|block| block.instructions)
+            .collect()
+    }
+
+    /// Index of the block that starts with each label.
+    pub fn label_map(&self) -> HashMap<String, usize> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, block)| block.label().map(|label| (label.to_string(), idx)))
+            .collect()
+    }
+
+    /// A label starting with `prefix` that no block uses yet.
+    pub fn fresh_label(&self, prefix: &str) -> String {
+        let labels = self.label_map();
+        (0..)
+            .map(|n| format!("{}.{}", prefix, n))
+            .find(|label| !labels.contains_key(label))
+            .unwrap()
+    }
+
+    /// Recomputes predecessor and successor lists from the instructions.
+    pub fn compute_edges(&mut self) {
+        let labels = self.label_map();
+        let block_count = self.blocks.len();
+
+        for idx in 0..block_count {
+            let block = &self.blocks[idx];
+            let mut successors = Vec::new();
+            match block.instructions.last() {
+                Some(Instruction::Jump(target)) => successors.push(labels[target]),
+                Some(
+                    Instruction::JumpIfZero { target, .. }
+                    | Instruction::JumpIfNotZero { target, .. },
+                ) => {
+                    successors.push(labels[target]);
+                    if idx + 1 < block_count {
+                        successors.push(idx + 1);
+                    }
+                }
+                Some(Instruction::Return(_) | Instruction::TailCall { .. }) => {}
+                _ => {
+                    if idx + 1 < block_count {
+                        successors.push(idx + 1);
+                    }
+                }
+            }
+            successors.dedup();
+            self.blocks[idx].successors = successors;
+        }
+
+        for block in &mut self.blocks {
+            block.predecessors.clear();
+        }
+        for idx in 0..block_count {
+            for succ in self.blocks[idx].successors.clone() {
+                if !self.blocks[succ].predecessors.contains(&idx) {
+                    self.blocks[succ].predecessors.push(idx);
+                }
+            }
+        }
+    }
+
+    /// Marks every block that can be reached from the entry block.
+    pub fn reachable_blocks(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut worklist = Vec::new();
+        if !self.blocks.is_empty() {
+            worklist.push(0);
+        }
+        while let Some(idx) = worklist.pop() {
+            if std::mem::replace(&mut reachable[idx], true) {
+                continue;
+            }
+            worklist.extend(self.blocks[idx].successors.iter().copied());
+        }
+        reachable
+    }
+
+    /// Removes every block whose entry in `keep` is `false` and reconnects
+    /// the remaining ones.
+    pub fn retain_blocks(&mut self, keep: &[bool]) {
+        let mut idx = 0;
+        self.blocks.retain(|_| {
+            idx += 1;
+            keep[idx - 1]
+        });
+        self.compute_edges();
+    }
+
+    /// Index of the block that follows `idx` in program order, if any.
+    pub fn fallthrough_successor(&self, idx: usize) -> Option<usize> {
+        (self.blocks[idx].falls_through() && idx + 1 < self.blocks.len()).then_some(idx + 1)
+    }
+
+    /// Blocks in reverse postorder of a depth-first walk from the entry.
+    /// Unreachable blocks are not included.
+    pub fn reverse_postorder(&self) -> Vec<usize> {
+        let mut visited = vec![false; self.blocks.len()];
+        let mut postorder = Vec::new();
+        if self.blocks.is_empty() {
+            return postorder;
+        }
+
+        // Iterative DFS: (block, index of the next successor to visit)
+        let mut stack = vec![(0, 0)];
+        visited[0] = true;
+        while let Some((block, next)) = stack.pop() {
+            if let Some(&succ) = self.blocks[block].successors.get(next) {
+                stack.push((block, next + 1));
+                if !visited[succ] {
+                    visited[succ] = true;
+                    stack.push((succ, 0));
+                }
+            } else {
+                postorder.push(block);
+            }
+        }
+        postorder.reverse();
+        postorder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tacky_base::Value;
+
+    fn label(name: &str) -> Instruction {
+        Instruction::Label(name.to_string())
+    }
+
+    fn copy(value: i64, dst: &str) -> Instruction {
+        Instruction::Copy {
+            src: Value::Constant(value),
+            dst: dst.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_cfg_straight_line_is_single_block() {
+        let cfg = Cfg::new(vec![
+            copy(1, "x"),
+            copy(2, "y"),
+            Instruction::Return(Value::Var("x".into())),
+        ]);
+        assert_eq!(cfg.blocks.len(), 1);
+        assert!(cfg.blocks[0].successors.is_empty());
+    }
+
+    #[test]
+    fn test_cfg_conditional_branch_edges() {
+        // 0: jz x, else     1: copy; jmp end     2: else: copy     3: end: ret
+        let cfg = Cfg::new(vec![
+            Instruction::JumpIfZero {
+                cond: Value::Var("x".into()),
+                target: "else".into(),
+            },
+            copy(1, "y"),
+            Instruction::Jump("end".into()),
+            label("else"),
+            copy(2, "y"),
+            label("end"),
+            Instruction::Return(Value::Var("y".into())),
+        ]);
+        assert_eq!(cfg.blocks.len(), 4);
+        assert_eq!(cfg.blocks[0].successors, vec![2, 1]);
+        assert_eq!(cfg.blocks[1].successors, vec![3]);
+        assert_eq!(cfg.blocks[2].successors, vec![3]);
+        assert_eq!(cfg.blocks[3].predecessors, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_cfg_reachability_and_retain() {
+        let mut cfg = Cfg::new(vec![
+            Instruction::Return(Value::Constant(0)),
+            copy(1, "dead"),
+            label("loop"),
+            Instruction::Jump("loop".into()),
+        ]);
+        let reachable = cfg.reachable_blocks();
+        assert_eq!(reachable, vec![true, false, false]);
+
+        cfg.retain_blocks(&reachable);
+        assert_eq!(
+            cfg.into_instructions(),
+            vec![Instruction::Return(Value::Constant(0))]
+        );
+    }
+
+    #[test]
+    fn test_cfg_reverse_postorder_visits_entry_first() {
+        let cfg = Cfg::new(vec![
+            label("head"),
+            Instruction::JumpIfZero {
+                cond: Value::Var("x".into()),
+                target: "out".into(),
+            },
+            copy(1, "x"),
+            Instruction::Jump("head".into()),
+            label("out"),
+            Instruction::Return(Value::Var("x".into())),
+        ]);
+        let rpo = cfg.reverse_postorder();
+        assert_eq!(rpo[0], 0);
+        assert_eq!(rpo.len(), 3);
+    }
+}
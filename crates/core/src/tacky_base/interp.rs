@@ -0,0 +1,279 @@
+//! Reference interpreter for TACKY.
+//!
+//! Optimization passes are checked against it: running a function before
+//! and after a pass must produce the same result. Arithmetic follows the
+//! code generator and works on wrapping 32-bit values.
+
+use std::collections::HashMap;
+
+use crate::{
+    grammar::{BinaryOp, UnaryOp},
+    tacky_base::{Instruction, TackyFuncDef, TackyProgram, Value},
+};
+
+/// Evaluates a unary operator on a constant operand.
+pub fn eval_unary(op: &UnaryOp, value: i64) -> i64 {
+    let value = value as i32;
+    let result = match op {
+        UnaryOp::Negate => value.wrapping_neg(),
+        UnaryOp::Not => (value == 0) as i32,
+        UnaryOp::Complement => !value,
+    };
+    result as i64
+}
+
+/// Evaluates a binary operator on constant operands. Returns `None` when the
+/// operation has no defined result, e.g. a division by zero or a shift by
+/// at least the width of `int`.
+pub fn eval_binary(op: &BinaryOp, lhs: i64, rhs: i64) -> Option<i64> {
+    let (lhs, rhs) = (lhs as i32, rhs as i32);
+    let result = match op {
+        BinaryOp::Add => lhs.wrapping_add(rhs),
+        BinaryOp::Subtract => lhs.wrapping_sub(rhs),
+        BinaryOp::Multiply => lhs.wrapping_mul(rhs),
+        BinaryOp::Divide => lhs.checked_div(rhs)?,
+        BinaryOp::Remainder => lhs.checked_rem(rhs)?,
+        BinaryOp::BitwiseAnd => lhs & rhs,
+        BinaryOp::BitwiseOr => lhs | rhs,
+        BinaryOp::BitwiseXor => lhs ^ rhs,
+        // Bits shifted out of a negative value are lost, as on the target
+        BinaryOp::LeftShift => lhs.checked_shl(u32::try_from(rhs).ok()?)?,
+        BinaryOp::RightShift => lhs.checked_shr(u32::try_from(rhs).ok()?)?,
+        BinaryOp::LessThan => (lhs < rhs) as i32,
+        BinaryOp::GreaterThan => (lhs > rhs) as i32,
+        BinaryOp::Equal => (lhs == rhs) as i32,
+        BinaryOp::NotEqual => (lhs != rhs) as i32,
+        BinaryOp::LessThanOrEqual => (lhs <= rhs) as i32,
+        BinaryOp::GreaterThanOrEqual => (lhs >= rhs) as i32,
+    };
+    Some(result as i64)
+}
+
+/// Executes the functions of a [`TackyProgram`].
+///
+/// Calling a function that is not part of the program, reading a variable
+/// that was never written or dividing by zero panics.
+pub struct Interpreter<'p> {
+    functions: HashMap<&'p str, &'p TackyFuncDef>,
+    /// Upper bound on executed instructions, so a miscompiled loop fails a
+    /// test instead of hanging it.
+    fuel: usize,
+}
+
+impl<'p> Interpreter<'p> {
+    pub fn new(program: &'p TackyProgram) -> Self {
+        Self {
+            functions: program
+                .functions
+                .iter()
+                .map(|func| (func.name.as_str(), func))
+                .collect(),
+            fuel: 10_000_000,
+        }
+    }
+
+    /// Limits the number of instructions the interpreter may execute.
+    pub fn with_fuel(mut self, fuel: usize) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    /// Calls `name` with `args` and returns its result.
+    pub fn call(&mut self, name: &str, args: &[i64]) -> i64 {
+        let func = *self
+            .functions
+            .get(name)
+            .unwrap_or_else(|| panic!("call to unknown function `{}`", name));
+        assert_eq!(
+            func.params.len(),
+            args.len(),
+            "wrong number of arguments to `{}`",
+            name
+        );
+
+        let labels: HashMap<&str, usize> = func
+            .body
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, inst)| match inst {
+                Instruction::Label(label) => Some((label.as_str(), idx)),
+                _ => None,
+            })
+            .collect();
+        let mut vars: HashMap<&str, i64> = func
+            .params
+            .iter()
+            .map(String::as_str)
+            .zip(args.iter().copied())
+            .collect();
+
+        let read = |vars: &HashMap<&str, i64>, value: &Value| match value {
+            Value::Constant(val) => *val as i32 as i64,
+            Value::Var(name) => *vars
+                .get(name.as_str())
+                .unwrap_or_else(|| panic!("read of uninitialized variable `{}`", name)),
+        };
+
+        let mut pc = 0;
+        loop {
+            self.fuel = self
+                .fuel
+                .checked_sub(1)
+                .expect("interpreter ran out of fuel");
+            let inst = func
+                .body
+                .get(pc)
+                .unwrap_or_else(|| panic!("fell off the end of `{}`", name));
+            pc += 1;
+
+            match inst {
+                Instruction::Return(value) => return read(&vars, value),
+                Instruction::Copy { src, dst } => {
+                    vars.insert(dst, read(&vars, src));
+                }
+                Instruction::Unary { op, src, dst } => {
+                    vars.insert(dst, eval_unary(op, read(&vars, src)));
+                }
+                Instruction::Binary { op, lhs, rhs, dst } => {
+                    let result = eval_binary(op, read(&vars, lhs), read(&vars, rhs))
+                        .unwrap_or_else(|| panic!("undefined {:?} in `{}`", op, name));
+                    vars.insert(dst, result);
+                }
+                Instruction::Jump(target) => pc = labels[target.as_str()],
+                Instruction::JumpIfZero { cond, target } => {
+                    if read(&vars, cond) == 0 {
+                        pc = labels[target.as_str()];
+                    }
+                }
+                Instruction::JumpIfNotZero { cond, target } => {
+                    if read(&vars, cond) != 0 {
+                        pc = labels[target.as_str()];
+                    }
+                }
+                Instruction::Label(_) | Instruction::Loc { .. } => {}
+                Instruction::FunCall {
+                    name: callee,
+                    args,
+                    dst,
+                } => {
+                    let args: Vec<i64> = args.iter().map(|arg| read(&vars, arg)).collect();
+                    let result = self.call(callee, &args);
+                    if let Some(dst) = dst {
+                        vars.insert(dst, result);
+                    }
+                }
+                Instruction::TailCall { name: callee, args } => {
+                    let args: Vec<i64> = args.iter().map(|arg| read(&vars, arg)).collect();
+                    return self.call(callee, &args);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tacky_base::lower_source;
+
+    #[test]
+    fn test_eval_wraps_to_32_bits() {
+        assert_eq!(
+            eval_binary(&BinaryOp::Add, i32::MAX as i64, 1),
+            Some(i32::MIN as i64)
+        );
+        assert_eq!(eval_binary(&BinaryOp::Divide, 7, -2), Some(-3));
+        assert_eq!(eval_binary(&BinaryOp::Divide, 1, 0), None);
+        assert_eq!(eval_binary(&BinaryOp::Divide, i32::MIN as i64, -1), None);
+        assert_eq!(eval_unary(&UnaryOp::Not, 5), 0);
+    }
+
+    #[test]
+    fn test_eval_remainder_bitwise_and_shifts() {
+        assert_eq!(eval_unary(&UnaryOp::Complement, 5), -6);
+        assert_eq!(eval_binary(&BinaryOp::Remainder, -7, 2), Some(-1));
+        assert_eq!(eval_binary(&BinaryOp::Remainder, 7, 0), None);
+        assert_eq!(eval_binary(&BinaryOp::Remainder, i32::MIN as i64, -1), None);
+        assert_eq!(eval_binary(&BinaryOp::BitwiseXor, 6, 3), Some(5));
+        // A right shift of a negative value is arithmetic
+        assert_eq!(eval_binary(&BinaryOp::RightShift, -8, 1), Some(-4));
+        assert_eq!(
+            eval_binary(&BinaryOp::LeftShift, 1, 31),
+            Some(i32::MIN as i64)
+        );
+        assert_eq!(eval_binary(&BinaryOp::LeftShift, 1, 32), None);
+        assert_eq!(eval_binary(&BinaryOp::RightShift, 1, -1), None);
+    }
+
+    #[test]
+    fn test_interpret_loop_and_calls() {
+        let program = lower_source(
+            "int sq(int x) { return x * x; }
+             int main() {
+                 int s = 0;
+                 int i;
+                 for (i = 1; i <= 4; i = i + 1) s = s + sq(i);
+                 return s;
+             }",
+        );
+        assert_eq!(Interpreter::new(&program).call("main", &[]), 30);
+    }
+
+    #[test]
+    fn test_interpret_logical_operators_short_circuit() {
+        // Calling hang() would run out of fuel
+        let program = lower_source(
+            "int hang(void) { while (1) {} return 0; }
+             int main() {
+                 int a = 0 && hang();
+                 int b = 1 || hang();
+                 int c = 7 && -3;
+                 int d = 0 || 0;
+                 return a + 2 * b + 4 * c + 8 * d;
+             }",
+        );
+        assert_eq!(
+            Interpreter::new(&program).with_fuel(1000).call("main", &[]),
+            6
+        );
+    }
+
+    #[test]
+    fn test_interpret_compound_assignments() {
+        for (op, expected) in [
+            ("+=", 29),
+            ("-=", 19),
+            ("*=", 120),
+            ("/=", 4),
+            ("%=", 4),
+            ("&=", 0),
+            ("|=", 29),
+            ("^=", 29),
+            ("<<=", 768),
+            (">>=", 0),
+        ] {
+            // The result is the new value of the target
+            let program = lower_source(&format!(
+                "int main() {{ int x = 24; int y = x {} 5; return y == x ? x : -1; }}",
+                op
+            ));
+            assert_eq!(
+                Interpreter::new(&program).call("main", &[]),
+                expected,
+                "x {} 5",
+                op
+            );
+        }
+        let program = lower_source(
+            "int main() { int x = -24; x >>= 2; int y = 3; y = x = 7; return x * y + x; }",
+        );
+        assert_eq!(Interpreter::new(&program).call("main", &[]), 56);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of fuel")]
+    fn test_interpret_infinite_loop_runs_out_of_fuel() {
+        let program = lower_source("int main() { while (1) {} return 0; }");
+        Interpreter::new(&program).with_fuel(1000).call("main", &[]);
+    }
+}
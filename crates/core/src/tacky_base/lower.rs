@@ -0,0 +1,877 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    debug_base::{FunctionInfo, VariableInfo},
+    error::IntoCompilerError,
+    grammar::{IncDecOp, LogicalOp, Span, Type},
+    sema_base::{
+        source_name,
+        typed::{
+            ExpressionKind, StatementKind, TypedExpression, TypedFunction, TypedProgram,
+            TypedStatement,
+        },
+    },
+    tacky_base::{Instruction, TackyFuncDef, TackyProgram, Value},
+    warning::{CompilerWarning, Warning},
+};
+
+/// Lowers the typed AST into TACKY.
+///
+/// Besides producing instructions, the generator keeps track of whether the
+/// code it is currently emitting can be reached from the function entry, so
+/// that statements which can never execute are reported with
+/// [`Warning::UnreachableCode`].
+pub struct TackyGenerator {
+    instructions: Vec<Instruction>,
+    /// Counter shared by temporaries and labels, so every generated name
+    /// is unique within the program.
+    name_counter: usize,
+    /// `(continue_label, break_label)` of the enclosing loops
+    loops: Vec<(String, String)>,
+    /// TACKY labels of the user labels of the current function
+    user_labels: HashMap<String, String>,
+    /// Whether the next emitted instruction can be reached from the entry
+    reachable: bool,
+    /// Labels that are the target of at least one reachable jump
+    live_labels: HashSet<String>,
+    /// Whether the current unreachable region has already been reported
+    reported_unreachable: bool,
+    warnings: Vec<CompilerWarning>,
+    /// Functions lowered so far, when generating debug info
+    debug_info: Option<Vec<FunctionInfo>>,
+}
+
+impl TackyGenerator {
+    pub fn new() -> Self {
+        Self {
+            instructions: Vec::new(),
+            name_counter: 0,
+            loops: Vec::new(),
+            user_labels: HashMap::new(),
+            reachable: true,
+            live_labels: HashSet::new(),
+            reported_unreachable: false,
+            warnings: Vec::new(),
+            debug_info: None,
+        }
+    }
+
+    /// Marks the start of every statement with an [`Instruction::Loc`] and
+    /// records the functions and variables of the program.
+    pub fn with_debug_info(mut self) -> Self {
+        self.debug_info = Some(Vec::new());
+        self
+    }
+
+    /// Warnings collected while generating TACKY
+    pub fn warnings(&self) -> &[CompilerWarning] {
+        &self.warnings
+    }
+
+    /// Functions generated so far, if debug info was requested
+    pub fn debug_info(&self) -> Option<&[FunctionInfo]> {
+        self.debug_info.as_deref()
+    }
+
+    pub fn generate(&mut self, program: &TypedProgram) -> TackyProgram {
+        TackyProgram {
+            functions: program
+                .functions
+                .iter()
+                .map(|func| self.generate_function(func))
+                .collect(),
+        }
+    }
+
+    fn generate_function(&mut self, func: &TypedFunction) -> TackyFuncDef {
+        self.instructions.clear();
+        self.reachable = true;
+        self.live_labels.clear();
+        self.user_labels.clear();
+        self.reported_unreachable = false;
+
+        if let Some(functions) = &mut self.debug_info {
+            functions.push(FunctionInfo {
+                name: func.name.to_string(),
+                line: func.span.line,
+                column: func.span.column,
+                returns_value: func.return_type == Type::Int,
+                variables: Vec::new(),
+            });
+        }
+
+        for param in &func.params {
+            self.record_variable(param, func.span, true);
+        }
+        self.generate_block(&func.body);
+
+        // Falling off the end of a function returns 0, as `main` requires.
+        self.emit(Instruction::Return(Value::Constant(0)));
+
+        TackyFuncDef {
+            name: func.name.to_string(),
+            is_global: true,
+            params: func.params.clone(),
+            body: std::mem::take(&mut self.instructions),
+        }
+    }
+
+    fn make_name(&mut self, prefix: &str) -> String {
+        let name = format!("{}.{}", prefix, self.name_counter);
+        self.name_counter += 1;
+        name
+    }
+
+    /// Temporaries have no dot between prefix and number, unlike resolved
+    /// locals, so they never clash with a variable named `tmp`.
+    fn make_temporary(&mut self) -> String {
+        let name = format!("tmp{}", self.name_counter);
+        self.name_counter += 1;
+        name
+    }
+
+    fn make_label(&mut self, prefix: &str) -> String {
+        self.make_name(prefix)
+    }
+
+    /// TACKY label of the user label `name`. User labels are only unique
+    /// within their function, so each gets a fresh name of its own.
+    fn user_label(&mut self, name: &str) -> String {
+        if let Some(label) = self.user_labels.get(name) {
+            return label.clone();
+        }
+        let label = self.make_label(&format!("user_{}", name));
+        self.user_labels.insert(name.to_string(), label.clone());
+        label
+    }
+
+    /// Adds a variable of the current function, by its resolved name, to
+    /// the debug info.
+    fn record_variable(&mut self, tacky_name: &str, span: Span, is_param: bool) {
+        if let Some(function) = self.debug_info.as_mut().and_then(|f| f.last_mut()) {
+            function.variables.push(VariableInfo {
+                name: source_name(tacky_name).to_string(),
+                tacky_name: tacky_name.to_string(),
+                line: span.line,
+                is_param,
+            });
+        }
+    }
+
+    /// Appends an instruction, updating the reachability of the code that
+    /// follows it.
+    fn emit(&mut self, inst: Instruction) {
+        match &inst {
+            Instruction::Label(label) if !self.reachable && self.live_labels.contains(label) => {
+                self.reachable = true;
+                self.reported_unreachable = false;
+            }
+            Instruction::Jump(target) => {
+                if self.reachable {
+                    self.live_labels.insert(target.clone());
+                }
+                self.reachable = false;
+            }
+            Instruction::JumpIfZero { cond, target }
+            | Instruction::JumpIfNotZero { cond, target } => {
+                let jumps_when_zero = matches!(inst, Instruction::JumpIfZero { .. });
+                let (may_jump, must_jump) = match cond {
+                    Value::Constant(c) => {
+                        let taken = (*c == 0) == jumps_when_zero;
+                        (taken, taken)
+                    }
+                    Value::Var(_) => (true, false),
+                };
+                if self.reachable && may_jump {
+                    self.live_labels.insert(target.clone());
+                }
+                if must_jump {
+                    self.reachable = false;
+                }
+            }
+            Instruction::Return(_) => self.reachable = false,
+            _ => {}
+        }
+        self.instructions.push(inst);
+    }
+
+    /// Marks the code that follows as belonging to `span`, when generating
+    /// debug info.
+    fn emit_location(&mut self, span: Span) {
+        if self.debug_info.is_some() {
+            self.emit(Instruction::Loc {
+                line: span.line,
+                column: span.column,
+            });
+        }
+    }
+
+    fn generate_block(&mut self, block: &[TypedStatement]) {
+        for stmt in block {
+            self.generate_statement(stmt);
+        }
+    }
+
+    fn generate_statement(&mut self, stmt: &TypedStatement) {
+        let has_code = !matches!(
+            stmt.kind,
+            StatementKind::Block(_) | StatementKind::Labeled { .. } | StatementKind::Null
+        );
+        if !self.reachable && !self.reported_unreachable && has_code {
+            self.warnings
+                .push(Warning::UnreachableCode.with_span(stmt.span));
+            self.reported_unreachable = true;
+        }
+        if has_code {
+            self.emit_location(stmt.span);
+        }
+
+        match &stmt.kind {
+            StatementKind::Return(expr) => {
+                // `void` functions return 0 too, which callers ignore
+                let value = match expr {
+                    Some(expr) => self.generate_expression(expr),
+                    None => Value::Constant(0),
+                };
+                self.emit(Instruction::Return(value));
+            }
+            StatementKind::Block(block) => self.generate_block(block),
+            StatementKind::Null => {}
+            StatementKind::Break => {
+                let (_, break_label) = self
+                    .loops
+                    .last()
+                    .expect("the resolver rejects `break` outside of a loop");
+                self.emit(Instruction::Jump(break_label.clone()));
+            }
+            StatementKind::Continue => {
+                let (continue_label, _) = self
+                    .loops
+                    .last()
+                    .expect("the resolver rejects `continue` outside of a loop");
+                self.emit(Instruction::Jump(continue_label.clone()));
+            }
+            StatementKind::Goto(label) => {
+                let target = self.user_label(label);
+                self.emit(Instruction::Jump(target));
+            }
+            StatementKind::Labeled { label, body } => {
+                // A `goto` further down may still jump here, so the label is
+                // taken to be reachable
+                let label = self.user_label(label);
+                self.live_labels.insert(label.clone());
+                self.emit(Instruction::Label(label));
+                self.generate_statement(body);
+            }
+            StatementKind::Declaration { name, initializer } => {
+                self.record_variable(name, stmt.span, false);
+                if let Some(init) = initializer {
+                    let src = self.generate_expression(init);
+                    self.emit(Instruction::Copy {
+                        src,
+                        dst: name.clone(),
+                    });
+                }
+            }
+            StatementKind::Expr(expr) => {
+                self.generate_expression(expr);
+            }
+            StatementKind::If {
+                cond,
+                then_block,
+                else_block,
+            } => self.generate_if(cond, then_block, else_block.as_deref()),
+            StatementKind::While { cond, body } => {
+                let continue_label = self.make_label("while_continue");
+                let break_label = self.make_label("while_break");
+
+                self.emit(Instruction::Label(continue_label.clone()));
+                let cond = self.generate_expression(cond);
+                self.emit(Instruction::JumpIfZero {
+                    cond,
+                    target: break_label.clone(),
+                });
+                self.generate_loop_body(body, &continue_label, &break_label);
+                self.emit(Instruction::Jump(continue_label));
+                self.emit(Instruction::Label(break_label));
+            }
+            StatementKind::DoWhile { body, cond } => {
+                let start_label = self.make_label("do_start");
+                let continue_label = self.make_label("do_continue");
+                let break_label = self.make_label("do_break");
+
+                self.emit(Instruction::Label(start_label.clone()));
+                self.generate_loop_body(body, &continue_label, &break_label);
+                self.emit(Instruction::Label(continue_label));
+                self.emit_location(stmt.span);
+                let cond = self.generate_expression(cond);
+                self.emit(Instruction::JumpIfNotZero {
+                    cond,
+                    target: start_label,
+                });
+                self.emit(Instruction::Label(break_label));
+            }
+            StatementKind::For {
+                init,
+                cond,
+                post,
+                body,
+            } => {
+                let start_label = self.make_label("for_start");
+                let continue_label = self.make_label("for_continue");
+                let break_label = self.make_label("for_break");
+
+                if let Some(init) = init {
+                    self.generate_expression(init);
+                }
+                self.emit(Instruction::Label(start_label.clone()));
+                if let Some(cond) = cond {
+                    let cond = self.generate_expression(cond);
+                    self.emit(Instruction::JumpIfZero {
+                        cond,
+                        target: break_label.clone(),
+                    });
+                }
+                self.generate_loop_body(body, &continue_label, &break_label);
+                self.emit(Instruction::Label(continue_label));
+                self.emit_location(stmt.span);
+                if let Some(post) = post {
+                    self.generate_expression(post);
+                }
+                self.emit(Instruction::Jump(start_label));
+                self.emit(Instruction::Label(break_label));
+            }
+        }
+    }
+
+    fn generate_loop_body(
+        &mut self,
+        body: &TypedStatement,
+        continue_label: &str,
+        break_label: &str,
+    ) {
+        self.loops
+            .push((continue_label.to_string(), break_label.to_string()));
+        self.generate_statement(body);
+        self.loops.pop();
+    }
+
+    fn generate_if(
+        &mut self,
+        cond: &TypedExpression,
+        then_block: &TypedStatement,
+        else_block: Option<&TypedStatement>,
+    ) {
+        let end_label = self.make_label("if_end");
+        let cond = self.generate_expression(cond);
+
+        match else_block {
+            None => {
+                self.emit(Instruction::JumpIfZero {
+                    cond,
+                    target: end_label.clone(),
+                });
+                self.generate_statement(then_block);
+            }
+            Some(else_block) => {
+                let else_label = self.make_label("if_else");
+                self.emit(Instruction::JumpIfZero {
+                    cond,
+                    target: else_label.clone(),
+                });
+                self.generate_statement(then_block);
+                self.emit(Instruction::Jump(end_label.clone()));
+                self.emit(Instruction::Label(else_label));
+                self.generate_statement(else_block);
+            }
+        }
+        self.emit(Instruction::Label(end_label));
+    }
+
+    /// Emits the instructions computing `expr` and returns its value. The
+    /// value of a `void` expression is never used, and is a dummy.
+    fn generate_expression(&mut self, expr: &TypedExpression) -> Value {
+        match &expr.kind {
+            ExpressionKind::Constant(val) => Value::Constant(*val),
+            ExpressionKind::Variable(name) => Value::Var(name.clone()),
+            ExpressionKind::Unary { op, expr } => {
+                let src = self.generate_expression(expr);
+                let dst = self.make_temporary();
+                self.emit(Instruction::Unary {
+                    op: op.clone(),
+                    src,
+                    dst: dst.clone(),
+                });
+                Value::Var(dst)
+            }
+            ExpressionKind::Binary { op, lhs, rhs } => {
+                let lhs = self.generate_expression(lhs);
+                let rhs = self.generate_expression(rhs);
+                let dst = self.make_temporary();
+                self.emit(Instruction::Binary {
+                    op: op.clone(),
+                    lhs,
+                    rhs,
+                    dst: dst.clone(),
+                });
+                Value::Var(dst)
+            }
+            ExpressionKind::IncDec { op, expr } => self.generate_inc_dec(op, expr),
+            ExpressionKind::Logical { op, lhs, rhs } => self.generate_logical(op, lhs, rhs),
+            ExpressionKind::Conditional {
+                cond,
+                then_expr,
+                else_expr,
+            } => self.generate_conditional(cond, then_expr, else_expr, &expr.ty),
+            ExpressionKind::Comma { lhs, rhs } => {
+                self.generate_expression(lhs);
+                self.generate_expression(rhs)
+            }
+            ExpressionKind::Assignment { op, lhs, rhs } => {
+                let var = Self::lvalue(lhs);
+                let src = self.generate_expression(rhs);
+                match op.binary_op() {
+                    None => self.emit(Instruction::Copy {
+                        src,
+                        dst: var.clone(),
+                    }),
+                    // The target is read and written where it is, rather
+                    // than evaluated again as an operand
+                    Some(op) => self.emit(Instruction::Binary {
+                        op,
+                        lhs: Value::Var(var.clone()),
+                        rhs: src,
+                        dst: var.clone(),
+                    }),
+                }
+                Value::Var(var)
+            }
+            ExpressionKind::FunctionCall { name, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.generate_expression(arg))
+                    .collect();
+                let dst = (expr.ty != Type::Void).then(|| self.make_temporary());
+                self.emit(Instruction::FunCall {
+                    name: name.clone(),
+                    args,
+                    dst: dst.clone(),
+                });
+                dst.map_or(Value::Constant(0), Value::Var)
+            }
+            // `int` is the only type with values, so a conversion keeps the
+            // value as it is
+            ExpressionKind::Convert(expr) => self.generate_expression(expr),
+        }
+    }
+
+    /// The variable an lvalue designates.
+    fn lvalue(expr: &TypedExpression) -> String {
+        match &expr.kind {
+            ExpressionKind::Variable(name) => name.clone(),
+            _ => unreachable!("the parser only accepts variables as lvalues"),
+        }
+    }
+
+    /// Updates the variable `expr` in place, first saving its old value if
+    /// that is the result.
+    fn generate_inc_dec(&mut self, op: &IncDecOp, expr: &TypedExpression) -> Value {
+        let var = Self::lvalue(expr);
+        let old = op.is_postfix().then(|| {
+            let old = self.make_temporary();
+            self.emit(Instruction::Copy {
+                src: Value::Var(var.clone()),
+                dst: old.clone(),
+            });
+            old
+        });
+        self.emit(Instruction::Binary {
+            op: op.binary_op(),
+            lhs: Value::Var(var.clone()),
+            rhs: Value::Constant(1),
+            dst: var.clone(),
+        });
+        Value::Var(old.unwrap_or(var))
+    }
+
+    /// Evaluates `cond` and then only the arm it selects. A `void`
+    /// conditional has no result.
+    fn generate_conditional(
+        &mut self,
+        cond: &TypedExpression,
+        then_expr: &TypedExpression,
+        else_expr: &TypedExpression,
+        ty: &Type,
+    ) -> Value {
+        let else_label = self.make_label("cond_else");
+        let end_label = self.make_label("cond_end");
+        let dst = (*ty != Type::Void).then(|| self.make_temporary());
+
+        let cond = self.generate_expression(cond);
+        self.emit(Instruction::JumpIfZero {
+            cond,
+            target: else_label.clone(),
+        });
+        self.generate_arm(then_expr, dst.as_deref());
+        self.emit(Instruction::Jump(end_label.clone()));
+        self.emit(Instruction::Label(else_label));
+        self.generate_arm(else_expr, dst.as_deref());
+        self.emit(Instruction::Label(end_label));
+        dst.map_or(Value::Constant(0), Value::Var)
+    }
+
+    /// Evaluates one arm of a conditional into `dst`.
+    fn generate_arm(&mut self, arm: &TypedExpression, dst: Option<&str>) {
+        let src = self.generate_expression(arm);
+        if let Some(dst) = dst {
+            self.emit(Instruction::Copy {
+                src,
+                dst: dst.to_string(),
+            });
+        }
+    }
+
+    /// Evaluates `rhs` only when `lhs` does not decide the result: when it
+    /// is true for `&&`, false for `||`. The result is 0 or 1.
+    fn generate_logical(
+        &mut self,
+        op: &LogicalOp,
+        lhs: &TypedExpression,
+        rhs: &TypedExpression,
+    ) -> Value {
+        // The value the result has when `lhs` decides it
+        let (decided, short_label, end_label) = match op {
+            LogicalOp::And => (0, self.make_label("and_false"), self.make_label("and_end")),
+            LogicalOp::Or => (1, self.make_label("or_true"), self.make_label("or_end")),
+        };
+        let dst = self.make_temporary();
+
+        let jump = |cond: Value, target: String| match op {
+            LogicalOp::And => Instruction::JumpIfZero { cond, target },
+            LogicalOp::Or => Instruction::JumpIfNotZero { cond, target },
+        };
+        let lhs = self.generate_expression(lhs);
+        self.emit(jump(lhs, short_label.clone()));
+        let rhs = self.generate_expression(rhs);
+        self.emit(jump(rhs, short_label.clone()));
+        self.emit(Instruction::Copy {
+            src: Value::Constant(1 - decided),
+            dst: dst.clone(),
+        });
+        self.emit(Instruction::Jump(end_label.clone()));
+        self.emit(Instruction::Label(short_label));
+        self.emit(Instruction::Copy {
+            src: Value::Constant(decided),
+            dst: dst.clone(),
+        });
+        self.emit(Instruction::Label(end_label));
+        Value::Var(dst)
+    }
+}
+
+impl Default for TackyGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        grammar::BinaryOp,
+        lexer_base::Lexer,
+        parser_base::Parser,
+        sema_base::{resolve, typecheck},
+    };
+
+    fn parse(input: &str) -> TypedProgram {
+        let mut program = Parser::new(Lexer::new(input)).parse().unwrap();
+        resolve(&mut program).unwrap();
+        typecheck(&program).unwrap()
+    }
+
+    fn generate(input: &str) -> (TackyProgram, Vec<CompilerWarning>) {
+        let program = parse(input);
+        let mut generator = TackyGenerator::new();
+        let tacky = generator.generate(&program);
+        (tacky, generator.warnings().to_vec())
+    }
+
+    #[test]
+    fn test_generate_return_constant() {
+        let (program, warnings) = generate("int main(void) { return 2; }");
+        assert!(warnings.is_empty());
+        assert_eq!(program.functions.len(), 1);
+        assert_eq!(
+            program.functions[0].body[0],
+            Instruction::Return(Value::Constant(2))
+        );
+    }
+
+    #[test]
+    fn test_generate_shadowed_variables_get_unique_names() {
+        let (program, _) = generate("int main(void) { int x = 1; { int x = 2; } return x; }");
+        let dsts: Vec<_> = program.functions[0]
+            .body
+            .iter()
+            .filter_map(|inst| inst.dst())
+            .collect();
+        assert_eq!(dsts.len(), 2);
+        assert_ne!(dsts[0], dsts[1]);
+        assert_eq!(
+            program.functions[0].body[2],
+            Instruction::Return(Value::Var(dsts[0].to_string()))
+        );
+    }
+
+    #[test]
+    fn test_generate_function_call_arguments() {
+        let (program, _) =
+            generate("int f(int a, int b) { return a; } int main(void) { return f(1, 2); }");
+        assert_eq!(program.functions[0].params.len(), 2);
+        assert!(program.functions[1].body.iter().any(|inst| matches!(
+            inst,
+            Instruction::FunCall { name, args, .. } if name == "f" && args.len() == 2
+        )));
+    }
+
+    #[test]
+    fn test_void_calls_have_no_result() {
+        let (program, _) = generate("void g(void) { return; } int main(void) { g(); return 0; }");
+        assert_eq!(
+            program.functions[0].body[0],
+            Instruction::Return(Value::Constant(0))
+        );
+        assert!(program.functions[1].body.iter().any(|inst| matches!(
+            inst,
+            Instruction::FunCall { name, dst: None, .. } if name == "g"
+        )));
+    }
+
+    #[test]
+    fn test_declarations_without_body_are_not_generated() {
+        let (program, _) = generate("int f(int a); int main(void) { return f(1); }");
+        assert_eq!(program.functions.len(), 1);
+        assert_eq!(program.functions[0].name, "main");
+    }
+
+    #[test]
+    fn test_logical_and_jumps_past_its_right_operand() {
+        let (program, _) =
+            generate("int f(void) { return 1; } int main(void) { return 0 && f(); }");
+        let body = &program.functions[1].body;
+        let jump = body
+            .iter()
+            .position(|inst| matches!(inst, Instruction::JumpIfZero { .. }))
+            .unwrap();
+        let call = body
+            .iter()
+            .position(|inst| matches!(inst, Instruction::FunCall { .. }))
+            .unwrap();
+        assert!(jump < call);
+        // Both outcomes are normalized to 0 or 1
+        let results: Vec<_> = body
+            .iter()
+            .filter_map(|inst| match inst {
+                Instruction::Copy {
+                    src: Value::Constant(c),
+                    ..
+                } => Some(*c),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(results, [1, 0]);
+    }
+
+    #[test]
+    fn test_inc_dec_updates_the_variable_in_place() {
+        let (program, _) = generate("int main(void) { int x = 1; int y = x++; return ++x; }");
+        let body = &program.functions[0].body;
+        let add = |var: &str| Instruction::Binary {
+            op: BinaryOp::Add,
+            lhs: Value::Var(var.into()),
+            rhs: Value::Constant(1),
+            dst: var.into(),
+        };
+        let Instruction::Copy { dst: x, .. } = &body[0] else {
+            panic!("expected the initializer of x");
+        };
+        // The postfix form gives the old value
+        let Instruction::Copy {
+            src: Value::Var(src),
+            dst: old,
+        } = &body[1]
+        else {
+            panic!("expected the old value to be saved");
+        };
+        assert_eq!(src, x);
+        assert_eq!(body[2], add(x));
+        assert!(matches!(&body[3], Instruction::Copy { src: Value::Var(src), .. } if src == old));
+        assert_eq!(body[4], add(x));
+        assert_eq!(body[5], Instruction::Return(Value::Var(x.clone())));
+    }
+
+    #[test]
+    fn test_compound_assignment_updates_the_target_once() {
+        let (program, _) = generate("int main(void) { int x = 1; return x <<= 3; }");
+        let body = &program.functions[0].body;
+        let Instruction::Copy { dst: x, .. } = &body[0] else {
+            panic!("expected the initializer of x");
+        };
+        assert_eq!(
+            body[1..],
+            [
+                Instruction::Binary {
+                    op: BinaryOp::LeftShift,
+                    lhs: Value::Var(x.clone()),
+                    rhs: Value::Constant(3),
+                    dst: x.clone(),
+                },
+                Instruction::Return(Value::Var(x.clone())),
+                Instruction::Return(Value::Constant(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_conversions_keep_the_value() {
+        let mut program = parse("int main(void) { return 7; }");
+        let StatementKind::Return(Some(expr)) = &mut program.functions[0].body[0].kind else {
+            panic!("expected a return with a value");
+        };
+        *expr = TypedExpression::new(ExpressionKind::Convert(Box::new(expr.clone())), Type::Int);
+        let tacky = TackyGenerator::new().generate(&program);
+        assert_eq!(
+            tacky.functions[0].body[0],
+            Instruction::Return(Value::Constant(7))
+        );
+    }
+
+    #[test]
+    fn test_user_labels_are_unique_per_function() {
+        let input = "int f(void) { goto out; out: return 1; } int main(void) { out: return 0; }";
+        let (program, _) = generate(input);
+        let labels: Vec<_> = program
+            .functions
+            .iter()
+            .map(|func| match &func.body[..] {
+                [Instruction::Jump(target), Instruction::Label(label), ..] => {
+                    assert_eq!(target, label);
+                    label.clone()
+                }
+                [Instruction::Label(label), ..] => label.clone(),
+                body => panic!("expected a label, found {:?}", body),
+            })
+            .collect();
+        assert!(labels[0].starts_with("user_out."));
+        assert_ne!(labels[0], labels[1]);
+    }
+
+    #[test]
+    fn test_labeled_statement_after_goto_is_reachable() {
+        let input = "int main(void) { int x = 0; goto end; x = 1; end: return x; }";
+        let (_, warnings) = generate(input);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].error, Warning::UnreachableCode);
+
+        let input =
+            "int main(void) { int x = 0; again: x = x + 1; if (x < 3) goto again; return x; }";
+        let (_, warnings) = generate(input);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_warn_statement_after_return() {
+        let input = "int main(void) {\n    return 1;\n    int x = 2;\n    x = 3;\n}";
+        let (_, warnings) = generate(input);
+        // Only the first statement of an unreachable region is reported
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].error, Warning::UnreachableCode);
+        assert_eq!(warnings[0].span.line, 3);
+        assert_eq!(warnings[0].span.column, 5);
+    }
+
+    #[test]
+    fn test_warn_after_if_where_both_branches_return() {
+        let input = "int main(void) { int x = 0; if (x) return 1; else return 2; return 3; }";
+        let (_, warnings) = generate(input);
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_warn_after_infinite_loop_without_break() {
+        let (_, warnings) = generate("int main(void) { while (1) { } return 0; }");
+        assert_eq!(warnings.len(), 1);
+
+        let (_, warnings) = generate("int main(void) { for (;;) { break; } return 0; }");
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_warn_after_loop_control() {
+        let (_, warnings) = generate("int main(void) { while (1) { break; return 1; } return 0; }");
+        assert_eq!(warnings.len(), 1);
+
+        let (_, warnings) =
+            generate("int main(void) { int i = 0; while (i) { continue; i = 1; } return 0; }");
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_no_warning_for_reachable_code() {
+        let input = r#"
+            int main(void) {
+                int x = 0;
+                if (x) return 1;
+                do { x = x + 1; } while (x < 10);
+                return x;
+            }
+        "#;
+        let (_, warnings) = generate(input);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_debug_info_marks_statements_and_records_variables() {
+        let input = "int f(int a) {\n    int b = a;\n    {\n        return b;\n    }\n}";
+        let program = parse(input);
+        let mut generator = TackyGenerator::new().with_debug_info();
+        let tacky = generator.generate(&program);
+
+        let locs: Vec<_> = tacky.functions[0]
+            .body
+            .iter()
+            .filter_map(|inst| match inst {
+                Instruction::Loc { line, column } => Some((*line, *column)),
+                _ => None,
+            })
+            .collect();
+        // Blocks have no code of their own
+        assert_eq!(locs, vec![(2, 5), (4, 9)]);
+
+        let functions = generator.debug_info().unwrap();
+        assert_eq!(functions.len(), 1);
+        assert_eq!(functions[0].name, "f");
+        assert_eq!(functions[0].line, 1);
+        let variables: Vec<_> = functions[0]
+            .variables
+            .iter()
+            .map(|var| (var.name.as_str(), var.line, var.is_param))
+            .collect();
+        assert_eq!(variables, vec![("a", 1, true), ("b", 2, false)]);
+        let b = tacky.functions[0].body.iter().find_map(|inst| inst.dst());
+        assert_eq!(Some(functions[0].variables[1].tacky_name.as_str()), b);
+    }
+
+    #[test]
+    fn test_no_locations_without_debug_info() {
+        let (program, _) = generate("int main(void) { int x = 1; return x; }");
+        assert!(
+            !program.functions[0]
+                .body
+                .iter()
+                .any(|inst| matches!(inst, Instruction::Loc { .. }))
+        );
+    }
+}
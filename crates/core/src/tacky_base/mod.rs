@@ -0,0 +1,199 @@
+//! TACKY: the three-address intermediate representation that sits between
+//! the AST and the assembly IR in `ir_base`.
+//!
+//! Every expression is flattened into instructions that read from
+//! [`Value`]s and write into named temporaries, and all control flow is
+//! expressed with labels and (conditional) jumps. This makes the
+//! representation convenient for building control-flow graphs and running
+//! optimization passes over them.
+
+pub mod cfg;
+pub mod interp;
+mod lower;
+
+pub use lower::TackyGenerator;
+
+use crate::grammar::{BinaryOp, UnaryOp};
+
+/// Operand of a TACKY instruction
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Value {
+    Constant(i64),
+    Var(String),
+}
+
+impl Value {
+    pub fn as_var(&self) -> Option<&str> {
+        match self {
+            Value::Var(name) => Some(name),
+            Value::Constant(_) => None,
+        }
+    }
+}
+
+/// Individual TACKY instruction
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    Return(Value),
+    Copy {
+        src: Value,
+        dst: String,
+    },
+    Unary {
+        op: UnaryOp,
+        src: Value,
+        dst: String,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Value,
+        rhs: Value,
+        dst: String,
+    },
+    Jump(String),
+    JumpIfZero {
+        cond: Value,
+        target: String,
+    },
+    JumpIfNotZero {
+        cond: Value,
+        target: String,
+    },
+    Label(String),
+    FunCall {
+        name: String,
+        args: Vec<Value>,
+        dst: Option<String>,
+    },
+    /// Call whose result is returned right away. Ends the function: the
+    /// callee returns directly to our caller.
+    TailCall {
+        name: String,
+        args: Vec<Value>,
+    },
+    /// Marks where the code of the statement at `line`:`column` starts.
+    /// Only generated with debug info; has no effect when executed.
+    Loc {
+        line: usize,
+        column: usize,
+    },
+}
+
+impl Instruction {
+    /// The variable written by this instruction, if any.
+    pub fn dst(&self) -> Option<&str> {
+        match self {
+            Instruction::Copy { dst, .. }
+            | Instruction::Unary { dst, .. }
+            | Instruction::Binary { dst, .. } => Some(dst),
+            Instruction::FunCall { dst, .. } => dst.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Mutable access to the variable written by this instruction.
+    pub fn dst_mut(&mut self) -> Option<&mut String> {
+        match self {
+            Instruction::Copy { dst, .. }
+            | Instruction::Unary { dst, .. }
+            | Instruction::Binary { dst, .. } => Some(dst),
+            Instruction::FunCall { dst, .. } => dst.as_mut(),
+            _ => None,
+        }
+    }
+
+    /// The values read by this instruction.
+    pub fn operands(&self) -> Vec<&Value> {
+        match self {
+            Instruction::Return(value) => vec![value],
+            Instruction::Copy { src, .. } | Instruction::Unary { src, .. } => vec![src],
+            Instruction::Binary { lhs, rhs, .. } => vec![lhs, rhs],
+            Instruction::JumpIfZero { cond, .. } | Instruction::JumpIfNotZero { cond, .. } => {
+                vec![cond]
+            }
+            Instruction::FunCall { args, .. } | Instruction::TailCall { args, .. } => {
+                args.iter().collect()
+            }
+            Instruction::Jump(_) | Instruction::Label(_) | Instruction::Loc { .. } => vec![],
+        }
+    }
+
+    /// Mutable access to the values read by this instruction.
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Instruction::Return(value) => vec![value],
+            Instruction::Copy { src, .. } | Instruction::Unary { src, .. } => vec![src],
+            Instruction::Binary { lhs, rhs, .. } => vec![lhs, rhs],
+            Instruction::JumpIfZero { cond, .. } | Instruction::JumpIfNotZero { cond, .. } => {
+                vec![cond]
+            }
+            Instruction::FunCall { args, .. } | Instruction::TailCall { args, .. } => {
+                args.iter_mut().collect()
+            }
+            Instruction::Jump(_) | Instruction::Label(_) | Instruction::Loc { .. } => vec![],
+        }
+    }
+
+    /// Whether executing this instruction can have an effect other than
+    /// writing its destination.
+    pub fn has_side_effects(&self) -> bool {
+        !matches!(
+            self,
+            Instruction::Copy { .. } | Instruction::Unary { .. } | Instruction::Binary { .. }
+        )
+    }
+}
+
+/// TACKY Function Definition
+#[derive(Debug, Clone, PartialEq)]
+pub struct TackyFuncDef {
+    pub name: String,
+    pub is_global: bool,
+    pub params: Vec<String>,
+    pub body: Vec<Instruction>,
+}
+
+/// Complete program in TACKY
+#[derive(Debug, Clone, PartialEq)]
+pub struct TackyProgram {
+    pub functions: Vec<TackyFuncDef>,
+}
+
+/// Parses, resolves, type checks and lowers `input`, which must be a valid
+/// program.
+#[cfg(test)]
+pub(crate) fn lower_source(input: &str) -> TackyProgram {
+    use crate::{
+        lexer_base::Lexer,
+        parser_base::Parser,
+        sema_base::{resolve, typecheck},
+    };
+
+    let mut ast = Parser::new(Lexer::new(input)).parse().unwrap();
+    resolve(&mut ast).unwrap();
+    let typed = typecheck(&ast).unwrap();
+    TackyGenerator::new().generate(&typed)
+}
+
+/// Lowers `input` and applies `transform` to it, checking that calling
+/// `func` with `args` returns `expected` both before and after.
+#[cfg(test)]
+pub(crate) fn transform_and_check(
+    input: &str,
+    func: &str,
+    args: &[i64],
+    expected: i64,
+    transform: impl FnOnce(&mut TackyProgram),
+) -> TackyProgram {
+    let mut program = lower_source(input);
+    assert_eq!(
+        interp::Interpreter::new(&program).call(func, args),
+        expected
+    );
+    transform(&mut program);
+    assert_eq!(
+        interp::Interpreter::new(&program).call(func, args),
+        expected
+    );
+    program
+}
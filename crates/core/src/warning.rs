@@ -0,0 +1,23 @@
+use thiserror::Error;
+
+use crate::error::{CompilerError, IntoCompilerError};
+
+/// A diagnostic that is reported to the user but does not stop compilation
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum Warning {
+    #[error("code will never be executed")]
+    UnreachableCode,
+}
+
+impl Warning {
+    /// Name of the `-W<name>` flag that enables this warning
+    pub const fn flag(&self) -> &'static str {
+        match self {
+            Warning::UnreachableCode => "unreachable-code",
+        }
+    }
+}
+
+impl IntoCompilerError for Warning {}
+
+pub type CompilerWarning = CompilerError<Warning>;
@@ -1,8 +1,11 @@
-use std::{fs, path::PathBuf};
+use std::{env, fs, path::PathBuf};
 
 use clap::Parser;
 use colored::Colorize;
-use compiler_core::{codegen_base::CodeGenerator, ir_base::Emitter, lexer_base, parser_base};
+use compiler_core::{
+    debug_base::DebugInfo, ir_base::Emitter, lexer_base, opt_base::OptLevel, parser_base,
+    pass_base::PassManager, sema_base, tacky_base::TackyGenerator,
+};
 
 #[derive(Parser)]
 struct Cli {
@@ -22,9 +25,45 @@ struct Cli {
     #[arg(long)]
     parse_only: bool,
 
+    /// Stop after semantic analysis
+    #[arg(long)]
+    validate_only: bool,
+
+    /// Stop after TACKY generation
+    #[arg(long)]
+    tacky_only: bool,
+
     /// Stop after IR generation
     #[arg(long)]
     ir_only: bool,
+
+    /// Optimization level: `-O0`, `-O1`, `-O2` or `-Os`
+    #[arg(short = 'O', value_name = "LEVEL", default_value = "0")]
+    opt_level: OptLevel,
+
+    /// Enable a warning, e.g. `-Wunreachable-code`
+    #[arg(short = 'W', value_name = "WARNING")]
+    warnings: Vec<String>,
+
+    /// Turn a pass on or off with `-f<pass>` or `-fno-<pass>` (e.g.
+    /// `-fno-inline`), or set `-finline-limit=N`, `-fregalloc=linear|graph`,
+    /// `-fomit-frame-pointer` or `-fpic`/`-fpie`/`-fno-pic`
+    #[arg(short = 'f', value_name = "FLAG")]
+    flags: Vec<String>,
+
+    /// Target option: `-mno-red-zone` keeps functions from using the
+    /// stack below `rsp`
+    #[arg(short = 'm', value_name = "FLAG")]
+    machine_flags: Vec<String>,
+
+    /// Generate debug info, so that debuggers can step through the source
+    /// and print variables
+    #[arg(short = 'g')]
+    debug_info: bool,
+
+    /// Print the program to stderr after each run of a pass
+    #[arg(long, value_name = "PASS")]
+    print_after: Vec<String>,
 }
 
 fn main() {
@@ -61,6 +100,21 @@ fn main() {
         }
     };
 
+    let mut passes = PassManager::new(cli.opt_level);
+    let flags = cli.flags.iter().map(|flag| passes.apply_flag(flag));
+    let result = flags.collect::<Result<(), _>>().and_then(|()| {
+        cli.machine_flags
+            .iter()
+            .try_for_each(|flag| passes.apply_machine_flag(flag))?;
+        cli.print_after
+            .iter()
+            .try_for_each(|pass| passes.print_after(pass))
+    });
+    if let Err(err) = result {
+        eprintln!("{}: {}", "Error".red().bold(), err);
+        std::process::exit(1);
+    }
+
     let lexer = lexer_base::Lexer::new(&source);
     if cli.lex_only {
         println!("{}:", "Tokens".yellow().bold());
@@ -70,7 +124,7 @@ fn main() {
         return;
     }
 
-    let ast = match parser_base::Parser::new(lexer).parse() {
+    let mut ast = match parser_base::Parser::new(lexer).parse() {
         Ok(ast) => ast,
         Err(e) => {
             eprintln!(
@@ -89,8 +143,52 @@ fn main() {
         return;
     }
 
-    let mut codegen = CodeGenerator::new();
-    let ir_program = codegen.generate(&ast);
+    let analyzed = sema_base::resolve(&mut ast).and_then(|()| {
+        passes.run_ast(&mut ast);
+        sema_base::typecheck(&ast)
+    });
+    let typed_ast = match analyzed {
+        Ok(typed_ast) => typed_ast,
+        Err(errors) => {
+            for e in errors {
+                eprintln!("{}: {}: {}", "Error".red().bold(), cli.input.display(), e);
+            }
+            std::process::exit(1);
+        }
+    };
+
+    if cli.validate_only {
+        println!("{}:", "Typed AST".yellow().bold());
+        println!("{:#?}", typed_ast);
+        return;
+    }
+
+    let mut tacky_generator = TackyGenerator::new();
+    if cli.debug_info {
+        tacky_generator = tacky_generator.with_debug_info();
+    }
+    let mut tacky_program = tacky_generator.generate(&typed_ast);
+
+    for warning in tacky_generator.warnings() {
+        if cli.warnings.iter().any(|flag| flag == warning.error.flag()) {
+            eprintln!(
+                "{}: {}: {} [-W{}]",
+                "Warning".yellow().bold(),
+                cli.input.display(),
+                warning,
+                warning.error.flag()
+            );
+        }
+    }
+
+    if cli.tacky_only {
+        println!("{}:", "TACKY".yellow().bold());
+        println!("{:#?}", tacky_program);
+        return;
+    }
+
+    passes.run_ir(&mut tacky_program);
+    let ir_program = passes.generate(&tacky_program);
 
     if cli.ir_only {
         println!("{}:", "IR".yellow().bold());
@@ -99,6 +197,15 @@ fn main() {
     }
 
     let mut emitter = Emitter::new();
+    if let Some(functions) = tacky_generator.debug_info() {
+        emitter = emitter.with_debug_info(DebugInfo {
+            file: cli.input.display().to_string(),
+            directory: env::current_dir()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_default(),
+            functions: functions.to_vec(),
+        });
+    }
     let assembly = emitter.emit_program(&ir_program);
 
     let output_path = cli.output.unwrap_or_else(|| cli.input.with_extension("s"));
@@ -0,0 +1,347 @@
+use std::{fs, path::Path, process::Command};
+
+use compiler_core::{
+    codegen_base::RegAllocator,
+    debug_base::DebugInfo,
+    ir_base::Emitter,
+    lexer_base,
+    opt_base::{self, OptLevel},
+    parser_base,
+    pass_base::PassManager,
+    sema_base,
+    tacky_base::{TackyGenerator, interp::Interpreter},
+};
+
+const OPT_LEVELS: [OptLevel; 4] = [OptLevel::O0, OptLevel::O1, OptLevel::O2, OptLevel::Os];
+
+/// Programs under `tests/programs` and the exit code `main` returns.
+const PROGRAMS: &[(&str, i64)] = &[
+    ("addressing.c", 75),
+    ("bitwise.c", 55),
+    ("calls_and_loops.c", 39),
+    ("comma.c", 28),
+    ("common_subexpressions.c", 142),
+    ("compound.c", 218),
+    ("conditional.c", 236),
+    ("constant_branches.c", 20),
+    ("goto.c", 192),
+    ("increment.c", 137),
+    ("inlining.c", 100),
+    ("logical.c", 139),
+    ("loop_invariants.c", 186),
+    ("nested_loops.c", 100),
+    ("recursion.c", 55),
+    ("register_pressure.c", 28),
+    ("wrapping.c", 1),
+];
+
+fn compile(source: &str, level: OptLevel) -> String {
+    compile_with(source, &PassManager::new(level))
+}
+
+fn compile_with(source: &str, passes: &PassManager) -> String {
+    let lexer = lexer_base::Lexer::new(source);
+    let mut ast = parser_base::Parser::new(lexer)
+        .parse()
+        .expect("program should parse");
+    sema_base::resolve(&mut ast).expect("program should resolve");
+    passes.run_ast(&mut ast);
+    let typed = sema_base::typecheck(&ast).expect("program should type check");
+    let mut tacky_program = TackyGenerator::new().generate(&typed);
+    passes.run_ir(&mut tacky_program);
+    let ir_program = passes.generate(&tacky_program);
+    Emitter::new().emit_program(&ir_program)
+}
+
+/// Compiles `source` as `-g` does.
+fn compile_with_debug_info(source: &str, level: OptLevel) -> String {
+    let passes = PassManager::new(level);
+    let lexer = lexer_base::Lexer::new(source);
+    let mut ast = parser_base::Parser::new(lexer)
+        .parse()
+        .expect("program should parse");
+    sema_base::resolve(&mut ast).expect("program should resolve");
+    passes.run_ast(&mut ast);
+    let typed = sema_base::typecheck(&ast).expect("program should type check");
+    let mut generator = TackyGenerator::new().with_debug_info();
+    let mut tacky_program = generator.generate(&typed);
+    passes.run_ir(&mut tacky_program);
+    let ir_program = passes.generate(&tacky_program);
+    let debug_info = DebugInfo {
+        file: "test.c".into(),
+        directory: "/tmp".into(),
+        functions: generator.debug_info().unwrap().to_vec(),
+    };
+    Emitter::new()
+        .with_debug_info(debug_info)
+        .emit_program(&ir_program)
+}
+
+/// Assembles `assembly` with the system C compiler and returns the exit code
+/// of the resulting executable, or `None` when no C compiler is available.
+fn assemble_and_run(assembly: &str, name: &str) -> Option<i32> {
+    let dir = std::env::temp_dir().join(format!("native_tests_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let asm_path = dir.join(format!("{}.s", name));
+    let exe_path = dir.join(name);
+    fs::write(&asm_path, assembly).unwrap();
+
+    let status = Command::new("cc")
+        .arg(&asm_path)
+        .arg("-o")
+        .arg(&exe_path)
+        .status()
+        .ok()?;
+    assert!(
+        status.success(),
+        "failed to assemble {}",
+        asm_path.display()
+    );
+
+    let output = Command::new(&exe_path).output().unwrap();
+    output.status.code()
+}
+
+#[test]
+fn test_interpreter_agrees_at_every_level() {
+    for (file, expected) in PROGRAMS {
+        let source = fs::read_to_string(Path::new("tests/programs").join(file)).unwrap();
+        let mut ast = parser_base::Parser::new(lexer_base::Lexer::new(&source))
+            .parse()
+            .unwrap();
+        sema_base::resolve(&mut ast).unwrap();
+        let typed = sema_base::typecheck(&ast).unwrap();
+        for level in OPT_LEVELS {
+            let mut program = TackyGenerator::new().generate(&typed);
+            opt_base::optimize(&mut program, level);
+            let result = Interpreter::new(&program).call("main", &[]);
+            assert_eq!(result, *expected, "{} at {:?}", file, level);
+        }
+    }
+}
+
+#[test]
+fn test_native_exit_codes_at_every_level() {
+    for (file, expected) in PROGRAMS {
+        let source = fs::read_to_string(Path::new("tests/programs").join(file)).unwrap();
+        for level in OPT_LEVELS {
+            let assembly = compile(&source, level);
+            let name = format!("{}_{:?}", file.trim_end_matches(".c"), level);
+            let Some(code) = assemble_and_run(&assembly, &name) else {
+                eprintln!("skipping native tests: no C compiler found");
+                return;
+            };
+            assert_eq!(code as i64, *expected, "{} at {:?}", file, level);
+            println!("  ✓ {} at {:?}", file, level);
+        }
+    }
+}
+
+#[test]
+fn test_deep_tail_recursion_runs_in_constant_stack_at_o2() {
+    // Millions of frames would overflow the stack without tail calls
+    let source = fs::read_to_string("tests/programs/deep_tail_recursion.c").unwrap();
+    let assembly = compile(&source, OptLevel::O2);
+    if let Some(code) = assemble_and_run(&assembly, "deep_tail_recursion_O2") {
+        assert_eq!(code, 7);
+    }
+}
+
+#[test]
+fn test_instruction_selection_uses_addressing_modes() {
+    let source = fs::read_to_string("tests/programs/addressing.c").unwrap();
+    let assembly = compile(&source, OptLevel::O2);
+    // `a + b * 4 + 8`, `x * 3` and `x * 9` each take a single `lea`
+    assert!(assembly.contains("leal 8(%rdi,%rsi,4)"), "{}", assembly);
+    assert!(assembly.contains("(%rdi,%rdi,2)"), "{}", assembly);
+    assert!(assembly.contains("(%rdi,%rdi,8)"), "{}", assembly);
+    // `if (x)` tests the register instead of comparing it with 0
+    assert!(assembly.contains("testl %edi, %edi"), "{}", assembly);
+    assert!(!assembly.contains("cmpl $0,"), "{}", assembly);
+    // Every comparison feeds a branch, so none is materialized with `set`
+    let sets = assembly
+        .lines()
+        .filter(|line| line.trim_start().starts_with("set"));
+    assert_eq!(sets.count(), 0, "{}", assembly);
+}
+
+/// Scheduling only reorders instructions, so every program must behave the
+/// same with it, including on the memory-heavy code of `-O0`.
+#[test]
+fn test_scheduled_code_matches_interpreter() {
+    for (file, expected) in PROGRAMS {
+        let source = fs::read_to_string(Path::new("tests/programs").join(file)).unwrap();
+        for level in OPT_LEVELS {
+            let mut passes = PassManager::new(level);
+            passes.enable("schedule-insns").unwrap();
+            let assembly = compile_with(&source, &passes);
+            let name = format!("{}_{:?}_scheduled", file.trim_end_matches(".c"), level);
+            let Some(code) = assemble_and_run(&assembly, &name) else {
+                return;
+            };
+            assert_eq!(code as i64, *expected, "{} at {:?}", file, level);
+        }
+    }
+}
+
+/// Debug info only adds directives and sections: the instructions must be
+/// the same as without `-g`, at every level.
+#[test]
+fn test_debug_info_leaves_code_unchanged() {
+    let instructions = |assembly: &str| -> Vec<String> {
+        assembly
+            .lines()
+            .filter(|line| line.starts_with("    ") && !line.trim_start().starts_with('.'))
+            .map(str::to_string)
+            .collect()
+    };
+    for (file, expected) in PROGRAMS {
+        let source = fs::read_to_string(Path::new("tests/programs").join(file)).unwrap();
+        for level in OPT_LEVELS {
+            let plain = compile(&source, level);
+            let debug = compile_with_debug_info(&source, level);
+            assert!(debug.contains(".loc 1 "), "{}", debug);
+            assert!(debug.contains(".debug_info"), "{}", debug);
+            assert_eq!(
+                instructions(&plain),
+                instructions(&debug),
+                "{} at {:?}",
+                file,
+                level
+            );
+
+            let name = format!("{}_{:?}_debug", file.trim_end_matches(".c"), level);
+            let Some(code) = assemble_and_run(&debug, &name) else {
+                return;
+            };
+            assert_eq!(code as i64, *expected, "{} at {:?}", file, level);
+        }
+    }
+}
+
+/// Every program must run the same without a frame pointer, where `rbp`
+/// holds values instead, and without the red zone.
+#[test]
+fn test_frame_layouts_at_every_level() {
+    let layouts: [(&[&str], &[&str]); 3] = [
+        (&["omit-frame-pointer"], &[]),
+        (&[], &["no-red-zone"]),
+        (&["omit-frame-pointer"], &["no-red-zone"]),
+    ];
+    for (file, expected) in PROGRAMS {
+        let source = fs::read_to_string(Path::new("tests/programs").join(file)).unwrap();
+        for level in OPT_LEVELS {
+            for (idx, (flags, machine_flags)) in layouts.iter().enumerate() {
+                let mut passes = PassManager::new(level);
+                for flag in *flags {
+                    passes.apply_flag(flag).unwrap();
+                }
+                for flag in *machine_flags {
+                    passes.apply_machine_flag(flag).unwrap();
+                }
+                let assembly = compile_with(&source, &passes);
+                if passes.codegen_options.omit_frame_pointer {
+                    assert!(!assembly.contains("movq %rsp, %rbp"), "{}", assembly);
+                    assert!(!assembly.contains("(%rbp)"), "{}", assembly);
+                }
+                let name = format!("{}_{:?}_frame{}", file.trim_end_matches(".c"), level, idx);
+                let Some(code) = assemble_and_run(&assembly, &name) else {
+                    return;
+                };
+                assert_eq!(
+                    code as i64, *expected,
+                    "{} at {:?} with {:?} {:?}",
+                    file, level, flags, machine_flags
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_leaf_function_uses_red_zone() {
+    let source = "int square(int x) { int y = x * x; return y; }
+                  int main() { return square(7); }";
+    let assembly = compile(source, OptLevel::O0);
+    let square = assembly
+        .split("square:")
+        .nth(1)
+        .and_then(|rest| rest.split("main:").next())
+        .unwrap();
+    // `square` calls nothing, so its variables stay below `rsp`
+    assert!(!square.contains("subq"), "{}", assembly);
+    assert!(square.contains("(%rbp)"), "{}", assembly);
+
+    let mut passes = PassManager::new(OptLevel::O0);
+    passes.apply_machine_flag("no-red-zone").unwrap();
+    assert!(compile_with(source, &passes).contains("subq $16, %rsp"));
+    if let Some(code) = assemble_and_run(&assembly, "red_zone") {
+        assert_eq!(code, 49);
+    }
+}
+
+/// `cc` links position-independent executables, which must reach the C
+/// library through the PLT.
+#[test]
+fn test_external_functions_are_called_through_the_plt() {
+    let source = "int putchar(int c); int main() { putchar(72); putchar(10); return 3; }";
+    for flag in ["pie", "pic"] {
+        for level in OPT_LEVELS {
+            let mut passes = PassManager::new(level);
+            passes.apply_flag(flag).unwrap();
+            let assembly = compile_with(source, &passes);
+            if cfg!(not(target_os = "macos")) {
+                assert!(assembly.contains("putchar@PLT"), "{}", assembly);
+            }
+            let name = format!("external_call_{}_{:?}", flag, level);
+            if let Some(code) = assemble_and_run(&assembly, &name) {
+                assert_eq!(code, 3, "-f{} at {:?}", flag, level);
+            }
+        }
+    }
+}
+
+/// Runs the benchmark programs with each register allocator at `-O2` and
+/// compares the code they produce: graph coloring should never need more
+/// instructions or stack accesses than linear scan.
+#[test]
+fn test_register_allocators_on_benchmarks() {
+    let count = |assembly: &str, pattern: &str| {
+        assembly
+            .lines()
+            .filter(|line| line.starts_with("    ") && line.contains(pattern))
+            .count()
+    };
+
+    println!("{:<26} {:>14} {:>14}", "program", "linear", "graph");
+    for (file, expected) in PROGRAMS {
+        let source = fs::read_to_string(Path::new("tests/programs").join(file)).unwrap();
+        let mut stats = Vec::new();
+        for allocator in [RegAllocator::Linear, RegAllocator::Graph] {
+            let mut passes = PassManager::new(OptLevel::O2);
+            passes.codegen_options.register_allocator = Some(allocator);
+            let assembly = compile_with(&source, &passes);
+            let name = format!("{}_{:?}", file.trim_end_matches(".c"), allocator);
+            if let Some(code) = assemble_and_run(&assembly, &name) {
+                assert_eq!(code as i64, *expected, "{} with {:?}", file, allocator);
+            }
+            stats.push((count(&assembly, ""), count(&assembly, "(%rbp)")));
+        }
+
+        let [(linear, linear_stack), (graph, graph_stack)] = stats[..] else {
+            unreachable!()
+        };
+        println!(
+            "{:<26} {:>6} ({:>3} mem) {:>6} ({:>3} mem)",
+            file, linear, linear_stack, graph, graph_stack
+        );
+        assert!(
+            graph <= linear,
+            "{}: {} > {} instructions",
+            file,
+            graph,
+            linear
+        );
+        assert!(graph_stack <= linear_stack, "{}: more stack accesses", file);
+    }
+}
@@ -0,0 +1,18 @@
+int scaled(int a, int b) { return a + b * 4 + 8; }
+int times(int x) { return x * 3 + x * 9 - 1; }
+int is_zero(int x) {
+    if (x) return 0;
+    return 1;
+}
+int count_below(int n) {
+    int c = 0;
+    int i;
+    for (i = 0; i < n; i = i + 1) {
+        if (10 > i) c = c + 2;
+    }
+    return c;
+}
+int wraps(int x) { return x * 8 + 2147483647; }
+int main() {
+    return scaled(3, 5) + times(2) + count_below(20) + is_zero(0) + (wraps(1) + 2147483641);
+}
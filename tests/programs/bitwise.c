@@ -0,0 +1,16 @@
+int low_bits(int x, int n) { return x & ((1 << n) - 1); }
+int parity(int x) {
+    int p = 0;
+    while (x != 0) {
+        p = p ^ (x & 1);
+        x = x >> 1;
+    }
+    return p;
+}
+int main() {
+    int a = 1234567;
+    int checks = (a % 1000 == 567) + (-7 % 3 == -1) * 2 + (low_bits(a, 4) == 7) * 4
+        + ((a | 255) - (a & ~255) == 255) * 8 + (parity(7) == 1) * 16 + ((-16 >> 2) == -4) * 32
+        + ((1 << 31) < 0) * 64;
+    return checks + ((a ^ a) == 0) * 128 - 200;
+}
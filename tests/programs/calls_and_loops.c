@@ -0,0 +1,16 @@
+int add(int a, int b) { return a + b; }
+int many(int a, int b, int c, int d, int e, int f, int g) {
+    return a + b + c + d + e + f + g;
+}
+int main() {
+    int x = 3;
+    int y = x + 4;
+    int s = 0;
+    int i;
+    for (i = 0; i < 10; i = i + 1) {
+        if (i == 5) continue;
+        s = s + i;
+    }
+    while (1) { break; }
+    return add(y, s) + many(1, 2, 3, 4, 5, 6, 7) - 40 + (10 / 3) * (x > 2) + !0;
+}
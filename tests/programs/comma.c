@@ -0,0 +1,12 @@
+int add(int a, int b) {
+    return a + b;
+}
+
+int main(void) {
+    int i, j, steps = 0;
+    for (i = 0, j = 10; i < j; i = i + 1, j = j - 1)
+        steps = steps + 1;
+    int x = (steps = steps * 2, steps + 1);
+    int sum = add((i, j), x);
+    return sum + (i = 3, j = 4, i * j);
+}
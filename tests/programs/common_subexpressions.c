@@ -0,0 +1,12 @@
+int f(int a, int b) {
+    int x = a * b + a * b;
+    int y = 0;
+    int i;
+    for (i = 0; i < 4; i = i + 1) {
+        y = y + (a + b) * (b + a);
+        a = a + 1;
+        if (a * b > 10) y = y - a * b;
+    }
+    return x + y + ((x + 1) < (x + 1)) + (a * b == b * a);
+}
+int main() { return f(2, 3); }
@@ -0,0 +1,27 @@
+int main(void) {
+    int a = 10;
+    int b = 1000;
+    int r = 0;
+    a += 5;
+    a -= 3;
+    a *= 4;
+    a /= 3;
+    a %= 7;
+    r = r + a;
+    b &= 1020;
+    b |= 3;
+    b ^= 5;
+    b <<= 2;
+    b >>= 4;
+    r = r + b;
+    int x = 3;
+    int y = (x += 2) * 3;
+    int z = y -= x;
+    r = r + y + z;
+    int s = 0;
+    int i;
+    for (i = 1; i <= 10; i += 1)
+        s += i;
+    r -= s;
+    return r;
+}
@@ -0,0 +1,28 @@
+int absolute(int x) {
+    return x < 0 ? -x : x;
+}
+
+int clamp(int x, int lo, int hi) {
+    return x < lo ? lo : x > hi ? hi : x;
+}
+
+int sign(int x) {
+    return x > 0 ? 1 : x ? -1 : 0;
+}
+
+int fail(void) {
+    int zero = 0;
+    return 1 / zero;
+}
+
+int main(void) {
+    int sum = 0;
+    int i;
+    for (i = -20; i <= 20; i = i + 1) {
+        sum = sum + clamp(i, -5, 7) + sign(i) * 2;
+        sum = sum + (i % 2 ? absolute(i) : 0);
+    }
+    int zero = 0;
+    sum = sum + (zero ? fail() : 3) + (1 ? 4 : fail());
+    return sum;
+}
@@ -0,0 +1,8 @@
+int main() {
+    int x = 2;
+    int y = x;
+    if (y > 1) x = x * 10; else x = 0;
+    while (x < 5) x = x + 1;
+    do { y = y - 1; } while (0);
+    return x + y - 1;
+}
@@ -0,0 +1,16 @@
+int sum_to(int n, int acc) {
+    if (n == 0) return acc;
+    return sum_to(n - 1, acc + n);
+}
+int is_odd(int n);
+int is_even(int n) {
+    if (n == 0) return 1;
+    return is_odd(n - 1);
+}
+int is_odd(int n) {
+    if (n == 0) return 0;
+    return is_even(n - 1);
+}
+int main() {
+    return (sum_to(10000000, 0) == -2004260032) + is_even(5000000) * 2 + is_odd(3000001) * 4;
+}
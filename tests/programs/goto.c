@@ -0,0 +1,40 @@
+int find(int target) {
+    int i;
+    int j;
+    for (i = 0; i < 10; i++)
+        for (j = 0; j < 10; j++)
+            if (i * j == target)
+                goto found;
+    return 0;
+found:
+    return i * 10 + j;
+}
+
+int checked_sum(int n) {
+    int status = 0;
+    int sum = 0;
+    if (n < 0) {
+        status = 100;
+        goto cleanup;
+    }
+    while (n > 0) {
+        sum += n;
+        n--;
+    }
+cleanup:
+    return status + sum;
+}
+
+int main(void) {
+    int count = 0;
+    int total = 0;
+again:
+    count++;
+    total += count;
+    if (count < 5)
+        goto again;
+    goto skip;
+    total = 0;
+skip:
+    return total + find(42) + checked_sum(-1) + checked_sum(4);
+}
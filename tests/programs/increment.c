@@ -0,0 +1,16 @@
+int main(void) {
+    int sum = 0;
+    int i;
+    int j;
+    for (i = 0, j = 10; i < j; i++, j--)
+        sum = sum + i * j;
+    int a = 5;
+    int b = a++;
+    int c = ++a;
+    int d = a--;
+    int e = --a;
+    int k = 0;
+    while (k++ < 3)
+        sum++;
+    return sum + b + c * 2 + d * 3 + e * 4 + k;
+}
@@ -0,0 +1,15 @@
+int clamp(int x, int lo, int hi) {
+    if (x < lo) return lo;
+    if (x > hi) return hi;
+    return x;
+}
+int square(int x) { return x * x; }
+int sum_squares(int n) {
+    int s = 0;
+    int i;
+    for (i = 1; i <= n; i = i + 1) s = s + square(i);
+    return s;
+}
+int main() {
+    return clamp(sum_squares(6), 0, 100) + clamp(-5, 0, 10) + clamp(square(3), 0, 50);
+}
@@ -0,0 +1,26 @@
+int fail(void) {
+    int zero = 0;
+    return 1 / zero;
+}
+
+int count(int n) {
+    int hits = 0;
+    int i;
+    for (i = 0; i < n; i = i + 1) {
+        if (i % 3 == 0 || i % 5 == 0)
+            hits = hits + 1;
+    }
+    return hits;
+}
+
+int main(void) {
+    int x = 0;
+    int r = 0;
+    if (x != 0 && 10 / x > 1)
+        r = 100;
+    r = r + (x == 0 || fail());
+    r = r + 2 * (5 && 9);
+    r = r + 4 * (x && fail());
+    r = r + 8 * !(x || 0);
+    return r + count(16) * 16;
+}
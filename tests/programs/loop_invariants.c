@@ -0,0 +1,18 @@
+int f(int a, int b, int n) {
+    int s = 0;
+    int i;
+    int j;
+    for (i = 0; i < n; i = i + 1) {
+        for (j = 0; j < n; j = j + 1) {
+            s = s + a * b + i * 3 + j * a;
+        }
+        s = s - i * 7;
+    }
+    i = 10;
+    while (i > 0) {
+        s = s + i * 2 + (a + b) / 2;
+        i = i - 3;
+    }
+    return s;
+}
+int main() { return f(3, 4, 5) - 400; }
@@ -0,0 +1,14 @@
+int main() {
+    int total = 0;
+    int i;
+    int j;
+    for (i = 0; i < 10; i = i + 1) {
+        j = 0;
+        while (j < 20) {
+            j = j + 1;
+            if (j > 10) break;
+            total = total + 1;
+        }
+    }
+    return total;
+}
@@ -0,0 +1,25 @@
+int mix(int a, int b) { return a * 3 - b; }
+int pressure(int n) {
+    int a = n + 1;
+    int b = n + 2;
+    int c = n + 3;
+    int d = n + 4;
+    int e = n + 5;
+    int f = n + 6;
+    int g = n + 7;
+    int h = n + 8;
+    int i = n + 9;
+    int j = n + 10;
+    int k = n + 11;
+    int l = n + 12;
+    int m = n + 13;
+    int o = n + 14;
+    int p = mix(a, b) + mix(c, d);
+    return a * b - c + d * e - f + g * h - i + j * k - l + m * o - p;
+}
+int main() {
+    int s = 0;
+    int t;
+    for (t = 0; t < 3; t = t + 1) s = s + pressure(t);
+    return s - 1200;
+}
@@ -0,0 +1,5 @@
+int main() {
+    int big = 2147483647;
+    int wrapped = big + 1;
+    return wrapped < 0;
+}